@@ -0,0 +1,284 @@
+//! Perl バージョン間の API 比較
+//!
+//! 2 つの [`CodegenReport`]（Pipeline を実行して得たもの、または
+//! `--report-json` で保存したもの）を名前で突き合わせ、
+//!
+//! - 追加 / 削除されたマクロ・inline 関数
+//! - シグネチャ（パラメータ・戻り値・THX）が変わったもの
+//! - 生成結果（`GenerateStatus` / `ReportOutcome`）が変わったもの
+//!
+//! を列挙する。サポートする Perl の範囲（5.30〜5.42 など）で何が変わったかを
+//! 追うためのもの。
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::codegen_report::{CodegenReport, ReportEntry, ReportItemKind, ReportOutcome};
+use crate::pipeline::{PipelineBuilder, PipelineError};
+use crate::rust_codegen::GenerateStatus;
+
+/// 片方にしかないアイテム
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ApiItem {
+    pub name: String,
+    pub kind: ReportItemKind,
+    pub outcome: ReportOutcome,
+}
+
+impl ApiItem {
+    fn from_entry(entry: &ReportEntry) -> Self {
+        Self { name: entry.name.clone(), kind: entry.kind, outcome: entry.outcome }
+    }
+}
+
+/// シグネチャの変更点
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SignatureDelta {
+    /// パラメータが変わった
+    Params { old: Vec<String>, new: Vec<String> },
+    /// 戻り値の型が変わった
+    Return { old: String, new: String },
+    /// THX 依存かどうかが変わった
+    Thx { old: bool, new: bool },
+}
+
+impl std::fmt::Display for SignatureDelta {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SignatureDelta::Params { old, new } => write!(f, "params ({}) -> ({})", old.join(", "), new.join(", ")),
+            SignatureDelta::Return { old, new } => write!(f, "return {} -> {}", old, new),
+            SignatureDelta::Thx { old, new } => write!(f, "THX {} -> {}", old, new),
+        }
+    }
+}
+
+/// シグネチャが変わったアイテム
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SignatureChange {
+    pub name: String,
+    pub kind: ReportItemKind,
+    pub changes: Vec<SignatureDelta>,
+}
+
+/// 生成結果が変わったアイテム
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StatusChange {
+    pub name: String,
+    pub kind: ReportItemKind,
+    pub old_outcome: ReportOutcome,
+    pub new_outcome: ReportOutcome,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_status: Option<GenerateStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_status: Option<GenerateStatus>,
+}
+
+/// 2 つのバージョン間の API の差分
+///
+/// 各リストは名前順。
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ApiDiff {
+    /// 新しい側にのみあるもの
+    pub added: Vec<ApiItem>,
+    /// 古い側にのみあるもの
+    pub removed: Vec<ApiItem>,
+    /// シグネチャが変わったもの
+    pub signature_changed: Vec<SignatureChange>,
+    /// 生成結果が変わったもの
+    pub status_changed: Vec<StatusChange>,
+}
+
+impl ApiDiff {
+    /// 2 つのレポートを比較する
+    pub fn between(old: &CodegenReport, new: &CodegenReport) -> Self {
+        let index = |report: &CodegenReport| -> HashMap<(ReportItemKind, String), usize> {
+            report.entries.iter().enumerate().map(|(i, e)| ((e.kind, e.name.clone()), i)).collect()
+        };
+        let old_index = index(old);
+        let new_index = index(new);
+
+        let mut diff = ApiDiff::default();
+        for entry in &old.entries {
+            let Some(&i) = new_index.get(&(entry.kind, entry.name.clone())) else {
+                diff.removed.push(ApiItem::from_entry(entry));
+                continue;
+            };
+            let new_entry = &new.entries[i];
+
+            let changes = signature_deltas(entry, new_entry);
+            if !changes.is_empty() {
+                diff.signature_changed.push(SignatureChange {
+                    name: entry.name.clone(),
+                    kind: entry.kind,
+                    changes,
+                });
+            }
+            if entry.outcome != new_entry.outcome || entry.generate_status != new_entry.generate_status {
+                diff.status_changed.push(StatusChange {
+                    name: entry.name.clone(),
+                    kind: entry.kind,
+                    old_outcome: entry.outcome,
+                    new_outcome: new_entry.outcome,
+                    old_status: entry.generate_status,
+                    new_status: new_entry.generate_status,
+                });
+            }
+        }
+        for entry in &new.entries {
+            if !old_index.contains_key(&(entry.kind, entry.name.clone())) {
+                diff.added.push(ApiItem::from_entry(entry));
+            }
+        }
+
+        diff.added.sort_by(|a, b| a.name.cmp(&b.name));
+        diff.removed.sort_by(|a, b| a.name.cmp(&b.name));
+        diff.signature_changed.sort_by(|a, b| a.name.cmp(&b.name));
+        diff.status_changed.sort_by(|a, b| a.name.cmp(&b.name));
+        diff
+    }
+
+    /// 2 つの設定で Pipeline を実行し、その結果を比較する
+    ///
+    /// 生成コードは捨て、レポートのみを使う。
+    pub fn from_pipelines(old: PipelineBuilder, new: PipelineBuilder) -> Result<Self, PipelineError> {
+        let old = old.build()?.generate(io::sink())?;
+        let new = new.build()?.generate(io::sink())?;
+        Ok(Self::between(old.report(), new.report()))
+    }
+
+    /// 差分がないか
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.signature_changed.is_empty()
+            && self.status_changed.is_empty()
+    }
+
+    /// 人が読むための一覧
+    pub fn to_text(&self) -> String {
+        let mut out = format!(
+            "=== API diff ===\n\
+             added: {}, removed: {}, signature changed: {}, status changed: {}\n",
+            self.added.len(), self.removed.len(), self.signature_changed.len(), self.status_changed.len(),
+        );
+        for item in &self.added {
+            out.push_str(&format!("+ {} ({:?}, {:?})\n", item.name, item.kind, item.outcome));
+        }
+        for item in &self.removed {
+            out.push_str(&format!("- {} ({:?}, {:?})\n", item.name, item.kind, item.outcome));
+        }
+        for change in &self.signature_changed {
+            for delta in &change.changes {
+                out.push_str(&format!("~ {} ({:?}): {}\n", change.name, change.kind, delta));
+            }
+        }
+        for change in &self.status_changed {
+            out.push_str(&format!("! {} ({:?}): {:?} -> {:?}",
+                change.name, change.kind, change.old_outcome, change.new_outcome));
+            if change.old_status != change.new_status {
+                out.push_str(&format!(" (status {:?} -> {:?})", change.old_status, change.new_status));
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// JSON 文字列に変換
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// JSON ファイルに書き出す
+    pub fn write_json(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writeln!(writer)?;
+        writer.flush()
+    }
+}
+
+/// 同名アイテムのシグネチャの変更点
+///
+/// パラメータと戻り値は両方で生成できた場合のみ比較する。
+fn signature_deltas(old: &ReportEntry, new: &ReportEntry) -> Vec<SignatureDelta> {
+    let mut changes = Vec::new();
+    if let (Some(o), Some(n)) = (&old.signature, &new.signature) {
+        if o.params != n.params {
+            changes.push(SignatureDelta::Params { old: o.params.clone(), new: n.params.clone() });
+        }
+        if o.ret != n.ret {
+            changes.push(SignatureDelta::Return { old: o.ret.clone(), new: n.ret.clone() });
+        }
+    }
+    if old.thx != new.thx {
+        changes.push(SignatureDelta::Thx { old: old.thx, new: new.thx });
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen_report::ReportSignature;
+
+    fn entry(name: &str, outcome: ReportOutcome, params: &[&str], ret: &str) -> ReportEntry {
+        let mut e = ReportEntry::new(name, ReportItemKind::Macro, outcome);
+        e.signature = Some(ReportSignature {
+            params: params.iter().map(|p| p.to_string()).collect(),
+            ret: ret.to_string(),
+        });
+        e
+    }
+
+    #[test]
+    fn test_api_diff() {
+        let mut old_thx = entry("SvTRUE", ReportOutcome::Success, &["sv: *mut SV"], "bool");
+        old_thx.thx = false;
+        let old = CodegenReport {
+            entries: vec![
+                entry("SvIV", ReportOutcome::Success, &["sv: *mut SV"], "IV"),
+                entry("SvOLD", ReportOutcome::Success, &[], "()"),
+                old_thx,
+                entry("CvGV", ReportOutcome::Success, &["cv: *mut CV"], "*mut GV"),
+            ],
+        };
+        let mut new_thx = entry("SvTRUE", ReportOutcome::Success, &["my_perl: *mut PerlInterpreter", "sv: *mut SV"], "bool");
+        new_thx.thx = true;
+        let mut cascade = ReportEntry::new("CvGV", ReportItemKind::Macro, ReportOutcome::CascadeUnavailable);
+        cascade.generate_status = Some(GenerateStatus::Success);
+        let new = CodegenReport {
+            entries: vec![
+                entry("SvIV", ReportOutcome::Success, &["sv: *mut SV"], "IV"),
+                new_thx,
+                cascade,
+                entry("SvNEW", ReportOutcome::Success, &[], "()"),
+            ],
+        };
+
+        let diff = ApiDiff::between(&old, &new);
+        assert_eq!(diff.added.iter().map(|i| i.name.as_str()).collect::<Vec<_>>(), ["SvNEW"]);
+        assert_eq!(diff.removed.iter().map(|i| i.name.as_str()).collect::<Vec<_>>(), ["SvOLD"]);
+        assert_eq!(diff.signature_changed.len(), 1);
+        assert_eq!(diff.signature_changed[0].name, "SvTRUE");
+        assert!(matches!(diff.signature_changed[0].changes[..],
+            [SignatureDelta::Params { .. }, SignatureDelta::Thx { old: false, new: true }]));
+        assert_eq!(diff.status_changed.len(), 1);
+        assert_eq!(diff.status_changed[0].name, "CvGV");
+        assert_eq!(diff.status_changed[0].new_outcome, ReportOutcome::CascadeUnavailable);
+
+        let text = diff.to_text();
+        assert!(text.contains("+ SvNEW (Macro, Success)"));
+        assert!(text.contains("~ SvTRUE (Macro): THX false -> true"));
+        assert!(text.contains("! CvGV (Macro): Success -> CascadeUnavailable (status None -> Some(Success))"));
+
+        let json: serde_json::Value = serde_json::from_str(&diff.to_json().unwrap()).unwrap();
+        assert_eq!(json["signature_changed"][0]["changes"][1]["kind"], "thx");
+        assert_eq!(json["status_changed"][0]["new_outcome"], "cascade_unavailable");
+        assert!(ApiDiff::between(&old, &old).is_empty());
+    }
+}
@@ -109,6 +109,9 @@ pub struct ApidocEntry {
     /// トークン合成を行うマクロか（引数型が `"name"` のような引用符付きの場合）
     #[serde(default)]
     pub has_token_pasting: bool,
+    /// `=for apidoc` に続く POD 本文（ヘッダーのコメントから収集した場合のみ）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub doc: Option<String>,
 }
 
 /// apidoc辞書（名前でエントリを検索可能）
@@ -236,9 +239,14 @@ impl ApidocArg {
             return ("...".to_string(), String::new());
         }
 
+        // 特殊なケース: `SP` はスタックポインタ（`dSP` の `sp`）を渡す引数
+        if s == "SP" {
+            return ("SV **".to_string(), "SP".to_string());
+        }
+
         // 特殊なケース: 型のみ (type, cast, block, number, token, "string")
         // これらは名前がない
-        if s == "type" || s == "cast" || s == "SP" || s == "block"
+        if s == "type" || s == "cast" || s == "block"
             || s == "number" || s == "token" || s.starts_with('"')
         {
             return (s.to_string(), String::new());
@@ -347,6 +355,7 @@ impl ApidocEntry {
                 source_file: None,
                 line_number: None,
                 has_token_pasting: false,
+                doc: None,
             })
         }
     }
@@ -393,9 +402,96 @@ impl ApidocEntry {
             source_file: None,
             line_number: None,
             has_token_pasting,
+            doc: None,
         })
     }
 
+    /// コメント 1 つ分の POD から apidoc エントリを取り出す
+    ///
+    /// `=for apidoc` とそれに続く `=for apidoc_item` を 1 つのグループとし、
+    /// その後の本文（次の `=for apidoc` / `=head` / `=cut` まで）をグループの
+    /// 全エントリの `doc` にする。
+    pub fn parse_apidoc_block(text: &str) -> Vec<Self> {
+        fn flush(entries: &mut [ApidocEntry], doc_lines: &mut Vec<&str>) {
+            let start = doc_lines.iter().position(|l| !l.trim().is_empty());
+            let end = doc_lines.iter().rposition(|l| !l.trim().is_empty());
+            let doc = start.zip(end).map(|(s, e)| doc_lines[s..=e].join("\n"));
+            for entry in entries {
+                entry.doc = doc.clone();
+            }
+            doc_lines.clear();
+        }
+
+        let mut entries = Vec::new();
+        // 現在のグループの先頭（グループ外なら None）
+        let mut group_start: Option<usize> = None;
+        let mut doc_lines = Vec::new();
+
+        for line in text.lines() {
+            let trimmed = line.trim();
+            if trimmed.starts_with("=for apidoc_item") {
+                if let Some(entry) = Self::parse_apidoc_line(trimmed) {
+                    group_start.get_or_insert(entries.len());
+                    entries.push(entry);
+                }
+                continue;
+            }
+            if trimmed.starts_with("=for apidoc") || trimmed.starts_with("=head") || trimmed.starts_with("=cut") {
+                if let Some(start) = group_start.take() {
+                    flush(&mut entries[start..], &mut doc_lines);
+                }
+                // `=for apidoc_section` などはエントリではない
+                if trimmed.starts_with("=for apidoc") && !trimmed.starts_with("=for apidoc_")
+                    && let Some(entry) = Self::parse_apidoc_line(trimmed)
+                {
+                    group_start = Some(entries.len());
+                    entries.push(entry);
+                }
+                continue;
+            }
+            if group_start.is_some() {
+                doc_lines.push(line);
+            }
+        }
+        if let Some(start) = group_start {
+            flush(&mut entries[start..], &mut doc_lines);
+        }
+        entries
+    }
+
+    /// C のシグネチャ（例: `char* SvPV(SV* sv, STRLEN len)`）
+    ///
+    /// 名前だけのエントリ（`=for apidoc name`）では None。
+    /// NN / NULLOK / NZ の注釈は含めない。
+    pub fn c_signature(&self) -> Option<String> {
+        if self.flags.raw.is_empty() && self.return_type.is_none() && self.args.is_empty() {
+            return None;
+        }
+        let mut sig = String::new();
+        if let Some(ret) = &self.return_type {
+            sig.push_str(ret);
+            sig.push(' ');
+        }
+        sig.push_str(&self.name);
+        if !self.flags.no_args {
+            let args: Vec<&str> = self.args.iter()
+                .map(|arg| {
+                    let mut raw = arg.raw.trim();
+                    while let Some(rest) = ["NN ", "NULLOK ", "NZ "].iter()
+                        .find_map(|prefix| raw.strip_prefix(prefix))
+                    {
+                        raw = rest.trim_start();
+                    }
+                    raw
+                })
+                .collect();
+            sig.push('(');
+            sig.push_str(&args.join(", "));
+            sig.push(')');
+        }
+        Some(sig)
+    }
+
     /// この関数がAPI公開かどうか
     pub fn is_public_api(&self) -> bool {
         self.flags.api
@@ -957,7 +1053,18 @@ pub fn resolve_apidoc_path(
 
     // 自動モード: Perl バージョンから検索
     let (major, minor) = get_perl_version()?;
+    resolve_apidoc_path_for_version(apidoc_dir, major, minor).map(Some)
+}
 
+/// 指定した Perl バージョンの apidoc ファイルのパスを解決
+///
+/// `resolve_apidoc_path` の自動モードと同じだが、バージョンを perl から
+/// 取得しない（`PerlConfigSource` でバージョンが分かっている場合用）。
+pub fn resolve_apidoc_path_for_version(
+    apidoc_dir: Option<&Path>,
+    major: u32,
+    minor: u32,
+) -> Result<PathBuf, ApidocResolveError> {
     // 奇数マイナーバージョン（開発版）はエラー
     if minor % 2 == 1 {
         return Err(ApidocResolveError::DevelopmentVersion { major, minor });
@@ -975,7 +1082,7 @@ pub fn resolve_apidoc_path(
             minor,
         })?;
 
-    Ok(Some(json_path))
+    Ok(json_path)
 }
 
 /// 統計情報
@@ -1056,16 +1163,14 @@ impl Default for ApidocCollector {
 
 impl CommentCallback for ApidocCollector {
     fn on_comment(&mut self, comment: &Comment, _file_id: FileId, _is_target: bool) {
-        // コメント内の各行を処理
+        // コメント内の =for apidoc とその POD 本文を処理
         // （is_target チェックは呼び出し側で行われるため、ここでは常に処理）
-        for line in comment.text.lines() {
-            if let Some(entry) = ApidocEntry::parse_apidoc_line(line) {
-                // token 型の引数を持つマクロを記録
-                if entry.has_token_arg() {
-                    self.token_type_macros.push(entry.name.clone());
-                }
-                self.entries.insert(entry.name.clone(), entry);
+        for entry in ApidocEntry::parse_apidoc_block(&comment.text) {
+            // token 型の引数を持つマクロを記録
+            if entry.has_token_arg() {
+                self.token_type_macros.push(entry.name.clone());
             }
+            self.entries.insert(entry.name.clone(), entry);
         }
     }
 
@@ -1143,6 +1248,13 @@ mod tests {
         assert_eq!(arg.name, "name");
     }
 
+    #[test]
+    fn test_parse_arg_stack_pointer() {
+        let arg = ApidocArg::parse("SP").unwrap();
+        assert_eq!(arg.ty, "SV **");
+        assert_eq!(arg.name, "SP");
+    }
+
     #[test]
     fn test_parse_arg_varargs() {
         let arg = ApidocArg::parse("...").unwrap();
@@ -1220,6 +1332,42 @@ mod tests {
         assert_eq!(entry.name, "SvPV_const");
     }
 
+    #[test]
+    fn test_parse_apidoc_block() {
+        let text = r#"
+=for apidoc_section $SV
+
+=for apidoc Am|char*|SvPV_force|SV* sv|STRLEN len
+=for apidoc_item ||SvPV_force_nolen|SV* sv
+
+These are like C<L</SvPV>>.
+
+    SvPV_force(sv, len);
+
+=for apidoc Am|STRLEN|SvCUR|SV* sv
+Returns the length.
+
+=cut
+"#;
+        let entries = ApidocEntry::parse_apidoc_block(text);
+        let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["SvPV_force", "SvPV_force_nolen", "SvCUR"]);
+        let doc = "These are like C<L</SvPV>>.\n\n    SvPV_force(sv, len);";
+        assert_eq!(entries[0].doc.as_deref(), Some(doc));
+        assert_eq!(entries[1].doc.as_deref(), Some(doc));
+        assert_eq!(entries[2].doc.as_deref(), Some("Returns the length."));
+    }
+
+    #[test]
+    fn test_c_signature() {
+        let entry = ApidocEntry::parse_apidoc_line("=for apidoc Am|char*|SvPV|SV* sv|NN STRLEN len").unwrap();
+        assert_eq!(entry.c_signature().as_deref(), Some("char* SvPV(SV* sv, STRLEN len)"));
+        let entry = ApidocEntry::parse_apidoc_line("=for apidoc Amn|void|dSP").unwrap();
+        assert_eq!(entry.c_signature().as_deref(), Some("void dSP"));
+        let entry = ApidocEntry::parse_apidoc_line("=for apidoc av_pop").unwrap();
+        assert!(entry.c_signature().is_none());
+    }
+
     #[test]
     fn test_embed_fnc_str() {
         let content = r#"
@@ -108,15 +108,21 @@ fn try_extract_to(cache_base: &std::path::Path) -> io::Result<PathBuf> {
         .join("libperl-macrogen")
         .join(format!("apidoc-v{}", APIDOC_DATA_VERSION));
 
-    // キャッシュが既に存在していて整合していればそれを返す
+    // キャッシュが既に存在していて整合していればそれを返す。
+    // patches の追加などで埋め込みデータが変わったら（バージョンが同じでも）展開し直す
     let apidoc_dir = cache_dir.join("apidoc");
-    if apidoc_dir.is_dir() {
-        let version_file = cache_dir.join("version");
-        if let Ok(cached_version) = fs::read_to_string(&version_file) {
-            if cached_version.trim() == APIDOC_DATA_VERSION {
-                return Ok(apidoc_dir);
-            }
-        }
+    let version_file = cache_dir.join("version");
+    let version = embedded_version();
+    if apidoc_dir.is_dir()
+        && let Ok(cached_version) = fs::read_to_string(&version_file)
+        && cached_version.trim() == version
+    {
+        return Ok(apidoc_dir);
+    }
+
+    // 古いデータで消えた・名前が変わったファイルを残さない
+    if apidoc_dir.exists() {
+        fs::remove_dir_all(&apidoc_dir)?;
     }
 
     // キャッシュディレクトリを作成
@@ -130,11 +136,16 @@ fn try_extract_to(cache_base: &std::path::Path) -> io::Result<PathBuf> {
     archive.unpack(&cache_dir)?;
 
     // バージョンファイルを書き込み
-    fs::write(cache_dir.join("version"), APIDOC_DATA_VERSION)?;
+    fs::write(&version_file, version)?;
 
     Ok(apidoc_dir)
 }
 
+/// キャッシュの version ファイルの内容（データバージョンと埋め込みデータのハッシュ）
+fn embedded_version() -> String {
+    format!("{} {}", APIDOC_DATA_VERSION, crate::gen_cache::hash_bytes(EMBEDDED_APIDOC))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -147,6 +158,24 @@ mod tests {
         assert_eq!(EMBEDDED_APIDOC[1], 0x8b);
     }
 
+    #[test]
+    fn test_stale_cache_is_reextracted() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let dir = try_extract_to(tmp.path()).unwrap();
+        let version_file = dir.parent().unwrap().join("version");
+        assert_eq!(fs::read_to_string(&version_file).unwrap(), embedded_version());
+
+        // 同じバージョン番号でも内容が古いキャッシュは展開し直す
+        fs::write(&version_file, APIDOC_DATA_VERSION).unwrap();
+        fs::write(dir.join("common.patches.json"), "{}").unwrap();
+        fs::write(dir.join("v5.0.json"), "{}").unwrap();
+        assert_eq!(try_extract_to(tmp.path()).unwrap(), dir);
+        // 埋め込みデータにないファイルは消える
+        assert!(!dir.join("v5.0.json").exists());
+        assert_eq!(fs::read_to_string(&version_file).unwrap(), embedded_version());
+        assert_ne!(fs::read_to_string(dir.join("common.patches.json")).unwrap(), "{}");
+    }
+
     #[test]
     fn test_get_apidoc_dir() {
         let dir = get_apidoc_dir();
@@ -93,6 +93,55 @@ pub struct ApidocPatchFile {
 
 fn default_schema_version() -> u32 { 1 }
 
+impl ApidocPatchFile {
+    /// パッチのない空のファイル
+    pub fn new(comment: Option<String>) -> Self {
+        Self { schema_version: default_schema_version(), comment, patches: Vec::new() }
+    }
+
+    /// JSON ファイルをそのまま読み込む（schema_version の検査のみ）
+    pub fn load_json<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let content = std::fs::read_to_string(path.as_ref())?;
+        let file: ApidocPatchFile = serde_json::from_str(&content).map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidData,
+                format!("apidoc patches JSON parse error: {}", e))
+        })?;
+        if file.schema_version != 1 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                format!("unsupported apidoc patches schema_version: {}", file.schema_version)));
+        }
+        Ok(file)
+    }
+
+    /// `skip_codegen` パッチを追加する（名前順の位置に挿入）
+    ///
+    /// 同じ名前の `skip_codegen` が既にあれば、手で書かれた reason 等を
+    /// 残すため何もしない。追加したら true。
+    pub fn add_skip_codegen(&mut self, name: &str, reason: &str) -> bool {
+        if self.patches.iter().any(|p| p.name == name && p.kind == PatchKind::SkipCodegen) {
+            return false;
+        }
+        let pos = self.patches.partition_point(|p| p.name.as_str() <= name);
+        self.patches.insert(pos, ApidocPatch {
+            name: name.to_string(),
+            kind: PatchKind::SkipCodegen,
+            value: None,
+            arg_index: None,
+            source_loc: None,
+            reason: reason.to_string(),
+            upstream_status: None,
+        });
+        true
+    }
+
+    /// JSON ファイルとして書き出す
+    pub fn write_json<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        std::fs::write(path, json + "\n")
+    }
+}
+
 /// 1 つのパッチエントリ
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ApidocPatch {
@@ -101,18 +150,18 @@ pub struct ApidocPatch {
     /// パッチ種別
     pub kind: PatchKind,
     /// `*_override` 系で必須の値
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub value: Option<String>,
     /// `arg_type_override` 用の引数 index
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub arg_index: Option<usize>,
     /// バグ箇所（デバッグ・上流報告用、`/path/to/file.h:line`）
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub source_loc: Option<String>,
     /// 何が間違っているか・なぜパッチが必要かの説明（必須）
     pub reason: String,
     /// 上流ステータス: "to-report" / "reported:URL" / "merged" / "fixed-in-5.42" 等
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub upstream_status: Option<String>,
 }
 
@@ -161,15 +210,7 @@ impl ApidocPatchSet {
     /// JSON ファイルから読み込み
     pub fn load_json<P: AsRef<Path>>(path: P) -> io::Result<Self> {
         let path_ref = path.as_ref();
-        let content = std::fs::read_to_string(path_ref)?;
-        let file: ApidocPatchFile = serde_json::from_str(&content).map_err(|e| {
-            io::Error::new(io::ErrorKind::InvalidData,
-                format!("apidoc patches JSON parse error: {}", e))
-        })?;
-        if file.schema_version != 1 {
-            return Err(io::Error::new(io::ErrorKind::InvalidData,
-                format!("unsupported apidoc patches schema_version: {}", file.schema_version)));
-        }
+        let file = ApidocPatchFile::load_json(path_ref)?;
         let mut set = Self::default();
         set.source_paths.push(path_ref.to_path_buf());
         for p in file.patches {
@@ -467,6 +508,40 @@ impl ApidocPatchSet {
     pub fn skip_reason(&self, name: &str) -> Option<&str> {
         self.skip_codegen.get(name).map(|s| s.as_str())
     }
+
+    /// パッチファイル形式に変換（名前順）
+    ///
+    /// `removals` と `source_paths` はロード時の情報なので含めない。
+    pub fn to_patch_file(&self, comment: Option<String>) -> ApidocPatchFile {
+        let patch = |name: &str, kind, value, arg_index, reason: &str| ApidocPatch {
+            name: name.to_string(),
+            kind,
+            value,
+            arg_index,
+            source_loc: None,
+            reason: reason.to_string(),
+            upstream_status: None,
+        };
+        let mut patches = Vec::new();
+        for (name, (ty, reason)) in &self.return_overrides {
+            patches.push(patch(name, PatchKind::ReturnTypeOverride, Some(ty.clone()), None, reason));
+        }
+        for (name, list) in &self.arg_overrides {
+            for (idx, ty, reason) in list {
+                patches.push(patch(name, PatchKind::ArgTypeOverride, Some(ty.clone()), Some(*idx), reason));
+            }
+        }
+        for (name, reason) in &self.skip_codegen {
+            patches.push(patch(name, PatchKind::SkipCodegen, None, None, reason));
+        }
+        patches.sort_by(|a, b| a.name.cmp(&b.name).then(a.arg_index.cmp(&b.arg_index)));
+        ApidocPatchFile { schema_version: default_schema_version(), comment, patches }
+    }
+
+    /// パッチファイル（`v$X.$Y.patches.json` 形式）として書き出す
+    pub fn write_json<P: AsRef<Path>>(&self, path: P, comment: Option<String>) -> io::Result<()> {
+        self.to_patch_file(comment).write_json(path)
+    }
 }
 
 #[cfg(test)]
@@ -582,4 +657,49 @@ mod tests {
         assert!(set.skip_codegen.is_empty());
         assert!(set.removals.contains("FOO"));
     }
+
+    #[test]
+    fn test_write_json_round_trip() {
+        let tmp = TempDir::new().unwrap();
+        let mut set = ApidocPatchSet::empty();
+        set.skip_codegen.insert("SvIV".to_string(), "rustc: [E0308] mismatched types".to_string());
+        set.skip_codegen.insert("Perl_foo".to_string(), "type-check fails".to_string());
+        let path = tmp.path().join("v5.36.patches.json");
+        set.write_json(&path, Some("auto-generated".to_string())).unwrap();
+
+        let loaded = ApidocPatchSet::load_json(&path).unwrap();
+        assert_eq!(loaded.skip_codegen, set.skip_codegen);
+        let file = set.to_patch_file(None);
+        assert_eq!(file.patches[0].name, "Perl_foo");
+        assert_eq!(file.patches[0].kind, PatchKind::SkipCodegen);
+    }
+
+    #[test]
+    fn test_patch_file_add_skip_codegen_keeps_existing() {
+        let tmp = TempDir::new().unwrap();
+        let json = r#"{
+            "schema_version": 1,
+            "comment": "curated",
+            "patches": [
+                { "name": "AvFILL", "kind": "return_type_override", "value": "SSize_t",
+                  "source_loc": "av.h:42", "reason": "wrong", "upstream_status": "to-report" },
+                { "name": "SvIV", "kind": "skip_codegen", "reason": "hand-written" },
+                { "name": "ZZZ", "kind": "remove", "reason": "fixed" }
+            ]
+        }"#;
+        let path = write_json(tmp.path(), "v5.36.patches.json", json);
+        let mut file = ApidocPatchFile::load_json(&path).unwrap();
+        assert!(!file.add_skip_codegen("SvIV", "rustc: [E0308]"));
+        assert!(file.add_skip_codegen("Perl_foo", "rustc: [E0599]"));
+        file.write_json(&path).unwrap();
+
+        let file = ApidocPatchFile::load_json(&path).unwrap();
+        assert_eq!(file.comment.as_deref(), Some("curated"));
+        let names: Vec<&str> = file.patches.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["AvFILL", "Perl_foo", "SvIV", "ZZZ"]);
+        assert_eq!(file.patches[0].source_loc.as_deref(), Some("av.h:42"));
+        assert_eq!(file.patches[0].upstream_status.as_deref(), Some("to-report"));
+        assert_eq!(file.patches[2].reason, "hand-written");
+        assert_eq!(file.patches[3].kind, PatchKind::Remove);
+    }
 }
@@ -4,6 +4,8 @@
 
 use std::sync::atomic::{AtomicU64, Ordering};
 
+use serde::Serialize;
+
 use crate::intern::InternedStr;
 use crate::source::SourceLocation;
 use crate::token::Comment;
@@ -15,7 +17,7 @@ use crate::token::Comment;
 /// 式ID（一意の通し番号）
 ///
 /// 各式ノードに一意のIDを付与することで、型推論結果の紐付けに使用する。
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize)]
 pub struct ExprId(pub u64);
 
 /// ExprId 生成用のグローバルカウンター
@@ -47,7 +49,7 @@ impl std::fmt::Display for ExprId {
 // ============================================================================
 
 /// 単一のマクロ呼び出し情報
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct MacroInvocation {
     /// マクロ名
     pub name: InternedStr,
@@ -81,7 +83,7 @@ impl MacroInvocation {
 ///
 /// ネストしたマクロ展開を追跡するためのチェーン構造を持つ。
 /// 例: A が B を含み、B が C を含む場合: [A, B, C]
-#[derive(Debug, Clone, Default, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
 pub struct MacroExpansionInfo {
     /// マクロ展開のチェーン（外側から内側へ）
     pub chain: Vec<MacroInvocation>,
@@ -123,7 +125,7 @@ impl MacroExpansionInfo {
 ///
 /// ソース位置とオプションのマクロ展開情報を保持する。
 /// Phase 5 で各ASTノードの `loc: SourceLocation` を `info: NodeInfo` に置き換える。
-#[derive(Debug, Clone, Default, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
 pub struct NodeInfo {
     /// ソース位置
     pub loc: SourceLocation,
@@ -170,13 +172,13 @@ impl NodeInfo {
 // ============================================================================
 
 /// 翻訳単位（ファイル全体）
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct TranslationUnit {
     pub decls: Vec<ExternalDecl>,
 }
 
 /// 外部宣言
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum ExternalDecl {
     /// 関数定義
     FunctionDef(FunctionDef),
@@ -195,7 +197,7 @@ impl ExternalDecl {
 }
 
 /// 関数定義
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct FunctionDef {
     pub specs: DeclSpecs,
     pub declarator: Declarator,
@@ -218,7 +220,7 @@ impl FunctionDef {
 }
 
 /// 宣言
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Declaration {
     pub specs: DeclSpecs,
     pub declarators: Vec<InitDeclarator>,
@@ -236,7 +238,7 @@ impl Declaration {
 }
 
 /// 宣言指定子
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct DeclSpecs {
     pub storage: Option<StorageClass>,
     pub type_specs: Vec<TypeSpec>,
@@ -245,7 +247,7 @@ pub struct DeclSpecs {
 }
 
 /// ストレージクラス
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub enum StorageClass {
     Typedef,
     Extern,
@@ -255,7 +257,7 @@ pub enum StorageClass {
 }
 
 /// 型指定子
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum TypeSpec {
     Void,
     Char,
@@ -286,7 +288,7 @@ pub enum TypeSpec {
 }
 
 /// 型修飾子
-#[derive(Debug, Clone, Default, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
 pub struct TypeQualifiers {
     pub is_const: bool,
     pub is_volatile: bool,
@@ -301,7 +303,7 @@ impl TypeQualifiers {
 }
 
 /// 構造体/共用体指定
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct StructSpec {
     pub name: Option<InternedStr>,
     pub members: Option<Vec<StructMember>>,
@@ -309,21 +311,21 @@ pub struct StructSpec {
 }
 
 /// 構造体メンバー
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct StructMember {
     pub specs: DeclSpecs,
     pub declarators: Vec<StructDeclarator>,
 }
 
 /// 構造体メンバー宣言子
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct StructDeclarator {
     pub declarator: Option<Declarator>,
     pub bitfield: Option<Box<Expr>>,
 }
 
 /// 列挙型指定
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct EnumSpec {
     pub name: Option<InternedStr>,
     pub enumerators: Option<Vec<Enumerator>>,
@@ -331,7 +333,7 @@ pub struct EnumSpec {
 }
 
 /// 列挙子
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Enumerator {
     pub name: InternedStr,
     pub value: Option<Box<Expr>>,
@@ -339,7 +341,7 @@ pub struct Enumerator {
 }
 
 /// パラメータ宣言
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ParamDecl {
     pub specs: DeclSpecs,
     pub declarator: Option<Declarator>,
@@ -347,21 +349,21 @@ pub struct ParamDecl {
 }
 
 /// パラメータリスト
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ParamList {
     pub params: Vec<ParamDecl>,
     pub is_variadic: bool,
 }
 
 /// 初期化子付き宣言子
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct InitDeclarator {
     pub declarator: Declarator,
     pub init: Option<Initializer>,
 }
 
 /// 宣言子
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Declarator {
     pub name: Option<InternedStr>,
     pub derived: Vec<DerivedDecl>,
@@ -369,7 +371,7 @@ pub struct Declarator {
 }
 
 /// 派生宣言子（ポインタ、配列、関数）
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum DerivedDecl {
     Pointer(TypeQualifiers),
     Array(ArrayDecl),
@@ -377,7 +379,7 @@ pub enum DerivedDecl {
 }
 
 /// 配列宣言子
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ArrayDecl {
     pub size: Option<Box<Expr>>,
     pub qualifiers: TypeQualifiers,
@@ -386,28 +388,28 @@ pub struct ArrayDecl {
 }
 
 /// 初期化子
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum Initializer {
     Expr(Box<Expr>),
     List(Vec<InitializerItem>),
 }
 
 /// 初期化子リスト項目
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct InitializerItem {
     pub designation: Vec<Designator>,
     pub init: Initializer,
 }
 
 /// 指示子
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum Designator {
     Index(Box<Expr>),
     Member(InternedStr),
 }
 
 /// 文
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum Stmt {
     /// 複合文
     Compound(CompoundStmt),
@@ -478,14 +480,14 @@ pub enum Stmt {
 }
 
 /// for文の初期化部
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum ForInit {
     Expr(Box<Expr>),
     Decl(Declaration),
 }
 
 /// 複合文
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct CompoundStmt {
     pub items: Vec<BlockItem>,
     pub info: NodeInfo,
@@ -499,7 +501,7 @@ impl CompoundStmt {
 }
 
 /// ブロック内項目
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum BlockItem {
     Decl(Declaration),
     Stmt(Stmt),
@@ -508,14 +510,14 @@ pub enum BlockItem {
 /// ビルトイン呼び出しの引数（型名 or 式）
 ///
 /// `offsetof(type, member)` のように、引数に型名を取りうるビルトイン関数で使用。
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum BuiltinArg {
     Expr(Box<Expr>),
     TypeName(Box<TypeName>),
 }
 
 /// 式の種類
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum ExprKind {
     // 一次式
     Ident(InternedStr),
@@ -629,7 +631,7 @@ pub enum ExprKind {
 }
 
 /// 式ノード
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Expr {
     /// 式の一意識別子
     pub id: ExprId,
@@ -656,20 +658,20 @@ impl Expr {
 }
 
 /// 型名（キャストやsizeofで使用）
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct TypeName {
     pub specs: DeclSpecs,
     pub declarator: Option<AbstractDeclarator>,
 }
 
 /// 抽象宣言子（名前なし）
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct AbstractDeclarator {
     pub derived: Vec<DerivedDecl>,
 }
 
 /// 二項演算子
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub enum BinOp {
     // 乗除
     Mul,
@@ -698,7 +700,7 @@ pub enum BinOp {
 }
 
 /// 代入演算子
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub enum AssignOp {
     Assign,
     MulAssign,
@@ -714,7 +716,7 @@ pub enum AssignOp {
 }
 
 /// アサーションマクロの種類
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub enum AssertKind {
     /// assert(condition)
     Assert,
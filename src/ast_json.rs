@@ -0,0 +1,76 @@
+//! AST・マクロ型推論結果の JSON 出力
+//!
+//! S 式（`SexpPrinter` / `TypedSexpPrinter`）は外部の解析スクリプトから
+//! 扱いにくいため、serde で JSON に出力する。
+//!
+//! - `InternedStr` は名前（文字列）に解決する
+//! - `SourceLocation.file_id` はトップレベルの `files` 配列の添字
+
+use std::io::Write;
+
+use serde::Serialize;
+
+use crate::ast::TranslationUnit;
+use crate::infer_api::InferResult;
+use crate::intern::StringInterner;
+use crate::macro_infer::MacroInferInfo;
+use crate::source::FileRegistry;
+
+#[derive(Serialize)]
+struct AstJson<'a> {
+    files: Vec<String>,
+    #[serde(flatten)]
+    tu: &'a TranslationUnit,
+}
+
+#[derive(Serialize)]
+struct InferJson<'a> {
+    files: Vec<String>,
+    macros: Vec<&'a MacroInferInfo>,
+}
+
+/// `file_id` の添字順のファイルパス
+fn file_paths(files: &FileRegistry) -> Vec<String> {
+    files.iter().map(|(_, path)| path.display().to_string()).collect()
+}
+
+fn write_json<W: Write, T: Serialize>(writer: W, value: &T, pretty: bool) -> serde_json::Result<()> {
+    if pretty {
+        serde_json::to_writer_pretty(writer, value)
+    } else {
+        serde_json::to_writer(writer, value)
+    }
+}
+
+/// 翻訳単位を JSON で出力する
+///
+/// 形式: `{"files": [...], "decls": [...]}`
+pub fn write_translation_unit_json<W: Write>(
+    writer: W,
+    tu: &TranslationUnit,
+    interner: &StringInterner,
+    files: &FileRegistry,
+    pretty: bool,
+) -> serde_json::Result<()> {
+    let json = AstJson { files: file_paths(files), tu };
+    interner.serialize_scope(|| write_json(writer, &json, pretty))
+}
+
+/// マクロ型推論結果（パラメータ・型制約・推論状態・確定型など）を JSON で出力する
+///
+/// 形式: `{"files": [...], "macros": [...]}`（マクロは名前順）。
+/// `targets_only` のときはターゲットディレクトリ内で定義されたマクロのみ。
+pub fn write_macro_infer_json<W: Write>(
+    writer: W,
+    result: &InferResult,
+    targets_only: bool,
+    pretty: bool,
+) -> serde_json::Result<()> {
+    let interner = result.preprocessor.interner();
+    let mut macros: Vec<&MacroInferInfo> = result.infer_ctx.macros.values()
+        .filter(|info| !targets_only || info.is_target)
+        .collect();
+    macros.sort_by_key(|info| interner.get(info.name));
+    let json = InferJson { files: file_paths(result.preprocessor.files()), macros };
+    interner.serialize_scope(|| write_json(writer, &json, pretty))
+}
@@ -0,0 +1,372 @@
+//! Rust に変換できないマクロの C shim
+//!
+//! `ParseFailed` / `TypeIncomplete` / `GenericUnsupported` で生成できなかった
+//! マクロは従来コメントになるだけだったが、apidoc に完全なシグネチャがあるものは
+//! 「マクロを呼ぶだけの C 関数」を経由すれば Rust から使える。
+//!
+//! - C 側: マクロごとに `macrogen_shim_NAME` 関数を持つ `.c` ファイル
+//!   （利用側の build.rs で `cc` crate によりコンパイル・リンクする）
+//! - Rust 側: その `extern "C"` 宣言と、マクロ名で呼べる薄い `pub unsafe fn`
+//!
+//! 両方の出力は同じ [`CFnDeclDict`] から作るので、名前・パラメータ・THX の扱いが
+//! 食い違わない。`CFnDecl::params` には `my_perl` を含めず、`is_thx` のとき
+//! 両側で先頭に追加する。
+
+use std::io::{self, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::apidoc::ApidocEntry;
+use crate::c_fn_decl::{CFnDecl, CFnDeclDict, CParam};
+use crate::codegen_report::{CodegenReport, ReportItemKind, ReportOutcome};
+use crate::infer_api::InferResult;
+use crate::intern::{InternedStr, StringInterner};
+use crate::rust_codegen::escape_rust_keyword;
+use crate::token::{Token, TokenKind};
+use crate::unified_type::UnifiedType;
+
+/// C 側の関数名の接頭辞
+pub const SHIM_PREFIX: &str = "macrogen_shim_";
+
+/// マクロ名に対応する C 側の関数名
+pub fn shim_symbol(macro_name: &str) -> String {
+    format!("{}{}", SHIM_PREFIX, macro_name)
+}
+
+/// shim の対象になる生成結果
+fn is_fallback_outcome(outcome: ReportOutcome) -> bool {
+    matches!(
+        outcome,
+        ReportOutcome::ParseFailed | ReportOutcome::TypeIncomplete | ReportOutcome::GenericUnsupported
+    )
+}
+
+/// C shim の生成結果（C ファイルの内容と、呼び出し側が報告に使う集計）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CShimOutput {
+    /// C ファイルの内容
+    pub source: String,
+    /// C shim 経由にしたマクロの数
+    pub count: usize,
+    /// 対象だったが C 関数にできなかったマクロと理由
+    pub skipped: Vec<(String, String)>,
+}
+
+/// C shim の集合
+#[derive(Debug, Default)]
+pub struct CShimSet {
+    /// shim の宣言（キーはマクロ名）
+    pub decls: CFnDeclDict,
+    /// 出力順（マクロ名順）
+    order: Vec<InternedStr>,
+    /// 関数形式マクロか（false なら括弧なしで展開する）
+    is_function: Vec<bool>,
+    /// 対象だったが C 関数にできなかったマクロと理由
+    pub skipped: Vec<(String, &'static str)>,
+}
+
+impl CShimSet {
+    /// 生成レポートで shim 対象になったマクロについて、apidoc のシグネチャから shim を作る
+    pub fn collect(result: &InferResult, report: &CodegenReport) -> Self {
+        let interner = result.preprocessor.interner();
+        let threaded = result.perl_build_mode.is_threaded();
+        let mut set = Self::default();
+
+        let mut entries: Vec<_> = report.entries.iter()
+            .filter(|e| e.kind == ReportItemKind::Macro && is_fallback_outcome(e.outcome))
+            .collect();
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+        for entry in entries {
+            let Some(apidoc) = result.apidoc.get(&entry.name) else {
+                continue;
+            };
+            let Some(name) = interner.lookup(&entry.name) else {
+                continue;
+            };
+            let Some(info) = result.infer_ctx.macros.get(&name) else {
+                continue;
+            };
+            let body = result.preprocessor.macros().get(name).map_or(&[][..], |def| &def.body[..]);
+            if let Err(reason) = check_signature(apidoc, info.params.len(), info.is_function)
+                .and_then(|()| check_context(&entry.name, body, interner))
+            {
+                set.skipped.push((entry.name.clone(), reason));
+                continue;
+            }
+
+            let params = info.params.iter().zip(&apidoc.args)
+                .map(|(p, arg)| CParam { name: Some(p.name), ty: arg.ty.clone() })
+                .collect();
+            let location = apidoc.source_file.as_ref()
+                .map(|file| format!("{}:{}", file, apidoc.line_number.unwrap_or(0)));
+            set.insert(CFnDecl {
+                name,
+                params,
+                ret_ty: apidoc.return_type.clone().unwrap_or_default(),
+                // apidoc の T フラグ（スレッドコンテキスト不要）がなければ pTHX_ を取る
+                is_thx: threaded && (!apidoc.flags.no_thread_ctx || info.is_thx_dependent),
+                is_target: info.is_target,
+                location,
+            }, info.is_function);
+        }
+        set
+    }
+
+    fn insert(&mut self, decl: CFnDecl, is_function: bool) {
+        self.order.push(decl.name);
+        self.is_function.push(is_function);
+        self.decls.insert(decl);
+    }
+
+    /// shim の数
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    /// shim がないか
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    /// 出力順の (宣言, 関数形式マクロか)
+    fn iter(&self) -> impl Iterator<Item = (&CFnDecl, bool)> {
+        self.order.iter().zip(&self.is_function)
+            .filter_map(|(name, &is_function)| Some((self.decls.get(*name)?, is_function)))
+    }
+
+    /// C ファイルの内容と集計をまとめる
+    pub fn output(&self, interner: &StringInterner) -> CShimOutput {
+        CShimOutput {
+            source: self.c_source(interner),
+            count: self.len(),
+            skipped: self.skipped.iter()
+                .map(|(name, reason)| (name.clone(), reason.to_string()))
+                .collect(),
+        }
+    }
+
+    /// C ファイルの内容を生成する
+    pub fn c_source(&self, interner: &StringInterner) -> String {
+        let mut out = String::from(
+            "/* Generated by libperl-macrogen: C shims for Perl macros that could not be\n \
+             * translated to Rust. Compile against the perl CORE headers (e.g. with the\n \
+             * cc crate) and link into the crate that includes the generated Rust code. */\n\
+             \n\
+             #define PERL_NO_GET_CONTEXT\n\
+             #include \"EXTERN.h\"\n\
+             #include \"perl.h\"\n\
+             #include \"XSUB.h\"\n",
+        );
+        for (decl, is_function) in self.iter() {
+            let name = interner.get(decl.name);
+            let params: Vec<String> = decl.params.iter()
+                .map(|p| format!("{} {}", p.ty, param_name(p, interner)))
+                .collect();
+            let params = match (decl.is_thx, params.is_empty()) {
+                (true, true) => "pTHX".to_string(),
+                (true, false) => format!("pTHX_ {}", params.join(", ")),
+                (false, true) => "void".to_string(),
+                (false, false) => params.join(", "),
+            };
+            let call = if is_function {
+                let args: Vec<&str> = decl.params.iter().map(|p| param_name(p, interner)).collect();
+                format!("{}({})", name, args.join(", "))
+            } else {
+                name.to_string()
+            };
+            let body = if decl.ret_ty == "void" { format!("{};", call) } else { format!("return {};", call) };
+
+            out.push('\n');
+            if let Some(loc) = &decl.location {
+                out.push_str(&format!("/* {} ({}) */\n", name, loc));
+            }
+            out.push_str(&format!("{} {}({}) {{ {} }}\n", decl.ret_ty, shim_symbol(name), params, body));
+        }
+        out
+    }
+
+    /// Rust 側の `extern "C"` 宣言とラッパー関数を出力する
+    pub fn emit_rust<W: Write>(&self, out: &mut W, interner: &StringInterner) -> io::Result<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        writeln!(out, "// =============================================================================")?;
+        writeln!(out, "// C shims: macros that could not be translated, called through the companion C file")?;
+        writeln!(out, "// =============================================================================")?;
+        writeln!(out)?;
+
+        let sigs: Vec<(&str, String, String, Vec<String>)> = self.iter()
+            .map(|(decl, _)| {
+                let mut params = Vec::new();
+                let mut args = Vec::new();
+                if decl.is_thx {
+                    params.push("my_perl: *mut PerlInterpreter".to_string());
+                    args.push("my_perl".to_string());
+                }
+                for p in &decl.params {
+                    let name = escape_rust_keyword(param_name(p, interner));
+                    params.push(format!("{}: {}", name, rust_type(&p.ty)));
+                    args.push(name);
+                }
+                let ret = if decl.ret_ty == "void" {
+                    String::new()
+                } else {
+                    format!(" -> {}", rust_type(&decl.ret_ty))
+                };
+                (interner.get(decl.name), params.join(", "), ret, args)
+            })
+            .collect();
+
+        writeln!(out, "unsafe extern \"C\" {{")?;
+        for (name, params, ret, _) in &sigs {
+            writeln!(out, "    fn {}({}){};", shim_symbol(name), params, ret)?;
+        }
+        writeln!(out, "}}")?;
+        writeln!(out)?;
+
+        for (name, params, ret, args) in &sigs {
+            writeln!(out, "/// {} - C shim (`{}`)", name, shim_symbol(name))?;
+            writeln!(out, "#[inline]")?;
+            writeln!(out, "pub unsafe fn {}({}){} {{", name, params, ret)?;
+            writeln!(out, "    unsafe {{ {}({}) }}", shim_symbol(name), args.join(", "))?;
+            writeln!(out, "}}")?;
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+}
+
+/// apidoc のシグネチャが C 関数として書けるかを判定する
+fn check_signature(apidoc: &ApidocEntry, param_count: usize, is_function: bool) -> Result<(), &'static str> {
+    if apidoc.has_token_pasting || apidoc.has_token_arg() {
+        return Err("token argument");
+    }
+    if apidoc.is_generic() {
+        return Err("type argument");
+    }
+    if apidoc.args.iter().any(|a| ApidocEntry::is_literal_string_keyword(&a.ty)) {
+        return Err("literal string argument");
+    }
+    if apidoc.args.iter().any(|a| a.ty == "..." || a.ty.is_empty() || a.name.is_empty()) {
+        return Err("variadic or untyped argument");
+    }
+    if apidoc.args.iter().any(|a| a.ty == "void") {
+        return Err("void argument");
+    }
+    if apidoc.return_type.is_none() {
+        return Err("no return type");
+    }
+    if apidoc.args.len() != param_count || (!is_function && !apidoc.args.is_empty()) {
+        return Err("apidoc arguments do not match the macro parameters");
+    }
+    Ok(())
+}
+
+/// XS 関数のローカル変数（`dXSARGS` などで宣言される）
+const CALLER_LOCALS: &[&str] = &["ax", "items", "sp", "mark", "ix", "RETVAL", "targ"];
+
+/// 呼び出し側の文脈に依存しないマクロかを判定する
+///
+/// `dTHX` / `dSP` のような宣言マクロや、XS 関数のローカル変数を参照するマクロは
+/// 別の C 関数の中に移すと意味が変わる（またはコンパイルできない）。
+fn check_context(name: &str, body: &[Token], interner: &StringInterner) -> Result<(), &'static str> {
+    let mut chars = name.chars();
+    if chars.next() == Some('d') && chars.next().is_some_and(|c| c.is_ascii_uppercase()) {
+        return Err("declaration macro");
+    }
+    let uses_locals = body.iter().any(|t| match t.kind {
+        TokenKind::Ident(id) => CALLER_LOCALS.contains(&interner.get(id)),
+        _ => false,
+    });
+    if uses_locals {
+        return Err("uses caller-local variables");
+    }
+    Ok(())
+}
+
+fn param_name<'a>(param: &CParam, interner: &'a StringInterner) -> &'a str {
+    param.name.map_or("arg", |n| interner.get(n))
+}
+
+/// C の型を Rust の型に変換する
+fn rust_type(c_type: &str) -> String {
+    UnifiedType::from_c_str(c_type).to_rust_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::SourceLocation;
+
+    #[test]
+    fn test_c_and_rust_agree() {
+        let mut interner = StringInterner::new();
+        let mut set = CShimSet::default();
+        let sv = interner.intern("sv");
+        let name = interner.intern("SvPV_nolen");
+        set.insert(CFnDecl {
+            name,
+            params: vec![CParam { name: Some(sv), ty: "SV *".to_string() }],
+            ret_ty: "char *".to_string(),
+            is_thx: true,
+            is_target: true,
+            location: Some("sv.h:1".to_string()),
+        }, true);
+        let name = interner.intern("PUTBACK");
+        set.insert(CFnDecl {
+            name,
+            params: vec![],
+            ret_ty: "void".to_string(),
+            is_thx: false,
+            is_target: true,
+            location: None,
+        }, false);
+
+        set.skipped.push(("SvPV".to_string(), "no apidoc signature"));
+        let output = set.output(&interner);
+        assert_eq!(output.count, 2);
+        assert_eq!(output.skipped, vec![("SvPV".to_string(), "no apidoc signature".to_string())]);
+        let c = output.source;
+        assert!(c.contains("char * macrogen_shim_SvPV_nolen(pTHX_ SV * sv) { return SvPV_nolen(sv); }\n"));
+        assert!(c.contains("void macrogen_shim_PUTBACK(void) { PUTBACK; }\n"));
+
+        let mut rust = Vec::new();
+        set.emit_rust(&mut rust, &interner).unwrap();
+        let rust = String::from_utf8(rust).unwrap();
+        assert!(rust.contains(
+            "    fn macrogen_shim_SvPV_nolen(my_perl: *mut PerlInterpreter, sv: *mut SV) -> *mut c_char;\n"
+        ));
+        assert!(rust.contains(
+            "pub unsafe fn SvPV_nolen(my_perl: *mut PerlInterpreter, sv: *mut SV) -> *mut c_char {\n    \
+             unsafe { macrogen_shim_SvPV_nolen(my_perl, sv) }\n}\n"
+        ));
+        assert!(rust.contains("pub unsafe fn PUTBACK() {\n    unsafe { macrogen_shim_PUTBACK() }\n}\n"));
+    }
+
+    #[test]
+    fn test_check_signature() {
+        let entry = ApidocEntry::parse_apidoc_line("=for apidoc Am|void|Newx|void* ptr|int nitems|type").unwrap();
+        assert_eq!(check_signature(&entry, 3, true), Err("type argument"));
+        let entry = ApidocEntry::parse_apidoc_line("=for apidoc Am|char*|SvPV_nolen|SV* sv").unwrap();
+        assert_eq!(check_signature(&entry, 1, true), Ok(()));
+        assert!(check_signature(&entry, 2, true).is_err());
+        let entry = ApidocEntry::parse_apidoc_line("=for apidoc Am||LIKELY|bool expr").unwrap();
+        assert_eq!(check_signature(&entry, 1, true), Err("no return type"));
+        let entry = ApidocEntry::parse_apidoc_line("=for apidoc Amxu|void|BhkDISABLE|BHK *hk|which").unwrap();
+        assert_eq!(check_signature(&entry, 2, true), Err("variadic or untyped argument"));
+    }
+
+    #[test]
+    fn test_check_context() {
+        let mut interner = StringInterner::new();
+        let ident = |interner: &mut StringInterner, s: &str| {
+            Token::new(TokenKind::Ident(interner.intern(s)), SourceLocation::default())
+        };
+        let body = vec![ident(&mut interner, "PL_stack_base"), ident(&mut interner, "ax")];
+        assert_eq!(check_context("XSRETURN", &body, &interner), Err("uses caller-local variables"));
+        assert_eq!(check_context("dTHXa", &[], &interner), Err("declaration macro"));
+        let body = vec![ident(&mut interner, "sv")];
+        assert_eq!(check_context("SvSETMAGIC", &body, &interner), Ok(()));
+    }
+}
@@ -0,0 +1,254 @@
+//! コード生成レポート（JSON 出力用）
+//!
+//! `macro_bindings.rs` のコメントブロックと `CodegenStats` の集計値だけでは
+//! 個々のマクロ / inline 関数がなぜ生成されなかったかを機械的に追えないため、
+//! 名前ごとに 1 レコードの構造化レポートを作る。
+//! Perl バージョン間でカバレッジを比較する CI 用途を想定している。
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::rust_codegen::GenerateStatus;
+
+/// レポート対象の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportItemKind {
+    /// 関数形式マクロ
+    Macro,
+    /// オブジェクトマクロ
+    ObjectMacro,
+    /// inline 関数
+    InlineFn,
+}
+
+/// 最終的な生成結果
+///
+/// `GenerateStatus` は生成前の事前判定なので、生成後に判明する
+/// 未解決シンボルやカスケード降格などはこちらで区別する。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportOutcome {
+    /// 正常生成
+    Success,
+    /// `macro_rules!` として生成
+    MacroRules,
+    /// `pub const` として生成（オブジェクトマクロ）
+    Const,
+    /// パース失敗
+    ParseFailed,
+    /// 型推論不完全（不完全マーカーを含む場合も含む）
+    TypeIncomplete,
+    /// 利用不可関数を呼び出す
+    CallsUnavailable,
+    /// 依存先が生成されなかった
+    CascadeUnavailable,
+    /// 再構成できない goto を含む
+    ContainsGoto,
+    /// ジェネリクス未対応
+    GenericUnsupported,
+    /// 未解決シンボルを含む
+    UnresolvedNames,
+    /// codegen エラー検出
+    CodegenError,
+    /// apidoc patch で抑止
+    Suppressed,
+}
+
+/// ソース位置（ファイルパスは FileRegistry で解決済み）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportLocation {
+    pub file: String,
+    pub line: u32,
+}
+
+/// 生成した Rust 関数 / 定数のシグネチャ
+///
+/// Perl バージョン間の API 比較に使う。定数は `params` が空で
+/// `ret` がその型になる。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportSignature {
+    /// パラメータ（`name: Type` 形式、THX の `my_perl` を含む）
+    pub params: Vec<String>,
+    /// 戻り値の型（定数の場合はその型）
+    pub ret: String,
+}
+
+impl ReportSignature {
+    /// codegen が組み立てたパラメータリスト（`a: T, b: U`）と戻り値の型から作る
+    pub fn new(params: &str, ret: &str) -> Self {
+        Self { params: split_top_level(params), ret: ret.trim().to_string() }
+    }
+
+    /// 定数のシグネチャ
+    pub fn constant(ty: &str) -> Self {
+        Self { params: Vec::new(), ret: ty.trim().to_string() }
+    }
+}
+
+/// 括弧の外のカンマで分割する（`->` の `>` は数えない）
+fn split_top_level(s: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut depth = 0i32;
+    let mut start = 0;
+    let mut prev = ' ';
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' | '<' | '[' => depth += 1,
+            '>' if prev == '-' => {}
+            ')' | '>' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(s[start..i].trim().to_string());
+                start = i + 1;
+            }
+            _ => {}
+        }
+        prev = c;
+    }
+    let last = s[start..].trim();
+    if !last.is_empty() {
+        parts.push(last.to_string());
+    }
+    parts
+}
+
+/// 1 つのマクロ / inline 関数の生成結果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportEntry {
+    /// 名前
+    pub name: String,
+    /// 種類
+    pub kind: ReportItemKind,
+    /// 最終的な生成結果
+    pub outcome: ReportOutcome,
+    /// 事前判定のステータス（マクロのみ）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generate_status: Option<GenerateStatus>,
+    /// THX 依存か
+    pub thx: bool,
+    /// 定義位置
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<ReportLocation>,
+    /// 生成したシグネチャ（生成できた場合のみ）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<ReportSignature>,
+    /// codegen で検出されたエラー
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub codegen_errors: Vec<String>,
+    /// 未解決シンボル名
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unresolved_names: Vec<String>,
+    /// 存在しない呼び出し先
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unavailable_calls: Vec<String>,
+    /// カスケード元（生成されなかった依存先）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cascade_from: Vec<String>,
+    /// 補足（apidoc patch の理由など）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl ReportEntry {
+    /// 新しいエントリを作成
+    pub fn new(name: impl Into<String>, kind: ReportItemKind, outcome: ReportOutcome) -> Self {
+        Self {
+            name: name.into(),
+            kind,
+            outcome,
+            generate_status: None,
+            thx: false,
+            location: None,
+            signature: None,
+            codegen_errors: Vec::new(),
+            unresolved_names: Vec::new(),
+            unavailable_calls: Vec::new(),
+            cascade_from: Vec::new(),
+            reason: None,
+        }
+    }
+}
+
+/// コード生成レポート
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CodegenReport {
+    /// エントリ（出力順）
+    pub entries: Vec<ReportEntry>,
+}
+
+impl CodegenReport {
+    /// 名前でエントリを検索
+    pub fn get(&self, name: &str) -> Option<&ReportEntry> {
+        self.entries.iter().find(|e| e.name == name)
+    }
+
+    /// 指定した結果のエントリ数
+    pub fn count(&self, outcome: ReportOutcome) -> usize {
+        self.entries.iter().filter(|e| e.outcome == outcome).count()
+    }
+
+    /// JSON 文字列に変換
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// JSON ファイルに書き出す
+    pub fn write_json(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writeln!(writer)?;
+        writer.flush()
+    }
+
+    /// `write_json` で保存したレポートを読み込む
+    pub fn read_json(path: &Path) -> io::Result<Self> {
+        let file = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(file)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_json_shape() {
+        let mut entry = ReportEntry::new("SvIV", ReportItemKind::Macro, ReportOutcome::CascadeUnavailable);
+        entry.generate_status = Some(GenerateStatus::Success);
+        entry.thx = true;
+        entry.cascade_from.push("sv_2iv_flags".to_string());
+        let report = CodegenReport { entries: vec![entry] };
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+        let e = &json["entries"][0];
+        assert_eq!(e["name"], "SvIV");
+        assert_eq!(e["kind"], "macro");
+        assert_eq!(e["outcome"], "cascade_unavailable");
+        assert_eq!(e["generate_status"], "success");
+        assert_eq!(e["thx"], true);
+        assert_eq!(e["cascade_from"][0], "sv_2iv_flags");
+        // 空のフィールドは省略される
+        assert!(e.get("codegen_errors").is_none());
+        assert!(e.get("location").is_none());
+        assert_eq!(report.count(ReportOutcome::CascadeUnavailable), 1);
+    }
+
+    #[test]
+    fn test_signature_new() {
+        let sig = ReportSignature::new("my_perl: *mut PerlInterpreter, sv: *mut SV", "IV");
+        assert_eq!(sig.params, vec!["my_perl: *mut PerlInterpreter", "sv: *mut SV"]);
+        assert_eq!(sig.ret, "IV");
+
+        let sig = ReportSignature::new("cb: Option<unsafe extern \"C\" fn(a: I32) -> I32>, n: [u8; 2]", "()");
+        assert_eq!(sig.params, vec!["cb: Option<unsafe extern \"C\" fn(a: I32) -> I32>", "n: [u8; 2]"]);
+        assert_eq!(sig.ret, "()");
+
+        assert!(ReportSignature::new("", "U32").params.is_empty());
+        let sig = ReportSignature::constant("u32");
+        assert!(sig.params.is_empty());
+        assert_eq!(sig.ret, "u32");
+    }
+}
@@ -0,0 +1,388 @@
+//! 生成コードのコンパイル検査
+//!
+//! 生成した `macro_bindings.rs` を bindings.rs と一緒にスクラッチ crate として
+//! ローカルの `rustc` でコンパイルし、エラー位置をそれを含む生成関数名に対応付ける。
+//! Pipeline はこの結果を使って失敗した関数を `[CODEGEN_SUPPRESSED]` に降格し、
+//! エラーがなくなるまで再生成する（`tools/build-error-to-skiplist.pl` の置き換え）。
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// スクラッチ crate 内での生成コードのファイル名
+const GENERATED_FILE_NAME: &str = "macro_bindings.rs";
+
+/// 生成コードが `use libc::{...}` で参照する関数の宣言
+///
+/// スクラッチ crate は外部 crate を持たないため、`LIBC_FUNCTIONS` と同じ
+/// 関数を libc crate と同じシグネチャで宣言するモジュールで代用する。
+const LIBC_SHIM: &str = r#"mod libc {
+    use std::ffi::{c_char, c_int, c_void};
+    unsafe extern "C" {
+        pub fn strcmp(a: *const c_char, b: *const c_char) -> c_int;
+        pub fn strlen(s: *const c_char) -> usize;
+        pub fn strncmp(a: *const c_char, b: *const c_char, n: usize) -> c_int;
+        pub fn strcpy(dst: *mut c_char, src: *const c_char) -> *mut c_char;
+        pub fn strncpy(dst: *mut c_char, src: *const c_char, n: usize) -> *mut c_char;
+        pub fn memset(s: *mut c_void, c: c_int, n: usize) -> *mut c_void;
+        pub fn memchr(s: *const c_void, c: c_int, n: usize) -> *mut c_void;
+        pub fn memcpy(dst: *mut c_void, src: *const c_void, n: usize) -> *mut c_void;
+        pub fn memmove(dst: *mut c_void, src: *const c_void, n: usize) -> *mut c_void;
+    }
+}
+"#;
+
+/// コンパイル検査の設定
+#[derive(Debug, Clone)]
+pub struct CompileCheckConfig {
+    /// rustc コマンド
+    pub rustc: PathBuf,
+    /// Rust edition
+    pub edition: String,
+    /// bindings.rs のパス（None なら Pipeline の bindings を使う）
+    pub bindings_path: Option<PathBuf>,
+    /// スクラッチ crate を置くディレクトリ（None なら一時ディレクトリ）
+    pub scratch_dir: Option<PathBuf>,
+    /// 再生成の最大回数
+    pub max_iterations: usize,
+    /// 抑止した関数を書き出す patches JSON のパス
+    pub patches_out: Option<PathBuf>,
+}
+
+impl Default for CompileCheckConfig {
+    fn default() -> Self {
+        Self {
+            rustc: PathBuf::from("rustc"),
+            edition: "2024".to_string(),
+            bindings_path: None,
+            scratch_dir: None,
+            max_iterations: 8,
+            patches_out: None,
+        }
+    }
+}
+
+impl CompileCheckConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// rustc コマンドを指定
+    pub fn with_rustc(mut self, rustc: impl Into<PathBuf>) -> Self {
+        self.rustc = rustc.into();
+        self
+    }
+
+    /// Rust edition を指定
+    pub fn with_edition(mut self, edition: impl Into<String>) -> Self {
+        self.edition = edition.into();
+        self
+    }
+
+    /// bindings.rs のパスを指定
+    pub fn with_bindings(mut self, path: impl Into<PathBuf>) -> Self {
+        self.bindings_path = Some(path.into());
+        self
+    }
+
+    /// スクラッチ crate のディレクトリを指定
+    pub fn with_scratch_dir(mut self, path: impl Into<PathBuf>) -> Self {
+        self.scratch_dir = Some(path.into());
+        self
+    }
+
+    /// 再生成の最大回数を指定
+    pub fn with_max_iterations(mut self, n: usize) -> Self {
+        self.max_iterations = n;
+        self
+    }
+
+    /// 抑止結果の patches JSON 出力先を指定
+    pub fn with_patches_out(mut self, path: impl Into<PathBuf>) -> Self {
+        self.patches_out = Some(path.into());
+        self
+    }
+}
+
+/// rustc のエラー 1 件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RustcDiagnostic {
+    /// 生成コード内の行番号（1 始まり）。生成コード外のエラーなら None
+    pub line: Option<u32>,
+    /// エラーコード（`E0308` など）
+    pub code: Option<String>,
+    /// メッセージ
+    pub message: String,
+}
+
+impl RustcDiagnostic {
+    /// `[E0308] mismatched types` 形式の 1 行要約
+    pub fn summary(&self) -> String {
+        match &self.code {
+            Some(code) => format!("[{}] {}", code, self.message),
+            None => self.message.clone(),
+        }
+    }
+}
+
+/// コンパイル検査ループの結果
+#[derive(Debug, Clone, Default)]
+pub struct CompileCheckOutcome {
+    /// 実行したコンパイル回数
+    pub iterations: usize,
+    /// 抑止した関数名 → 理由（rustc のメッセージ）
+    pub suppressed: BTreeMap<String, String>,
+    /// 最終回に残ったエラー（生成関数に対応付けられなかったもの）
+    pub remaining: Vec<RustcDiagnostic>,
+    /// 関数を抑止して再生成した回ごとの記録
+    pub rounds: Vec<CompileCheckRound>,
+    /// 抑止結果を書き出した patches JSON と、新たに追加したエントリ数
+    pub patches_written: Option<(PathBuf, usize)>,
+}
+
+/// コンパイル検査で関数を抑止した 1 回分
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompileCheckRound {
+    /// rustc のエラー数
+    pub errors: usize,
+    /// この回に新たに抑止した関数の数
+    pub suppressed: usize,
+}
+
+impl CompileCheckOutcome {
+    /// 最終的にエラーなしでコンパイルできたか
+    pub fn is_clean(&self) -> bool {
+        self.remaining.is_empty()
+    }
+}
+
+/// 生成コードを bindings と一緒にコンパイルし、エラーを返す
+pub fn run_rustc_check(
+    config: &CompileCheckConfig,
+    bindings_path: &Path,
+    generated: &[u8],
+) -> io::Result<Vec<RustcDiagnostic>> {
+    run_rustc_check_in(config, bindings_path, generated, &std::env::temp_dir())
+}
+
+/// `run_rustc_check` の本体。`scratch_dir` 未指定時は `temp_base` の下に一時ディレクトリを作る
+fn run_rustc_check_in(
+    config: &CompileCheckConfig,
+    bindings_path: &Path,
+    generated: &[u8],
+    temp_base: &Path,
+) -> io::Result<Vec<RustcDiagnostic>> {
+    // 一時ディレクトリは呼び出しごとに別の名前で作り、drop でどの経路でも削除する
+    let temp_dir;
+    let dir = match &config.scratch_dir {
+        Some(dir) => {
+            fs::create_dir_all(dir)?;
+            dir.as_path()
+        }
+        None => {
+            temp_dir = tempfile::Builder::new().prefix("libperl-macrogen-check-").tempdir_in(temp_base)?;
+            temp_dir.path()
+        }
+    };
+
+    let bindings_path = fs::canonicalize(bindings_path)?;
+    fs::write(dir.join(GENERATED_FILE_NAME), generated)?;
+    let lib_rs = format!(
+        "#![allow(warnings)]\n{}include!({:?});\ninclude!({:?});\n",
+        LIBC_SHIM,
+        bindings_path.display().to_string(),
+        GENERATED_FILE_NAME,
+    );
+    let lib_path = dir.join("lib.rs");
+    fs::write(&lib_path, lib_rs)?;
+
+    let output = Command::new(&config.rustc)
+        .arg("--edition").arg(&config.edition)
+        .arg("--crate-type").arg("lib")
+        .arg("--crate-name").arg("macrogen_check")
+        .arg("--emit=metadata")
+        .arg("--error-format=json")
+        .arg("--out-dir").arg(dir)
+        .arg(&lib_path)
+        .output()
+        .map_err(|e| io::Error::new(e.kind(), format!("failed to run {}: {}", config.rustc.display(), e)))?;
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let diags = parse_rustc_json(&stderr);
+    // 引数の誤り・sysroot の欠落・ICE などは JSON の診断を出さずに失敗する
+    if !output.status.success() && diags.is_empty() {
+        return Err(io::Error::other(format!(
+            "{} failed ({}): {}",
+            config.rustc.display(), output.status, stderr.trim()
+        )));
+    }
+    Ok(diags)
+}
+
+/// `rustc --error-format=json` の出力からエラーを取り出す
+pub fn parse_rustc_json(stderr: &str) -> Vec<RustcDiagnostic> {
+    let mut diags = Vec::new();
+    for line in stderr.lines() {
+        let Ok(msg) = serde_json::from_str::<serde_json::Value>(line) else {
+            continue;
+        };
+        if msg["level"] != "error" {
+            continue;
+        }
+        let message = msg["message"].as_str().unwrap_or("").to_string();
+        // "aborting due to N previous errors" などの要約は span を持たない
+        if message.starts_with("aborting due to") {
+            continue;
+        }
+        let line = msg["spans"].as_array().and_then(|spans| {
+            spans.iter()
+                .filter(|s| s["is_primary"] == true)
+                .find(|s| s["file_name"].as_str().is_some_and(|f| f.ends_with(GENERATED_FILE_NAME)))
+                .and_then(|s| s["line_start"].as_u64())
+                .map(|l| l as u32)
+        });
+        let code = msg["code"]["code"].as_str().map(|s| s.to_string());
+        diags.push(RustcDiagnostic { line, code, message });
+    }
+    diags
+}
+
+/// エラー行をそれを含む生成アイテム名に対応付ける
+///
+/// 返り値は アイテム名 → 最初のエラー。アイテムに対応付けられなかった
+/// エラーは第 2 要素で返す。
+pub fn map_diagnostics_to_items(
+    generated: &str,
+    diags: &[RustcDiagnostic],
+) -> (BTreeMap<String, RustcDiagnostic>, Vec<RustcDiagnostic>) {
+    // 行番号（1 始まり）→ その行を含むアイテム名
+    let owners: Vec<Option<&str>> = {
+        let mut current = None;
+        generated.lines()
+            .map(|line| {
+                if let Some(name) = item_header_name(line) {
+                    current = Some(name);
+                } else if starts_other_top_level_item(line) {
+                    current = None;
+                }
+                current
+            })
+            .collect()
+    };
+
+    let mut items: BTreeMap<String, RustcDiagnostic> = BTreeMap::new();
+    let mut unmapped = Vec::new();
+    for diag in diags {
+        let owner = diag.line
+            .and_then(|l| owners.get((l as usize).wrapping_sub(1)).copied().flatten());
+        match owner {
+            Some(name) => {
+                items.entry(name.to_string()).or_insert_with(|| diag.clone());
+            }
+            None => unmapped.push(diag.clone()),
+        }
+    }
+    (items, unmapped)
+}
+
+/// トップレベルの生成アイテム（関数・定数・macro_rules!）の名前を返す
+fn item_header_name(line: &str) -> Option<&str> {
+    const PREFIXES: &[&str] = &[
+        "pub unsafe fn ", "pub const unsafe fn ", "pub const fn ", "pub fn ",
+        "pub const ", "macro_rules! ",
+    ];
+    let rest = PREFIXES.iter().find_map(|p| line.strip_prefix(p))?;
+    let end = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(rest.len());
+    (end > 0).then(|| &rest[..end])
+}
+
+/// 生成アイテム以外のトップレベル要素（struct・use 文など）の開始行か
+fn starts_other_top_level_item(line: &str) -> bool {
+    let Some(first) = line.chars().next() else {
+        return false;
+    };
+    // インデントされた行・属性・コメント・閉じ括弧はアイテムの続き
+    !(first.is_whitespace() || line.starts_with('#') || line.starts_with("//") || line.starts_with('}'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rustc_json() {
+        let stderr = concat!(
+            r#"{"$message_type":"diagnostic","message":"mismatched types","code":{"code":"E0308","explanation":null},"level":"error","spans":[{"file_name":"/tmp/x/macro_bindings.rs","line_start":12,"is_primary":true}]}"#, "\n",
+            r#"{"$message_type":"diagnostic","message":"unused variable","code":null,"level":"warning","spans":[]}"#, "\n",
+            r#"{"$message_type":"diagnostic","message":"aborting due to 1 previous error","code":null,"level":"error","spans":[]}"#, "\n",
+        );
+        let diags = parse_rustc_json(stderr);
+        assert_eq!(diags, vec![RustcDiagnostic {
+            line: Some(12),
+            code: Some("E0308".to_string()),
+            message: "mismatched types".to_string(),
+        }]);
+        assert_eq!(diags[0].summary(), "[E0308] mismatched types");
+    }
+
+    #[test]
+    fn test_map_diagnostics_to_items() {
+        let generated = "\
+use std::ffi::c_int;
+/// SvIV
+#[inline]
+pub unsafe fn SvIV(sv: *mut SV) -> IV {
+    bad()
+}
+pub const FOO: c_int = 1;
+macro_rules! NewOp {
+    ($x:expr) => { $x };
+}
+pub struct Bar;
+";
+        let diag = |line| RustcDiagnostic { line: Some(line), code: None, message: String::new() };
+        let (items, unmapped) = map_diagnostics_to_items(
+            generated,
+            &[diag(5), diag(5), diag(7), diag(9), diag(11), diag(1)],
+        );
+        assert_eq!(items.keys().collect::<Vec<_>>(), vec!["FOO", "NewOp", "SvIV"]);
+        assert_eq!(unmapped.len(), 2);
+    }
+
+    #[test]
+    fn test_run_rustc_check_fails_without_diagnostics() {
+        let config = CompileCheckConfig::new().with_edition("1999");
+        let bindings = tempfile::NamedTempFile::new().unwrap();
+        let err = run_rustc_check(&config, bindings.path(), b"pub const FOO: u32 = 1;\n").unwrap_err();
+        assert!(err.to_string().contains("edition"), "{err}");
+    }
+
+    #[test]
+    fn test_run_rustc_check_removes_scratch_dir() {
+        // 他のプロセスと共有しないよう、テスト専用のディレクトリの下に作らせる
+        let temp_base = tempfile::tempdir().unwrap();
+        let check = |config: &CompileCheckConfig, bindings: &Path, generated: &[u8]| {
+            run_rustc_check_in(config, bindings, generated, temp_base.path())
+        };
+        let config = CompileCheckConfig::new();
+        let bindings = tempfile::NamedTempFile::new().unwrap();
+        fs::write(bindings.path(), "pub type U32 = u32;\n").unwrap();
+
+        let diags = check(&config, bindings.path(), b"pub const FOO: U32 = 1;\n").unwrap();
+        assert!(diags.is_empty(), "{diags:?}");
+        let diags = check(&config, bindings.path(), b"pub const FOO: U32 = \"\";\n").unwrap();
+        assert_eq!(diags[0].line, Some(1));
+        // エラーで途中から戻っても残さない
+        assert!(check(&config, Path::new("no-such-bindings.rs"), b"").is_err());
+        let bogus_edition = config.clone().with_edition("1999");
+        assert!(check(&bogus_edition, bindings.path(), b"").is_err());
+        let config = config.with_rustc("no-such-rustc");
+        assert!(check(&config, bindings.path(), b"").is_err());
+
+        let left: Vec<_> = fs::read_dir(temp_base.path()).unwrap().map(|e| e.unwrap().path()).collect();
+        assert!(left.is_empty(), "{left:?}");
+    }
+}
@@ -0,0 +1,716 @@
+//! threaded / non-threaded 両対応の単一出力
+//!
+//! `PerlBuildMode` ごとに生成すると、両方の perl に対応する crate は
+//! 2 つの生成ファイルを持ち回ることになる。そこで 2 つの設定で推論を行い、
+//! 2 つの `InferResult` をアイテム（マクロ / inline 関数）単位でまとめた
+//! [`MergedInferResult`] をもとに、生成コードを 1 つの出力にまとめる。
+//!
+//! - 推論結果もコードも両方で同じになったアイテムは 1 回だけ出力する
+//! - 異なるもの（THX 引数の有無、`PL_` 変数のアクセス方法など）や片方にしか
+//!   ないものは `#[cfg(perl_threaded)]` / `#[cfg(not(perl_threaded))]` を付けて出力する
+//! - PERLVAR アクセサマクロも同様に `#[cfg]` で分ける
+//!
+//! 利用側は build.rs で threaded perl の場合に
+//! `cargo:rustc-cfg=perl_threaded` を出力する。
+//! 何が異なったかは [`DualModeReport`] に記録する。
+
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::ast::DerivedDecl;
+use crate::codegen_report::{CodegenReport, ReportEntry, ReportItemKind, ReportOutcome};
+use crate::infer_api::InferResult;
+use crate::module_split::OutputItem;
+use crate::perl_config::PerlBuildMode;
+use crate::pipeline::{InferredPipeline, PipelineBuilder, PipelineError};
+use crate::rust_codegen::CodegenStats;
+
+/// threaded 版を選ぶ `#[cfg]` の述語
+pub const THREADED_CFG: &str = "perl_threaded";
+/// non-threaded 版を選ぶ `#[cfg]` の述語
+pub const NON_THREADED_CFG: &str = "not(perl_threaded)";
+
+// ============================================================================
+// Report
+// ============================================================================
+
+/// 2 つの build mode で異なった理由
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DualModeDifference {
+    /// THX 依存（`my_perl` 引数の有無）が異なる
+    Thx,
+    /// 生成結果が異なる（片方だけ生成できたなど）
+    Outcome { threaded: ReportOutcome, non_threaded: ReportOutcome },
+    /// シグネチャが異なる
+    Signature,
+    /// 本体のみ異なる（`PL_` 変数のアクセス方法など）
+    Body,
+}
+
+impl std::fmt::Display for DualModeDifference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DualModeDifference::Thx => write!(f, "THX"),
+            DualModeDifference::Outcome { threaded, non_threaded } => {
+                write!(f, "outcome {:?} / {:?}", threaded, non_threaded)
+            }
+            DualModeDifference::Signature => write!(f, "signature"),
+            DualModeDifference::Body => write!(f, "body"),
+        }
+    }
+}
+
+/// 片方の build mode にしかないアイテム
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DualModeItem {
+    pub name: String,
+    pub kind: ReportItemKind,
+}
+
+/// 2 つの build mode で異なったアイテム
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DualModeDiff {
+    pub name: String,
+    pub kind: ReportItemKind,
+    /// 異なった理由（該当するものすべて）
+    pub differences: Vec<DualModeDifference>,
+}
+
+// ============================================================================
+// Inference merge
+// ============================================================================
+
+/// 片方の build mode でのアイテムの推論結果（突き合わせに使う部分）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InferredItem {
+    /// `my_perl` 引数を取るか
+    pub thx: bool,
+    /// パラメータの名前と推論された Rust の型（inline 関数では空）
+    pub params: Vec<(String, String)>,
+    /// 推論された戻り値の Rust の型（inline 関数では `None`）
+    pub return_type: Option<String>,
+}
+
+/// threaded / non-threaded の 2 つの `InferResult` をアイテム単位でまとめたもの
+///
+/// 対象はコード生成の対象になるマクロ（本体のあるもの）と inline 関数。
+#[derive(Debug, Clone, Default)]
+pub struct MergedInferResult {
+    threaded: HashMap<(ReportItemKind, String), InferredItem>,
+    non_threaded: HashMap<(ReportItemKind, String), InferredItem>,
+}
+
+impl MergedInferResult {
+    /// 2 つの推論結果から作成する
+    pub fn new(threaded: &InferResult, non_threaded: &InferResult) -> Self {
+        Self { threaded: summarize(threaded), non_threaded: summarize(non_threaded) }
+    }
+
+    /// 片方ずつの推論結果から作成する
+    pub fn from_items(
+        threaded: HashMap<(ReportItemKind, String), InferredItem>,
+        non_threaded: HashMap<(ReportItemKind, String), InferredItem>,
+    ) -> Self {
+        Self { threaded, non_threaded }
+    }
+
+    /// threaded 版の推論結果
+    pub fn threaded(&self, kind: ReportItemKind, name: &str) -> Option<&InferredItem> {
+        self.threaded.get(&(kind, name.to_string()))
+    }
+
+    /// non-threaded 版の推論結果
+    pub fn non_threaded(&self, kind: ReportItemKind, name: &str) -> Option<&InferredItem> {
+        self.non_threaded.get(&(kind, name.to_string()))
+    }
+
+    /// 推論結果の違い（THX 依存・シグネチャ）。片方にしかない場合は空
+    pub fn differences(&self, kind: ReportItemKind, name: &str) -> Vec<DualModeDifference> {
+        let mut differences = Vec::new();
+        if let (Some(t), Some(n)) = (self.threaded(kind, name), self.non_threaded(kind, name)) {
+            if t.thx != n.thx {
+                differences.push(DualModeDifference::Thx);
+            }
+            if t.params != n.params || t.return_type != n.return_type {
+                differences.push(DualModeDifference::Signature);
+            }
+        }
+        differences
+    }
+
+    /// 両方の推論結果が同じアイテムの数
+    pub fn identical_count(&self) -> usize {
+        self.threaded.iter().filter(|(key, item)| self.non_threaded.get(*key) == Some(*item)).count()
+    }
+}
+
+/// `InferResult` からコード生成対象のアイテムの推論結果を取り出す
+fn summarize(result: &InferResult) -> HashMap<(ReportItemKind, String), InferredItem> {
+    let interner = result.preprocessor.interner();
+    let mut items = HashMap::new();
+
+    let mut param_types = HashMap::new();
+    for (&name, info) in &result.infer_ctx.macros {
+        if !info.is_target || !info.has_body {
+            continue;
+        }
+        result.infer_ctx.cache_param_types_to(name, interner, &mut param_types);
+        let name = interner.get(name).to_string();
+        let kind = if info.is_function { ReportItemKind::Macro } else { ReportItemKind::ObjectMacro };
+        items.insert((kind, name.clone()), InferredItem {
+            thx: info.is_thx_dependent,
+            params: param_types.remove(&name).unwrap_or_default(),
+            return_type: info.get_return_type().map(|ty| ty.to_rust_string(interner)),
+        });
+    }
+
+    for (&name, func_def) in result.inline_fn_dict.iter() {
+        if !func_def.is_target {
+            continue;
+        }
+        let thx = func_def.declarator.derived.iter().any(|d| match d {
+            DerivedDecl::Function(params) => params.params.iter().any(|p| {
+                p.declarator.as_ref()
+                    .and_then(|d| d.name)
+                    .is_some_and(|n| interner.get(n) == "my_perl")
+            }),
+            _ => false,
+        });
+        items.insert(
+            (ReportItemKind::InlineFn, interner.get(name).to_string()),
+            InferredItem { thx, params: Vec::new(), return_type: None },
+        );
+    }
+    items
+}
+
+/// threaded / non-threaded の突き合わせ結果
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DualModeReport {
+    /// 両方で同じコードになり、1 回だけ出力したアイテムの数
+    pub identical: usize,
+    /// `#[cfg]` で分けて出力したアイテム（出力順）
+    pub differing: Vec<DualModeDiff>,
+    /// threaded でのみ出力されたアイテム
+    pub threaded_only: Vec<DualModeItem>,
+    /// non-threaded でのみ出力されたアイテム
+    pub non_threaded_only: Vec<DualModeItem>,
+}
+
+impl DualModeReport {
+    /// 人が読むための一覧
+    pub fn to_text(&self) -> String {
+        let mut out = format!(
+            "=== Dual-mode (threaded / non-threaded) ===\n\
+             identical: {}, differing: {}, threaded only: {}, non-threaded only: {}\n",
+            self.identical, self.differing.len(), self.threaded_only.len(), self.non_threaded_only.len(),
+        );
+        for diff in &self.differing {
+            let reasons: Vec<String> = diff.differences.iter().map(|d| d.to_string()).collect();
+            out.push_str(&format!("  differs: {} ({:?}): {}\n", diff.name, diff.kind, reasons.join(", ")));
+        }
+        for item in &self.threaded_only {
+            out.push_str(&format!("  threaded only: {} ({:?})\n", item.name, item.kind));
+        }
+        for item in &self.non_threaded_only {
+            out.push_str(&format!("  non-threaded only: {} ({:?})\n", item.name, item.kind));
+        }
+        out
+    }
+
+    /// JSON 文字列に変換
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// JSON ファイルに書き出す
+    pub fn write_json(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writeln!(writer)?;
+        writer.flush()
+    }
+}
+
+// ============================================================================
+// Pipeline
+// ============================================================================
+
+/// threaded / non-threaded の 2 つの Pipeline をまとめて実行する
+///
+/// 2 つの builder は同じ入力・オプションで、対象 perl（Config と bindings.rs）
+/// だけを変えたものを渡す。どちらが threaded かは build mode から判断する。
+pub struct DualModePipeline {
+    threaded: PipelineBuilder,
+    non_threaded: PipelineBuilder,
+}
+
+impl DualModePipeline {
+    /// 2 つの builder から作成する
+    ///
+    /// build mode が決まっていない（`with_auto_perl_config` も
+    /// `with_perl_build_mode` も呼んでいない）場合や、両方が同じ
+    /// build mode の場合はエラー。`with_report_json` を指定した場合も
+    /// エラー（突き合わせ結果は [`DualGenerated::report`] で受け取る）。
+    pub fn new(a: PipelineBuilder, b: PipelineBuilder) -> Result<Self, PipelineError> {
+        if a.codegen_config().report_json.is_some() || b.codegen_config().report_json.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "report JSON is not supported in dual-mode generation; use the dual-mode report instead",
+            ).into());
+        }
+        let mode = |builder: &PipelineBuilder| builder.infer_config().perl_build_mode.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "dual-mode generation requires a known perl build mode for both pipelines",
+            )
+        });
+        match (mode(&a)?, mode(&b)?) {
+            (PerlBuildMode::Threaded, PerlBuildMode::NonThreaded) => Ok(Self { threaded: a, non_threaded: b }),
+            (PerlBuildMode::NonThreaded, PerlBuildMode::Threaded) => Ok(Self { threaded: b, non_threaded: a }),
+            (m, _) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("dual-mode generation needs one threaded and one non-threaded perl (both are {:?})", m),
+            ).into()),
+        }
+    }
+
+    /// 両方の推論を実行
+    pub fn infer(self) -> Result<DualInferredPipeline, PipelineError> {
+        let threaded = self.threaded.build()?.infer()?;
+        let non_threaded = self.non_threaded.build()?.infer()?;
+        Ok(DualInferredPipeline { threaded, non_threaded })
+    }
+
+    /// 推論とコード生成をまとめて実行
+    pub fn generate<W: Write>(self, writer: W) -> Result<DualGenerated, PipelineError> {
+        self.infer()?.generate(writer)
+    }
+}
+
+/// 両方の推論が完了した状態
+pub struct DualInferredPipeline {
+    threaded: InferredPipeline,
+    non_threaded: InferredPipeline,
+}
+
+impl DualInferredPipeline {
+    /// threaded perl 向けの推論結果
+    pub fn threaded(&self) -> &InferredPipeline {
+        &self.threaded
+    }
+
+    /// non-threaded perl 向けの推論結果
+    pub fn non_threaded(&self) -> &InferredPipeline {
+        &self.non_threaded
+    }
+
+    /// 2 つの推論結果をアイテム単位でまとめる
+    pub fn merged(&self) -> MergedInferResult {
+        MergedInferResult::new(self.threaded.result(), self.non_threaded.result())
+    }
+
+    /// 両方のコードを生成し、`#[cfg(perl_threaded)]` で 1 つにまとめて書き出す
+    ///
+    /// コンパイル検査・モジュール分割の設定はここでは使わない。
+    pub fn generate<W: Write>(self, mut writer: W) -> Result<DualGenerated, PipelineError> {
+        let inferred = self.merged();
+        let mut thr_buf = Vec::new();
+        let thr = self.threaded.generate_code(&mut thr_buf)?;
+        let mut nothr_buf = Vec::new();
+        let nothr = self.non_threaded.generate_code(&mut nothr_buf)?;
+
+        let thr_code = String::from_utf8_lossy(&thr_buf);
+        let nothr_code = String::from_utf8_lossy(&nothr_buf);
+        let (merged, report) = merge_outputs(
+            ModeOutput { code: &thr_code, items: &thr.items, report: &thr.report },
+            ModeOutput { code: &nothr_code, items: &nothr.items, report: &nothr.report },
+            &inferred,
+        );
+        writer.write_all(merged.as_bytes())?;
+        crate::perlvar_emitter::emit_perlvar_section_dual(
+            &mut writer,
+            &self.threaded.result().perlvar_dict,
+            &self.non_threaded.result().perlvar_dict,
+        )?;
+
+        Ok(DualGenerated {
+            report,
+            threaded_stats: thr.stats,
+            non_threaded_stats: nothr.stats,
+            threaded_report: thr.report,
+            non_threaded_report: nothr.report,
+        })
+    }
+}
+
+/// 両対応コードの生成結果
+pub struct DualGenerated {
+    /// 突き合わせ結果
+    pub report: DualModeReport,
+    /// threaded 版の統計情報
+    pub threaded_stats: CodegenStats,
+    /// non-threaded 版の統計情報
+    pub non_threaded_stats: CodegenStats,
+    /// threaded 版の名前ごとの生成結果
+    pub threaded_report: CodegenReport,
+    /// non-threaded 版の名前ごとの生成結果
+    pub non_threaded_report: CodegenReport,
+}
+
+// ============================================================================
+// Merge
+// ============================================================================
+
+/// 片方の build mode で生成したコード
+#[derive(Debug, Clone, Copy)]
+pub struct ModeOutput<'a> {
+    /// 生成コード全体
+    pub code: &'a str,
+    /// `code` 中のアイテムの範囲
+    pub items: &'a [OutputItem],
+    /// 名前ごとの生成結果
+    pub report: &'a CodegenReport,
+}
+
+/// 生成コードの一部分
+#[derive(Debug, Clone, Copy)]
+enum Part<'a> {
+    /// アイテムの間の部分（ヘッダー・見出し・use 文・自動生成 struct など）
+    Glue(&'a str),
+    /// アイテム
+    Item(&'a OutputItem, &'a str),
+}
+
+/// 突き合わせのキー
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum PartKey<'a> {
+    Glue(&'a str),
+    Item(ReportItemKind, &'a str),
+}
+
+impl<'a> Part<'a> {
+    fn key(&self) -> PartKey<'a> {
+        match *self {
+            Part::Glue(text) => PartKey::Glue(text),
+            Part::Item(item, _) => PartKey::Item(item.kind, &item.name),
+        }
+    }
+
+    fn text(&self) -> &'a str {
+        match *self {
+            Part::Glue(text) | Part::Item(_, text) => text,
+        }
+    }
+}
+
+/// 生成コードをアイテムとその間の部分に分ける（空の部分は除く）
+///
+/// 中身が空白だけのアイテムと、同じ `(kind, name)` の 2 回目以降のアイテム
+/// （オブジェクトマクロとして再度区切られたものなど）は前後の部分と合わせて
+/// 1 つの `Glue` にする。
+fn split_parts<'a>(out: &ModeOutput<'a>) -> Vec<Part<'a>> {
+    let mut parts = Vec::new();
+    let mut seen = HashSet::new();
+    let mut pos = 0;
+    for item in out.items {
+        let text = &out.code[item.start..item.end];
+        if text.trim().is_empty() || !seen.insert((item.kind, item.name.as_str())) {
+            continue;
+        }
+        if item.start > pos {
+            parts.push(Part::Glue(&out.code[pos..item.start]));
+        }
+        parts.push(Part::Item(item, text));
+        pos = item.end;
+    }
+    if pos < out.code.len() {
+        parts.push(Part::Glue(&out.code[pos..]));
+    }
+    parts
+}
+
+/// 2 つの生成コードを `#[cfg(perl_threaded)]` で 1 つにまとめる
+///
+/// 順序は threaded 版に従い、non-threaded 版にしかない部分は
+/// non-threaded 版で直前にあった部分の後ろに置く。
+/// アイテムは `inferred` の推論結果とコードの両方が同じ場合だけ 1 回出力し、
+/// 異なった理由は推論結果の違いと生成結果から判定する。
+pub fn merge_outputs(
+    threaded: ModeOutput<'_>,
+    non_threaded: ModeOutput<'_>,
+    inferred: &MergedInferResult,
+) -> (String, DualModeReport) {
+    let a = split_parts(&threaded);
+    let b = split_parts(&non_threaded);
+    let a_keys: HashSet<PartKey> = a.iter().map(|p| p.key()).collect();
+    // 同じ見出しなど、同じキーの Glue は出現順に対応させる
+    let mut b_index: HashMap<PartKey, Vec<usize>> = HashMap::new();
+    for (i, part) in b.iter().enumerate() {
+        b_index.entry(part.key()).or_default().push(i);
+    }
+    let thr_entries = report_index(threaded.report);
+    let nothr_entries = report_index(non_threaded.report);
+
+    let mut out = String::new();
+    let mut report = DualModeReport::default();
+    let mut consumed = vec![false; b.len()];
+    let mut j = 0;
+
+    let emit_only = |out: &mut String, report: &mut DualModeReport, part: &Part, is_threaded: bool| {
+        let cfg = if is_threaded { THREADED_CFG } else { NON_THREADED_CFG };
+        out.push_str(&gate_items(part.text(), cfg));
+        if let Part::Item(item, _) = part {
+            let entry = DualModeItem { name: item.name.clone(), kind: item.kind };
+            if is_threaded {
+                report.threaded_only.push(entry);
+            } else {
+                report.non_threaded_only.push(entry);
+            }
+        }
+    };
+
+    for part in &a {
+        let next = b_index.get(&part.key()).and_then(|ks| ks.iter().copied().find(|&k| !consumed[k]));
+        let Some(k) = next else {
+            emit_only(&mut out, &mut report, part, true);
+            continue;
+        };
+        // 対応する部分より前にある non-threaded 専用の部分を先に出す
+        while j < k {
+            if !consumed[j] && !a_keys.contains(&b[j].key()) {
+                emit_only(&mut out, &mut report, &b[j], false);
+                consumed[j] = true;
+            }
+            j += 1;
+        }
+        consumed[k] = true;
+
+        let (thr_text, nothr_text) = (part.text(), b[k].text());
+        let Part::Item(item, _) = part else {
+            if thr_text == nothr_text {
+                out.push_str(thr_text);
+            } else {
+                out.push_str(&gate_items(thr_text, THREADED_CFG));
+                out.push_str(&gate_items(nothr_text, NON_THREADED_CFG));
+            }
+            continue;
+        };
+        let differences = classify_difference(
+            inferred.differences(item.kind, &item.name),
+            thr_entries.get(item.name.as_str()).copied(),
+            nothr_entries.get(item.name.as_str()).copied(),
+            thr_text,
+            nothr_text,
+        );
+        if differences.is_empty() {
+            out.push_str(thr_text);
+            report.identical += 1;
+            continue;
+        }
+        out.push_str(&gate_items(thr_text, THREADED_CFG));
+        out.push_str(&gate_items(nothr_text, NON_THREADED_CFG));
+        report.differing.push(DualModeDiff { name: item.name.clone(), kind: item.kind, differences });
+    }
+    for (i, part) in b.iter().enumerate() {
+        if !consumed[i] {
+            emit_only(&mut out, &mut report, part, false);
+        }
+    }
+
+    (out, report)
+}
+
+/// レポートを名前で引けるようにする
+fn report_index(report: &CodegenReport) -> HashMap<&str, &ReportEntry> {
+    report.entries.iter().map(|e| (e.name.as_str(), e)).collect()
+}
+
+/// 同名アイテムが異なった理由を判定する（同じなら空）
+///
+/// `inferred` は推論結果の違い（[`MergedInferResult::differences`]）。
+/// 推論結果・生成結果が同じでコードだけ異なる場合はシグネチャ行か本体かで分ける。
+fn classify_difference(
+    inferred: Vec<DualModeDifference>,
+    threaded: Option<&ReportEntry>,
+    non_threaded: Option<&ReportEntry>,
+    thr_text: &str,
+    nothr_text: &str,
+) -> Vec<DualModeDifference> {
+    let mut differences = inferred;
+    if let (Some(t), Some(n)) = (threaded, non_threaded)
+        && t.outcome != n.outcome
+    {
+        differences.push(DualModeDifference::Outcome { threaded: t.outcome, non_threaded: n.outcome });
+    }
+    if differences.is_empty() && thr_text != nothr_text {
+        if signature_line(thr_text) != signature_line(nothr_text) {
+            differences.push(DualModeDifference::Signature);
+        } else {
+            differences.push(DualModeDifference::Body);
+        }
+    }
+    differences
+}
+
+/// アイテムの最初の宣言行の `{` より前（`pub unsafe fn ...(...) -> T` など）
+fn signature_line(text: &str) -> Option<&str> {
+    text.lines()
+        .find(|line| starts_item(line))
+        .map(|line| line.split('{').next().unwrap_or(line).trim_end())
+}
+
+/// 行頭から始まるトップレベルアイテムの宣言行か
+fn starts_item(line: &str) -> bool {
+    const KEYWORDS: &[&str] = &[
+        "pub ", "pub(", "fn ", "unsafe ", "const ", "static ", "struct ", "union ", "enum ",
+        "type ", "impl ", "impl<", "use ", "mod ", "extern ", "trait ", "macro_rules!",
+    ];
+    KEYWORDS.iter().any(|kw| line.starts_with(kw))
+}
+
+/// トップレベルのアイテムそれぞれに `#[cfg(...)]` を付ける
+///
+/// アイテム直前の doc コメント・属性の並びがあれば、その先頭に付ける。
+/// アイテムを含まない部分（失敗理由のコメントだけの出力など）はそのまま返す。
+pub fn gate_items(text: &str, cfg: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut attrs_start: Option<usize> = None;
+    for line in text.split_inclusive('\n') {
+        if line.starts_with("///") || (line.starts_with("#[") && !line.starts_with("#![")) {
+            attrs_start.get_or_insert(out.len());
+        } else if starts_item(line) {
+            let at = attrs_start.take().unwrap_or(out.len());
+            out.insert_str(at, &format!("#[cfg({})]\n", cfg));
+        } else {
+            attrs_start = None;
+        }
+        out.push_str(line);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `/// 名前` の行から次の `///` の行までを 1 アイテムとする
+    fn items(code: &str, names: &[&str]) -> Vec<OutputItem> {
+        names.iter()
+            .map(|name| {
+                let start = code.find(&format!("/// {}\n", name)).unwrap();
+                let end = code[start + 1..].find("/// ").map_or(code.len(), |i| start + 1 + i);
+                OutputItem { name: name.to_string(), kind: ReportItemKind::Macro, start, end }
+            })
+            .collect()
+    }
+
+    fn inferred_item(name: &str, thx: bool) -> ((ReportItemKind, String), InferredItem) {
+        let params = vec![("sv".to_string(), "*mut SV".to_string())];
+        ((ReportItemKind::Macro, name.to_string()), InferredItem { thx, params, return_type: None })
+    }
+
+    #[test]
+    fn test_gate_items() {
+        let text = "// [PARSE_FAILED] X\n\n/// doc\n#[inline]\npub unsafe fn f() {\n    g();\n}\n\nmacro_rules! m { () => {} }\n";
+        assert_eq!(
+            gate_items(text, THREADED_CFG),
+            "// [PARSE_FAILED] X\n\n#[cfg(perl_threaded)]\n/// doc\n#[inline]\npub unsafe fn f() {\n    g();\n}\n\n\
+             #[cfg(perl_threaded)]\nmacro_rules! m { () => {} }\n",
+        );
+        assert_eq!(gate_items("// comment only\n", THREADED_CFG), "// comment only\n");
+    }
+
+    #[test]
+    fn test_merge_outputs() {
+        let mut thr_report = CodegenReport::default();
+        let mut thx = ReportEntry::new("SvREFCNT_inc", ReportItemKind::Macro, ReportOutcome::Success);
+        thx.thx = true;
+        thr_report.entries.push(thx);
+        let nothr_report = CodegenReport {
+            entries: vec![ReportEntry::new("SvREFCNT_inc", ReportItemKind::Macro, ReportOutcome::Success)],
+        };
+
+        let thr_code = "// header\n\
+            /// SvOK\npub unsafe fn SvOK(sv: *mut SV) -> bool { f(sv) }\n\n\
+            /// SvREFCNT_inc\npub unsafe fn SvREFCNT_inc(my_perl: *mut PerlInterpreter, sv: *mut SV) {}\n\n\
+            /// dTHX\nmacro_rules! dTHX { () => {} }\n\n";
+        let nothr_code = "// header\n\
+            /// SvOK\npub unsafe fn SvOK(sv: *mut SV) -> bool { f(sv) }\n\n\
+            /// PL_stack\npub unsafe fn PL_stack() {}\n\n\
+            /// SvREFCNT_inc\npub unsafe fn SvREFCNT_inc(sv: *mut SV) {}\n\n";
+        let thr_items = items(thr_code, &["SvOK", "SvREFCNT_inc", "dTHX"]);
+        let nothr_items = items(nothr_code, &["SvOK", "PL_stack", "SvREFCNT_inc"]);
+
+        let inferred = MergedInferResult::from_items(
+            HashMap::from([inferred_item("SvOK", false), inferred_item("SvREFCNT_inc", true)]),
+            HashMap::from([inferred_item("SvOK", false), inferred_item("SvREFCNT_inc", false)]),
+        );
+        let (merged, report) = merge_outputs(
+            ModeOutput { code: thr_code, items: &thr_items, report: &thr_report },
+            ModeOutput { code: nothr_code, items: &nothr_items, report: &nothr_report },
+            &inferred,
+        );
+        assert_eq!(
+            merged,
+            "// header\n\
+             /// SvOK\npub unsafe fn SvOK(sv: *mut SV) -> bool { f(sv) }\n\n\
+             #[cfg(not(perl_threaded))]\n/// PL_stack\npub unsafe fn PL_stack() {}\n\n\
+             #[cfg(perl_threaded)]\n/// SvREFCNT_inc\npub unsafe fn SvREFCNT_inc(my_perl: *mut PerlInterpreter, sv: *mut SV) {}\n\n\
+             #[cfg(not(perl_threaded))]\n/// SvREFCNT_inc\npub unsafe fn SvREFCNT_inc(sv: *mut SV) {}\n\n\
+             #[cfg(perl_threaded)]\n/// dTHX\nmacro_rules! dTHX { () => {} }\n\n",
+        );
+        assert_eq!(report.identical, 1);
+        assert_eq!(report.differing.len(), 1);
+        assert_eq!(report.differing[0].name, "SvREFCNT_inc");
+        assert_eq!(report.differing[0].differences, vec![DualModeDifference::Thx]);
+        assert_eq!(report.threaded_only, vec![DualModeItem { name: "dTHX".to_string(), kind: ReportItemKind::Macro }]);
+        assert_eq!(report.non_threaded_only[0].name, "PL_stack");
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+        assert_eq!(json["differing"][0]["differences"][0]["kind"], "thx");
+        assert!(report.to_text().contains("differs: SvREFCNT_inc (Macro): THX"));
+    }
+
+    #[test]
+    fn test_merge_outputs_skips_empty_and_duplicate_items() {
+        // 空のアイテム（生成しなかった PERL_ARGS_ASSERT_X）と、
+        // オブジェクトマクロとしてもう一度区切られた dSP
+        let code = "// header\n\
+            /// dSP\nmacro_rules! dSP { () => {} }\n\n\
+            /// dSP\n// dup\n";
+        let dsp = code.find("/// dSP").unwrap();
+        let dup = code.rfind("/// dSP").unwrap();
+        let items = vec![
+            OutputItem { name: "PERL_ARGS_ASSERT_X".to_string(), kind: ReportItemKind::Macro, start: dsp, end: dsp },
+            OutputItem { name: "dSP".to_string(), kind: ReportItemKind::Macro, start: dsp, end: dup },
+            OutputItem { name: "dSP".to_string(), kind: ReportItemKind::Macro, start: dup, end: code.len() },
+        ];
+        let report = CodegenReport::default();
+        let out = ModeOutput { code, items: &items, report: &report };
+        let (merged, report) = merge_outputs(out, out, &MergedInferResult::default());
+        assert_eq!(merged, code);
+        assert_eq!(report.identical, 1);
+        assert!(report.differing.is_empty());
+        assert!(report.threaded_only.is_empty() && report.non_threaded_only.is_empty());
+    }
+
+    #[test]
+    fn test_classify_body_difference() {
+        let thr = "/// X\npub unsafe fn X(my_perl: *mut PerlInterpreter) -> I32 { (*my_perl).Itmps_ix }\n";
+        let nothr = "/// X\npub unsafe fn X(my_perl: *mut PerlInterpreter) -> I32 { PL_tmps_ix }\n";
+        assert_eq!(classify_difference(Vec::new(), None, None, thr, nothr), vec![DualModeDifference::Body]);
+        let nothr = "/// X\npub unsafe fn X() -> I32 { PL_tmps_ix }\n";
+        assert_eq!(classify_difference(Vec::new(), None, None, thr, nothr), vec![DualModeDifference::Signature]);
+        assert!(classify_difference(Vec::new(), None, None, thr, thr).is_empty());
+        assert_eq!(
+            classify_difference(vec![DualModeDifference::Thx], None, None, thr, thr),
+            vec![DualModeDifference::Thx],
+        );
+    }
+}
@@ -1,7 +1,7 @@
 use crate::source::{FileRegistry, SourceLocation};
 use crate::token::TokenKind;
 use std::fmt;
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 
 /// エラー表示用のロケーション（ファイル名解決付き）
 pub struct DisplayLocation<'a> {
@@ -152,6 +152,15 @@ impl fmt::Display for ParseError {
     }
 }
 
+/// マクロ展開の 1 段（エラーのバックトレース用）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpansionFrame {
+    /// 展開されたマクロ名
+    pub macro_name: String,
+    /// マクロの呼び出し位置
+    pub call_loc: SourceLocation,
+}
+
 /// 統合エラー型
 #[derive(Debug)]
 pub enum CompileError {
@@ -161,6 +170,10 @@ pub enum CompileError {
     Preprocess { loc: SourceLocation, kind: PPError },
     /// パースエラー
     Parse { loc: SourceLocation, kind: ParseError },
+    /// マクロ展開中に発生したエラー
+    ///
+    /// `backtrace` は外側の展開から内側の展開の順。
+    InMacroExpansion { error: Box<CompileError>, backtrace: Vec<ExpansionFrame> },
 }
 
 impl fmt::Display for CompileError {
@@ -175,6 +188,7 @@ impl fmt::Display for CompileError {
             CompileError::Parse { loc, kind } => {
                 write!(f, "{}:{}:{}: parse error: {}", loc.file_id.as_u32(), loc.line, loc.column, kind)
             }
+            CompileError::InMacroExpansion { error, .. } => write!(f, "{}", error),
         }
     }
 }
@@ -188,6 +202,44 @@ impl CompileError {
             CompileError::Lex { loc, .. } => loc,
             CompileError::Preprocess { loc, .. } => loc,
             CompileError::Parse { loc, .. } => loc,
+            CompileError::InMacroExpansion { error, .. } => error.loc(),
+        }
+    }
+
+    /// マクロ展開のバックトレースを付ける
+    ///
+    /// `backtrace` が空の場合や、既に付いている場合（内側で付けたものを優先）は
+    /// そのまま返す。
+    pub fn with_backtrace(self, backtrace: Vec<ExpansionFrame>) -> Self {
+        if backtrace.is_empty() || matches!(self, CompileError::InMacroExpansion { .. }) {
+            return self;
+        }
+        CompileError::InMacroExpansion { error: Box::new(self), backtrace }
+    }
+
+    /// マクロ展開のバックトレース（外側から内側へ）
+    pub fn backtrace(&self) -> &[ExpansionFrame] {
+        match self {
+            CompileError::InMacroExpansion { backtrace, .. } => backtrace,
+            _ => &[],
+        }
+    }
+
+    /// バックトレースを除いた元のエラー
+    pub fn base(&self) -> &CompileError {
+        match self {
+            CompileError::InMacroExpansion { error, .. } => error.base(),
+            _ => self,
+        }
+    }
+
+    /// エラー種別のラベルとメッセージ
+    fn label_and_message(&self) -> (&'static str, String) {
+        match self.base() {
+            CompileError::Lex { kind, .. } => ("lexer error", kind.to_string()),
+            CompileError::Preprocess { kind, .. } => ("preprocessor error", kind.to_string()),
+            CompileError::Parse { kind, .. } => ("parse error", kind.to_string()),
+            CompileError::InMacroExpansion { .. } => unreachable!("base() never returns InMacroExpansion"),
         }
     }
 
@@ -206,7 +258,25 @@ impl CompileError {
                 let disp = DisplayLocation { loc, files };
                 format!("{}: parse error: {}", disp, kind)
             }
+            CompileError::InMacroExpansion { error, .. } => error.format_with_files(files),
+        }
+    }
+
+    /// gcc / rustc 風の複数行の診断メッセージにする
+    ///
+    /// `format_with_files` の 1 行に続けて、ソースの該当行と caret、
+    /// マクロ展開 1 段ごとの `in expansion of macro` ノート（内側から外側へ）を出す。
+    /// ソースが読めない場合（`<cmdline>` など）は該当行を省略する。
+    pub fn render(&self, files: &FileRegistry) -> String {
+        let loc = self.loc();
+        let source_line = files.source_line(loc.file_id, loc.line);
+        let mut out = self.format_with_files(files);
+        let _ = write_snippet(&mut out, loc, source_line.as_deref());
+        for note in expansion_notes(self.backtrace(), files) {
+            out.push('\n');
+            out.push_str(&note);
         }
+        out
     }
 
     /// FileRegistry を使い、ファイルパスとソース該当行を付加した
@@ -217,26 +287,38 @@ impl CompileError {
     pub fn with_files(self, files: &FileRegistry) -> EnrichedCompileError {
         let loc = self.loc().clone();
         let file_path = files.try_get_path(loc.file_id).map(|p| p.to_path_buf());
-        let source_line = file_path.as_deref().and_then(|p| read_source_line(p, loc.line));
+        let source_line = files.source_line(loc.file_id, loc.line);
+        let expansion_notes = expansion_notes(self.backtrace(), files);
         EnrichedCompileError {
             error: self,
             file_path,
             source_line,
+            expansion_notes,
         }
     }
 }
 
-/// 該当行 1 行をファイルから読み出す。失敗時は None を返す。
-fn read_source_line(path: &Path, line: u32) -> Option<String> {
-    use std::io::{BufRead, BufReader};
-    if line == 0 {
-        return None;
+/// バックトレースを `= note: in expansion of macro ...` 行にする（内側から外側へ）
+fn expansion_notes(backtrace: &[ExpansionFrame], files: &FileRegistry) -> Vec<String> {
+    backtrace.iter().rev()
+        .map(|frame| {
+            let at = match files.try_get_path(frame.call_loc.file_id) {
+                Some(p) => format!("{}:{}", p.display(), frame.call_loc.line),
+                None => format!("<file_id={}>:{}", frame.call_loc.file_id.as_u32(), frame.call_loc.line),
+            };
+            format!("       = note: in expansion of macro `{}` at {}", frame.macro_name, at)
+        })
+        .collect()
+}
+
+/// 該当行と caret を書き出す
+fn write_snippet(out: &mut impl fmt::Write, loc: &SourceLocation, source_line: Option<&str>) -> fmt::Result {
+    if let Some(line_text) = source_line {
+        write!(out, "\n  {:>4} | {}", loc.line, line_text)?;
+        let col = (loc.column as usize).saturating_sub(1);
+        write!(out, "\n       | {}^", " ".repeat(col))?;
     }
-    let f = std::fs::File::open(path).ok()?;
-    let reader = BufReader::new(f);
-    let mut iter = reader.lines();
-    let target = (line as usize).saturating_sub(1);
-    iter.nth(target).and_then(|r| r.ok())
+    Ok(())
 }
 
 /// CompileError + ファイルパス・該当行で強化されたエラー。
@@ -249,6 +331,8 @@ pub struct EnrichedCompileError {
     pub error: CompileError,
     pub file_path: Option<PathBuf>,
     pub source_line: Option<String>,
+    /// マクロ展開ごとのノート行（内側から外側へ）
+    pub expansion_notes: Vec<String>,
 }
 
 impl EnrichedCompileError {
@@ -266,11 +350,7 @@ impl EnrichedCompileError {
 impl fmt::Display for EnrichedCompileError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let loc = self.error.loc();
-        let (kind_label, kind_msg) = match &self.error {
-            CompileError::Lex { kind, .. } => ("lexer error", format!("{}", kind)),
-            CompileError::Preprocess { kind, .. } => ("preprocessor error", format!("{}", kind)),
-            CompileError::Parse { kind, .. } => ("parse error", format!("{}", kind)),
-        };
+        let (kind_label, kind_msg) = self.error.label_and_message();
         match &self.file_path {
             Some(p) => write!(
                 f,
@@ -283,10 +363,9 @@ impl fmt::Display for EnrichedCompileError {
                 loc.file_id.as_u32(), loc.line, loc.column, kind_label, kind_msg
             )?,
         }
-        if let Some(line_text) = &self.source_line {
-            write!(f, "\n  {:>4} | {}", loc.line, line_text)?;
-            let col = (loc.column as usize).saturating_sub(1);
-            write!(f, "\n       | {}^", " ".repeat(col))?;
+        write_snippet(f, loc, self.source_line.as_deref())?;
+        for note in &self.expansion_notes {
+            write!(f, "\n{}", note)?;
         }
         Ok(())
     }
@@ -321,4 +400,27 @@ mod tests {
         };
         assert!(format!("{}", err).contains("invalid character"));
     }
+
+    #[test]
+    fn test_with_backtrace() {
+        let loc = SourceLocation::new(FileId::default(), 3, 9);
+        let frame = |name: &str| ExpansionFrame { macro_name: name.to_string(), call_loc: loc.clone() };
+        let err = CompileError::Parse { loc: loc.clone(), kind: ParseError::UnexpectedEof };
+        assert!(matches!(err.with_backtrace(Vec::new()), CompileError::Parse { .. }));
+
+        let err = CompileError::Parse { loc: loc.clone(), kind: ParseError::UnexpectedEof }
+            .with_backtrace(vec![frame("INNER")])
+            .with_backtrace(vec![frame("OUTER")]);
+        // 先に付けたバックトレースを保つ
+        assert_eq!(err.backtrace(), &[frame("INNER")][..]);
+        assert!(matches!(err.base(), CompileError::Parse { .. }));
+        assert_eq!(err.loc(), &loc);
+
+        let mut files = FileRegistry::new();
+        files.register(PathBuf::from("<cmdline>"));
+        assert_eq!(
+            err.render(&files),
+            "<cmdline>:3:9: parse error: unexpected end of file\n       = note: in expansion of macro `INNER` at <cmdline>:3",
+        );
+    }
 }
@@ -0,0 +1,360 @@
+//! マクロ展開のトレース
+//!
+//! 生成されたラッパーがおかしいとき、マクロがどう展開されたかを段階的に
+//! 追えるようにする。`--emit-macro-markers` や `--debug-pp` の出力は平坦な
+//! トークン列なので、ここでは MacroBegin/MacroEnd マーカーの入れ子から
+//! 展開木を復元し、Preprocessor が展開時に記録した情報
+//! （引数の prescan 結果・置換直後のトークン列・展開を止めた識別子）を
+//! 各ノードに付ける。
+//!
+//! 引数の prescan で展開されたマクロは、置換後の本体にもマーカーごと
+//! 埋め込まれて現れる。これらは呼び出し側の子ノードではなく、
+//! 該当する引数（[`TracedArg::expansions`]）の下に置く。
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Write as _};
+
+use serde::Serialize;
+
+use crate::intern::{InternedStr, StringInterner};
+use crate::source::{FileRegistry, SourceLocation};
+use crate::token::{Token, TokenId, TokenKind};
+
+/// 展開されなかった理由
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BlockReason {
+    /// 展開中のマクロ自身（NoExpandRegistry による再帰防止）
+    SelfReference,
+    /// 展開抑制リスト（bindings.rs の定数など）
+    SkipExpand,
+}
+
+impl fmt::Display for BlockReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockReason::SelfReference => write!(f, "already being expanded"),
+            BlockReason::SkipExpand => write!(f, "in skip-expand list"),
+        }
+    }
+}
+
+/// 展開されなかったマクロ名
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BlockedMacro {
+    pub name: String,
+    pub reason: BlockReason,
+}
+
+/// 関数マクロの引数 1 つ分
+#[derive(Debug, Clone, Serialize)]
+pub struct TracedArg {
+    /// パラメータ名
+    pub param: String,
+    /// 展開前の引数
+    pub raw: String,
+    /// prescan 後の引数
+    pub expanded: String,
+    /// prescan 中に展開されたマクロ
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub expansions: Vec<ExpansionNode>,
+}
+
+/// 1 回のマクロ呼び出し
+#[derive(Debug, Clone, Serialize)]
+pub struct ExpansionNode {
+    /// マクロ名
+    pub name: String,
+    /// 定義位置（`file:line`）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub defined_at: Option<String>,
+    /// 引数（オブジェクトマクロなら None）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub args: Option<Vec<TracedArg>>,
+    /// 引数置換・`#`・`##` を適用した直後（再走査前）
+    pub replacement: String,
+    /// 再走査まで終えた最終結果
+    pub result: String,
+    /// 再走査中に展開を止めた識別子
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub blocked: Vec<BlockedMacro>,
+    /// 再走査中に展開されたマクロ
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<ExpansionNode>,
+}
+
+impl ExpansionNode {
+    /// `NAME(arg, ...)` 形式の呼び出し表記
+    pub fn invocation(&self) -> String {
+        match &self.args {
+            Some(args) => {
+                let args: Vec<&str> = args.iter().map(|a| a.raw.as_str()).collect();
+                format!("{}({})", self.name, args.join(", "))
+            }
+            None => self.name.clone(),
+        }
+    }
+
+    fn write_text(&self, out: &mut String, indent: usize) {
+        let pad = "  ".repeat(indent);
+        let _ = write!(out, "{}{}", pad, self.invocation());
+        if let Some(at) = &self.defined_at {
+            let _ = write!(out, "  [{}]", at);
+        }
+        out.push('\n');
+        for arg in self.args.iter().flatten() {
+            if arg.raw == arg.expanded {
+                let _ = writeln!(out, "{}  arg {}: {}", pad, arg.param, arg.raw);
+            } else {
+                let _ = writeln!(out, "{}  arg {}: {} -> {}", pad, arg.param, arg.raw, arg.expanded);
+            }
+            for node in &arg.expansions {
+                node.write_text(out, indent + 2);
+            }
+        }
+        let _ = writeln!(out, "{}  replacement: {}", pad, self.replacement);
+        for child in &self.children {
+            child.write_text(out, indent + 1);
+        }
+        for blocked in &self.blocked {
+            let _ = writeln!(out, "{}  blocked: {} ({})", pad, blocked.name, blocked.reason);
+        }
+        if !self.children.is_empty() {
+            let _ = writeln!(out, "{}  result: {}", pad, self.result);
+        }
+    }
+}
+
+/// 展開トレースの結果
+#[derive(Debug, Clone, Serialize)]
+pub struct ExpansionTrace {
+    /// 展開した入力
+    pub input: String,
+    /// 最終的な展開結果
+    pub result: String,
+    /// 入力中で展開されたマクロ
+    pub expansions: Vec<ExpansionNode>,
+    /// 入力中で展開を止めた識別子
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub blocked: Vec<BlockedMacro>,
+}
+
+impl ExpansionTrace {
+    /// マーカー付きの展開結果と記録から展開木を組み立てる
+    pub(crate) fn build(
+        input: &str,
+        tokens: &[Token],
+        recorder: &ExpansionRecorder,
+        interner: &StringInterner,
+        files: &FileRegistry,
+    ) -> Self {
+        let builder = TreeBuilder { recorder, interner, files };
+        let root = builder.build(tokens, &HashSet::new());
+        Self {
+            input: input.to_string(),
+            result: builder.text(&root.tokens),
+            expansions: root.children,
+            blocked: root.blocked,
+        }
+    }
+
+    /// インデント付きのテキスト表現
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        for node in &self.expansions {
+            node.write_text(&mut out, 0);
+        }
+        for blocked in &self.blocked {
+            let _ = writeln!(out, "blocked: {} ({})", blocked.name, blocked.reason);
+        }
+        let _ = writeln!(out, "=> {}", self.result);
+        out
+    }
+}
+
+/// 記録する引数 1 つ分: (パラメータ, 生の引数, prescan 後の引数)
+pub(crate) type RecordedArg = (InternedStr, Vec<Token>, Vec<Token>);
+
+/// 展開 1 回分の記録
+#[derive(Debug, Clone)]
+pub(crate) struct RecordedInvocation {
+    /// マクロの定義位置
+    pub def_loc: SourceLocation,
+    /// 関数マクロの引数
+    pub args: Option<Vec<RecordedArg>>,
+    /// 置換直後のトークン列
+    pub replacement: Vec<Token>,
+}
+
+/// Preprocessor が展開中に記録する情報
+#[derive(Debug, Default)]
+pub(crate) struct ExpansionRecorder {
+    /// MacroBegin のマーカーID → 展開の記録
+    invocations: HashMap<TokenId, RecordedInvocation>,
+    /// 展開を止めたトークンのID → (マクロ名, 理由)
+    blocked: HashMap<TokenId, (InternedStr, BlockReason)>,
+}
+
+impl ExpansionRecorder {
+    pub fn record_invocation(&mut self, marker_id: TokenId, invocation: RecordedInvocation) {
+        self.invocations.insert(marker_id, invocation);
+    }
+
+    pub fn record_blocked(&mut self, token_id: TokenId, name: InternedStr, reason: BlockReason) {
+        self.blocked.insert(token_id, (name, reason));
+    }
+}
+
+/// 組み立て中のノード
+#[derive(Default)]
+struct Frame<'t> {
+    marker_id: Option<TokenId>,
+    name: Option<InternedStr>,
+    /// 引数の prescan 由来（呼び出し側の引数の下に表示済み）
+    transparent: bool,
+    tokens: Vec<&'t Token>,
+    children: Vec<ExpansionNode>,
+    blocked: Vec<BlockedMacro>,
+}
+
+struct TreeBuilder<'a> {
+    recorder: &'a ExpansionRecorder,
+    interner: &'a StringInterner,
+    files: &'a FileRegistry,
+}
+
+impl<'a> TreeBuilder<'a> {
+    /// トークン列を走査して最上位のフレームを返す
+    ///
+    /// `arg_markers` に含まれる MacroBegin（呼び出し側の引数の prescan で
+    /// 生じた展開）とその内側は透過的に扱う。
+    fn build<'t>(&self, tokens: &'t [Token], arg_markers: &HashSet<TokenId>) -> Frame<'t> {
+        let mut stack = vec![Frame::default()];
+        // 透過でない各フレームの「引数由来」マーカー集合
+        let mut marker_sets = vec![arg_markers.clone()];
+
+        for token in tokens {
+            match &token.kind {
+                TokenKind::MacroBegin(info) => {
+                    let parent = stack.last().unwrap();
+                    let transparent = parent.transparent
+                        || marker_sets.last().unwrap().contains(&info.marker_id);
+                    if !transparent {
+                        marker_sets.push(self.arg_markers_of(info.marker_id));
+                    }
+                    stack.push(Frame {
+                        marker_id: Some(info.marker_id),
+                        name: Some(info.macro_name),
+                        transparent,
+                        ..Default::default()
+                    });
+                }
+                TokenKind::MacroEnd(_) if stack.len() > 1 => {
+                    let frame = stack.pop().unwrap();
+                    let parent = stack.last_mut().unwrap();
+                    parent.tokens.extend(frame.tokens.iter().copied());
+                    if !frame.transparent {
+                        marker_sets.pop();
+                        parent.children.push(self.finish(frame));
+                    }
+                }
+                TokenKind::MacroEnd(_) | TokenKind::Newline => {}
+                _ => {
+                    let frame = stack.last_mut().unwrap();
+                    if !frame.transparent
+                        && let Some(&(name, reason)) = self.recorder.blocked.get(&token.id)
+                    {
+                        let name = self.interner.get(name).to_string();
+                        if !frame.blocked.iter().any(|b| b.name == name) {
+                            frame.blocked.push(BlockedMacro { name, reason });
+                        }
+                    }
+                    frame.tokens.push(token);
+                }
+            }
+        }
+
+        // 閉じていないマーカーは外側に畳み込む
+        while stack.len() > 1 {
+            let frame = stack.pop().unwrap();
+            let parent = stack.last_mut().unwrap();
+            parent.tokens.extend(frame.tokens.iter().copied());
+            if !frame.transparent {
+                parent.children.push(self.finish(frame));
+            }
+        }
+        stack.pop().unwrap()
+    }
+
+    /// ある呼び出しの引数 prescan 結果に含まれる MacroBegin のID
+    fn arg_markers_of(&self, marker_id: TokenId) -> HashSet<TokenId> {
+        let mut set = HashSet::new();
+        if let Some(RecordedInvocation { args: Some(args), .. }) = self.recorder.invocations.get(&marker_id) {
+            for (_, _, prescanned) in args {
+                for t in prescanned {
+                    if let TokenKind::MacroBegin(info) = &t.kind {
+                        set.insert(info.marker_id);
+                    }
+                }
+            }
+        }
+        set
+    }
+
+    fn finish(&self, frame: Frame<'_>) -> ExpansionNode {
+        let name = frame.name.map(|n| self.interner.get(n).to_string()).unwrap_or_default();
+        let recorded = frame.marker_id.and_then(|id| self.recorder.invocations.get(&id));
+        let defined_at = recorded
+            .filter(|r| r.def_loc.line > 0)
+            .and_then(|r| {
+                let path = self.files.try_get_path(r.def_loc.file_id)?;
+                let file = path.file_name().map_or_else(|| path.display().to_string(), |f| f.to_string_lossy().into_owned());
+                Some(format!("{}:{}", file, r.def_loc.line))
+            });
+        let args = recorded.and_then(|r| r.args.as_ref()).map(|args| {
+            args.iter()
+                .map(|(param, raw, prescanned)| TracedArg {
+                    param: self.interner.get(*param).to_string(),
+                    raw: self.text(raw),
+                    expanded: self.text(prescanned),
+                    expansions: self.build(prescanned, &HashSet::new()).children,
+                })
+                .collect()
+        });
+        ExpansionNode {
+            name,
+            defined_at,
+            args,
+            replacement: recorded.map(|r| self.text(&r.replacement)).unwrap_or_default(),
+            result: self.text(&frame.tokens),
+            blocked: frame.blocked,
+            children: frame.children,
+        }
+    }
+
+    /// トークン列を読みやすい文字列にする（マーカーは除く）
+    fn text<T: std::borrow::Borrow<Token>>(&self, tokens: &[T]) -> String {
+        let mut out = String::new();
+        let mut prev: Option<&TokenKind> = None;
+        for t in tokens {
+            let kind = &t.borrow().kind;
+            if matches!(kind, TokenKind::MacroBegin(_) | TokenKind::MacroEnd(_) | TokenKind::Newline | TokenKind::Space) {
+                continue;
+            }
+            let no_space = matches!(
+                (prev, kind),
+                (None, _)
+                    | (_, TokenKind::RParen | TokenKind::RBracket | TokenKind::Comma | TokenKind::Semi
+                        | TokenKind::Dot | TokenKind::Arrow)
+                    | (Some(TokenKind::LParen | TokenKind::LBracket | TokenKind::Dot | TokenKind::Arrow), _)
+                    | (Some(TokenKind::Ident(_)), TokenKind::LParen | TokenKind::LBracket)
+            );
+            if !no_space {
+                out.push(' ');
+            }
+            out.push_str(&kind.format(self.interner));
+            prev = Some(kind);
+        }
+        out
+    }
+}
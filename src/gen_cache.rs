@@ -0,0 +1,297 @@
+//! 生成結果のディスクキャッシュ
+//!
+//! sys crate の `build.rs` から毎回 perl.h 全体をプリプロセス・型推論すると
+//! ビルドのたびに数秒かかる。入力（ヘッダーツリー・定義・bindings.rs・
+//! apidoc・crate バージョン・設定）が前回と同じなら、前回の生成結果を
+//! そのまま使って全フェーズを省略する。
+//!
+//! ヘッダーツリーはプリプロセスしてみるまで分からないため、前回の実行で
+//! 読み込んだファイルの一覧と内容ハッシュを manifest に記録しておき
+//! （make の `.d` ファイルと同じ考え方）、次回はそれらを再ハッシュして比較する。
+//! ミス時は最初に見つかった差分を [`CacheMiss`] として返す。
+//!
+//! ハッシュには FNV-1a (64bit) を使う。`DefaultHasher` は Rust のリリース間で
+//! 結果が変わり得るため、ツールチェーン更新だけでキャッシュが無効になるのを避ける。
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::c_shim::CShimOutput;
+use crate::codegen_report::CodegenReport;
+use crate::module_split::{OutputItem, OutputSegment};
+use crate::rust_codegen::CodegenStats;
+
+/// manifest のファイル名
+const MANIFEST_FILE: &str = "manifest.json";
+/// 生成コードのファイル名
+const OUTPUT_FILE: &str = "output.rs";
+/// 統計・レポートなどのファイル名
+const RESULT_FILE: &str = "result.json";
+
+/// キャッシュのキー情報
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct CacheManifest {
+    /// 生成した crate のバージョン
+    crate_version: String,
+    /// 設定項目名 → 値のハッシュ
+    options: BTreeMap<String, String>,
+    /// 読み込んだ入力ファイル → 内容のハッシュ
+    files: BTreeMap<PathBuf, String>,
+}
+
+/// キャッシュされた生成結果
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CachedOutput {
+    /// 生成コード
+    #[serde(skip)]
+    pub output: Vec<u8>,
+    /// コード生成の統計情報
+    pub stats: CodegenStats,
+    /// 名前ごとの生成結果レポート
+    pub report: CodegenReport,
+    /// 出力区間と定義元ヘッダーの対応
+    pub segments: Vec<OutputSegment>,
+    /// アイテムごとの出力範囲
+    #[serde(default)]
+    pub items: Vec<OutputItem>,
+    /// C shim の生成結果
+    #[serde(default)]
+    pub c_shim: Option<CShimOutput>,
+    /// パースエラー回復で読み飛ばした宣言のエラー（描画済み）
+    #[serde(default)]
+    pub skipped_declarations: Vec<String>,
+}
+
+/// キャッシュミスの理由
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CacheMiss {
+    /// キャッシュがまだない
+    NoEntry,
+    /// キャッシュが読めない（壊れている）
+    Unreadable(String),
+    /// crate のバージョンが変わった
+    CrateVersion { cached: String, current: String },
+    /// 設定項目が変わった
+    OptionChanged(String),
+    /// 入力ファイルの内容が変わった
+    FileChanged(PathBuf),
+    /// 入力ファイルが読めなくなった
+    FileMissing(PathBuf),
+}
+
+impl fmt::Display for CacheMiss {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CacheMiss::NoEntry => write!(f, "no cache entry"),
+            CacheMiss::Unreadable(e) => write!(f, "cache entry unreadable: {}", e),
+            CacheMiss::CrateVersion { cached, current } => {
+                write!(f, "crate version changed ({} -> {})", cached, current)
+            }
+            CacheMiss::OptionChanged(name) => write!(f, "option `{}` changed", name),
+            CacheMiss::FileChanged(path) => write!(f, "{} changed", path.display()),
+            CacheMiss::FileMissing(path) => write!(f, "{} is missing", path.display()),
+        }
+    }
+}
+
+/// キャッシュの利用結果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CacheStatus {
+    /// キャッシュから生成結果を復元した
+    Hit,
+    /// キャッシュを使わずに生成した（結果はキャッシュに保存済み）
+    Miss(CacheMiss),
+}
+
+/// キャッシュの設定項目
+///
+/// 項目名ごとに値をハッシュしておき、ミス時にどの項目が変わったかを示す。
+#[derive(Debug, Clone, Default)]
+pub struct CacheOptions {
+    options: BTreeMap<String, String>,
+    /// 設定から直接分かる入力ファイル（bindings.rs など）
+    inputs: Vec<PathBuf>,
+}
+
+impl CacheOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 設定項目を追加（値は Debug 表現などの正規化済み文字列）
+    pub fn add(&mut self, name: impl Into<String>, value: &str) {
+        self.options.insert(name.into(), hash_bytes(value.as_bytes()));
+    }
+
+    /// 入力ファイルを追加
+    pub fn add_input(&mut self, path: impl Into<PathBuf>) {
+        self.inputs.push(path.into());
+    }
+}
+
+/// 生成結果のディスクキャッシュ
+#[derive(Debug, Clone)]
+pub struct GenCache {
+    dir: PathBuf,
+}
+
+impl GenCache {
+    /// キャッシュディレクトリを指定して作成
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// キャッシュディレクトリ
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// キャッシュを検索する
+    pub fn lookup(&self, options: &CacheOptions) -> Result<CachedOutput, CacheMiss> {
+        let manifest_text = match fs::read_to_string(self.dir.join(MANIFEST_FILE)) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(CacheMiss::NoEntry),
+            Err(e) => return Err(CacheMiss::Unreadable(e.to_string())),
+        };
+        let manifest: CacheManifest = serde_json::from_str(&manifest_text)
+            .map_err(|e| CacheMiss::Unreadable(e.to_string()))?;
+
+        let current = env!("CARGO_PKG_VERSION");
+        if manifest.crate_version != current {
+            return Err(CacheMiss::CrateVersion {
+                cached: manifest.crate_version,
+                current: current.to_string(),
+            });
+        }
+
+        for (name, hash) in &options.options {
+            if manifest.options.get(name) != Some(hash) {
+                return Err(CacheMiss::OptionChanged(name.clone()));
+            }
+        }
+        if let Some(name) = manifest.options.keys().find(|n| !options.options.contains_key(*n)) {
+            return Err(CacheMiss::OptionChanged(name.clone()));
+        }
+
+        for path in &options.inputs {
+            if !manifest.files.contains_key(path) {
+                return Err(CacheMiss::FileChanged(path.clone()));
+            }
+        }
+        for (path, hash) in &manifest.files {
+            match hash_file(path) {
+                Ok(current) if &current == hash => {}
+                Ok(_) => return Err(CacheMiss::FileChanged(path.clone())),
+                Err(_) => return Err(CacheMiss::FileMissing(path.clone())),
+            }
+        }
+
+        let output = fs::read(self.dir.join(OUTPUT_FILE))
+            .map_err(|e| CacheMiss::Unreadable(e.to_string()))?;
+        let result_text = fs::read_to_string(self.dir.join(RESULT_FILE))
+            .map_err(|e| CacheMiss::Unreadable(e.to_string()))?;
+        let mut cached: CachedOutput = serde_json::from_str(&result_text)
+            .map_err(|e| CacheMiss::Unreadable(e.to_string()))?;
+        cached.output = output;
+        Ok(cached)
+    }
+
+    /// 生成結果を保存する
+    ///
+    /// `files` はプリプロセッサが読み込んだファイル（存在しない疑似パスは無視）。
+    pub fn store<'p>(
+        &self,
+        options: &CacheOptions,
+        files: impl IntoIterator<Item = &'p Path>,
+        cached: &CachedOutput,
+    ) -> io::Result<()> {
+        let mut hashes = BTreeMap::new();
+        for path in files {
+            if let Ok(hash) = hash_file(path) {
+                hashes.insert(path.to_path_buf(), hash);
+            }
+        }
+        for path in &options.inputs {
+            if let Ok(hash) = hash_file(path) {
+                hashes.insert(path.clone(), hash);
+            }
+        }
+        let manifest = CacheManifest {
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            options: options.options.clone(),
+            files: hashes,
+        };
+
+        fs::create_dir_all(&self.dir)?;
+        // manifest を最後に書くことで、途中で失敗しても不整合なヒットにならない
+        let _ = fs::remove_file(self.dir.join(MANIFEST_FILE));
+        fs::write(self.dir.join(OUTPUT_FILE), &cached.output)?;
+        fs::write(self.dir.join(RESULT_FILE), serde_json::to_string(cached)?)?;
+        fs::write(self.dir.join(MANIFEST_FILE), serde_json::to_string_pretty(&manifest)?)?;
+        Ok(())
+    }
+}
+
+/// FNV-1a (64bit) ハッシュを 16 進文字列で返す
+pub fn hash_bytes(bytes: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &b in bytes {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    format!("{:016x}", hash)
+}
+
+/// ファイル内容のハッシュ
+fn hash_file(path: &Path) -> io::Result<String> {
+    Ok(hash_bytes(&fs::read(path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_bytes() {
+        // FNV-1a 64bit の既知の値
+        assert_eq!(hash_bytes(b""), "cbf29ce484222325");
+        assert_eq!(hash_bytes(b"a"), "af63dc4c8601ec8c");
+    }
+
+    #[test]
+    fn test_cache_hit_and_miss_reasons() {
+        let dir = tempfile::tempdir().unwrap();
+        let header = dir.path().join("sv.h");
+        fs::write(&header, "#define SvIV(sv) 0\n").unwrap();
+        let cache = GenCache::new(dir.path().join("cache"));
+
+        let mut options = CacheOptions::new();
+        options.add("defines", "FOO=1");
+        assert_eq!(cache.lookup(&options).unwrap_err(), CacheMiss::NoEntry);
+
+        let cached = CachedOutput {
+            output: b"pub fn f() {}\n".to_vec(),
+            skipped_declarations: vec!["sv.h:1:1: error: unexpected token".to_string()],
+            ..Default::default()
+        };
+        cache.store(&options, [header.as_path(), Path::new("<cmdline>")], &cached).unwrap();
+        let hit = cache.lookup(&options).unwrap();
+        assert_eq!(hit.output, cached.output);
+        assert_eq!(hit.skipped_declarations, cached.skipped_declarations);
+
+        let mut changed = CacheOptions::new();
+        changed.add("defines", "FOO=2");
+        assert_eq!(cache.lookup(&changed).unwrap_err(), CacheMiss::OptionChanged("defines".to_string()));
+
+        fs::write(&header, "#define SvIV(sv) 1\n").unwrap();
+        assert_eq!(cache.lookup(&options).unwrap_err(), CacheMiss::FileChanged(header.clone()));
+
+        fs::remove_file(&header).unwrap();
+        assert_eq!(cache.lookup(&options).unwrap_err(), CacheMiss::FileMissing(header));
+    }
+}
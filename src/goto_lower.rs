@@ -0,0 +1,402 @@
+//! goto を含む関数本体の制御フロー再構成
+//!
+//! inline 関数でよく使われる 2 つのパターンを、Rust のラベル付きブロック /
+//! ラベル付きループで表現できるかを判定し、その範囲（リージョン）を計算する。
+//!
+//! 同じブロック内にラベル `L:` がある場合:
+//!
+//! - 前方 goto（`goto out` 型の共通後始末）: ラベルより前の文にある `goto L` は、
+//!   最初の goto を含む文からラベル直前までを `'L: { ... }` で囲み、
+//!   `break 'L;` に置き換える。
+//! - 後方 goto（`goto retry` 型の再試行）: ラベル以降の文にある `goto L` は、
+//!   ラベルの文からブロック末尾までを `'L: loop { ...; break 'L; }` で囲み、
+//!   `continue 'L;` に置き換える。
+//!
+//! 以下は再構成できないので、従来どおり `[CONTAINS_GOTO]` で除外される。
+//!
+//! - ラベルと別のブロック（外側・兄弟）にある goto
+//! - 複合文の要素でない位置のラベル（`if (x) L: ...`、switch 本体内など）
+//! - 交差するリージョン
+//! - 前方リージョン内の変数宣言（ブロックを抜けるとスコープ外になる）
+//! - リージョンの外側のループ / switch を対象とするラベルなしの `break` /
+//!   `continue`（後方リージョンでは `'L: loop` を、前方リージョンでは
+//!   ラベル付きブロック内の `break` として E0695 になる）
+//!
+//! 実際の Rust コード出力は `rust_codegen` 側で、ここで計算した
+//! [`GotoRegion`] を使って行う。
+
+use std::collections::HashMap;
+
+use crate::ast::{BlockItem, Stmt};
+use crate::intern::InternedStr;
+
+/// goto の向き
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GotoKind {
+    /// ラベルが goto より後ろにある（`break 'L`）
+    Forward,
+    /// ラベルが goto より前にある（`continue 'L`）
+    Backward,
+}
+
+/// ラベル付きブロック / ループで囲む範囲
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GotoRegion {
+    /// ラベル名
+    pub label: InternedStr,
+    /// goto の向き
+    pub kind: GotoKind,
+    /// 範囲の先頭（ブロック要素のインデックス）
+    pub start: usize,
+    /// 範囲の末尾（排他的）
+    pub end: usize,
+}
+
+impl GotoRegion {
+    fn contains(&self, other: &GotoRegion) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    fn is_disjoint(&self, other: &GotoRegion) -> bool {
+        self.end <= other.start || other.end <= self.start
+    }
+}
+
+/// 1 つのブロック要素列について、goto 再構成のリージョンを計算する
+///
+/// 返り値は `(start 昇順, end 降順)` に並んでおり、外側のリージョンが先に来る。
+/// このブロックにラベルがなければ空。再構成できない場合は None。
+pub fn plan_goto_regions(items: &[BlockItem]) -> Option<Vec<GotoRegion>> {
+    let mut regions = Vec::new();
+
+    for (j, item) in items.iter().enumerate() {
+        let BlockItem::Stmt(Stmt::Label { name, stmt, .. }) = item else {
+            continue;
+        };
+        if matches!(stmt.as_ref(), Stmt::Label { .. }) {
+            return None;
+        }
+
+        let forward_start = items[..j].iter()
+            .position(|it| count_gotos_in_item(it, *name) > 0);
+        if let Some(start) = forward_start {
+            if items[start..j].iter().any(|it| matches!(it, BlockItem::Decl(_))) {
+                return None;
+            }
+            regions.push(GotoRegion { label: *name, kind: GotoKind::Forward, start, end: j });
+        }
+
+        if items[j..].iter().any(|it| count_gotos_in_item(it, *name) > 0) {
+            regions.push(GotoRegion {
+                label: *name,
+                kind: GotoKind::Backward,
+                start: j,
+                end: items.len(),
+            });
+        }
+    }
+
+    // 外側へ抜ける break / continue はリージョンのブロック / ループに捕まる
+    if regions.iter().any(|r| items[r.start..r.end].iter().any(item_has_outer_jump)) {
+        return None;
+    }
+
+    for (a, ra) in regions.iter().enumerate() {
+        for rb in &regions[a + 1..] {
+            if !ra.is_disjoint(rb) && !ra.contains(rb) && !rb.contains(ra) {
+                return None;
+            }
+        }
+    }
+
+    regions.sort_by(|a, b| a.start.cmp(&b.start).then(b.end.cmp(&a.end)));
+    Some(regions)
+}
+
+/// 関数本体の goto がすべて再構成可能か判定する
+pub fn can_restructure_gotos(body: &[BlockItem]) -> bool {
+    let mut total: HashMap<InternedStr, usize> = HashMap::new();
+    for item in body {
+        collect_goto_counts_item(item, &mut total);
+    }
+
+    let mut resolved: HashMap<InternedStr, usize> = HashMap::new();
+    if !check_block(body, &mut resolved) {
+        return false;
+    }
+
+    total.iter().all(|(label, count)| resolved.get(label) == Some(count))
+}
+
+/// ブロック要素列を検査し、このブロックのリージョンで解決される goto を数える
+fn check_block(items: &[BlockItem], resolved: &mut HashMap<InternedStr, usize>) -> bool {
+    let Some(regions) = plan_goto_regions(items) else {
+        return false;
+    };
+
+    // 同じラベルに前方・後方両方のリージョンがあっても、goto は
+    // ブロック内で一度ずつしか数えない
+    let mut labels: Vec<InternedStr> = Vec::new();
+    for region in &regions {
+        if !labels.contains(&region.label) {
+            labels.push(region.label);
+        }
+    }
+    for label in labels {
+        let count: usize = items.iter().map(|it| count_gotos_in_item(it, label)).sum();
+        *resolved.entry(label).or_insert(0) += count;
+    }
+
+    items.iter().all(|item| match item {
+        BlockItem::Decl(_) => true,
+        BlockItem::Stmt(Stmt::Label { stmt, .. }) => check_stmt(stmt, resolved),
+        BlockItem::Stmt(stmt) => check_stmt(stmt, resolved),
+    })
+}
+
+/// 複合文の要素以外の位置にある文を検査する
+fn check_stmt(stmt: &Stmt, resolved: &mut HashMap<InternedStr, usize>) -> bool {
+    match stmt {
+        Stmt::Compound(c) => check_block(&c.items, resolved),
+        Stmt::Label { .. } => false,
+        Stmt::If { then_stmt, else_stmt, .. } => {
+            check_stmt(then_stmt, resolved)
+                && else_stmt.as_ref().is_none_or(|s| check_stmt(s, resolved))
+        }
+        Stmt::While { body, .. }
+        | Stmt::DoWhile { body, .. }
+        | Stmt::For { body, .. } => check_stmt(body, resolved),
+        // switch 本体は match アームに分解されるため、ラベルは置けない
+        Stmt::Switch { body, .. } => !stmt_contains_label(body),
+        Stmt::Case { stmt, .. } | Stmt::Default { stmt, .. } => check_stmt(stmt, resolved),
+        _ => true,
+    }
+}
+
+fn stmt_contains_label(stmt: &Stmt) -> bool {
+    match stmt {
+        Stmt::Label { .. } => true,
+        Stmt::Compound(c) => c.items.iter().any(|it| match it {
+            BlockItem::Stmt(s) => stmt_contains_label(s),
+            BlockItem::Decl(_) => false,
+        }),
+        Stmt::If { then_stmt, else_stmt, .. } => {
+            stmt_contains_label(then_stmt)
+                || else_stmt.as_ref().is_some_and(|s| stmt_contains_label(s))
+        }
+        Stmt::While { body, .. }
+        | Stmt::DoWhile { body, .. }
+        | Stmt::For { body, .. }
+        | Stmt::Switch { body, .. } => stmt_contains_label(body),
+        Stmt::Case { stmt, .. } | Stmt::Default { stmt, .. } => stmt_contains_label(stmt),
+        _ => false,
+    }
+}
+
+/// ブロック要素が、自身の外側のループ / switch を対象とする break / continue を含むか
+fn item_has_outer_jump(item: &BlockItem) -> bool {
+    match item {
+        BlockItem::Stmt(stmt) => has_outer_jump(stmt, true),
+        BlockItem::Decl(_) => false,
+    }
+}
+
+/// `break_escapes` は、この位置の break が外側を対象とするか（switch 内なら false）
+fn has_outer_jump(stmt: &Stmt, break_escapes: bool) -> bool {
+    match stmt {
+        Stmt::Break(_) => break_escapes,
+        Stmt::Continue(_) => true,
+        Stmt::While { .. } | Stmt::DoWhile { .. } | Stmt::For { .. } => false,
+        Stmt::Switch { body, .. } => has_outer_jump(body, false),
+        Stmt::Compound(c) => c.items.iter().any(|it| match it {
+            BlockItem::Stmt(s) => has_outer_jump(s, break_escapes),
+            BlockItem::Decl(_) => false,
+        }),
+        Stmt::If { then_stmt, else_stmt, .. } => {
+            has_outer_jump(then_stmt, break_escapes)
+                || else_stmt.as_ref().is_some_and(|s| has_outer_jump(s, break_escapes))
+        }
+        Stmt::Label { stmt, .. } | Stmt::Case { stmt, .. } | Stmt::Default { stmt, .. } => {
+            has_outer_jump(stmt, break_escapes)
+        }
+        _ => false,
+    }
+}
+
+fn count_gotos_in_item(item: &BlockItem, label: InternedStr) -> usize {
+    let mut counts = HashMap::new();
+    collect_goto_counts_item(item, &mut counts);
+    counts.get(&label).copied().unwrap_or(0)
+}
+
+fn collect_goto_counts_item(item: &BlockItem, counts: &mut HashMap<InternedStr, usize>) {
+    if let BlockItem::Stmt(stmt) = item {
+        collect_goto_counts(stmt, counts);
+    }
+}
+
+fn collect_goto_counts(stmt: &Stmt, counts: &mut HashMap<InternedStr, usize>) {
+    match stmt {
+        Stmt::Goto(label, _) => *counts.entry(*label).or_insert(0) += 1,
+        Stmt::Compound(c) => {
+            for item in &c.items {
+                collect_goto_counts_item(item, counts);
+            }
+        }
+        Stmt::If { then_stmt, else_stmt, .. } => {
+            collect_goto_counts(then_stmt, counts);
+            if let Some(s) = else_stmt {
+                collect_goto_counts(s, counts);
+            }
+        }
+        Stmt::While { body, .. }
+        | Stmt::DoWhile { body, .. }
+        | Stmt::For { body, .. }
+        | Stmt::Switch { body, .. } => collect_goto_counts(body, counts),
+        Stmt::Label { stmt, .. } | Stmt::Case { stmt, .. } | Stmt::Default { stmt, .. } => {
+            collect_goto_counts(stmt, counts)
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{CompoundStmt, NodeInfo};
+    use crate::intern::StringInterner;
+    use crate::source::SourceLocation;
+
+    fn goto(label: InternedStr) -> BlockItem {
+        BlockItem::Stmt(Stmt::Goto(label, SourceLocation::default()))
+    }
+
+    fn label(name: InternedStr) -> BlockItem {
+        BlockItem::Stmt(Stmt::Label {
+            name,
+            stmt: Box::new(Stmt::Return(None, SourceLocation::default())),
+            loc: SourceLocation::default(),
+        })
+    }
+
+    fn empty() -> BlockItem {
+        BlockItem::Stmt(Stmt::Expr(None, SourceLocation::default()))
+    }
+
+    fn block(items: Vec<BlockItem>) -> BlockItem {
+        BlockItem::Stmt(Stmt::Compound(CompoundStmt {
+            items,
+            info: NodeInfo::new(SourceLocation::default()),
+        }))
+    }
+
+    #[test]
+    fn test_forward_goto_region() {
+        let mut interner = StringInterner::new();
+        let out = interner.intern("out");
+        let items = vec![empty(), block(vec![goto(out)]), empty(), label(out)];
+
+        let regions = plan_goto_regions(&items).unwrap();
+        assert_eq!(regions, vec![GotoRegion {
+            label: out, kind: GotoKind::Forward, start: 1, end: 3,
+        }]);
+        assert!(can_restructure_gotos(&items));
+    }
+
+    #[test]
+    fn test_backward_goto_region() {
+        let mut interner = StringInterner::new();
+        let retry = interner.intern("retry");
+        let items = vec![empty(), label(retry), block(vec![goto(retry)]), empty()];
+
+        let regions = plan_goto_regions(&items).unwrap();
+        assert_eq!(regions, vec![GotoRegion {
+            label: retry, kind: GotoKind::Backward, start: 1, end: 4,
+        }]);
+        assert!(can_restructure_gotos(&items));
+    }
+
+    #[test]
+    fn test_goto_into_nested_block_is_rejected() {
+        let mut interner = StringInterner::new();
+        let inner = interner.intern("inner");
+        let items = vec![goto(inner), block(vec![empty(), label(inner)])];
+
+        assert!(!can_restructure_gotos(&items));
+    }
+
+    fn brk() -> BlockItem {
+        BlockItem::Stmt(Stmt::Break(SourceLocation::default()))
+    }
+
+    fn cont() -> BlockItem {
+        BlockItem::Stmt(Stmt::Continue(SourceLocation::default()))
+    }
+
+    fn while_loop(items: Vec<BlockItem>) -> BlockItem {
+        let BlockItem::Stmt(body) = block(items) else { unreachable!() };
+        BlockItem::Stmt(Stmt::While {
+            cond: Box::new(crate::ast::Expr::new(
+                crate::ast::ExprKind::IntLit(1), SourceLocation::default())),
+            body: Box::new(body),
+            loc: SourceLocation::default(),
+        })
+    }
+
+    fn switch(items: Vec<BlockItem>) -> BlockItem {
+        let BlockItem::Stmt(body) = block(items) else { unreachable!() };
+        BlockItem::Stmt(Stmt::Switch {
+            expr: Box::new(crate::ast::Expr::new(
+                crate::ast::ExprKind::IntLit(0), SourceLocation::default())),
+            body: Box::new(body),
+            loc: SourceLocation::default(),
+        })
+    }
+
+    #[test]
+    fn test_outer_break_continue_in_region_is_rejected() {
+        let mut interner = StringInterner::new();
+        let l = interner.intern("l");
+
+        // 後方リージョン内の break / continue は 'l: loop を対象にしてしまう
+        let items = vec![label(l), block(vec![brk()]), goto(l)];
+        assert!(plan_goto_regions(&items).is_none());
+        assert!(!can_restructure_gotos(&[while_loop(items)]));
+        let items = vec![label(l), cont(), goto(l)];
+        assert!(plan_goto_regions(&items).is_none());
+
+        // 前方リージョン内の break はラベル付きブロックでは書けない（E0695）
+        let items = vec![goto(l), brk(), label(l)];
+        assert!(plan_goto_regions(&items).is_none());
+
+        // switch 内の continue は外側のループを対象とする
+        let items = vec![goto(l), switch(vec![cont()]), label(l)];
+        assert!(plan_goto_regions(&items).is_none());
+    }
+
+    #[test]
+    fn test_inner_break_continue_in_region_is_allowed() {
+        let mut interner = StringInterner::new();
+        let l = interner.intern("l");
+
+        let items = vec![label(l), while_loop(vec![brk(), cont()]), goto(l)];
+        assert_eq!(plan_goto_regions(&items).unwrap().len(), 1);
+        let items = vec![goto(l), switch(vec![brk()]), label(l)];
+        assert_eq!(plan_goto_regions(&items).unwrap().len(), 1);
+
+        // リージョンの外の break は対象外
+        let items = vec![brk(), goto(l), empty(), label(l)];
+        assert_eq!(plan_goto_regions(&items).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_crossing_regions_are_rejected() {
+        let mut interner = StringInterner::new();
+        let a = interner.intern("a");
+        let b = interner.intern("b");
+        // goto a; goto b; a: ; b: ;  → [0,2) と [1,3) が交差
+        let items = vec![goto(a), goto(b), label(a), label(b)];
+
+        assert!(plan_goto_regions(&items).is_none());
+    }
+}
@@ -0,0 +1,196 @@
+//! インクルードグラフの出力
+//!
+//! perl の `CORE` 配下のヘッダーが解析対象（`is_target`）として扱われるか
+//! どうかは、そのファイルのパスが target_dir 配下かで決まる。どの
+//! `#include` 経由で（どのインクルードパスから）読み込まれたかを追えるよう、
+//! FileRegistry に記録した `#include` を JSON / Graphviz DOT で出力する。
+
+use std::fmt::Write as _;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::source::{FileId, FileRegistry, IncludeKind};
+
+/// 出力形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IncludeGraphFormat {
+    Json,
+    Dot,
+}
+
+impl FromStr for IncludeGraphFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "dot" => Ok(Self::Dot),
+            _ => Err(format!("unknown include graph format `{}` (expected json or dot)", s)),
+        }
+    }
+}
+
+/// グラフの頂点（ファイル）
+#[derive(Debug, Clone, Serialize)]
+pub struct IncludeGraphNode {
+    pub id: u32,
+    pub path: String,
+    /// target_dir 配下のファイルか
+    pub is_target: bool,
+}
+
+/// グラフの辺（`#include` 1 回分）
+#[derive(Debug, Clone, Serialize)]
+pub struct IncludeGraphEdge {
+    pub from: u32,
+    pub to: u32,
+    /// `#include` の行番号（インクルードした側のファイル）
+    pub line: u32,
+    /// 書かれたとおりのパス
+    pub spelling: String,
+    pub kind: IncludeKind,
+    pub include_next: bool,
+    /// インクルードガードにより読み込みを省略したか
+    pub skipped_by_guard: bool,
+}
+
+/// インクルードグラフ
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct IncludeGraph {
+    /// 入力ファイルとインクルードされたファイル（`<cmdline>` などは除く）
+    pub nodes: Vec<IncludeGraphNode>,
+    /// `#include`（出現順）
+    pub edges: Vec<IncludeGraphEdge>,
+}
+
+impl IncludeGraph {
+    /// FileRegistry に記録した `#include` からグラフを作る
+    pub fn from_registry(files: &FileRegistry, target_dir: Option<&Path>) -> Self {
+        let is_pseudo = |path: &Path| path.to_string_lossy().starts_with('<');
+        let nodes = files.iter()
+            .filter(|(_, path)| !is_pseudo(path))
+            .map(|(id, path)| IncludeGraphNode {
+                id: id.as_u32(),
+                path: path.display().to_string(),
+                is_target: target_dir.is_some_and(|dir| path.starts_with(dir)),
+            })
+            .collect();
+        // 疑似ファイルに接する辺も除き、辺の端点が必ず nodes にあるようにする
+        let edges = files.includes().iter()
+            .filter(|edge| [edge.from, edge.to].iter()
+                .all(|&id| files.try_get_path(id).is_some_and(|p| !is_pseudo(p))))
+            .map(|edge| IncludeGraphEdge {
+                from: edge.from.as_u32(),
+                to: edge.to.as_u32(),
+                line: edge.loc.line,
+                spelling: edge.spelling.clone(),
+                kind: edge.kind,
+                include_next: edge.is_include_next,
+                skipped_by_guard: edge.skipped_by_guard,
+            })
+            .collect();
+        Self { nodes, edges }
+    }
+
+    /// 指定した形式の文字列にする
+    pub fn render(&self, format: IncludeGraphFormat) -> String {
+        match format {
+            IncludeGraphFormat::Json => {
+                serde_json::to_string_pretty(self).expect("include graph is serializable") + "\n"
+            }
+            IncludeGraphFormat::Dot => self.to_dot(),
+        }
+    }
+
+    /// Graphviz DOT 形式
+    ///
+    /// target のファイルは塗りつぶし、`<...>` は実線・`"..."` は破線、
+    /// `#include_next` は青、ガードで省略した辺は灰色で描く。
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph includes {\n    rankdir=LR;\n    node [shape=box];\n");
+        for node in &self.nodes {
+            let label = Path::new(&node.path).file_name()
+                .map_or_else(|| node.path.clone(), |f| f.to_string_lossy().into_owned());
+            let _ = write!(out, "    f{} [label={:?}, tooltip={:?}", node.id, label, node.path);
+            if node.is_target {
+                out.push_str(", style=filled, fillcolor=lightyellow");
+            }
+            out.push_str("];\n");
+        }
+        for edge in &self.edges {
+            let mut attrs = vec![format!("label=\"{}\"", edge.line)];
+            if edge.kind == IncludeKind::Local {
+                attrs.push("style=dashed".to_string());
+            }
+            if edge.include_next {
+                attrs.push("color=blue".to_string());
+            }
+            if edge.skipped_by_guard {
+                attrs.push("color=gray".to_string());
+                attrs.push("fontcolor=gray".to_string());
+            }
+            let _ = writeln!(out, "    f{} -> f{} [{}];", edge.from, edge.to, attrs.join(", "));
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// ファイルを直接インクルードしているファイル
+    pub fn includers_of(&self, id: FileId) -> impl Iterator<Item = &IncludeGraphEdge> {
+        self.edges.iter().filter(move |e| e.to == id.as_u32())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::{IncludeEdge, SourceLocation};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_include_graph_render() {
+        let mut files = FileRegistry::new();
+        let main = files.register(PathBuf::from("/src/wrapper.h"));
+        let cmdline = files.register(PathBuf::from("<cmdline>"));
+        let perl = files.register(PathBuf::from("/perl/CORE/perl.h"));
+        files.add_include(IncludeEdge {
+            from: main,
+            to: perl,
+            loc: SourceLocation::new(main, 3, 1),
+            spelling: "perl.h".to_string(),
+            kind: IncludeKind::Local,
+            is_include_next: false,
+            skipped_by_guard: false,
+        });
+        // `-include` 相当で <cmdline> から取り込んだ辺はグラフに出さない
+        files.add_include(IncludeEdge {
+            from: cmdline,
+            to: perl,
+            loc: SourceLocation::new(cmdline, 1, 1),
+            spelling: "perl.h".to_string(),
+            kind: IncludeKind::Local,
+            is_include_next: false,
+            skipped_by_guard: true,
+        });
+
+        let graph = IncludeGraph::from_registry(&files, Some(Path::new("/perl/CORE")));
+        assert_eq!(graph.nodes.len(), 2);
+        assert!(!graph.nodes[0].is_target);
+        assert!(graph.nodes[1].is_target);
+        assert_eq!(graph.edges.len(), 1);
+        assert!(graph.edges.iter().all(|e| {
+            [e.from, e.to].iter().all(|id| graph.nodes.iter().any(|n| n.id == *id))
+        }));
+        assert_eq!(graph.includers_of(perl).map(|e| e.from).collect::<Vec<_>>(), vec![main.as_u32()]);
+
+        let dot = graph.render(IncludeGraphFormat::Dot);
+        assert!(dot.contains("f2 [label=\"perl.h\", tooltip=\"/perl/CORE/perl.h\", style=filled"));
+        assert!(dot.contains("f0 -> f2 [label=\"3\", style=dashed];"));
+
+        let json: serde_json::Value = serde_json::from_str(&graph.render(IncludeGraphFormat::Json)).unwrap();
+        assert_eq!(json["edges"][0]["kind"], "local");
+        assert_eq!(json["edges"][0]["spelling"], "perl.h");
+    }
+}
@@ -10,7 +10,7 @@ use crate::apidoc::{ApidocCollector, ApidocDict, ApidocResolveError};
 use crate::ast::{DerivedDecl, ExternalDecl, TypeSpec};
 use crate::c_fn_decl::{CFnDecl, CFnDeclDict, CParam};
 use crate::enum_dict::EnumDict;
-use crate::error::EnrichedCompileError;
+use crate::error::{CompileError, EnrichedCompileError};
 use crate::fields_dict::FieldsDict;
 use crate::inline_fn::InlineFnDict;
 use crate::intern::InternedStr;
@@ -31,7 +31,7 @@ pub enum InferError {
     /// apidoc 解決エラー
     ApidocResolve(ApidocResolveError),
     /// プリプロセッサ/パースエラー（ファイルパスと該当行で強化済み）
-    Compile(EnrichedCompileError),
+    Compile(Box<EnrichedCompileError>),
     /// ファイル I/O エラー
     Io(std::io::Error),
 }
@@ -52,7 +52,7 @@ impl std::error::Error for InferError {
         match self {
             InferError::PerlConfig(e) => Some(e),
             InferError::ApidocResolve(e) => Some(e),
-            InferError::Compile(e) => Some(e),
+            InferError::Compile(e) => Some(e.as_ref()),
             InferError::Io(e) => Some(e),
         }
     }
@@ -72,7 +72,7 @@ impl From<ApidocResolveError> for InferError {
 
 impl From<EnrichedCompileError> for InferError {
     fn from(e: EnrichedCompileError) -> Self {
-        InferError::Compile(e)
+        InferError::Compile(Box::new(e))
     }
 }
 
@@ -195,6 +195,8 @@ pub struct InferStats {
     pub c_fn_decl_count: usize,
     /// THX 依存の C 関数数
     pub c_fn_thx_count: usize,
+    /// エラー回復で読み飛ばした宣言の数
+    pub parse_error_count: usize,
 }
 
 /// 型推論の結果
@@ -224,6 +226,9 @@ pub struct InferResult {
     /// PERLVAR 観測辞書 (Phase 1 で収集、Phase 3 で `PL_xxx!()` 出力に使う)。
     /// Pipeline で `with_perlvar_collection(false)` した場合は空。
     pub perlvar_dict: crate::perlvar_dict::PerlvarDict,
+    /// 宣言のパース中に回復したエラー（該当宣言は収集されていない）。
+    /// エラー回復が有効な場合のみ入る。
+    pub parse_errors: Vec<CompileError>,
     /// プリプロセッサ（マクロテーブル、StringInterner、FileRegistry へのアクセス用）
     pub preprocessor: Preprocessor,
     /// 統計情報
@@ -241,12 +246,41 @@ pub struct InferResult {
 ///
 /// `debug_opts` が指定され、デバッグダンプで早期終了した場合は `Ok(None)` を返す。
 pub fn run_inference_with_preprocessor(
+    pp: Preprocessor,
+    apidoc_path: Option<&Path>,
+    bindings_path: Option<&Path>,
+    debug_opts: Option<&DebugOptions>,
+    skip_codegen_lists: &[PathBuf],
+    perl_build_mode_override: Option<crate::perl_config::PerlBuildMode>,
+) -> Result<Option<InferResult>, InferError> {
+    run_inference_impl(
+        pp, apidoc_path, bindings_path, debug_opts, skip_codegen_lists, perl_build_mode_override, false,
+    )
+}
+
+/// [`run_inference_with_preprocessor`] と同じだが、パースできない宣言を
+/// 読み飛ばして続け、そのエラーを `InferResult::parse_errors` に入れる。
+pub fn run_inference_with_parse_error_recovery(
+    pp: Preprocessor,
+    apidoc_path: Option<&Path>,
+    bindings_path: Option<&Path>,
+    debug_opts: Option<&DebugOptions>,
+    skip_codegen_lists: &[PathBuf],
+    perl_build_mode_override: Option<crate::perl_config::PerlBuildMode>,
+) -> Result<Option<InferResult>, InferError> {
+    run_inference_impl(
+        pp, apidoc_path, bindings_path, debug_opts, skip_codegen_lists, perl_build_mode_override, true,
+    )
+}
+
+fn run_inference_impl(
     mut pp: Preprocessor,
     apidoc_path: Option<&Path>,
     bindings_path: Option<&Path>,
     debug_opts: Option<&DebugOptions>,
     skip_codegen_lists: &[PathBuf],
     perl_build_mode_override: Option<crate::perl_config::PerlBuildMode>,
+    recover_parse_errors: bool,
 ) -> Result<Option<InferResult>, InferError> {
     // Perl build mode を確定（明示指定があれば優先、なければ auto-detect）
     let perl_build_mode = match perl_build_mode_override {
@@ -336,8 +370,10 @@ pub fn run_inference_with_preprocessor(
     // パーサー作成
     let mut parser = match Parser::new(&mut pp) {
         Ok(p) => p,
-        Err(e) => return Err(InferError::Compile(e.with_files(pp.files()))),
+        Err(e) => return Err(InferError::Compile(Box::new(e.with_files(pp.files())))),
     };
+    // システムヘッダ等の 1 宣言がパースできなくても残りの収集を続ける
+    parser.set_error_recovery(recover_parse_errors);
 
     // inline 関数辞書を作成
     let mut inline_fn_dict = InlineFnDict::new();
@@ -422,11 +458,15 @@ pub fn run_inference_with_preprocessor(
     if let Err(e) = parse_result {
         // parser を drop して pp の借用を解放してから enrich する
         drop(parser);
-        return Err(InferError::Compile(e.with_files(pp.files())));
+        return Err(InferError::Compile(Box::new(e.with_files(pp.files()))));
     }
 
+    // 回復したパースエラー（読み飛ばした宣言）
+    let parse_errors = parser.take_errors();
+
     // パーサーから typedef 辞書を取得
     let typedefs = parser.typedefs().clone();
+    drop(parser);
 
     // コールバックを取り出してダウンキャスト
     let callback = pp.take_comment_callback().expect("callback should exist");
@@ -603,6 +643,7 @@ pub fn run_inference_with_preprocessor(
         thx_dependent_count,
         c_fn_decl_count,
         c_fn_thx_count,
+        parse_error_count: parse_errors.len(),
     };
 
     // Phase 2 最終パス: パラメータ/戻り値型の確定（const/mut, bool）
@@ -627,6 +668,7 @@ pub fn run_inference_with_preprocessor(
         // Default: empty. Pipeline overwrites this with the collected dict
         // before returning the result.
         perlvar_dict: crate::perlvar_dict::PerlvarDict::new(),
+        parse_errors,
         preprocessor: pp,
         stats,
     }))
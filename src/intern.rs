@@ -1,5 +1,8 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
 
+use serde::{Serialize, Serializer};
+
 /// インターン済み文字列の識別子
 #[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Default)]
 pub struct InternedStr(u32);
@@ -11,6 +14,24 @@ impl InternedStr {
     }
 }
 
+thread_local! {
+    /// `StringInterner::serialize_scope` 中に `InternedStr` を解決するための文字列表
+    static SERDE_STRINGS: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+
+/// `StringInterner::serialize_scope` の中では文字列として、
+/// それ以外では内部 ID（数値）として serialize する
+impl Serialize for InternedStr {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SERDE_STRINGS.with(|strings| {
+            match strings.borrow().as_ref().and_then(|s| s.get(self.0 as usize)) {
+                Some(name) => serializer.serialize_str(name),
+                None => serializer.serialize_u32(self.0),
+            }
+        })
+    }
+}
+
 /// 文字列インターナー
 #[derive(Clone, Debug, Default)]
 pub struct StringInterner {
@@ -57,6 +78,16 @@ impl StringInterner {
     pub fn is_empty(&self) -> bool {
         self.strings.is_empty()
     }
+
+    /// `f` の実行中、`InternedStr` をこのインターナーで解決した名前として serialize する
+    ///
+    /// AST や型推論結果を JSON に出力するときに使う。文字列表は複製して保持する。
+    pub fn serialize_scope<R>(&self, f: impl FnOnce() -> R) -> R {
+        let prev = SERDE_STRINGS.with(|s| s.replace(Some(self.strings.clone())));
+        let result = f();
+        SERDE_STRINGS.with(|s| *s.borrow_mut() = prev);
+        result
+    }
 }
 
 #[cfg(test)]
@@ -84,6 +115,18 @@ mod tests {
         assert_eq!(interner.len(), 1);
     }
 
+    #[test]
+    fn test_serialize_scope() {
+        let mut interner = StringInterner::new();
+        interner.intern("SvIV");
+        let id = interner.intern("sv");
+
+        assert_eq!(serde_json::to_string(&id).unwrap(), "1");
+        let json = interner.serialize_scope(|| serde_json::to_string(&vec![id]).unwrap());
+        assert_eq!(json, r#"["sv"]"#);
+        assert_eq!(serde_json::to_string(&id).unwrap(), "1");
+    }
+
     #[test]
     fn test_intern_empty_string() {
         let mut interner = StringInterner::new();
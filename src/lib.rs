@@ -72,26 +72,40 @@
 //! Pre-1.0 — focused on the libperl-rs use case. Wider header-tree
 //! coverage and stable APIs come after libperl-rs hits 1.0.
 
+pub mod api_diff;
 pub mod apidoc;
 pub mod apidoc_data;
 pub mod apidoc_patches;
 pub mod ast;
+pub mod ast_json;
 pub mod c_fn_decl;
+pub mod c_shim;
+pub mod codegen_report;
+pub mod compile_check;
+pub mod dual_mode;
 pub mod error;
+pub mod expansion_trace;
 pub mod enum_dict;
 pub mod fields_dict;
+pub mod gen_cache;
 pub mod global_const_dict;
+pub mod goto_lower;
+pub mod include_graph;
 pub mod infer_api;
 pub mod inline_fn;
 pub mod intern;
 pub mod lexer;
 pub mod macro_def;
 pub mod macro_infer;
+pub mod macro_purity;
+pub mod module_split;
+pub mod nullability;
 pub mod parser;
 pub mod perl_config;
 pub mod perlvar_dict;
 pub mod perlvar_emitter;
 pub mod pipeline;
+pub mod pod;
 pub mod pp_expr;
 pub mod preprocessor;
 pub mod rust_codegen;
@@ -99,6 +113,7 @@ pub mod rust_decl;
 pub mod static_array_emitter;
 pub mod struct_emitter;
 pub mod semantic;
+pub mod stack_macro;
 pub mod sexp;
 pub mod syn_codegen;
 pub mod source;
@@ -110,16 +125,19 @@ pub mod unified_type;
 
 // 主要な型を再エクスポート
 pub use apidoc::{
-    find_apidoc_dir_from, resolve_apidoc_path,
+    find_apidoc_dir_from, resolve_apidoc_path, resolve_apidoc_path_for_version,
     ApidocArg, ApidocCollector, ApidocDict, ApidocEntry, ApidocFlags, ApidocResolveError, ApidocStats, Nullability,
 };
 pub use infer_api::{
-    run_inference_with_preprocessor,
+    run_inference_with_parse_error_recovery, run_inference_with_preprocessor,
     DebugOptions, InferConfig, InferError, InferResult, InferStats, TypedefDict,
 };
 pub use ast::*;
-pub use error::{CompileError, DisplayLocation, LexError, PPError, ParseError, Result};
+pub use ast_json::{write_macro_infer_json, write_translation_unit_json};
+pub use error::{CompileError, DisplayLocation, ExpansionFrame, LexError, PPError, ParseError, Result};
+pub use expansion_trace::{BlockReason, BlockedMacro, ExpansionNode, ExpansionTrace, TracedArg};
 pub use fields_dict::FieldsDict;
+pub use include_graph::{IncludeGraph, IncludeGraphEdge, IncludeGraphFormat, IncludeGraphNode};
 pub use inline_fn::InlineFnDict;
 pub use intern::{InternedStr, StringInterner};
 pub use rust_decl::RustDeclDict;
@@ -131,19 +149,20 @@ pub use macro_infer::{
 };
 pub use parser::{parse_expression_from_tokens, parse_expression_from_tokens_ref, parse_type_from_string, Parser};
 pub use perl_config::{
-    build_pp_config_for_perl, get_default_target_dir, get_perl_config, get_perl_version,
-    PerlConfig, PerlConfigError,
+    build_pp_config_for_perl, build_pp_config_for_perl_from, get_default_target_dir, get_perl_config,
+    get_perl_config_from, get_perl_version, PerlConfig, PerlConfigError, PerlConfigSource, PerlConfigValues,
 };
 pub use perlvar_dict::{
     ArrayLength, PerlvarCollector, PerlvarDict, PerlvarEntry, PerlvarKind,
 };
+pub use pp_expr::{FeatureTables, HasOperator};
 pub use preprocessor::{
-    CallbackPair, CommentCallback, MacroCalledCallback, MacroCallWatcher, MacroDefCallback,
-    PPConfig, Preprocessor,
+    CallbackPair, CommentCallback, IncludeGuardStats, MacroCalledCallback, MacroCallWatcher, MacroDefCallback,
+    PPConfig, PragmaCallback, Preprocessor,
 };
 pub use semantic::{SemanticAnalyzer, Symbol, SymbolKind, Type};
 pub use sexp::{SexpPrinter, TypedSexpPrinter};
-pub use source::{FileId, FileRegistry, SourceLocation};
+pub use source::{FileId, FileRegistry, IncludeEdge, IncludeKind, SourceLocation, VirtualFs};
 pub use token::{Comment, CommentKind, Token, TokenKind};
 pub use token_source::{TokenSlice, TokenSliceRef, TokenSource};
 pub use type_env::{ParamLink, TypeConstraint, TypeEnv};
@@ -152,6 +171,12 @@ pub use type_repr::{
     IntSize as TypeReprIntSize, RustPrimitiveKind, RustTypeRepr, RustTypeSource, TypeRepr,
 };
 pub use unified_type::{IntSize, SourcedType, TypeSource, UnifiedType};
+pub use gen_cache::{CacheMiss, CacheStatus};
+pub use module_split::{ModuleOutput, OutputSegment};
+pub use api_diff::{ApiDiff, ApiItem, SignatureChange, SignatureDelta, StatusChange};
+pub use dual_mode::{DualGenerated, DualInferredPipeline, DualModeDifference, DualModePipeline, DualModeReport};
+pub use compile_check::{CompileCheckConfig, CompileCheckOutcome, CompileCheckRound, RustcDiagnostic};
+pub use codegen_report::{CodegenReport, ReportEntry, ReportItemKind, ReportLocation, ReportOutcome, ReportSignature};
 pub use rust_codegen::{CodegenConfig, CodegenDriver, CodegenStats, GeneratedCode, GenerateStatus, RustCodegen};
 pub use pipeline::{
     Pipeline, PipelineBuilder, PipelineError,
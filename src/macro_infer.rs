@@ -5,10 +5,13 @@
 
 use std::collections::{HashMap, HashSet};
 
+use serde::Serialize;
+
 use crate::apidoc::ApidocDict;
 use crate::apidoc_patches::ApidocPatchSet;
-use crate::ast::{AssertKind, BlockItem, Expr, ExprKind};
+use crate::ast::{AssertKind, BlockItem, Expr, ExprId, ExprKind};
 use crate::c_fn_decl::CFnDeclDict;
+use crate::error::CompileError;
 use crate::fields_dict::FieldsDict;
 use crate::inline_fn::InlineFnDict;
 use crate::intern::{InternedStr, StringInterner};
@@ -22,6 +25,7 @@ use crate::parser::{
     parse_block_items_from_tokens_ref_with_generic_params,
     ParseStats,
 };
+use crate::pp_expr::CIntType;
 use crate::rust_decl::RustDeclDict;
 use crate::semantic::SemanticAnalyzer;
 use crate::preprocessor::Preprocessor;
@@ -140,7 +144,7 @@ impl ExplicitExpandSymbols {
 }
 
 /// マクロのパース結果
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum ParseResult {
     /// 式としてパース成功
     Expression(Box<Expr>),
@@ -158,7 +162,7 @@ pub enum ParseResult {
 ///
 /// 各パラメータは `Expr` として表現され、固有の `ExprId` を持つ。
 /// これにより、パラメータの型制約も `expr_constraints` に統一的に格納できる。
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct MacroParam {
     /// パラメータ名
     pub name: InternedStr,
@@ -182,7 +186,7 @@ impl MacroParam {
 }
 
 /// 推論状態
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub enum InferStatus {
     /// 未処理
     Pending,
@@ -234,22 +238,16 @@ fn collect_generic_params(entry: &crate::apidoc::ApidocEntry, info: &mut MacroIn
     }
 
     // 戻り値型の type/cast を収集
+    // 最初の type パラメータと同じ名前を使う（NUM2PTR や SSPTR のように
+    // 戻り値型がパラメータの type そのものである場合）。type パラメータが
+    // なければ戻り値のみのジェネリック T になる。
     if entry.returns_type_param() {
-        // 最初のパラメータの type と同じ場合は同じ名前を使う
-        // （NUM2PTR のように戻り値型とパラメータの type が同じ場合）
-        let name = if let Some(first_name) = info.generic_type_params.get(&0) {
-            first_name.clone()
-        } else if param_idx < PARAM_NAMES.len() {
-            PARAM_NAMES[param_idx].to_string()
-        } else {
-            "T".to_string()
-        };
-        info.generic_type_params.insert(-1, name); // -1 = return type
+        info.generic_type_params.insert(-1, PARAM_NAMES[0].to_string()); // -1 = return type
     }
 }
 
 /// マクロの型推論情報
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct MacroInferInfo {
     /// マクロ名
     pub name: InternedStr,
@@ -328,6 +326,12 @@ pub struct MacroInferInfo {
 
     /// bool を返すマクロか（依存順解析で確定）
     pub is_bool_return: bool,
+
+    /// オブジェクトマクロの定数畳み込み結果
+    ///
+    /// 本体が整数定数式に展開されるオブジェクトマクロのみ Some。
+    /// 値と C での型を持ち、codegen はこれを `pub const NAME: T = value;` として出力する。
+    pub const_value: Option<crate::pp_expr::FoldedConst>,
 }
 
 impl MacroInferInfo {
@@ -358,6 +362,7 @@ impl MacroInferInfo {
             resolved_return_type: None,
             const_pointer_positions: HashSet::new(),
             is_bool_return: false,
+            const_value: None,
         }
     }
 
@@ -533,6 +538,21 @@ impl MacroInferContext {
         count
     }
 
+    /// 推論後に追加された skip_codegen を反映し、利用不可を再伝播する
+    ///
+    /// Phase 2 の Step 4.4〜4.7 に相当する処理を推論済みの結果に対して行う。
+    /// 生成コードのコンパイル検査で失敗した関数を抑止するときに使う。
+    pub fn apply_late_suppressions(
+        &mut self,
+        patches: &ApidocPatchSet,
+        interner: &StringInterner,
+        inline_fn_dict: &mut InlineFnDict,
+    ) {
+        self.apply_apidoc_suppressions(patches, interner);
+        inline_fn_dict.apply_apidoc_suppressions(patches, interner);
+        self.propagate_unavailable_cross_domain(inline_fn_dict);
+    }
+
     /// def-use 関係を構築
     ///
     /// 各マクロの uses 情報から used_by を逆引きで構築する。
@@ -818,7 +838,6 @@ impl MacroInferContext {
 
         // パースを試行（pp から interner と files を取得）
         let interner = pp.interner();
-        let files = pp.files();
 
         // 関数マクロの場合、全仮引数を generic_params として渡す
         let generic_params: HashMap<InternedStr, usize> = params.iter()
@@ -827,7 +846,7 @@ impl MacroInferContext {
             .collect();
 
         let (parse_result, stats, detected_type_params) = self.try_parse_tokens(
-            &expanded_tokens, interner, files, typedefs, generic_params,
+            &expanded_tokens, pp, typedefs, generic_params,
         );
         info.parse_result = parse_result;
         info.function_call_count = stats.function_call_count;
@@ -995,6 +1014,18 @@ impl MacroInferContext {
                     analyzer.collect_stmt_constraints(stmt, &mut info.type_env);
                 }
             }
+
+            // 文マクロには値がないので、apidoc の戻り値型（通常は void）をそのまま使う
+            if let Some(entry) = apidoc.and_then(|dict| dict.get(interner.get(name)))
+                && let Some(ref return_type) = entry.return_type
+            {
+                let type_repr = TypeRepr::from_c_type_string(return_type, interner, files, typedefs);
+                info.type_env.add_return_constraint(TypeConstraint::new(
+                    ExprId::INVALID,
+                    type_repr,
+                    format!("return type of macro {}", interner.get(name)),
+                ));
+            }
         }
     }
 
@@ -1039,6 +1070,40 @@ impl MacroInferContext {
         false
     }
 
+    /// 宣言の先頭になり得るトークンか判定（型キーワード・型修飾子・typedef 名）
+    ///
+    /// `dSP` (`SV **sp = PL_stack_sp`) のような宣言だけのマクロを
+    /// 式 `SV * (*sp) = ...` と誤読しないために使う。
+    fn is_declaration_start(kind: &TokenKind, typedefs: &HashSet<InternedStr>) -> bool {
+        match kind {
+            TokenKind::KwVoid
+            | TokenKind::KwChar
+            | TokenKind::KwShort
+            | TokenKind::KwInt
+            | TokenKind::KwLong
+            | TokenKind::KwFloat
+            | TokenKind::KwDouble
+            | TokenKind::KwSigned
+            | TokenKind::KwSigned2
+            | TokenKind::KwUnsigned
+            | TokenKind::KwBool
+            | TokenKind::KwBool2
+            | TokenKind::KwConst
+            | TokenKind::KwConst2
+            | TokenKind::KwConst3
+            | TokenKind::KwVolatile
+            | TokenKind::KwVolatile2
+            | TokenKind::KwVolatile3
+            | TokenKind::KwStatic
+            | TokenKind::KwRegister
+            | TokenKind::KwStruct
+            | TokenKind::KwUnion
+            | TokenKind::KwEnum => true,
+            TokenKind::Ident(id) => typedefs.contains(id),
+            _ => false,
+        }
+    }
+
     /// トークン列を式または文としてパース試行
     ///
     /// # Returns
@@ -1046,11 +1111,17 @@ impl MacroInferContext {
     fn try_parse_tokens(
         &self,
         tokens: &[crate::token::Token],
-        interner: &StringInterner,
-        files: &FileRegistry,
+        pp: &Preprocessor,
         typedefs: &HashSet<InternedStr>,
         generic_params: HashMap<InternedStr, usize>,
     ) -> (ParseResult, ParseStats, HashSet<InternedStr>) {
+        let interner = pp.interner();
+        let files = pp.files();
+        // 展開元のマクロをたどれるよう、バックトレース付きの診断メッセージにする
+        let describe = |err: CompileError| {
+            let backtrace = pp.inference_backtrace(err.loc());
+            err.with_backtrace(backtrace).render(files)
+        };
         if tokens.is_empty() {
             return (ParseResult::Unparseable(Some("empty token sequence".to_string())), ParseStats::default(), HashSet::new());
         }
@@ -1089,6 +1160,28 @@ impl MacroInferContext {
             }
         }
 
+        // 宣言で始まるマクロ（`dSP`, `dXSARGS` など）は、呼び出し側が付ける
+        // 末尾の `;` を補って宣言列としてパースする
+        let is_declaration = first_significant
+            .is_some_and(|t| Self::is_declaration_start(&t.kind, typedefs));
+        let decl_tokens;
+        let tokens = if is_declaration {
+            let last_significant = tokens.iter().rev().find(|t| {
+                !matches!(t.kind, TokenKind::Space | TokenKind::Newline)
+            });
+            match last_significant {
+                Some(last) if !matches!(last.kind, TokenKind::Semi) => {
+                    let mut v = tokens.to_vec();
+                    v.push(Token::new(TokenKind::Semi, last.loc.clone()));
+                    decl_tokens = v;
+                    &decl_tokens[..]
+                }
+                _ => tokens,
+            }
+        } else {
+            tokens
+        };
+
         // トップレベルにセミコロンがあれば複数文パースを試行
         if Self::has_toplevel_semicolon(tokens) {
             if generic_params.is_empty() {
@@ -1124,7 +1217,7 @@ impl MacroInferContext {
                     stats,
                     HashSet::new(),
                 ),
-                Err(err) => (ParseResult::Unparseable(Some(err.format_with_files(files))), ParseStats::default(), HashSet::new()),
+                Err(err) => (ParseResult::Unparseable(Some(describe(err))), ParseStats::default(), HashSet::new()),
             }
         } else {
             match parse_expression_from_tokens_ref_with_generic_params(tokens.to_vec(), interner, files, typedefs, generic_params) {
@@ -1133,7 +1226,7 @@ impl MacroInferContext {
                     stats,
                     detected,
                 ),
-                Err(err) => (ParseResult::Unparseable(Some(err.format_with_files(files))), ParseStats::default(), HashSet::new()),
+                Err(err) => (ParseResult::Unparseable(Some(describe(err))), ParseStats::default(), HashSet::new()),
             }
         }
     }
@@ -1255,6 +1348,87 @@ impl MacroInferContext {
                 inline_fn_dict.as_deref(), typedefs
             );
         }
+
+        // Step 7: オブジェクトマクロの定数畳み込み
+        self.fold_object_macro_constants(&target_macros, pp, rust_decl_dict, typedefs, no_expand);
+    }
+
+    /// オブジェクトマクロの本体を整数定数として評価する
+    ///
+    /// 本体を完全展開したトークン列を `PPExprEvaluator` の定数畳み込み
+    /// モードで評価する。展開後に残った識別子は bindings.rs の定数値で
+    /// 解決し、解決できなければ定数ではないとみなす。キャスト先の typedef は
+    /// bindings.rs の型エイリアスを辿って整数型に解決する。
+    fn fold_object_macro_constants(
+        &mut self,
+        target_macros: &[MacroDef],
+        pp: &mut Preprocessor,
+        rust_decl_dict: Option<&RustDeclDict>,
+        typedefs: &HashSet<InternedStr>,
+        no_expand: NoExpandSymbols,
+    ) {
+        for sym in no_expand.iter() {
+            pp.add_skip_expand_macro(sym);
+        }
+
+        for def in target_macros {
+            if !matches!(def.kind, MacroKind::Object) || def.body.is_empty() {
+                continue;
+            }
+            match self.macros.get(&def.name) {
+                Some(info) if info.is_expression() && !info.calls_unavailable => {}
+                _ => continue,
+            }
+
+            let mut in_progress = HashSet::new();
+            in_progress.insert(def.name);
+            let expanded = match pp.expand_macro_body_for_inference(
+                &def.body, &[], &[], &mut in_progress,
+            ) {
+                Ok((tokens, _)) => tokens.into_iter()
+                    .filter(|t| !matches!(t.kind,
+                        TokenKind::MacroBegin(_) | TokenKind::MacroEnd(_) | TokenKind::Newline))
+                    .collect::<Vec<_>>(),
+                Err(_) => continue,
+            };
+
+            let interner = pp.interner();
+            let resolve = |id: InternedStr| {
+                rust_decl_dict
+                    .and_then(|d| d.lookup_const(interner.get(id)))
+                    .and_then(|c| c.value)
+            };
+            let resolve_type = |id: InternedStr| {
+                let mut name = interner.get(id).to_string();
+                // alias の循環に備えて深さを制限
+                for _ in 0..8 {
+                    if let Some(ty) = CIntType::from_rust_name(&name) {
+                        return Some(ty);
+                    }
+                    match rust_decl_dict.and_then(|d| d.lookup_type(&name)) {
+                        Some(alias) => name = alias.uty.to_rust_string(),
+                        None => return CIntType::from_std_typedef(&name),
+                    }
+                }
+                None
+            };
+            let env = crate::pp_expr::ConstFoldEnv {
+                resolve_ident: &resolve,
+                type_names: typedefs,
+                resolve_type: &resolve_type,
+            };
+            let mut evaluator = crate::pp_expr::PPExprEvaluator::new(
+                &expanded, interner, pp.macros(), def.def_loc.clone(),
+            ).with_const_env(env);
+
+            // `(bool)x` は bool を返す関数として出力する
+            if let Ok(value) = evaluator.evaluate_constant()
+                && value.ty != CIntType::BOOL
+                && let Some(info) = self.macros.get_mut(&def.name)
+            {
+                info.const_value = Some(value);
+            }
+        }
     }
 
     /// used_by を辿ってフラグを推移的に伝播
@@ -1636,11 +1810,18 @@ impl MacroInferContext {
         // 確定済みマクロのパラメータ型キャッシュ（ネストしたマクロ呼び出しからの型伝播用）
         let mut param_types_cache: HashMap<String, Vec<(String, String)>> = HashMap::new();
 
+        // 先に確定したマクロの型がキャッシュ経由で後のマクロに伝わるので、
+        // HashSet の反復順に依らないよう同順位は名前順にする
         loop {
-            let candidates = self.get_inference_candidates();
+            let mut candidates = self.get_inference_candidates();
+            candidates.sort_by_cached_key(|name| {
+                let uses = self.macros.get(name).map(|info| info.uses.len()).unwrap_or(0);
+                (uses, interner.get(*name))
+            });
             if candidates.is_empty() {
                 // 残りの未確定マクロにも型推論を実行（apidoc 情報を適用するため）
-                let remaining: Vec<_> = self.unconfirmed.iter().copied().collect();
+                let mut remaining: Vec<_> = self.unconfirmed.iter().copied().collect();
+                remaining.sort_by_key(|name| interner.get(*name));
                 for name in remaining {
                     // パラメータを取得
                     let params: Vec<InternedStr> = macro_table
@@ -2388,7 +2569,7 @@ fn visit_stmt_for_calls(
 }
 
 /// `Expr` の子ノードを構造的に列挙する小ヘルパ。Call の特殊扱いは呼出側で行う前提。
-fn walk_expr_children<F: FnMut(&Expr)>(expr: &Expr, f: &mut F) {
+pub(crate) fn walk_expr_children<F: FnMut(&Expr)>(expr: &Expr, f: &mut F) {
     match &expr.kind {
         ExprKind::Ident(_)
         | ExprKind::IntLit(_)
@@ -0,0 +1,300 @@
+//! マクロ本体の純粋性解析
+//!
+//! `MacroInferInfo::has_unsafe_ops()` は関数呼び出し・デリファレンスの有無しか
+//! 見ないので、生成する関数は常に `pub unsafe fn` になる。フラグ判定や
+//! `packWARN2` のような整数演算だけのマクロは、本体を調べれば安全な
+//! `pub fn`、さらに定数評価できれば `pub const fn` として出力できる。
+//!
+//! 判定は保守的に行う:
+//!
+//! - シグネチャ（パラメータ・戻り値）がすべて算術型（整数・浮動小数点・bool）
+//! - THX 依存・型引数・リテラル文字列引数を持たない
+//! - 本体は式で、リテラル・パラメータ・定数・enum 値、算術/比較/論理演算、
+//!   算術型へのキャスト、`sizeof`、条件式、コンマ式のみから成る
+//! - 関数呼び出しは、それ自身が純粋と判定済みのマクロに対するものだけ
+//!
+//! 呼び出し先の判定結果は [`PurityChecker`] に渡す表で参照する。
+//! `CodegenDriver` は依存順（葉から）に生成しながら表を埋める。
+
+use std::collections::HashMap;
+
+use crate::ast::{Expr, ExprKind, TypeName, TypeSpec};
+use crate::enum_dict::EnumDict;
+use crate::intern::{InternedStr, StringInterner};
+use crate::macro_infer::{MacroInferContext, MacroInferInfo, ParseResult};
+use crate::rust_decl::RustDeclDict;
+
+/// 生成する関数の安全性
+///
+/// 順序は `Unsafe < Safe < Const`。複数の部分式の結果は `min` で合成する。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Purity {
+    /// `pub unsafe fn`
+    #[default]
+    Unsafe,
+    /// `pub fn`
+    Safe,
+    /// `pub const fn`
+    Const,
+}
+
+impl Purity {
+    /// 関数定義の修飾子（`pub` と `fn` の間）
+    pub fn fn_qualifier(self) -> &'static str {
+        match self {
+            Purity::Unsafe => "unsafe ",
+            Purity::Safe => "",
+            Purity::Const => "const ",
+        }
+    }
+}
+
+/// Rust の算術プリミティブ型（`::std::os::raw::` 等のパスは除いた名前）
+const ARITHMETIC_TYPES: &[&str] = &[
+    "i8", "i16", "i32", "i64", "i128", "isize",
+    "u8", "u16", "u32", "u64", "u128", "usize",
+    "f32", "f64", "bool",
+    "c_char", "c_schar", "c_uchar", "c_short", "c_ushort", "c_int", "c_uint",
+    "c_long", "c_ulong", "c_longlong", "c_ulonglong", "c_float", "c_double",
+];
+
+/// Rust の型文字列が算術型か（bindings の型エイリアスを辿る）
+pub fn is_arithmetic_rust_type(ty: &str, rust_decl_dict: Option<&RustDeclDict>) -> bool {
+    let mut ty = ty.trim();
+    // `type A = B; type B = u32;` のような連鎖。循環に備えて深さを制限する
+    for _ in 0..8 {
+        // bindings の型文字列はトークン列由来で `:: std :: os :: raw :: c_uint` のように空白を含む
+        let base = ty.rsplit("::").next().unwrap_or(ty).trim();
+        if ARITHMETIC_TYPES.contains(&base) {
+            return true;
+        }
+        match rust_decl_dict.and_then(|d| d.types.get(base)) {
+            Some(alias) => ty = alias.ty.trim(),
+            None => return false,
+        }
+    }
+    false
+}
+
+/// マクロの純粋性判定器
+pub struct PurityChecker<'a> {
+    interner: &'a StringInterner,
+    enum_dict: &'a EnumDict,
+    macro_ctx: &'a MacroInferContext,
+    rust_decl_dict: Option<&'a RustDeclDict>,
+    /// 判定済みの呼び出し先マクロ（ここにないマクロの呼び出しは unsafe 扱い）
+    callees: &'a HashMap<InternedStr, Purity>,
+}
+
+impl<'a> PurityChecker<'a> {
+    /// 新しい判定器を作成
+    pub fn new(
+        interner: &'a StringInterner,
+        enum_dict: &'a EnumDict,
+        macro_ctx: &'a MacroInferContext,
+        rust_decl_dict: Option<&'a RustDeclDict>,
+        callees: &'a HashMap<InternedStr, Purity>,
+    ) -> Self {
+        Self { interner, enum_dict, macro_ctx, rust_decl_dict, callees }
+    }
+
+    /// マクロの純粋性を判定する
+    ///
+    /// `param_types` / `return_type` は codegen が決定した Rust の型文字列。
+    pub fn macro_purity(&self, info: &MacroInferInfo, param_types: &[String], return_type: &str) -> Purity {
+        if info.is_thx_dependent
+            || !info.generic_type_params.is_empty()
+            || !info.literal_string_params.is_empty()
+        {
+            return Purity::Unsafe;
+        }
+        if param_types.len() != info.params.len()
+            || !param_types.iter().all(|ty| is_arithmetic_rust_type(ty, self.rust_decl_dict))
+            || !is_arithmetic_rust_type(return_type, self.rust_decl_dict)
+        {
+            return Purity::Unsafe;
+        }
+        match &info.parse_result {
+            // オブジェクトマクロのトップレベルのカンマは配列の初期化子用の並びで、1 つの値ではない
+            ParseResult::Expression(expr)
+                if !info.is_function && matches!(expr.kind, ExprKind::Comma { .. }) => Purity::Unsafe,
+            ParseResult::Expression(expr) => self.expr_purity(expr, info),
+            _ => Purity::Unsafe,
+        }
+    }
+
+    /// 式の純粋性
+    fn expr_purity(&self, expr: &Expr, info: &MacroInferInfo) -> Purity {
+        match &expr.kind {
+            ExprKind::IntLit(_) | ExprKind::UIntLit(_)
+            | ExprKind::FloatLit(_) | ExprKind::CharLit(_) => Purity::Const,
+            ExprKind::Ident(name) => {
+                let is_value = info.params.iter().any(|p| p.name == *name)
+                    || self.enum_dict.is_enum_variant(*name)
+                    || self.rust_decl_dict
+                        .is_some_and(|d| d.consts.contains_key(self.interner.get(*name)));
+                if is_value { Purity::Const } else { Purity::Unsafe }
+            }
+            ExprKind::UnaryPlus(inner) | ExprKind::UnaryMinus(inner)
+            | ExprKind::BitNot(inner) | ExprKind::LogNot(inner) => self.expr_purity(inner, info),
+            ExprKind::Binary { lhs, rhs, .. } | ExprKind::Comma { lhs, rhs } => {
+                self.expr_purity(lhs, info).min(self.expr_purity(rhs, info))
+            }
+            ExprKind::Conditional { cond, then_expr, else_expr } => {
+                self.expr_purity(cond, info)
+                    .min(self.expr_purity(then_expr, info))
+                    .min(self.expr_purity(else_expr, info))
+            }
+            ExprKind::Cast { type_name, expr } => {
+                if self.is_arithmetic_type_name(type_name) {
+                    self.expr_purity(expr, info)
+                } else {
+                    Purity::Unsafe
+                }
+            }
+            ExprKind::SizeofType(_) => Purity::Const,
+            // `std::mem::size_of_val(&(expr))` として出力され、Rust では expr も評価される。
+            // size_of_val の const 化は新しい（1.85）ので const fn にはしない
+            ExprKind::Sizeof(inner) => Purity::Safe.min(self.expr_purity(inner, info)),
+            ExprKind::Call { func, args } => match &func.kind {
+                ExprKind::Ident(name) if self.interner.get(*name) == "__builtin_expect" => {
+                    self.args_purity(Purity::Const, args, info)
+                }
+                ExprKind::Ident(name) => self.call_purity(*name, args, info),
+                _ => Purity::Unsafe,
+            },
+            ExprKind::MacroCall { name, args, expanded, .. } => {
+                // codegen が呼び出しとして残すマクロ（should_emit_as_macro_call と同じ条件）
+                let kept_as_call = self.macro_ctx.macros.get(name)
+                    .is_some_and(|m| m.is_parseable() && !m.calls_unavailable);
+                if kept_as_call {
+                    self.call_purity(*name, args, info)
+                } else {
+                    self.expr_purity(expanded, info)
+                }
+            }
+            _ => Purity::Unsafe,
+        }
+    }
+
+    /// 判定済みマクロの呼び出しの純粋性
+    fn call_purity(&self, name: InternedStr, args: &[Expr], info: &MacroInferInfo) -> Purity {
+        match self.callees.get(&name) {
+            Some(&callee) => self.args_purity(callee, args, info),
+            None => Purity::Unsafe,
+        }
+    }
+
+    fn args_purity(&self, init: Purity, args: &[Expr], info: &MacroInferInfo) -> Purity {
+        args.iter().fold(init, |acc, arg| acc.min(self.expr_purity(arg, info)))
+    }
+
+    /// キャスト先が算術型か
+    fn is_arithmetic_type_name(&self, type_name: &TypeName) -> bool {
+        if type_name.declarator.as_ref().is_some_and(|d| !d.derived.is_empty()) {
+            return false;
+        }
+        match type_name.specs.type_specs.as_slice() {
+            [TypeSpec::TypedefName(name)] => {
+                is_arithmetic_rust_type(self.interner.get(*name), self.rust_decl_dict)
+            }
+            specs => !specs.is_empty() && specs.iter().all(|s| matches!(s,
+                TypeSpec::Char | TypeSpec::Short | TypeSpec::Int | TypeSpec::Long
+                | TypeSpec::Float | TypeSpec::Double | TypeSpec::Signed
+                | TypeSpec::Unsigned | TypeSpec::Bool)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::BinOp;
+    use crate::macro_infer::MacroParam;
+    use crate::source::SourceLocation;
+
+    fn e(kind: ExprKind) -> Expr {
+        Expr::new(kind, SourceLocation::default())
+    }
+
+    #[test]
+    fn test_is_arithmetic_rust_type() {
+        let dict = RustDeclDict::parse(
+            "pub type U32 = ::std::os::raw::c_uint;\n\
+             pub type line_t = U32;\n\
+             pub type SVPTR = *mut sv;\n",
+        );
+        assert!(is_arithmetic_rust_type("line_t", Some(&dict)));
+        assert!(is_arithmetic_rust_type("bool", None));
+        assert!(!is_arithmetic_rust_type("SVPTR", Some(&dict)));
+        assert!(!is_arithmetic_rust_type("*mut SV", Some(&dict)));
+    }
+
+    #[test]
+    fn test_macro_purity() {
+        let mut interner = StringInterner::new();
+        let enum_dict = EnumDict::new();
+        let macro_ctx = MacroInferContext::new();
+        let a = interner.intern("a");
+        let b = interner.intern("b");
+        let pack = interner.intern("packWARN");
+        let global = interner.intern("PL_dowarn");
+        let sizeof_a = interner.intern("SIZEOF_A");
+        let bytes = interner.intern("BYTES");
+
+        let mut info = MacroInferInfo::new(interner.intern("packWARN2"));
+        info.params = vec![MacroParam::new(a, SourceLocation::default()), MacroParam::new(b, SourceLocation::default())];
+        let types = ["U32".to_string(), "U32".to_string()];
+        let dict = RustDeclDict::parse("pub type U32 = ::std::os::raw::c_uint;\n");
+
+        // packWARN(a) | b << 8
+        let call = |arg: InternedStr| e(ExprKind::Call {
+            func: Box::new(e(ExprKind::Ident(pack))),
+            args: vec![e(ExprKind::Ident(arg))],
+        });
+        let shift = e(ExprKind::Binary {
+            op: BinOp::Shl,
+            lhs: Box::new(e(ExprKind::Ident(b))),
+            rhs: Box::new(e(ExprKind::IntLit(8))),
+        });
+        info.parse_result = ParseResult::Expression(Box::new(e(ExprKind::Binary {
+            op: BinOp::BitOr,
+            lhs: Box::new(call(a)),
+            rhs: Box::new(shift),
+        })));
+
+        // 呼び出し先が未判定なら unsafe
+        let mut callees = HashMap::new();
+        let checker = PurityChecker::new(&interner, &enum_dict, &macro_ctx, Some(&dict), &callees);
+        assert_eq!(checker.macro_purity(&info, &types, "U32"), Purity::Unsafe);
+
+        callees.insert(pack, Purity::Const);
+        let checker = PurityChecker::new(&interner, &enum_dict, &macro_ctx, Some(&dict), &callees);
+        assert_eq!(checker.macro_purity(&info, &types, "U32"), Purity::Const);
+        // ポインタを返す・THX 依存なら unsafe
+        assert_eq!(checker.macro_purity(&info, &types, "*mut SV"), Purity::Unsafe);
+        info.is_thx_dependent = true;
+        assert_eq!(checker.macro_purity(&info, &types, "U32"), Purity::Unsafe);
+        info.is_thx_dependent = false;
+
+        // sizeof(a) は安全だが const fn にはしない
+        let mut sized = MacroInferInfo::new(sizeof_a);
+        sized.params = vec![MacroParam::new(a, SourceLocation::default())];
+        sized.parse_result = ParseResult::Expression(Box::new(e(ExprKind::Sizeof(Box::new(e(ExprKind::Ident(a)))))));
+        assert_eq!(checker.macro_purity(&sized, &types[..1], "usize"), Purity::Safe);
+
+        // グローバル変数の参照は unsafe
+        info.parse_result = ParseResult::Expression(Box::new(e(ExprKind::Ident(global))));
+        assert_eq!(checker.macro_purity(&info, &types, "U32"), Purity::Unsafe);
+
+        // オブジェクトマクロの `1, 2` は値の並びなので純粋ではない
+        let mut list = MacroInferInfo::new(bytes);
+        list.parse_result = ParseResult::Expression(Box::new(e(ExprKind::Comma {
+            lhs: Box::new(e(ExprKind::IntLit(1))),
+            rhs: Box::new(e(ExprKind::IntLit(2))),
+        })));
+        assert_eq!(checker.macro_purity(&list, &[], "c_int"), Purity::Unsafe);
+        list.is_function = true;
+        assert_eq!(checker.macro_purity(&list, &[], "c_int"), Purity::Const);
+    }
+}
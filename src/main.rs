@@ -4,17 +4,19 @@
 
 use std::fs::File;
 use std::io::{self, BufWriter, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use std::ops::ControlFlow;
+use std::time::{Duration, Instant};
 
 use clap::Parser as ClapParser;
 use libperl_macrogen::{
-    ApidocDict, BlockItem, CompileError, FieldsDict, FileId,
-    ParseResult, Parser, Preprocessor, RustDeclDict, SexpPrinter,
+    ApiDiff, ApidocDict, BlockItem, CodegenReport, CompileError, FieldsDict, FileId, IncludeGraphFormat,
+    InferResult, ParseResult, Parser, Preprocessor, RustDeclDict, SexpPrinter,
+    write_macro_infer_json, write_translation_unit_json,
     SourceLocation, TokenKind, TypedSexpPrinter,
     // Pipeline API
-    Pipeline, PipelineError,
+    CacheStatus, CompileCheckConfig, DualModePipeline, PerlConfigSource, Pipeline, PipelineBuilder, PipelineError,
 };
 
 /// コマンドライン引数
@@ -37,6 +39,17 @@ struct Cli {
     #[arg(short = 'o', long = "output")]
     output: Option<PathBuf>,
 
+    /// --gen-rust の出力を定義元ヘッダー別のモジュール（sv.rs, cop.rs, ... と
+    /// 再エクスポート用の mod.rs）としてディレクトリに書き出す
+    #[arg(long = "output-dir", value_name = "DIR", conflicts_with = "output")]
+    output_dir: Option<PathBuf>,
+
+    /// --gen-rust の生成結果をキャッシュするディレクトリ。
+    /// 入力（ヘッダー・定義・bindings・apidoc・設定）が前回と同じなら再生成しない。
+    /// --compile-check 指定時はキャッシュを使わない
+    #[arg(long = "cache-dir", value_name = "DIR")]
+    cache_dir: Option<PathBuf>,
+
     /// プリプロセッサ出力のみ (cc -E 相当)
     #[arg(short = 'E')]
     preprocess_only: bool,
@@ -49,6 +62,15 @@ struct Cli {
     #[arg(long = "auto")]
     auto: bool,
 
+    /// --auto で Config を読む perl 実行ファイル（省略時は PATH 上の perl）
+    #[arg(long = "perl", value_name = "PATH", requires = "auto", conflicts_with = "perl_config")]
+    perl: Option<PathBuf>,
+
+    /// --auto で perl を実行せず、保存した Config ダンプ
+    /// （`perl -V:'.*'` の出力・Config_heavy.pl・config.sh）から設定を読む
+    #[arg(long = "perl-config", value_name = "FILE", requires = "auto")]
+    perl_config: Option<PathBuf>,
+
     /// GCC互換の出力形式 (-E と併用)
     #[arg(long = "gcc-format")]
     gcc_format: bool,
@@ -57,6 +79,11 @@ struct Cli {
     #[arg(long = "streaming")]
     streaming: bool,
 
+    /// パースできない宣言を読み飛ばして続行し、警告として表示する
+    /// （省略時は最初のパースエラーで失敗する）
+    #[arg(long = "recover-parse-errors")]
+    recover_parse_errors: bool,
+
     /// 型注釈付きS-expression出力
     #[arg(long = "typed-sexp")]
     typed_sexp: bool,
@@ -65,6 +92,15 @@ struct Cli {
     #[arg(long = "sexp")]
     sexp: bool,
 
+    /// AST を JSON で出力（マクロ型推論を行わない）。名前は文字列に解決し、
+    /// 位置の file_id は files 配列の添字
+    #[arg(long = "json-ast")]
+    json_ast: bool,
+
+    /// ターゲットマクロの型推論結果（パラメータ・型制約・推論状態・確定型）を JSON で出力
+    #[arg(long = "dump-infer-json")]
+    dump_infer_json: bool,
+
     /// 構造体フィールド辞書をダンプ
     #[arg(long = "dump-fields-dict")]
     dump_fields_dict: bool,
@@ -74,11 +110,26 @@ struct Cli {
     #[arg(long = "dump-macros", value_name = "FILTER")]
     dump_macros: Option<Option<String>>,
 
+    /// インクルードグラフを出力（json / dot）。各 #include の位置・<>/"" の別・
+    /// #include_next か、各ファイルが target_dir 配下（is_target）かを含む
+    #[arg(long = "include-graph", value_name = "FORMAT")]
+    include_graph: Option<IncludeGraphFormat>,
+
+    /// 指定したマクロの展開過程（呼び出しごとの展開木・引数の prescan・
+    /// 展開を止めた識別子）を表示
+    #[arg(long = "explain-expansion", value_name = "NAME")]
+    explain_expansion: Option<String>,
+
+    /// --explain-expansion の出力形式
+    #[arg(long = "explain-format", value_name = "FORMAT", default_value = "text",
+          value_parser = ["text", "json"], requires = "explain_expansion")]
+    explain_format: String,
+
     /// ApidocファイルをJSONに変換して出力（入力ファイルはapidoc）
     #[arg(long = "apidoc-to-json")]
     apidoc_to_json: bool,
 
-    /// コンパクトなJSON出力（--apidoc-to-json用）
+    /// コンパクトなJSON出力（--apidoc-to-json / --json-ast / --dump-infer-json 用）
     #[arg(long = "compact")]
     compact: bool,
 
@@ -122,6 +173,16 @@ struct Cli {
     #[arg(long = "macro-comments")]
     macro_comments: bool,
 
+    /// 生成する関数に perlapi の POD 本文（=for apidoc に続く説明）と
+    /// C のシグネチャを rustdoc として付ける
+    #[arg(long = "pod-docs", requires = "gen_rust")]
+    pod_docs: bool,
+
+    /// 生成コードを置くモジュールのクレートルートからのパス（例: ffi::macros）。
+    /// macro_rules! の本体は `use $crate::<PATH>::*;` で名前を解決する
+    #[arg(long = "macro-path", value_name = "PATH", requires = "gen_rust")]
+    macro_path: Option<String>,
+
     /// Rust コード生成（マクロと inline 関数）
     #[arg(long = "gen-rust")]
     gen_rust: bool,
@@ -155,10 +216,82 @@ struct Cli {
     #[arg(long = "skip-codegen-list", value_name = "FILE")]
     skip_codegen_list: Vec<PathBuf>,
 
+    /// マクロ / inline 関数ごとの生成結果を JSON で出力するファイル
+    #[arg(long = "report-json", value_name = "FILE")]
+    report_json: Option<PathBuf>,
+
+    /// 変換できないマクロ（パース失敗・型推論不完全・ジェネリクス）のうち apidoc に
+    /// シグネチャがあるものを、マクロを呼ぶ C 関数として FILE に書き出し、
+    /// Rust 側には extern "C" 宣言とラッパー関数を出力する
+    #[arg(long = "c-shim", value_name = "FILE", requires = "gen_rust",
+          conflicts_with_all = ["dual_mode_perl", "dual_mode_perl_config"])]
+    c_shim: Option<PathBuf>,
+
+    /// apidoc の NN / NULLOK / NZ 注釈を生成するマクロ関数に反映する
+    /// （assert: debug_assert! を入れる、nonnull: NonNull<T> / Option<NonNull<T>> で受け取る）
+    #[arg(long = "nullability", value_name = "MODE", value_parser = parse_nullability_mode,
+          requires = "gen_rust")]
+    nullability: Option<libperl_macrogen::nullability::NullabilityMode>,
+
+    /// 生成コードを rustc でコンパイルし、エラーになる関数を抑止して再生成する
+    #[arg(long = "compile-check")]
+    compile_check: bool,
+
+    /// --compile-check で抑止した関数を skip_codegen パッチとして書き出すファイル
+    /// （ディレクトリ指定時は v$X.$Y.patches.json）
+    #[arg(long = "compile-check-patches", value_name = "FILE", requires = "compile_check")]
+    compile_check_patches: Option<PathBuf>,
+
     /// 対象 perl の build mode（threaded / non-threaded / auto）
     /// 省略時は auto（実行時の `perl Config{usethreads}` から自動検出）
     #[arg(long = "perl-build-mode", value_name = "MODE", value_parser = parse_perl_build_mode)]
     perl_build_mode: Option<libperl_macrogen::perl_config::PerlBuildMode>,
+
+    /// threaded / non-threaded 両対応のコードを生成する。--perl / --perl-config と
+    /// build mode が異なるもう一方の perl 実行ファイルを指定する。
+    /// 異なるアイテムには #[cfg(perl_threaded)] / #[cfg(not(perl_threaded))] が付く
+    #[arg(long = "dual-mode-perl", value_name = "PATH", requires_all = ["auto", "gen_rust"],
+          conflicts_with_all = ["dual_mode_perl_config", "perl_build_mode", "output_dir", "cache_dir",
+                                "compile_check", "report_json"])]
+    dual_mode_perl: Option<PathBuf>,
+
+    /// --dual-mode-perl の代わりに、もう一方の perl の保存した Config ダンプを指定する
+    #[arg(long = "dual-mode-perl-config", value_name = "FILE", requires_all = ["auto", "gen_rust"],
+          conflicts_with_all = ["perl_build_mode", "output_dir", "cache_dir", "compile_check", "report_json"])]
+    dual_mode_perl_config: Option<PathBuf>,
+
+    /// もう一方の perl 用の Rust バインディングファイル（省略時は --bindings と同じ）
+    #[arg(long = "dual-mode-bindings", value_name = "FILE")]
+    dual_mode_bindings: Option<PathBuf>,
+
+    /// threaded / non-threaded で異なったアイテムの一覧を JSON で出力するファイル
+    #[arg(long = "dual-mode-report", value_name = "FILE")]
+    dual_mode_report: Option<PathBuf>,
+
+    /// 2 つの --report-json の出力（古い方・新しい方の順）を比較し、追加 / 削除・
+    /// シグネチャ変更・生成結果の変更を表示する（入力ファイル不要）
+    #[arg(long = "diff-reports", value_names = ["OLD", "NEW"], num_args = 2)]
+    diff_reports: Option<Vec<PathBuf>>,
+
+    /// --auto の perl（新しい側）に対し、この perl（古い側）でも Pipeline を実行して
+    /// API の差分を表示する
+    #[arg(long = "diff-base-perl", value_name = "PATH", requires = "auto",
+          conflicts_with_all = ["diff_base_perl_config", "dual_mode_perl", "dual_mode_perl_config"])]
+    diff_base_perl: Option<PathBuf>,
+
+    /// --diff-base-perl の代わりに、古い側の perl の保存した Config ダンプを指定する
+    #[arg(long = "diff-base-perl-config", value_name = "FILE", requires = "auto",
+          conflicts_with_all = ["dual_mode_perl", "dual_mode_perl_config"])]
+    diff_base_perl_config: Option<PathBuf>,
+
+    /// 古い側の Rust バインディングファイル（省略時は --bindings と同じ）
+    #[arg(long = "diff-base-bindings", value_name = "FILE")]
+    diff_base_bindings: Option<PathBuf>,
+
+    /// API 差分の出力形式
+    #[arg(long = "diff-format", value_name = "FORMAT", default_value = "text",
+          value_parser = ["text", "json"])]
+    diff_format: String,
 }
 
 /// `--perl-build-mode` の値パーサー
@@ -182,6 +315,19 @@ fn parse_perl_build_mode(s: &str) -> Result<libperl_macrogen::perl_config::PerlB
     }
 }
 
+/// `--nullability` の値をパース
+fn parse_nullability_mode(s: &str) -> Result<libperl_macrogen::nullability::NullabilityMode, String> {
+    use libperl_macrogen::nullability::NullabilityMode;
+    match s {
+        "off" => Ok(NullabilityMode::Off),
+        "assert" | "debug-assert" => Ok(NullabilityMode::DebugAssert),
+        "nonnull" => Ok(NullabilityMode::NonNull),
+        other => Err(format!(
+            "unknown nullability mode: {} (expected 'off', 'assert' or 'nonnull')", other
+        )),
+    }
+}
+
 fn main() {
     if let Err(e) = run() {
         eprintln!("Error: {}", e);
@@ -197,6 +343,15 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
         return run_parse_rust_bindings(rust_file);
     }
 
+    // --diff-reports: 保存したレポート同士を比較（プリプロセッサ不要）
+    if let Some(ref paths) = cli.diff_reports {
+        let old = CodegenReport::read_json(&paths[0])
+            .map_err(|e| format!("{}: {}", paths[0].display(), e))?;
+        let new = CodegenReport::read_json(&paths[1])
+            .map_err(|e| format!("{}: {}", paths[1].display(), e))?;
+        return write_api_diff(&ApiDiff::between(&old, &new), &cli.diff_format, cli.output.as_ref());
+    }
+
     // --apidoc-to-json: ApidocファイルをJSONに変換（プリプロセッサ不要）
     if cli.apidoc_to_json {
         let input = cli.input.ok_or("Input file (apidoc) is required for --apidoc-to-json")?;
@@ -204,17 +359,202 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     // 入力ファイルが必要
-    let input = cli.input.ok_or("Input file is required")?;
+    let input = cli.input.clone().ok_or("Input file is required")?;
 
     // PipelineBuilder を構築
-    let mut builder = Pipeline::builder(&input);
+    let perl_source = match (&cli.perl, &cli.perl_config) {
+        (Some(perl), _) => PerlConfigSource::executable(perl),
+        (_, Some(dump)) => PerlConfigSource::dump_file(dump),
+        _ => PerlConfigSource::default(),
+    };
+    let builder = configure_builder(&cli, &input, &perl_source, cli.bindings.as_ref())?;
+
+    // --diff-base-perl / --diff-base-perl-config: 古い perl との API 差分
+    let base_source = match (&cli.diff_base_perl, &cli.diff_base_perl_config) {
+        (Some(perl), _) => Some(PerlConfigSource::executable(perl)),
+        (_, Some(dump)) => Some(PerlConfigSource::dump_file(dump)),
+        _ => None,
+    };
+    if let Some(base_source) = base_source {
+        let bindings = cli.diff_base_bindings.as_ref().or(cli.bindings.as_ref());
+        let base = configure_builder(&cli, &input, &base_source, bindings)?;
+        let diff = ApiDiff::from_pipelines(base, builder)
+            .map_err(|e| format_pipeline_error(&e))?;
+        return write_api_diff(&diff, &cli.diff_format, cli.output.as_ref());
+    }
+
+    // --dual-mode-perl / --dual-mode-perl-config: threaded / non-threaded 両対応のコードを生成
+    let dual_source = match (&cli.dual_mode_perl, &cli.dual_mode_perl_config) {
+        (Some(perl), _) => Some(PerlConfigSource::executable(perl)),
+        (_, Some(dump)) => Some(PerlConfigSource::dump_file(dump)),
+        _ => None,
+    };
+    if let Some(dual_source) = dual_source {
+        let bindings = cli.dual_mode_bindings.as_ref().or(cli.bindings.as_ref());
+        let other = configure_builder(&cli, &input, &dual_source, bindings)?;
+        return run_gen_rust_dual(builder, other, &cli);
+    }
+
+    // --gen-rust + --cache-dir: キャッシュヒット時はプリプロセスも省略する
+    if cli.gen_rust && let Some(ref dir) = cli.cache_dir {
+        let mut buffer = Vec::new();
+        let generated = builder.with_cache_dir(dir).build()?.generate(&mut buffer)
+            .map_err(|e| format_pipeline_error(&e))?;
+        match generated.cache_status() {
+            Some(CacheStatus::Hit) => eprintln!("[cache] hit: {}", dir.display()),
+            Some(CacheStatus::Miss(miss)) => eprintln!("[cache] miss: {}", miss),
+            None => {}
+        }
+        // キャッシュヒット時は前回の生成時の警告を再表示する
+        for e in generated.skipped_declarations() {
+            eprintln!("warning: skipped declaration: {}", e);
+        }
+        if cli.progress {
+            match (generated.try_result(), generated.infer_elapsed()) {
+                (Some(result), Some(elapsed)) => print_include_guard_progress(&result.preprocessor, elapsed),
+                _ => eprintln!("Include guards: cache hit, no headers read"),
+            }
+        }
+        return write_gen_rust_output(
+            &generated, buffer, cli.output.as_ref(), cli.output_dir.as_ref(),
+            &cli.rust_edition, cli.strict_rustfmt,
+        );
+    }
+
+    // Pipeline を構築してプリプロセスを実行
+    let mut preprocessed = builder.build()?.preprocess()
+        .map_err(|e| format_pipeline_error(&e))?;
+
+    if cli.preprocess_only {
+        // -E: プリプロセス結果のみ出力
+        output_preprocessed(preprocessed.preprocessor_mut(), cli.output.as_ref(), cli.gcc_format)?;
+    } else if cli.streaming {
+        // --streaming: ストリーミングモード
+        run_streaming(preprocessed.preprocessor_mut(), cli.recover_parse_errors)?;
+    } else if cli.typed_sexp {
+        // --typed-sexp: 型注釈付きS-expression出力
+        run_typed_sexp(preprocessed.preprocessor_mut(), cli.output.as_ref())?;
+    } else if cli.dump_fields_dict {
+        // --dump-fields-dict: 構造体フィールド辞書をダンプ
+        run_dump_fields_dict(preprocessed.preprocessor_mut(), cli.target_dir.as_ref())?;
+    } else if let Some(ref filter_opt) = cli.dump_macros {
+        // --dump-macros: マクロ定義をダンプ
+        // まず全トークンを消費してマクロ定義を収集
+        let filter = filter_opt.as_deref().unwrap_or("");
+        let pp = preprocessed.preprocessor_mut();
+        loop {
+            match pp.next_token() {
+                Ok(token) if matches!(token.kind, libperl_macrogen::TokenKind::Eof) => break,
+                Ok(_) => continue,
+                Err(e) => return Err(format_error(&e, pp).into()),
+            }
+        }
+        pp.macros().dump_filtered(filter, pp.interner());
+    } else if let Some(format) = cli.include_graph {
+        // --include-graph: 全トークンを消費してからインクルードグラフを出力
+        let pp = preprocessed.preprocessor_mut();
+        loop {
+            match pp.next_token() {
+                Ok(token) if matches!(token.kind, TokenKind::Eof) => break,
+                Ok(_) => continue,
+                Err(e) => return Err(format_error(&e, pp).into()),
+            }
+        }
+        let text = pp.include_graph().render(format);
+        match cli.output {
+            Some(ref path) => std::fs::write(path, text)?,
+            None => io::stdout().write_all(text.as_bytes())?,
+        }
+    } else if let Some(ref name) = cli.explain_expansion {
+        // --explain-expansion: マクロの展開過程を表示
+        run_explain_expansion(
+            preprocessed.preprocessor_mut(), name, &cli.explain_format, cli.output.as_ref(),
+        )?;
+    } else if cli.sexp {
+        // --sexp: S-expression出力（マクロ型推論なし）
+        let pp = preprocessed.preprocessor_mut();
+        let mut parser = match Parser::new(pp) {
+            Ok(p) => p,
+            Err(e) => return Err(format_error(&e, pp).into()),
+        };
+        let tu = match parser.parse() {
+            Ok(tu) => tu,
+            Err(e) => return Err(format_error(&e, pp).into()),
+        };
+
+        // 出力
+        if let Some(output_path) = cli.output {
+            let file = File::create(&output_path)?;
+            let mut writer = BufWriter::new(file);
+            let mut printer = SexpPrinter::new(&mut writer, pp.interner());
+            printer.print_translation_unit(&tu)?;
+            writer.flush()?;
+        } else {
+            let stdout = io::stdout();
+            let mut handle = stdout.lock();
+            let mut printer = SexpPrinter::new(&mut handle, pp.interner());
+            printer.print_translation_unit(&tu)?;
+            handle.flush()?;
+        }
+    } else if cli.json_ast {
+        // --json-ast: AST の JSON 出力（マクロ型推論なし）
+        let pp = preprocessed.preprocessor_mut();
+        let mut parser = match Parser::new(pp) {
+            Ok(p) => p,
+            Err(e) => return Err(format_error(&e, pp).into()),
+        };
+        let tu = match parser.parse() {
+            Ok(tu) => tu,
+            Err(e) => return Err(format_error(&e, pp).into()),
+        };
+        let mut writer = open_output(cli.output.as_ref())?;
+        write_translation_unit_json(&mut writer, &tu, pp.interner(), pp.files(), !cli.compact)?;
+        writeln!(writer)?;
+        writer.flush()?;
+    } else if cli.dump_infer_json {
+        // --dump-infer-json: マクロ型推論結果の JSON 出力
+        let inferred = preprocessed.infer().map_err(|e| format_pipeline_error(&e))?;
+        let mut writer = open_output(cli.output.as_ref())?;
+        write_macro_infer_json(&mut writer, inferred.result(), true, !cli.compact)?;
+        writeln!(writer)?;
+        writer.flush()?;
+    } else if cli.gen_rust {
+        // --gen-rust: Rust コード生成（Pipeline API を使用）
+        run_gen_rust_pipeline(
+            preprocessed, cli.output.as_ref(), cli.output_dir.as_ref(),
+            cli.auto, cli.progress, &cli.rust_edition, cli.strict_rustfmt,
+        )?;
+    } else {
+        // デフォルト: マクロ型推論（統計出力）
+        run_infer_macro_types_pipeline(preprocessed, cli.auto, cli.progress)?;
+    }
+
+    Ok(())
+}
+
+/// CLI 引数から PipelineBuilder を構築する
+///
+/// `perl_source` は --auto 時に Config を読む perl、`bindings` は Rust
+/// バインディングファイル。--dual-mode-perl ではこの 2 つだけを変えて
+/// もう一方の builder を作る。
+fn configure_builder(
+    cli: &Cli,
+    input: &Path,
+    perl_source: &PerlConfigSource,
+    bindings: Option<&PathBuf>,
+) -> Result<PipelineBuilder, Box<dyn std::error::Error>> {
+    let mut builder = Pipeline::builder(input);
+    // --recover-parse-errors: パースできない宣言を読み飛ばし、警告として表示する
+    if cli.recover_parse_errors {
+        builder = builder.with_parse_error_recovery();
+    }
 
     if cli.auto {
         // --auto: Perl Config.pm から設定を取得
         if !cli.include.is_empty() {
             return Err("--auto cannot be used with -I options".into());
         }
-        builder = builder.with_auto_perl_config()
+        builder = builder.with_auto_perl_config_from(perl_source)
             .map_err(|e| format!("Failed to get Perl config: {}", e))?;
 
         // 追加の -D オプションがあればマージ
@@ -248,7 +588,7 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
     builder = builder.with_codegen_defaults();
 
     // bindings があれば設定
-    if let Some(ref bindings_path) = cli.bindings {
+    if let Some(bindings_path) = bindings {
         builder = builder.with_bindings(bindings_path);
     }
 
@@ -291,85 +631,138 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
     if cli.macro_comments {
         builder = builder.with_macro_comments();
     }
+    if cli.pod_docs {
+        builder = builder.with_pod_docs();
+    }
+    if let Some(ref path) = cli.macro_path {
+        builder = builder.with_macro_path(path);
+    }
     if let Some(ref name) = cli.dump_ast_for {
         builder = builder.with_dump_ast_for(name);
     }
     if let Some(ref name) = cli.dump_types_for {
         builder = builder.with_dump_types_for(name);
     }
+    if let Some(ref path) = cli.report_json {
+        builder = builder.with_report_json(path);
+    }
+    if let Some(ref path) = cli.c_shim {
+        builder = builder.with_c_shim(path);
+    }
+    if let Some(mode) = cli.nullability {
+        builder = builder.with_nullability(mode);
+    }
+    if cli.compile_check {
+        let mut check = CompileCheckConfig::new().with_edition(&cli.rust_edition);
+        if let Some(ref path) = cli.compile_check_patches {
+            check = check.with_patches_out(path);
+        }
+        builder = builder.with_compile_check(check);
+    }
 
-    // Pipeline を構築してプリプロセスを実行
-    let mut preprocessed = builder.build()?.preprocess()
+    Ok(builder)
+}
+
+/// threaded / non-threaded 両対応の Rust コードを生成する（--dual-mode-perl）
+fn run_gen_rust_dual(
+    a: PipelineBuilder,
+    b: PipelineBuilder,
+    cli: &Cli,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut buffer = Vec::new();
+    let generated = DualModePipeline::new(a, b)
+        .and_then(|dual| dual.generate(&mut buffer))
         .map_err(|e| format_pipeline_error(&e))?;
 
-    if cli.preprocess_only {
-        // -E: プリプロセス結果のみ出力
-        output_preprocessed(preprocessed.preprocessor_mut(), cli.output.as_ref(), cli.gcc_format)?;
-    } else if cli.streaming {
-        // --streaming: ストリーミングモード
-        run_streaming(preprocessed.preprocessor_mut())?;
-    } else if cli.typed_sexp {
-        // --typed-sexp: 型注釈付きS-expression出力
-        run_typed_sexp(preprocessed.preprocessor_mut(), cli.output.as_ref())?;
-    } else if cli.dump_fields_dict {
-        // --dump-fields-dict: 構造体フィールド辞書をダンプ
-        run_dump_fields_dict(preprocessed.preprocessor_mut(), cli.target_dir.as_ref())?;
-    } else if let Some(ref filter_opt) = cli.dump_macros {
-        // --dump-macros: マクロ定義をダンプ
-        // まず全トークンを消費してマクロ定義を収集
-        let filter = filter_opt.as_deref().unwrap_or("");
-        let pp = preprocessed.preprocessor_mut();
-        loop {
-            match pp.next_token() {
-                Ok(token) if matches!(token.kind, libperl_macrogen::TokenKind::Eof) => break,
-                Ok(_) => continue,
-                Err(e) => return Err(format_error(&e, pp).into()),
+    let formatted = match apply_rustfmt(&buffer, &cli.rust_edition) {
+        Ok(code) => code,
+        Err(e) => {
+            if cli.strict_rustfmt {
+                return Err(format!("rustfmt failed: {}", e).into());
             }
+            eprintln!("Warning: {}", e);
+            buffer
         }
-        pp.macros().dump_filtered(filter, pp.interner());
-    } else if cli.sexp {
-        // --sexp: S-expression出力（マクロ型推論なし）
-        let pp = preprocessed.preprocessor_mut();
-        let mut parser = match Parser::new(pp) {
-            Ok(p) => p,
-            Err(e) => return Err(format_error(&e, pp).into()),
-        };
-        let tu = match parser.parse() {
-            Ok(tu) => tu,
-            Err(e) => return Err(format_error(&e, pp).into()),
-        };
+    };
+    match cli.output {
+        Some(ref path) => {
+            std::fs::write(path, &formatted)?;
+            eprintln!("Output: {}", path.display());
+        }
+        None => io::stdout().write_all(&formatted)?,
+    }
 
-        // 出力
-        if let Some(output_path) = cli.output {
-            let file = File::create(&output_path)?;
-            let mut writer = BufWriter::new(file);
-            let mut printer = SexpPrinter::new(&mut writer, pp.interner());
-            printer.print_translation_unit(&tu)?;
-            writer.flush()?;
-        } else {
-            let stdout = io::stdout();
-            let mut handle = stdout.lock();
-            let mut printer = SexpPrinter::new(&mut handle, pp.interner());
-            printer.print_translation_unit(&tu)?;
-            handle.flush()?;
+    if let Some(ref path) = cli.dual_mode_report {
+        generated.report.write_json(path)?;
+    }
+    eprint!("{}", generated.report.to_text());
+    Ok(())
+}
+
+/// 出力先（-o 指定時はファイル、省略時は標準出力）を開く
+fn open_output(path: Option<&PathBuf>) -> io::Result<Box<dyn Write>> {
+    Ok(match path {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    })
+}
+
+/// API 差分を出力する（--diff-reports / --diff-base-perl）
+fn write_api_diff(diff: &ApiDiff, format: &str, output: Option<&PathBuf>) -> Result<(), Box<dyn std::error::Error>> {
+    let text = match format {
+        "json" => diff.to_json()? + "\n",
+        _ => diff.to_text(),
+    };
+    match output {
+        Some(path) => std::fs::write(path, text)?,
+        None => io::stdout().write_all(text.as_bytes())?,
+    }
+    Ok(())
+}
+
+/// マクロの展開過程を表示
+fn run_explain_expansion(
+    pp: &mut Preprocessor,
+    name: &str,
+    format: &str,
+    output: Option<&PathBuf>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // 全トークンを消費してマクロ定義を収集
+    loop {
+        match pp.next_token() {
+            Ok(token) if matches!(token.kind, TokenKind::Eof) => break,
+            Ok(_) => continue,
+            Err(e) => return Err(format_error(&e, pp).into()),
         }
-    } else if cli.gen_rust {
-        // --gen-rust: Rust コード生成（Pipeline API を使用）
-        run_gen_rust_pipeline(preprocessed, cli.output.as_ref(), cli.auto, &cli.rust_edition, cli.strict_rustfmt)?;
-    } else {
-        // デフォルト: マクロ型推論（統計出力）
-        run_infer_macro_types_pipeline(preprocessed, cli.auto)?;
     }
 
+    let trace = match pp.trace_macro(name) {
+        Ok(Some(trace)) => trace,
+        Ok(None) => return Err(format!("macro `{}` is not defined", name).into()),
+        Err(e) => return Err(format_error(&e, pp).into()),
+    };
+    let text = if format == "json" {
+        serde_json::to_string_pretty(&trace)? + "\n"
+    } else {
+        trace.to_text()
+    };
+
+    match output {
+        Some(path) => std::fs::write(path, text)?,
+        None => io::stdout().write_all(text.as_bytes())?,
+    }
     Ok(())
 }
 
 /// ストリーミングモードで実行
-fn run_streaming(pp: &mut Preprocessor) -> Result<(), Box<dyn std::error::Error>> {
+///
+/// `recover` が真なら、パースできない宣言があっても残りを出力し続ける。
+fn run_streaming(pp: &mut Preprocessor, recover: bool) -> Result<(), Box<dyn std::error::Error>> {
     let mut parser = match Parser::new(pp) {
         Ok(p) => p,
         Err(e) => return Err(format_error(&e, pp).into()),
     };
+    parser.set_error_recovery(recover);
 
     // ストリーミング出力用
     let stdout = io::stdout();
@@ -392,6 +785,13 @@ fn run_streaming(pp: &mut Preprocessor) -> Result<(), Box<dyn std::error::Error>
     });
 
     drop(handle);
+    let recovered = parser.take_errors();
+    drop(parser);
+
+    // 回復したパースエラーを全て表示
+    for error in &recovered {
+        eprintln!("\n{}", format_error(error, pp));
+    }
 
     // エラーがあった場合、詳細を表示
     if let Err(error) = parse_result {
@@ -411,6 +811,11 @@ fn run_streaming(pp: &mut Preprocessor) -> Result<(), Box<dyn std::error::Error>
         return Err("Parse failed".into());
     }
 
+    if !recovered.is_empty() {
+        eprintln!("\nParsed {} declarations, skipped {} with parse errors", count, recovered.len());
+        return Err("Parse failed".into());
+    }
+
     eprintln!("\nSuccessfully parsed {} declarations", count);
     Ok(())
 }
@@ -453,11 +858,13 @@ fn run_dump_fields_dict(pp: &mut Preprocessor, _target_dir: Option<&PathBuf>) ->
 fn run_infer_macro_types_pipeline(
     preprocessed: libperl_macrogen::PreprocessedPipeline,
     auto_mode: bool,
+    progress: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
     // 自動ロード時の通知（省略 - Pipeline 内部で解決）
     let _ = auto_mode;
 
     // 推論を実行
+    let started = Instant::now();
     let inferred = match preprocessed.infer() {
         Ok(r) => r,
         Err(e) => {
@@ -471,6 +878,10 @@ fn run_infer_macro_types_pipeline(
 
     // 結果から必要な情報を取り出す
     let result = inferred.result();
+    print_skipped_declarations(result);
+    if progress {
+        print_include_guard_progress(&result.preprocessor, started.elapsed());
+    }
     let infer_ctx = &result.infer_ctx;
     let interner = result.preprocessor.interner();
     let stats = &result.stats;
@@ -511,6 +922,8 @@ fn run_infer_macro_types_pipeline(
     eprintln!("THX-dependent macros: {}", stats.thx_dependent_count);
     // C 関数宣言統計
     eprintln!("C function declarations: {} ({} THX)", stats.c_fn_decl_count, stats.c_fn_thx_count);
+    // エラー回復で読み飛ばした宣言
+    eprintln!("Skipped declarations (parse errors): {}", stats.parse_error_count);
     // SV family 検出統計
     eprintln!("SV family members: {}", result.fields_dict.sv_family_members_count());
     eprintln!("typeName -> struct mapping: {}", result.fields_dict.sv_head_type_mapping_count());
@@ -594,7 +1007,13 @@ fn run_infer_macro_types_pipeline(
                 let _ = writeln!(handle);
             }
             ParseResult::Unparseable(Some(err_msg)) => {
-                println!("  error: {}", err_msg);
+                for (i, line) in err_msg.lines().enumerate() {
+                    if i == 0 {
+                        println!("  error: {}", line);
+                    } else {
+                        println!("  {}", line);
+                    }
+                }
             }
             _ => {}
         }
@@ -622,7 +1041,9 @@ fn run_infer_macro_types_pipeline(
 fn run_gen_rust_pipeline(
     preprocessed: libperl_macrogen::PreprocessedPipeline,
     output_path: Option<&PathBuf>,
+    output_dir: Option<&PathBuf>,
     auto_mode: bool,
+    progress: bool,
     rust_edition: &str,
     strict_rustfmt: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
@@ -636,6 +1057,7 @@ fn run_gen_rust_pipeline(
     }
 
     // 推論を実行
+    let started = Instant::now();
     let inferred = match preprocessed.infer() {
         Ok(r) => r,
         Err(e) => {
@@ -646,12 +1068,73 @@ fn run_gen_rust_pipeline(
             return Err(format_pipeline_error(&e).into());
         }
     };
+    print_skipped_declarations(inferred.result());
+    if progress {
+        print_include_guard_progress(&inferred.result().preprocessor, started.elapsed());
+    }
 
     // まずバッファに生成
     let mut buffer = Vec::new();
     let generated = inferred.generate(&mut buffer)
         .map_err(|e| format_pipeline_error(&e))?;
-    let stats = generated.stats();
+
+    write_gen_rust_output(&generated, buffer, output_path, output_dir, rust_edition, strict_rustfmt)
+}
+
+/// エラー回復で読み飛ばした宣言を警告として表示
+fn print_skipped_declarations(result: &InferResult) {
+    for e in &result.parse_errors {
+        eprintln!("warning: skipped declaration: {}", e.render(result.preprocessor.files()));
+    }
+}
+
+/// 多重インクルード最適化の効果を表示（--progress）
+///
+/// `elapsed` はプリプロセスを含む推論フェーズの所要時間。省略した時間は
+/// 読み込んだバイト数に比例するとみなした見積もり。
+fn print_include_guard_progress(pp: &Preprocessor, elapsed: Duration) {
+    let stats = pp.include_guard_stats();
+    eprintln!(
+        "Include guards: {} guarded headers, {} of {} #includes skipped ({:.1} MiB not re-read, ~{:.2}s of {:.2}s saved)",
+        stats.guarded_files,
+        stats.includes_skipped,
+        pp.files().includes().len(),
+        stats.bytes_skipped as f64 / (1024.0 * 1024.0),
+        stats.estimated_time_saved(elapsed).as_secs_f64(),
+        elapsed.as_secs_f64(),
+    );
+}
+
+/// 生成した Rust コードを rustfmt して出力し、統計情報を表示する
+fn write_gen_rust_output(
+    generated: &libperl_macrogen::GeneratedPipeline,
+    buffer: Vec<u8>,
+    output_path: Option<&PathBuf>,
+    output_dir: Option<&PathBuf>,
+    rust_edition: &str,
+    strict_rustfmt: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // ヘッダー別モジュールとしてディレクトリに出力
+    if let Some(dir) = output_dir {
+        let modules = generated.split_modules(&buffer);
+        let mut rustfmt_error = None;
+        modules.write_to_dir(dir, |src| match apply_rustfmt(src.as_bytes(), rust_edition) {
+            Ok(code) => String::from_utf8_lossy(&code).into_owned(),
+            Err(e) => {
+                rustfmt_error.get_or_insert(e);
+                src.to_string()
+            }
+        })?;
+        if let Some(e) = rustfmt_error {
+            if strict_rustfmt {
+                return Err(format!("rustfmt failed: {}", e).into());
+            }
+            eprintln!("Warning: {}", e);
+        }
+        eprintln!("Output: {} ({} files)", dir.display(), modules.file_count());
+        print_gen_rust_stats(generated);
+        return Ok(());
+    }
 
     // rustfmt を適用
     let formatted = match apply_rustfmt(&buffer, rust_edition) {
@@ -679,17 +1162,47 @@ fn run_gen_rust_pipeline(
         handle.flush()?;
     }
 
-    // 統計情報を出力
+    print_gen_rust_stats(generated);
+
+    Ok(())
+}
+
+/// Rust コード生成の統計情報を出力
+fn print_gen_rust_stats(generated: &libperl_macrogen::GeneratedPipeline) {
+    let stats = generated.stats();
     eprintln!("=== Rust Code Generation Stats ===");
-    eprintln!("Macros: {} success, {} parse failed, {} type incomplete, {} cascade unavailable, {} unresolved names",
+    eprintln!("Macros: {} success, {} parse failed, {} type incomplete, {} cascade unavailable, {} unresolved names, {} macro_rules, {} stack macro_rules",
         stats.macros_success, stats.macros_parse_failed, stats.macros_type_incomplete,
-        stats.macros_cascade_unavailable, stats.macros_unresolved_names);
+        stats.macros_cascade_unavailable, stats.macros_unresolved_names, stats.macros_generic_rules,
+        stats.macros_stack_rules);
+    eprintln!("Safe macro functions: {} fn, {} const fn",
+        stats.macros_safe_fn, stats.macros_const_fn);
     eprintln!("Inline functions: {} success, {} type incomplete, {} cascade unavailable, {} unresolved names, {} contains goto",
         stats.inline_fns_success, stats.inline_fns_type_incomplete,
         stats.inline_fns_cascade_unavailable, stats.inline_fns_unresolved_names,
         stats.inline_fns_contains_goto);
-
-    Ok(())
+    eprintln!("Object macros: {} const, {} fn",
+        stats.object_macros_const, stats.object_macros_fn);
+    if let Some(shims) = &generated.c_shim {
+        eprintln!("C shims: {} macro(s), {} skipped", shims.count, shims.skipped.len());
+        for (name, reason) in &shims.skipped {
+            eprintln!("  {}: {}", name, reason);
+        }
+    }
+    if let Some(outcome) = generated.compile_check() {
+        for (i, round) in outcome.rounds.iter().enumerate() {
+            eprintln!("[compile-check] iteration {}: {} error(s), suppressing {} function(s)",
+                i + 1, round.errors, round.suppressed);
+        }
+        if let Some((path, added)) = &outcome.patches_written {
+            eprintln!("[compile-check] wrote {} new skip_codegen patch(es) to {}", added, path.display());
+        }
+        eprintln!("Compile check: {} iteration(s), {} suppressed, {} remaining error(s)",
+            outcome.iterations, outcome.suppressed.len(), outcome.remaining.len());
+        for diag in &outcome.remaining {
+            eprintln!("  {}", diag.summary());
+        }
+    }
 }
 
 /// rustfmt を適用
@@ -879,9 +1392,9 @@ fn show_source_context(pp: &Preprocessor, loc: &SourceLocation) {
     eprintln!("{}", "-".repeat(60));
 }
 
-/// エラーをファイル名付きでフォーマット
+/// エラーをファイル名・該当行・マクロ展開のバックトレース付きでフォーマット
 fn format_error(e: &CompileError, pp: &Preprocessor) -> String {
-    e.format_with_files(pp.files())
+    e.render(pp.files())
 }
 
 /// PipelineError をフォーマット
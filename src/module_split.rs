@@ -0,0 +1,217 @@
+//! 生成コードのヘッダー別モジュール分割
+//!
+//! 単一の `macro_bindings.rs` はヘッダーを 1 つ変更しただけでも全体が
+//! 再生成・再コンパイルされ、差分も追いにくい。そこで CodegenDriver が
+//! 記録した出力区間（[`OutputSegment`]）を使い、定義元ヘッダーごとに
+//! モジュールファイルへ振り分ける（`sv.h` → `sv.rs`、`cop.h` → `cop.rs`）。
+//!
+//! use 文・enum import・自動生成 struct・PERLVAR マクロなどの共通部分は
+//! `mod.rs` に置き、各モジュールは `use super::*;` でそれらを参照する。
+//! `mod.rs` は全モジュールを `pub use` で再エクスポートするので、
+//! 利用側からは単一ファイル出力と同じ名前で参照できる。
+//!
+//! 単一ファイル出力は bindings.rs と同じモジュールに `include!` される前提
+//! なので、ディレクトリは bindings.rs を含むモジュールの子モジュールとして
+//! 置く（`mod.rs` も `use super::*;` で bindings の型を参照する）。
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::codegen_report::ReportItemKind;
+
+/// 共通部分を置くファイル名
+pub const MOD_RS: &str = "mod.rs";
+
+/// 出力バッファ中の 1 区間
+///
+/// 次の区間の `offset` までがこの区間に属する。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputSegment {
+    /// 区間の開始位置（バイトオフセット）
+    pub offset: usize,
+    /// 振り分け先モジュール名（None なら `mod.rs` の共通部分）
+    pub module: Option<String>,
+}
+
+/// 出力バッファ中の 1 アイテム（マクロ / inline 関数 1 つ分の出力）
+///
+/// 生成に失敗したアイテムのコメントブロックも 1 アイテムとして記録する。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputItem {
+    /// 名前
+    pub name: String,
+    /// 種類
+    pub kind: ReportItemKind,
+    /// 開始位置（バイトオフセット）
+    pub start: usize,
+    /// 終了位置（バイトオフセット、この位置は含まない）
+    pub end: usize,
+}
+
+/// ヘッダーファイルのパスからモジュールのファイル名（拡張子なし）を作る
+///
+/// 拡張子を除いたファイル名の英数字以外を `_` に置き換える。
+/// `mod.h` は `mod.rs` と衝突するので `mod_h` にする。
+pub fn module_name_for_header(path: &Path) -> String {
+    let stem = path.file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut name: String = stem.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if name.is_empty() {
+        name.push_str("header");
+    }
+    if name == "mod" {
+        name.push_str("_h");
+    }
+    name
+}
+
+/// `mod.rs` で宣言するモジュールの識別子
+///
+/// bindings.rs には `sv`・`cv`・`op` など小文字のヘッダー名と同じ struct が
+/// あり、同名のモジュールは `use super::*;` 経由でそれらを隠してしまう。
+/// そのため識別子には `_h` を付け、ファイルは `#[path]` で指定する。
+fn module_ident(name: &str) -> String {
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("h{}_h", name)
+    } else {
+        format!("{}_h", name)
+    }
+}
+
+/// ヘッダー別に分割した生成コード
+#[derive(Debug, Clone, Default)]
+pub struct ModuleOutput {
+    /// `mod.rs` の内容（共通部分 + モジュール宣言）
+    pub mod_rs: String,
+    /// モジュールのファイル名（拡張子なし）→ モジュールファイルの内容
+    pub modules: BTreeMap<String, String>,
+}
+
+impl ModuleOutput {
+    /// 出力バッファを区間情報に従って分割する
+    ///
+    /// 最初の区間より前の部分は共通部分として扱う。
+    pub fn split(buffer: &str, segments: &[OutputSegment]) -> Self {
+        let mut common = String::new();
+        let mut bodies: BTreeMap<String, String> = BTreeMap::new();
+
+        let first = segments.first().map_or(buffer.len(), |s| s.offset.min(buffer.len()));
+        common.push_str(&buffer[..first]);
+        for (i, seg) in segments.iter().enumerate() {
+            let start = seg.offset.min(buffer.len());
+            let end = segments.get(i + 1).map_or(buffer.len(), |s| s.offset.min(buffer.len()));
+            if start >= end {
+                continue;
+            }
+            let text = &buffer[start..end];
+            match &seg.module {
+                Some(module) => bodies.entry(module.clone()).or_default().push_str(text),
+                None => common.push_str(text),
+            }
+        }
+
+        let mut mod_rs = String::from(
+            "// Mount this directory as a child of the module that includes bindings.rs\n\
+             #[allow(unused_imports)]\n\
+             use super::*;\n\n",
+        );
+        mod_rs.push_str(&common);
+        if !bodies.is_empty() {
+            if !mod_rs.ends_with("\n\n") {
+                mod_rs.push('\n');
+            }
+            mod_rs.push_str("// Per-header modules\n");
+            for module in bodies.keys() {
+                let ident = module_ident(module);
+                mod_rs.push_str(&format!(
+                    "#[path = \"{}.rs\"]\nmod {};\npub use {}::*;\n",
+                    module, ident, ident,
+                ));
+            }
+        }
+
+        let modules = bodies.into_iter()
+            .map(|(module, body)| {
+                let content = format!(
+                    "// Auto-generated Rust bindings ({}.h)\n\
+                     // Generated by libperl-macrogen\n\n\
+                     #[allow(unused_imports)]\n\
+                     use super::*;\n\n{}",
+                    module, body,
+                );
+                (module, content)
+            })
+            .collect();
+
+        Self { mod_rs, modules }
+    }
+
+    /// 出力するファイル数（`mod.rs` を含む）
+    pub fn file_count(&self) -> usize {
+        self.modules.len() + 1
+    }
+
+    /// ディレクトリに書き出す
+    ///
+    /// `transform` は書き出す前に各ファイルの内容へ適用される（rustfmt など）。
+    pub fn write_to_dir<F>(&self, dir: &Path, mut transform: F) -> io::Result<()>
+    where
+        F: FnMut(&str) -> String,
+    {
+        fs::create_dir_all(dir)?;
+        fs::write(dir.join(MOD_RS), transform(&self.mod_rs))?;
+        for (module, content) in &self.modules {
+            fs::write(dir.join(format!("{}.rs", module)), transform(content))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_module_name_for_header() {
+        assert_eq!(module_name_for_header(Path::new("/usr/lib/perl5/CORE/sv.h")), "sv");
+        assert_eq!(module_name_for_header(Path::new("sv_inline.h")), "sv_inline");
+        assert_eq!(module_name_for_header(Path::new("perl-ext.h")), "perl_ext");
+        assert_eq!(module_name_for_header(Path::new("mod.h")), "mod_h");
+        assert_eq!(module_ident("sv"), "sv_h");
+        assert_eq!(module_ident("1st"), "h1st_h");
+    }
+
+    #[test]
+    fn test_split_output() {
+        let buffer = "use std::ffi::c_int;\n\nfn a() {}\nfn b() {}\nfn c() {}\nuse libc::strlen;\n";
+        let at = |s: &str| buffer.find(s).unwrap();
+        let seg = |offset, module: Option<&str>| OutputSegment {
+            offset,
+            module: module.map(|m| m.to_string()),
+        };
+        let segments = vec![
+            seg(at("fn a"), Some("sv")),
+            seg(at("fn b"), Some("cop")),
+            seg(at("fn c"), Some("sv")),
+            seg(at("use libc"), None),
+        ];
+
+        let out = ModuleOutput::split(buffer, &segments);
+        assert_eq!(out.modules.keys().collect::<Vec<_>>(), vec!["cop", "sv"]);
+        assert!(out.modules["sv"].ends_with("use super::*;\n\nfn a() {}\nfn c() {}\n"));
+        assert!(out.modules["cop"].ends_with("use super::*;\n\nfn b() {}\n"));
+        assert!(out.mod_rs.contains("use super::*;\n\nuse std::ffi::c_int;\n\nuse libc::strlen;\n"));
+        assert!(out.mod_rs.contains(concat!(
+            "#[path = \"cop.rs\"]\nmod cop_h;\npub use cop_h::*;\n",
+            "#[path = \"sv.rs\"]\nmod sv_h;\npub use sv_h::*;\n",
+        )));
+        assert_eq!(out.file_count(), 3);
+    }
+}
@@ -0,0 +1,196 @@
+//! apidoc の NN / NULLOK / NZ 注釈による引数の検査
+//!
+//! embed.fnc の引数には `NN SV *sv`（NULL 不可）、`NULLOK SV *sv`（NULL 可）、
+//! `NZ int n`（非ゼロ）の注釈が付く。既定の生成ではこれらを使わないが、
+//! [`NullabilityMode`] を指定すると生成するマクロ関数に反映する:
+//!
+//! - [`NullabilityMode::DebugAssert`]: NN のポインタ引数に
+//!   `debug_assert!(!p.is_null())`、NZ の引数に `debug_assert!(n != 0)` を入れる
+//! - [`NullabilityMode::NonNull`]: NN のポインタ引数を `NonNull<T>`、NULLOK の
+//!   ものを `Option<NonNull<T>>` で受け取る。本体の先頭で生ポインタに戻すので
+//!   本体のコードは変わらない。生成コード内での呼び出しは `NonNull::new_unchecked`
+//!   / `NonNull::new` で包む。NZ は `DebugAssert` と同じく `debug_assert!`
+//!
+//! apidoc は戻り値の NULL 許容性を持たないので、戻り値の型は変えない。
+//! 対象は apidoc の引数の数とパラメータの数が一致し、型引数・リテラル文字列
+//! 引数を持たないマクロのみ。
+
+use std::collections::HashMap;
+
+use crate::apidoc::Nullability;
+use crate::infer_api::InferResult;
+use crate::intern::InternedStr;
+use crate::rust_codegen::best_constraint_for_macro_param;
+
+/// apidoc の NULL 許容性・非ゼロ注釈の使い方
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NullabilityMode {
+    /// 注釈を使わない
+    #[default]
+    Off,
+    /// `debug_assert!` を入れる
+    DebugAssert,
+    /// NN / NULLOK を `NonNull<T>` / `Option<NonNull<T>>` にする（NZ は `debug_assert!`）
+    NonNull,
+}
+
+/// 1 つの引数の注釈
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ParamCheck {
+    /// ポインタ引数の NULL 許容性（ポインタでなければ常に `Unspecified`）
+    pub nullability: Nullability,
+    /// NZ（非ゼロ）
+    pub non_zero: bool,
+}
+
+/// マクロごとの引数の注釈
+#[derive(Debug, Default)]
+pub struct NullabilityDict {
+    mode: NullabilityMode,
+    /// マクロ名 → パラメータ順の注釈（注釈のあるマクロのみ）
+    params: HashMap<InternedStr, Vec<ParamCheck>>,
+}
+
+impl NullabilityDict {
+    /// apidoc からターゲットマクロの注釈を集める（`Off` なら空）
+    pub fn collect(result: &InferResult, mode: NullabilityMode) -> Self {
+        let mut params = HashMap::new();
+        if mode == NullabilityMode::Off {
+            return Self { mode, params };
+        }
+        let interner = result.preprocessor.interner();
+        for (&name, info) in &result.infer_ctx.macros {
+            if !info.is_target
+                || !info.generic_type_params.is_empty()
+                || !info.literal_string_params.is_empty()
+            {
+                continue;
+            }
+            let Some(entry) = result.apidoc.get(interner.get(name)) else {
+                continue;
+            };
+            if entry.args.len() != info.params.len() {
+                continue;
+            }
+            let checks: Vec<ParamCheck> = info.params.iter().zip(&entry.args)
+                .map(|(p, arg)| {
+                    let is_pointer = best_constraint_for_macro_param(info, p)
+                        .is_some_and(|ty| ty.has_outer_pointer());
+                    ParamCheck {
+                        nullability: if is_pointer { arg.nullability } else { Nullability::Unspecified },
+                        non_zero: arg.non_zero,
+                    }
+                })
+                .collect();
+            if checks.iter().any(|c| *c != ParamCheck::default()) {
+                params.insert(name, checks);
+            }
+        }
+        Self { mode, params }
+    }
+
+    /// 注釈の使い方
+    pub fn mode(&self) -> NullabilityMode {
+        self.mode
+    }
+
+    /// マクロの引数の注釈（パラメータ順）
+    pub fn get(&self, name: InternedStr) -> Option<&[ParamCheck]> {
+        self.params.get(&name).map(|v| v.as_slice())
+    }
+
+    /// `NonNull` で受け取る引数なら、その NULL 許容性（NN / NULLOK）
+    ///
+    /// `NonNull` モード以外では常に None。
+    pub fn nonnull_param(&self, name: InternedStr, index: usize) -> Option<Nullability> {
+        if self.mode != NullabilityMode::NonNull {
+            return None;
+        }
+        let check = self.params.get(&name)?.get(index)?;
+        (check.nullability != Nullability::Unspecified).then_some(check.nullability)
+    }
+
+    /// 注釈のあるマクロの数
+    pub fn len(&self) -> usize {
+        self.params.len()
+    }
+
+    /// 注釈のあるマクロがないか
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+}
+
+/// 生ポインタ型 `*mut T` / `*const T` を `NonNull<T>` / `Option<NonNull<T>>` にする
+///
+/// ポインタ型でなければ None。
+pub fn nonnull_type(raw: &str, nullability: Nullability) -> Option<String> {
+    let pointee = raw.strip_prefix("*mut ").or_else(|| raw.strip_prefix("*const "))?.trim();
+    let nonnull = format!("std::ptr::NonNull<{}>", pointee);
+    match nullability {
+        Nullability::NotNull => Some(nonnull),
+        Nullability::Nullable => Some(format!("Option<{}>", nonnull)),
+        Nullability::Unspecified => None,
+    }
+}
+
+/// `NonNull` で受け取る引数に渡す生ポインタ式を包む
+///
+/// NULLOK の引数に渡すヌルポインタリテラルは `None` にする
+/// （`NonNull::new(std::ptr::null() as *mut _)` は指す型を推論できない）。
+pub fn wrap_nonnull_arg(arg: &str, nullability: Nullability) -> String {
+    let is_null_literal = matches!(arg.trim(), "std::ptr::null()" | "std::ptr::null_mut()");
+    match nullability {
+        Nullability::Nullable if is_null_literal => "None".to_string(),
+        Nullability::NotNull => format!("std::ptr::NonNull::new_unchecked(({}) as *mut _)", arg),
+        Nullability::Nullable => format!("std::ptr::NonNull::new(({}) as *mut _)", arg),
+        Nullability::Unspecified => arg.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nonnull_type() {
+        assert_eq!(nonnull_type("*mut SV", Nullability::NotNull).as_deref(), Some("std::ptr::NonNull<SV>"));
+        assert_eq!(nonnull_type("*const c_char", Nullability::Nullable).as_deref(),
+            Some("Option<std::ptr::NonNull<c_char>>"));
+        assert_eq!(nonnull_type("*mut *mut SV", Nullability::NotNull).as_deref(),
+            Some("std::ptr::NonNull<*mut SV>"));
+        assert!(nonnull_type("IV", Nullability::NotNull).is_none());
+        assert!(nonnull_type("*mut SV", Nullability::Unspecified).is_none());
+    }
+
+    #[test]
+    fn test_wrap_nonnull_arg() {
+        assert_eq!(wrap_nonnull_arg("sv", Nullability::NotNull),
+            "std::ptr::NonNull::new_unchecked((sv) as *mut _)");
+        assert_eq!(wrap_nonnull_arg("(*cv).gv", Nullability::Nullable),
+            "std::ptr::NonNull::new(((*cv).gv) as *mut _)");
+        assert_eq!(wrap_nonnull_arg("std::ptr::null()", Nullability::Nullable), "None");
+        assert_eq!(wrap_nonnull_arg("std::ptr::null_mut()", Nullability::Unspecified),
+            "std::ptr::null_mut()");
+    }
+
+    #[test]
+    fn test_nonnull_param_mode() {
+        let mut interner = crate::intern::StringInterner::new();
+        let name = interner.intern("sv_setsv");
+        let checks = vec![
+            ParamCheck { nullability: Nullability::NotNull, non_zero: false },
+            ParamCheck { nullability: Nullability::Nullable, non_zero: false },
+        ];
+        let mut dict = NullabilityDict {
+            mode: NullabilityMode::DebugAssert,
+            params: HashMap::from([(name, checks)]),
+        };
+        assert_eq!(dict.get(name).map(|c| c.len()), Some(2));
+        assert_eq!(dict.nonnull_param(name, 0), None);
+        dict.mode = NullabilityMode::NonNull;
+        assert_eq!(dict.nonnull_param(name, 0), Some(Nullability::NotNull));
+        assert_eq!(dict.nonnull_param(name, 1), Some(Nullability::Nullable));
+        assert_eq!(dict.nonnull_param(name, 2), None);
+    }
+}
@@ -5,7 +5,7 @@
 use std::collections::{HashMap, HashSet};
 
 use crate::ast::*;
-use crate::error::{CompileError, ParseError, Result};
+use crate::error::{CompileError, ExpansionFrame, ParseError, Result};
 use crate::intern::{InternedStr, StringInterner};
 use crate::macro_infer::detect_assert_kind;
 use crate::preprocessor::Preprocessor;
@@ -104,6 +104,16 @@ pub struct Parser<'a, S: TokenSource> {
     generic_params: HashMap<InternedStr, usize>,
     /// パース中に型として使用が検出された generic param
     detected_type_params: HashSet<InternedStr>,
+    /// 消費したトークンで数えた `{` のネスト（エラー回復の再同期用）
+    brace_depth: usize,
+    /// 直前に消費したトークンが `)` か
+    last_was_rparen: bool,
+    /// ファイルスコープの `{` が `)` の直後（関数本体）だったか
+    brace_opens_body: bool,
+    /// エラー回復モード（パースエラーを記録して次の外部宣言から再開する）
+    recover_errors: bool,
+    /// エラー回復モードで記録したパースエラー
+    errors: Vec<CompileError>,
 }
 
 /// Preprocessor 専用の後方互換コンストラクタ
@@ -116,15 +126,14 @@ impl<'a> Parser<'a, Preprocessor> {
     /// ストリーミング形式でパース
     ///
     /// 各宣言をパースするたびにコールバックを呼び出す。
-    /// パースエラーが発生した場合はコールバックを呼ばずにエラーを返す。
+    /// パースエラーが発生した場合はコールバックを呼ばずにエラーを返す
+    /// （エラー回復モードでは記録して次の宣言へ進む）。
     /// コールバックが `ControlFlow::Break(())` を返した場合はループを終了。
     pub fn parse_each<F>(&mut self, mut callback: F) -> Result<()>
     where
         F: FnMut(&ExternalDecl, &crate::source::SourceLocation, &std::path::Path, &StringInterner) -> std::ops::ControlFlow<()>,
     {
-        while !self.is_eof() {
-            let loc = self.current.loc.clone();
-            let decl = self.parse_external_decl()?;
+        while let Some((loc, decl)) = self.next_external_decl()? {
             let path = self.source.files().get_path(loc.file_id);
             let interner = self.source.interner();
             if callback(&decl, &loc, path, interner).is_break() {
@@ -138,14 +147,13 @@ impl<'a> Parser<'a, Preprocessor> {
     ///
     /// `parse_each` と同様だが、コールバックに Preprocessor への可変参照も渡す。
     /// マクロ呼び出しコールバック（MacroCallWatcher など）にアクセスする場合に使用。
-    /// パースエラーが発生した場合はコールバックを呼ばずにエラーを返す。
+    /// パースエラーが発生した場合はコールバックを呼ばずにエラーを返す
+    /// （エラー回復モードでは記録して次の宣言へ進む）。
     pub fn parse_each_with_pp<F>(&mut self, mut callback: F) -> Result<()>
     where
         F: FnMut(&ExternalDecl, &crate::source::SourceLocation, &std::path::Path, &mut Preprocessor) -> std::ops::ControlFlow<()>,
     {
-        while !self.is_eof() {
-            let loc = self.current.loc.clone();
-            let decl = self.parse_external_decl()?;
+        while let Some((loc, decl)) = self.next_external_decl()? {
             let path = self.source.files().get_path(loc.file_id).to_path_buf();
             if callback(&decl, &loc, &path, self.source).is_break() {
                 break;
@@ -174,6 +182,11 @@ impl<'a, S: TokenSource> Parser<'a, S> {
             deref_count: 0,
             generic_params: HashMap::new(),
             detected_type_params: HashSet::new(),
+            brace_depth: 0,
+            last_was_rparen: false,
+            brace_opens_body: false,
+            recover_errors: false,
+            errors: Vec::new(),
         };
         // マーカーをスキップして最初のトークンを取得
         parser.current = parser.inner_next_token()?;
@@ -194,6 +207,11 @@ impl<'a, S: TokenSource> Parser<'a, S> {
             deref_count: 0,
             generic_params: HashMap::new(),
             detected_type_params: HashSet::new(),
+            brace_depth: 0,
+            last_was_rparen: false,
+            brace_opens_body: false,
+            recover_errors: false,
+            errors: Vec::new(),
         };
         // マーカーをスキップして最初のトークンを取得
         parser.current = parser.inner_next_token()?;
@@ -238,18 +256,83 @@ impl<'a, S: TokenSource> Parser<'a, S> {
         &self.typedefs
     }
 
+    /// エラー回復モードを設定する
+    ///
+    /// 有効にすると、外部宣言のパースエラーで止まらず、エラーを記録して
+    /// ファイルスコープの `;` か対応の取れた `}` まで読み飛ばして続行する。
+    /// 字句解析・プリプロセスのエラーは従来通り即座に返す。
+    pub fn set_error_recovery(&mut self, enabled: bool) {
+        self.recover_errors = enabled;
+    }
+
+    /// エラー回復モードで記録したパースエラー（出現順）
+    pub fn errors(&self) -> &[CompileError] {
+        &self.errors
+    }
+
+    /// 記録したパースエラーを取り出す
+    pub fn take_errors(&mut self) -> Vec<CompileError> {
+        std::mem::take(&mut self.errors)
+    }
+
     /// 翻訳単位をパース
+    ///
+    /// エラー回復モードでは、パースできなかった宣言を除いた残りを返す。
     pub fn parse(&mut self) -> Result<TranslationUnit> {
         let mut decls = Vec::new();
 
-        while !self.is_eof() {
-            let decl = self.parse_external_decl()?;
+        while let Some((_, decl)) = self.next_external_decl()? {
             decls.push(decl);
         }
 
         Ok(TranslationUnit { decls })
     }
 
+    /// 次の外部宣言をパースし、先頭位置と共に返す（EOF なら None）
+    ///
+    /// エラー回復モードでは、パースエラーを記録して再同期し、次の宣言を試す。
+    fn next_external_decl(&mut self) -> Result<Option<(SourceLocation, ExternalDecl)>> {
+        while !self.is_eof() {
+            let loc = self.current.loc.clone();
+            self.brace_depth = 0;
+            self.brace_opens_body = false;
+            match self.parse_external_decl() {
+                Ok(decl) => return Ok(Some((loc, decl))),
+                Err(e) if self.recover_errors && matches!(e.base(), CompileError::Parse { .. }) => {
+                    self.errors.push(e);
+                    self.synchronize()?;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(None)
+    }
+
+    /// エラー後、次の外部宣言の先頭までトークンを読み飛ばす
+    ///
+    /// ファイルスコープの `;`、または関数本体を閉じる `}` までを消費する。
+    /// 構造体定義や初期化子の `}` の後は、宣言を終える `;` まで読み飛ばす。
+    fn synchronize(&mut self) -> Result<()> {
+        while !self.is_eof() {
+            match self.current.kind {
+                TokenKind::Semi if self.brace_depth == 0 => {
+                    self.advance()?;
+                    return Ok(());
+                }
+                TokenKind::RBrace => {
+                    self.advance()?;
+                    if self.brace_depth == 0 && self.brace_opens_body {
+                        return Ok(());
+                    }
+                }
+                _ => {
+                    self.advance()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// 式のみをパース
     ///
     /// マクロ本体など、式だけをパースしたい場合に使用
@@ -1220,13 +1303,10 @@ impl<'a, S: TokenSource> Parser<'a, S> {
         let body = Box::new(self.parse_stmt()?);
         // expect 'while' keyword
         if !matches!(self.current.kind, TokenKind::KwWhile) {
-            return Err(CompileError::Parse {
-                loc: self.current.loc.clone(),
-                kind: ParseError::UnexpectedToken {
-                    expected: "while".to_string(),
-                    found: self.current.kind.clone(),
-                },
-            });
+            return Err(self.parse_error(self.current.loc.clone(), ParseError::UnexpectedToken {
+                expected: "while".to_string(),
+                found: self.current.kind.clone(),
+            }));
         }
         self.advance()?;
         self.expect(&TokenKind::LParen)?;
@@ -2122,13 +2202,10 @@ impl<'a, S: TokenSource> Parser<'a, S> {
                 self.advance()?;
                 self.parse_generic_selection(loc)
             }
-            _ => Err(CompileError::Parse {
-                loc,
-                kind: ParseError::UnexpectedToken {
-                    expected: "primary expression".to_string(),
-                    found: self.current.kind.clone(),
-                },
-            }),
+            _ => Err(self.parse_error(loc, ParseError::UnexpectedToken {
+                expected: "primary expression".to_string(),
+                found: self.current.kind.clone(),
+            })),
         }
     }
 
@@ -2168,13 +2245,10 @@ impl<'a, S: TokenSource> Parser<'a, S> {
         }
         self.expect(&TokenKind::RParen)?;
 
-        default_expr.or(first_expr).ok_or_else(|| CompileError::Parse {
-            loc,
-            kind: ParseError::UnexpectedToken {
-                expected: "generic association".to_string(),
-                found: self.current.kind.clone(),
-            },
-        })
+        default_expr.or(first_expr).ok_or_else(|| self.parse_error(loc, ParseError::UnexpectedToken {
+            expected: "generic association".to_string(),
+            found: self.current.kind.clone(),
+        }))
     }
 
     /// wrapped マクロ（assert 等）を Assert 式としてパース
@@ -2194,10 +2268,7 @@ impl<'a, S: TokenSource> Parser<'a, S> {
                     MacroInvocationKind::Function { args } => args.clone(),
                     MacroInvocationKind::Object => {
                         let name = self.source.interner().get(info.macro_name).to_string();
-                        return Err(CompileError::Parse {
-                            loc,
-                            kind: ParseError::AssertNotFunctionMacro { macro_name: name },
-                        });
+                        return Err(self.parse_error(loc, ParseError::AssertNotFunctionMacro { macro_name: name }));
                     }
                 };
                 (info.marker_id, info.macro_name, args)
@@ -2208,13 +2279,10 @@ impl<'a, S: TokenSource> Parser<'a, S> {
         // 引数数チェック（assert は 1 引数）
         if args.len() != 1 {
             let name = self.source.interner().get(macro_name).to_string();
-            return Err(CompileError::Parse {
-                loc,
-                kind: ParseError::InvalidAssertArgs {
-                    macro_name: name,
-                    arg_count: args.len(),
-                },
-            });
+            return Err(self.parse_error(loc, ParseError::InvalidAssertArgs {
+                macro_name: name,
+                arg_count: args.len(),
+            }));
         }
 
         // args[0] から condition をパース
@@ -2350,13 +2418,10 @@ impl<'a, S: TokenSource> Parser<'a, S> {
                     }
                 }
                 TokenKind::Eof => {
-                    return Err(CompileError::Parse {
-                        loc: token.loc,
-                        kind: ParseError::UnexpectedToken {
-                            expected: "MacroEnd".to_string(),
-                            found: token.kind,
-                        },
-                    });
+                    return Err(self.parse_error(token.loc, ParseError::UnexpectedToken {
+                        expected: "MacroEnd".to_string(),
+                        found: token.kind,
+                    }));
                 }
                 _ => {
                     tokens.push(token);
@@ -2404,10 +2469,7 @@ impl<'a, S: TokenSource> Parser<'a, S> {
         for token in tokens {
             if let TokenKind::MacroBegin(info) = &token.kind {
                 if info.is_wrapped {
-                    return Err(CompileError::Parse {
-                        loc: loc.clone(),
-                        kind: ParseError::NestedAssertNotSupported,
-                    });
+                    return Err(self.parse_error(loc.clone(), ParseError::NestedAssertNotSupported));
                 }
             }
         }
@@ -2439,10 +2501,7 @@ impl<'a, S: TokenSource> Parser<'a, S> {
                     return Ok(());
                 }
                 TokenKind::Eof => {
-                    return Err(CompileError::Parse {
-                        loc: token.loc.clone(),
-                        kind: ParseError::MacroEndNotFound,
-                    });
+                    return Err(self.parse_error(token.loc.clone(), ParseError::MacroEndNotFound));
                 }
                 _ => {
                     // 他のトークンはスキップ（MacroBegin/MacroEnd 含む）
@@ -2499,6 +2558,17 @@ impl<'a, S: TokenSource> Parser<'a, S> {
     fn advance(&mut self) -> Result<Token> {
         let next = self.inner_next_token()?;
         let old = std::mem::replace(&mut self.current, next);
+        match old.kind {
+            TokenKind::LBrace => {
+                if self.brace_depth == 0 {
+                    self.brace_opens_body = self.last_was_rparen;
+                }
+                self.brace_depth += 1;
+            }
+            TokenKind::RBrace => self.brace_depth = self.brace_depth.saturating_sub(1),
+            _ => {}
+        }
+        self.last_was_rparen = matches!(old.kind, TokenKind::RParen);
         Ok(old)
     }
 
@@ -2520,17 +2590,36 @@ impl<'a, S: TokenSource> Parser<'a, S> {
         self.macro_ctx.depth()
     }
 
+    /// パースエラーを作る（現在位置のマクロ展開バックトレース付き）
+    fn parse_error(&self, loc: SourceLocation, kind: ParseError) -> CompileError {
+        CompileError::Parse { loc, kind }.with_backtrace(self.expansion_backtrace())
+    }
+
+    /// 現在のトークンのマクロ展開バックトレース（外側から内側へ）
+    ///
+    /// マーカーを処理している場合は MacroContext から、そうでなければ
+    /// トークンソースが記録している展開情報から取得する。
+    fn expansion_backtrace(&self) -> Vec<ExpansionFrame> {
+        if self.macro_ctx.is_in_macro() {
+            let interner = self.source.interner();
+            return self.macro_ctx.stack.iter()
+                .map(|info| ExpansionFrame {
+                    macro_name: interner.get(info.macro_name).to_string(),
+                    call_loc: info.call_loc.clone(),
+                })
+                .collect();
+        }
+        self.source.expansion_backtrace(&self.current)
+    }
+
     fn expect(&mut self, kind: &TokenKind) -> Result<Token> {
         if self.check(kind) {
             self.advance()
         } else {
-            Err(CompileError::Parse {
-                loc: self.current.loc.clone(),
-                kind: ParseError::UnexpectedToken {
-                    expected: format!("{:?}", kind),
-                    found: self.current.kind.clone(),
-                },
-            })
+            Err(self.parse_error(self.current.loc.clone(), ParseError::UnexpectedToken {
+                expected: format!("{:?}", kind),
+                found: self.current.kind.clone(),
+            }))
         }
     }
 
@@ -2539,13 +2628,10 @@ impl<'a, S: TokenSource> Parser<'a, S> {
             self.advance()?;
             Ok(id)
         } else {
-            Err(CompileError::Parse {
-                loc: self.current.loc.clone(),
-                kind: ParseError::UnexpectedToken {
-                    expected: "identifier".to_string(),
-                    found: self.current.kind.clone(),
-                },
-            })
+            Err(self.parse_error(self.current.loc.clone(), ParseError::UnexpectedToken {
+                expected: "identifier".to_string(),
+                found: self.current.kind.clone(),
+            }))
         }
     }
 
@@ -2725,13 +2811,10 @@ impl<'a, S: TokenSource> Parser<'a, S> {
                 TokenKind::LParen => depth += 1,
                 TokenKind::RParen => depth -= 1,
                 TokenKind::Eof => {
-                    return Err(CompileError::Parse {
-                        loc: self.current.loc.clone(),
-                        kind: ParseError::UnexpectedToken {
-                            expected: ")".to_string(),
-                            found: TokenKind::Eof,
-                        },
-                    });
+                    return Err(self.parse_error(self.current.loc.clone(), ParseError::UnexpectedToken {
+                        expected: ")".to_string(),
+                        found: TokenKind::Eof,
+                    }));
                 }
                 _ => {}
             }
@@ -1,5 +1,11 @@
 //! Perl Config.pm から設定を取得するモジュール
+//!
+//! Config 値の取得元は [`PerlConfigSource`] で選べる。既定は PATH 上の
+//! `perl` だが、perlbrew/plenv の perl を直接指定したり、`perl -V:'.*'` の
+//! 出力や `config.sh` を保存したダンプファイル・キーと値の組を渡したりすれば、
+//! 生成時に対象 perl を実行する必要がなくなる（クロスビルド・再現性のため）。
 
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use std::process::Command;
 
@@ -14,6 +20,212 @@ pub struct PerlConfig {
     pub defines: Vec<(String, Option<String>)>,
     /// 対象 perl の build mode (threaded / non-threaded)
     pub build_mode: PerlBuildMode,
+    /// ターゲットディレクトリ (archlib/CORE)
+    pub target_dir: Option<PathBuf>,
+    /// Perl のメジャー・マイナーバージョン
+    pub version: Option<(u32, u32)>,
+}
+
+/// Perl Config 値の取得元
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PerlConfigSource {
+    /// perl 実行ファイル（`perl -V:'.*'` を 1 回実行して全キーを読む）
+    Executable(PathBuf),
+    /// `key='value'` 行のダンプファイル
+    ///
+    /// `perl -V:'.*'` の出力、`Config_heavy.pl`、`config.sh` のいずれも読める。
+    DumpFile(PathBuf),
+    /// キーと値の組
+    Map(BTreeMap<String, String>),
+}
+
+impl Default for PerlConfigSource {
+    /// PATH 上の `perl`
+    fn default() -> Self {
+        PerlConfigSource::Executable(PathBuf::from("perl"))
+    }
+}
+
+impl PerlConfigSource {
+    /// perl 実行ファイルを指定
+    pub fn executable(path: impl Into<PathBuf>) -> Self {
+        PerlConfigSource::Executable(path.into())
+    }
+
+    /// ダンプファイルを指定
+    pub fn dump_file(path: impl Into<PathBuf>) -> Self {
+        PerlConfigSource::DumpFile(path.into())
+    }
+
+    /// キーと値の組から作成
+    pub fn from_map<K: Into<String>, V: Into<String>>(values: impl IntoIterator<Item = (K, V)>) -> Self {
+        PerlConfigSource::Map(values.into_iter().map(|(k, v)| (k.into(), v.into())).collect())
+    }
+
+    /// 全キーを読み込む
+    pub fn load(&self) -> Result<PerlConfigValues, PerlConfigError> {
+        match self {
+            PerlConfigSource::Executable(perl) => {
+                let output = Command::new(perl)
+                    .arg("-V:.*")
+                    .output()
+                    .map_err(|e| PerlConfigError::CommandFailed(format!("{}: {}", perl.display(), e)))?;
+
+                if !output.status.success() {
+                    return Err(PerlConfigError::CommandFailed(
+                        String::from_utf8_lossy(&output.stderr).to_string(),
+                    ));
+                }
+
+                Ok(PerlConfigValues::parse_dump(&String::from_utf8_lossy(&output.stdout)))
+            }
+            PerlConfigSource::DumpFile(path) => {
+                let text = std::fs::read_to_string(path)
+                    .map_err(|e| PerlConfigError::DumpFileRead(path.clone(), e))?;
+                let values = PerlConfigValues::parse_dump(&text);
+                if values.is_empty() {
+                    return Err(PerlConfigError::ParseError(format!(
+                        "no key='value' lines in {}", path.display()
+                    )));
+                }
+                Ok(values)
+            }
+            PerlConfigSource::Map(map) => Ok(PerlConfigValues { values: map.clone() }),
+        }
+    }
+}
+
+/// 読み込み済みの Perl Config 値
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PerlConfigValues {
+    values: BTreeMap<String, String>,
+}
+
+impl PerlConfigValues {
+    /// `key='value'` 行のダンプをパース
+    ///
+    /// 行末の `;` は省略可（`Config_heavy.pl` / `config.sh` 形式）。
+    /// 閉じ引用符が同じ行にない値は次の行以降に続く。
+    pub fn parse_dump(text: &str) -> Self {
+        let mut values = BTreeMap::new();
+        let mut lines = text.lines();
+        while let Some(line) = lines.next() {
+            let Some((key, rest)) = line.split_once("='") else {
+                continue;
+            };
+            if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                continue;
+            }
+            let mut value = rest.to_string();
+            loop {
+                if let Some(v) = value.strip_suffix("';").or_else(|| value.strip_suffix('\'')) {
+                    value = v.to_string();
+                    break;
+                }
+                match lines.next() {
+                    Some(next) => {
+                        value.push('\n');
+                        value.push_str(next);
+                    }
+                    None => break,
+                }
+            }
+            values.insert(key.to_string(), value);
+        }
+        Self { values }
+    }
+
+    /// 値を取得
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(|v| v.as_str())
+    }
+
+    /// 値を取得（未定義・空文字列ならエラー）
+    fn require(&self, key: &str) -> Result<&str, PerlConfigError> {
+        match self.get(key) {
+            Some(v) if !v.is_empty() => Ok(v),
+            _ => Err(PerlConfigError::ConfigNotFound(key.to_string())),
+        }
+    }
+
+    /// キーの数
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// 空かどうか
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// build mode（`usethreads` が `define` なら Threaded）
+    pub fn build_mode(&self) -> PerlBuildMode {
+        if self.get("usethreads") == Some("define") {
+            PerlBuildMode::Threaded
+        } else {
+            PerlBuildMode::NonThreaded
+        }
+    }
+
+    /// ターゲットディレクトリ (archlib/CORE)
+    pub fn target_dir(&self) -> Result<PathBuf, PerlConfigError> {
+        Ok(PathBuf::from(self.require("archlib")?).join("CORE"))
+    }
+
+    /// Perl のメジャー・マイナーバージョン
+    pub fn perl_version(&self) -> Result<(u32, u32), PerlConfigError> {
+        parse_version(self.require("version")?)
+    }
+
+    /// PerlConfig を構築
+    pub fn to_perl_config(&self) -> PerlConfig {
+        // インクルードパスを取得
+        let mut include_paths = parse_incpth(self.get("incpth").unwrap_or(""));
+
+        // archlib/CORE を追加 (Perl ヘッダー)
+        let target_dir = self.target_dir().ok();
+        if let Some(core_path) = &target_dir
+            && core_path.exists()
+        {
+            include_paths.push(core_path.clone());
+        }
+
+        // cppsymbols を取得
+        let mut defines = parse_cppsymbols(self.get("cppsymbols").unwrap_or(""));
+
+        // ccflags（= ExtUtils::Embed の ccopts から -I を除いたもの）の
+        // -D オプションを抽出して追加
+        for (name, value) in parse_ccflags_defines(self.get("ccflags").unwrap_or("")) {
+            // 既存の定義を上書き（ccflags の方が優先）
+            if let Some(pos) = defines.iter().position(|(n, _)| n == &name) {
+                defines[pos] = (name, value);
+            } else {
+                defines.push((name, value));
+            }
+        }
+
+        // PERL_CORE を追加 (perl.h内のDFA表などを正しく展開するために必要)
+        defines.push(("PERL_CORE".to_string(), None));
+
+        // デバッグ: __x86_64__ が含まれているか確認
+        if std::env::var("DEBUG_PERL_CONFIG").is_ok() {
+            eprintln!("[perl_config] include_paths: {:?}", include_paths);
+            eprintln!("[perl_config] defines count: {}", defines.len());
+            for (name, value) in &defines {
+                if name.contains("x86") || name.contains("LP64") {
+                    eprintln!("[perl_config] {} = {:?}", name, value);
+                }
+            }
+        }
+
+        PerlConfig {
+            include_paths,
+            defines,
+            build_mode: self.build_mode(),
+            target_dir,
+            version: self.perl_version().ok(),
+        }
+    }
 }
 
 /// 対象 perl の build mode
@@ -39,12 +251,12 @@ impl PerlBuildMode {
     /// 1. `Config{usethreads}` == `"define"` → `Threaded`
     /// 2. それ以外（`"undef"` / 空文字列）→ `NonThreaded`
     pub fn detect_from_perl_config() -> Result<Self, PerlConfigError> {
-        let usethreads = get_config_value("usethreads")?;
-        if usethreads == "define" {
-            Ok(PerlBuildMode::Threaded)
-        } else {
-            Ok(PerlBuildMode::NonThreaded)
-        }
+        Self::detect_from(&PerlConfigSource::default())
+    }
+
+    /// 指定した取得元の `Config{usethreads}` から検出する
+    pub fn detect_from(source: &PerlConfigSource) -> Result<Self, PerlConfigError> {
+        Ok(source.load()?.build_mode())
     }
 
     pub fn is_threaded(self) -> bool {
@@ -61,6 +273,8 @@ pub enum PerlConfigError {
     ConfigNotFound(String),
     /// パースエラー
     ParseError(String),
+    /// Config ダンプファイルの読み込み失敗
+    DumpFileRead(PathBuf, std::io::Error),
 }
 
 impl std::fmt::Display for PerlConfigError {
@@ -69,27 +283,20 @@ impl std::fmt::Display for PerlConfigError {
             PerlConfigError::CommandFailed(msg) => write!(f, "perl command failed: {}", msg),
             PerlConfigError::ConfigNotFound(key) => write!(f, "Config key not found: {}", key),
             PerlConfigError::ParseError(msg) => write!(f, "parse error: {}", msg),
+            PerlConfigError::DumpFileRead(path, e) => {
+                write!(f, "failed to read Config dump {}: {}", path.display(), e)
+            }
         }
     }
 }
 
-impl std::error::Error for PerlConfigError {}
-
-/// Perl Config.pm から指定されたキーの値を取得
-fn get_config_value(key: &str) -> Result<String, PerlConfigError> {
-    let output = Command::new("perl")
-        .args(["-MConfig", "-le", &format!("print $Config{{{}}}", key)])
-        .output()
-        .map_err(|e| PerlConfigError::CommandFailed(e.to_string()))?;
-
-    if !output.status.success() {
-        return Err(PerlConfigError::CommandFailed(
-            String::from_utf8_lossy(&output.stderr).to_string(),
-        ));
+impl std::error::Error for PerlConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PerlConfigError::DumpFileRead(_, e) => Some(e),
+            _ => None,
+        }
     }
-
-    let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
-    Ok(value)
 }
 
 /// cppsymbols 文字列をパースして (名前, 値) のペアに変換
@@ -150,51 +357,17 @@ fn parse_incpth(incpth: &str) -> Vec<PathBuf> {
         .collect()
 }
 
-/// ExtUtils::Embed の ccopts から -D オプションを抽出
-fn get_ccopts_defines() -> Result<Vec<(String, Option<String>)>, PerlConfigError> {
-    let output = Command::new("perl")
-        .args(["-MExtUtils::Embed", "-e", "print ccopts"])
-        .output()
-        .map_err(|e| PerlConfigError::CommandFailed(e.to_string()))?;
-
-    if !output.status.success() {
-        return Err(PerlConfigError::CommandFailed(
-            String::from_utf8_lossy(&output.stderr).to_string(),
-        ));
-    }
-
-    let ccopts = String::from_utf8_lossy(&output.stdout);
-    let mut defines = Vec::new();
-
-    for part in ccopts.split_whitespace() {
-        if let Some(def) = part.strip_prefix("-D") {
-            defines.push(parse_single_define(def));
-        }
-    }
-
-    Ok(defines)
-}
-
-/// Perl のデフォルトターゲットディレクトリを取得
-/// archlib/CORE (例: /usr/lib64/perl5/CORE)
-pub fn get_default_target_dir() -> Result<PathBuf, PerlConfigError> {
-    let archlib = get_config_value("archlib")?;
-    if archlib.is_empty() {
-        return Err(PerlConfigError::ConfigNotFound("archlib".to_string()));
-    }
-    Ok(PathBuf::from(&archlib).join("CORE"))
+/// ccflags から -D オプションを抽出
+fn parse_ccflags_defines(ccflags: &str) -> Vec<(String, Option<String>)> {
+    ccflags
+        .split_whitespace()
+        .filter_map(|part| part.strip_prefix("-D"))
+        .map(parse_single_define)
+        .collect()
 }
 
-/// Perl のメジャー・マイナーバージョンを取得
-///
-/// $Config{version} は "5.40.0" のような形式
-/// 戻り値: (major, minor) タプル (例: (5, 40))
-pub fn get_perl_version() -> Result<(u32, u32), PerlConfigError> {
-    let version = get_config_value("version")?;
-    if version.is_empty() {
-        return Err(PerlConfigError::ConfigNotFound("version".to_string()));
-    }
-
+/// "5.40.0" 形式のバージョン文字列から (major, minor) を取り出す
+fn parse_version(version: &str) -> Result<(u32, u32), PerlConfigError> {
     // "5.40.0" -> ["5", "40", "0"]
     let parts: Vec<&str> = version.split('.').collect();
     if parts.len() < 2 {
@@ -215,58 +388,28 @@ pub fn get_perl_version() -> Result<(u32, u32), PerlConfigError> {
     Ok((major, minor))
 }
 
-/// Perl Config.pm から設定を取得
-pub fn get_perl_config() -> Result<PerlConfig, PerlConfigError> {
-    // インクルードパスを取得
-    let incpth = get_config_value("incpth")?;
-    let mut include_paths = parse_incpth(&incpth);
-
-    // archlib/CORE を追加 (Perl ヘッダー)
-    let archlib = get_config_value("archlib")?;
-    if !archlib.is_empty() {
-        let core_path = PathBuf::from(&archlib).join("CORE");
-        if core_path.exists() {
-            include_paths.push(core_path);
-        }
-    }
-
-    // cppsymbols を取得
-    let cppsymbols = get_config_value("cppsymbols")?;
-    let mut defines = parse_cppsymbols(&cppsymbols);
-
-    // ccopts から -D オプションを抽出して追加（重複は後で上書きされる）
-    if let Ok(ccopts_defines) = get_ccopts_defines() {
-        for (name, value) in ccopts_defines {
-            // 既存の定義を上書き（ccoptの方が優先）
-            if let Some(pos) = defines.iter().position(|(n, _)| n == &name) {
-                defines[pos] = (name, value);
-            } else {
-                defines.push((name, value));
-            }
-        }
-    }
-
-    // PERL_CORE を追加 (perl.h内のDFA表などを正しく展開するために必要)
-    defines.push(("PERL_CORE".to_string(), None));
+/// Perl のデフォルトターゲットディレクトリを取得
+/// archlib/CORE (例: /usr/lib64/perl5/CORE)
+pub fn get_default_target_dir() -> Result<PathBuf, PerlConfigError> {
+    PerlConfigSource::default().load()?.target_dir()
+}
 
-    // デバッグ: __x86_64__ が含まれているか確認
-    if std::env::var("DEBUG_PERL_CONFIG").is_ok() {
-        eprintln!("[perl_config] include_paths: {:?}", include_paths);
-        eprintln!("[perl_config] defines count: {}", defines.len());
-        for (name, value) in &defines {
-            if name.contains("x86") || name.contains("LP64") {
-                eprintln!("[perl_config] {} = {:?}", name, value);
-            }
-        }
-    }
+/// Perl のメジャー・マイナーバージョンを取得
+///
+/// $Config{version} は "5.40.0" のような形式
+/// 戻り値: (major, minor) タプル (例: (5, 40))
+pub fn get_perl_version() -> Result<(u32, u32), PerlConfigError> {
+    PerlConfigSource::default().load()?.perl_version()
+}
 
-    let build_mode = PerlBuildMode::detect_from_perl_config()?;
+/// Perl Config.pm から設定を取得
+pub fn get_perl_config() -> Result<PerlConfig, PerlConfigError> {
+    get_perl_config_from(&PerlConfigSource::default())
+}
 
-    Ok(PerlConfig {
-        include_paths,
-        defines,
-        build_mode,
-    })
+/// 指定した取得元から設定を取得
+pub fn get_perl_config_from(source: &PerlConfigSource) -> Result<PerlConfig, PerlConfigError> {
+    Ok(source.load()?.to_perl_config())
 }
 
 /// Perl 環境用の PPConfig を構築
@@ -274,14 +417,20 @@ pub fn get_perl_config() -> Result<PerlConfig, PerlConfigError> {
 /// get_perl_config() と get_default_target_dir() を組み合わせて
 /// プリプロセッサ設定を構築する。build.rs から呼び出すことを想定。
 pub fn build_pp_config_for_perl() -> Result<PPConfig, PerlConfigError> {
-    let perl_cfg = get_perl_config()?;
-    let target_dir = get_default_target_dir().ok();
+    build_pp_config_for_perl_from(&PerlConfigSource::default())
+}
+
+/// 指定した取得元から Perl 環境用の PPConfig を構築
+pub fn build_pp_config_for_perl_from(source: &PerlConfigSource) -> Result<PPConfig, PerlConfigError> {
+    let perl_cfg = get_perl_config_from(source)?;
     Ok(PPConfig {
         include_paths: perl_cfg.include_paths,
         predefined: perl_cfg.defines,
         debug_pp: false,
-        target_dir,
+        target_dir: perl_cfg.target_dir,
         emit_markers: false,
+        feature_tables: Default::default(),
+        overlays: Default::default(),
     })
 }
 
@@ -340,21 +489,66 @@ mod tests {
 
     #[test]
     fn test_parse_version() {
-        // バージョン文字列のパースをテスト（内部ヘルパー関数で）
-        fn parse_version(version: &str) -> Option<(u32, u32)> {
-            let parts: Vec<&str> = version.split('.').collect();
-            if parts.len() < 2 {
-                return None;
-            }
-            let major = parts[0].parse::<u32>().ok()?;
-            let minor = parts[1].parse::<u32>().ok()?;
-            Some((major, minor))
-        }
+        assert_eq!(parse_version("5.40.0").unwrap(), (5, 40));
+        assert_eq!(parse_version("5.38.2").unwrap(), (5, 38));
+        assert_eq!(parse_version("5.10.1").unwrap(), (5, 10));
+        assert_eq!(parse_version("5.8").unwrap(), (5, 8));
+        assert!(parse_version("invalid").is_err());
+    }
+
+    #[test]
+    fn test_parse_dump() {
+        // perl -V:'.*' 形式と config.sh 形式（; なし・複数行）
+        let dump = concat!(
+            "archlib='/opt/perl/lib/5.40.0/x86_64-linux-thread-multi';\n",
+            "usethreads='define';\n",
+            "version='5.40.0';\n",
+            "# comment\n",
+            "ccflags='-D_REENTRANT -fwrapv -DFOO=1'\n",
+            "multi='line1\n",
+            "line2'\n",
+            "empty='';\n",
+        );
+        let values = PerlConfigValues::parse_dump(dump);
+        assert_eq!(values.len(), 6);
+        assert_eq!(values.get("multi"), Some("line1\nline2"));
+        assert_eq!(values.get("empty"), Some(""));
+        assert_eq!(values.build_mode(), PerlBuildMode::Threaded);
+        assert_eq!(values.perl_version().unwrap(), (5, 40));
+        assert_eq!(
+            values.target_dir().unwrap(),
+            PathBuf::from("/opt/perl/lib/5.40.0/x86_64-linux-thread-multi/CORE")
+        );
+    }
 
-        assert_eq!(parse_version("5.40.0"), Some((5, 40)));
-        assert_eq!(parse_version("5.38.2"), Some((5, 38)));
-        assert_eq!(parse_version("5.10.1"), Some((5, 10)));
-        assert_eq!(parse_version("5.8"), Some((5, 8)));
-        assert_eq!(parse_version("invalid"), None);
+    #[test]
+    fn test_dump_file_read_error() {
+        let err = PerlConfigSource::dump_file("no-such-config.sh").load().unwrap_err();
+        assert!(matches!(err, PerlConfigError::DumpFileRead(ref path, _) if path == std::path::Path::new("no-such-config.sh")));
+        assert!(err.to_string().starts_with("failed to read Config dump no-such-config.sh: "), "{err}");
+    }
+
+    #[test]
+    fn test_perl_config_from_map() {
+        let source = PerlConfigSource::from_map([
+            ("incpth", "/usr/include"),
+            ("cppsymbols", "__GNUC__=15 _REENTRANT=0"),
+            ("ccflags", "-D_REENTRANT -O2 -I/usr/local/include"),
+            ("version", "5.38.2"),
+        ]);
+        let cfg = get_perl_config_from(&source).unwrap();
+        assert_eq!(cfg.include_paths, vec![PathBuf::from("/usr/include")]);
+        assert_eq!(cfg.defines, vec![
+            ("__GNUC__".to_string(), Some("15".to_string())),
+            ("_REENTRANT".to_string(), None),
+            ("PERL_CORE".to_string(), None),
+        ]);
+        assert_eq!(cfg.build_mode, PerlBuildMode::NonThreaded);
+        assert_eq!(cfg.target_dir, None);
+        assert_eq!(cfg.version, Some((5, 38)));
+        assert!(matches!(
+            PerlBuildMode::detect_from(&source),
+            Ok(PerlBuildMode::NonThreaded)
+        ));
     }
 }
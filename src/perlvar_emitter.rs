@@ -1,9 +1,9 @@
 //! Emit `PL_<name>!($my_perl)` declarative macros for each entry in
 //! [`PerlvarDict`].
 //!
-//! Output is target-specific: only one form per entry is emitted (no
-//! `#[cfg]` branches), based on the build's threading mode and the
-//! variable's prefix:
+//! [`emit_perlvar_section`] output is target-specific: only one form per
+//! entry is emitted (no `#[cfg]` branches), based on the build's
+//! threading mode and the variable's prefix:
 //!
 //! | prefix | threaded build               | non-threaded build           |
 //! |--------|------------------------------|------------------------------|
@@ -21,9 +21,14 @@
 //! The non-threaded form intentionally still takes the `$my_perl`
 //! argument and evaluates it once, so that the same source compiles
 //! against both threading modes without modification.
+//!
+//! [`emit_perlvar_section_dual`] serves the dual-mode pipeline: entries
+//! whose form differs between the two builds get one definition per
+//! mode, gated by `#[cfg(perl_threaded)]` / `#[cfg(not(perl_threaded))]`.
 
 use std::io::{self, Write};
 
+use crate::dual_mode::{NON_THREADED_CFG, THREADED_CFG};
 use crate::perlvar_dict::{ArrayLength, PerlvarDict, PerlvarEntry, PerlvarKind};
 
 /// Emit the PERLVAR section into `out`.
@@ -39,6 +44,48 @@ pub fn emit_perlvar_section<W: Write>(
         return Ok(());
     }
 
+    emit_section_header(out)?;
+    for entry in dict.iter() {
+        emit_one(out, entry, threaded, None)?;
+    }
+    Ok(())
+}
+
+/// Emit the PERLVAR section covering both threading modes.
+///
+/// An entry present in both dicts whose expansion is the same in both
+/// modes (`'G'` prefix, same declaration) is emitted once without
+/// `#[cfg]`. Every other entry is emitted once per dict it appears in,
+/// gated by `#[cfg(perl_threaded)]` / `#[cfg(not(perl_threaded))]`.
+pub fn emit_perlvar_section_dual<W: Write>(
+    out: &mut W,
+    threaded: &PerlvarDict,
+    non_threaded: &PerlvarDict,
+) -> io::Result<()> {
+    if threaded.is_empty() && non_threaded.is_empty() {
+        return Ok(());
+    }
+
+    emit_section_header(out)?;
+    for entry in threaded.iter() {
+        match non_threaded.get(&entry.name) {
+            Some(other) if other == entry && entry.prefix == 'G' => {
+                emit_one(out, entry, true, None)?;
+            }
+            Some(other) => {
+                emit_one(out, entry, true, Some(THREADED_CFG))?;
+                emit_one(out, other, false, Some(NON_THREADED_CFG))?;
+            }
+            None => emit_one(out, entry, true, Some(THREADED_CFG))?,
+        }
+    }
+    for entry in non_threaded.iter().filter(|e| threaded.get(&e.name).is_none()) {
+        emit_one(out, entry, false, Some(NON_THREADED_CFG))?;
+    }
+    Ok(())
+}
+
+fn emit_section_header<W: Write>(out: &mut W) -> io::Result<()> {
     writeln!(out)?;
     writeln!(out, "// =====================================================================")?;
     writeln!(out, "// PERLVAR Accessor Macros")?;
@@ -49,14 +96,10 @@ pub fn emit_perlvar_section<W: Write>(
     writeln!(out, "// caller's `my_perl`; the explicit argument keeps the source portable")?;
     writeln!(out, "// across both threading modes.)")?;
     writeln!(out, "// =====================================================================")?;
-
-    for entry in dict.iter() {
-        emit_one(out, entry, threaded)?;
-    }
     Ok(())
 }
 
-fn emit_one<W: Write>(out: &mut W, e: &PerlvarEntry, threaded: bool) -> io::Result<()> {
+fn emit_one<W: Write>(out: &mut W, e: &PerlvarEntry, threaded: bool, cfg: Option<&str>) -> io::Result<()> {
     let pl_name = format!("PL_{}", e.name);
 
     // Doc comment summarising the originating PERLVAR declaration.
@@ -72,6 +115,9 @@ fn emit_one<W: Write>(out: &mut W, e: &PerlvarEntry, threaded: bool) -> io::Resu
     // The `'G'` prefix means process-global (always a `PL_<name>` static).
     let use_struct_field = threaded && e.prefix == 'I';
 
+    if let Some(cfg) = cfg {
+        writeln!(out, "#[cfg({cfg})]")?;
+    }
     writeln!(out, "#[macro_export]")?;
     writeln!(out, "macro_rules! {pl_name} {{")?;
     writeln!(out, "    ($my_perl:expr) => {{{{")?;
@@ -188,4 +234,25 @@ mod tests {
         assert!(s.contains("$crate::PL_op_mutex"), "{s}");
         assert!(!s.contains("__my_perl"), "{s}");
     }
+
+    #[test]
+    fn dual_emits_cfg_variants_only_where_forms_differ() {
+        let mut thr = PerlvarDict::new();
+        thr.insert(entry("main_start", 'I', PerlvarKind::Var, "OP *"));
+        thr.insert(entry("op_mutex", 'G', PerlvarKind::Var, "perl_mutex"));
+        let mut nothr = thr.clone();
+        nothr.insert(entry("sig_pending", 'G', PerlvarKind::Var, "int"));
+        let mut out = Vec::new();
+        emit_perlvar_section_dual(&mut out, &thr, &nothr).unwrap();
+        let s = String::from_utf8(out).unwrap();
+
+        assert_eq!(s.matches("macro_rules! PL_main_start").count(), 2, "{s}");
+        assert!(s.contains("#[cfg(perl_threaded)]\n#[macro_export]\nmacro_rules! PL_main_start"), "{s}");
+        assert!(s.contains("#[cfg(not(perl_threaded))]\n#[macro_export]\nmacro_rules! PL_main_start"), "{s}");
+        // 'G' entries in both builds are shared
+        assert_eq!(s.matches("macro_rules! PL_op_mutex").count(), 1, "{s}");
+        assert!(s.contains("`PERLVAR(G, op_mutex, perl_mutex)`\n#[macro_export]"), "{s}");
+        // present only in the non-threaded build
+        assert!(s.contains("#[cfg(not(perl_threaded))]\n#[macro_export]\nmacro_rules! PL_sig_pending"), "{s}");
+    }
 }
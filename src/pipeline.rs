@@ -37,10 +37,22 @@
 
 use std::collections::HashMap;
 use std::io::Write;
-use std::path::PathBuf;
-
-use crate::perl_config::{get_perl_config, PerlConfigError, get_default_target_dir};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::apidoc_patches::ApidocPatchFile;
+use crate::c_shim::{CShimOutput, CShimSet};
+use crate::codegen_report::CodegenReport;
+use crate::compile_check::{
+    map_diagnostics_to_items, run_rustc_check, CompileCheckConfig, CompileCheckOutcome, CompileCheckRound,
+};
+use crate::gen_cache::{CacheOptions, CacheStatus, CachedOutput, GenCache};
+use crate::module_split::{ModuleOutput, OutputItem, OutputSegment};
+use crate::nullability::NullabilityMode;
+use crate::perl_config::{get_perl_config_from, PerlConfigError, PerlConfigSource};
+use crate::pp_expr::FeatureTables;
 use crate::preprocessor::{PPConfig, Preprocessor};
+use crate::source::VirtualFs;
 use crate::rust_codegen::{BindingsInfo, CodegenConfig as RustCodegenConfig, CodegenDriver, CodegenStats};
 use crate::infer_api::{InferResult, InferError};
 use crate::error::EnrichedCompileError;
@@ -55,7 +67,7 @@ pub enum PipelineError {
     /// Perl 設定取得エラー
     PerlConfig(PerlConfigError),
     /// プリプロセス/パースエラー（ファイルパスと該当行で強化済み）
-    Compile(EnrichedCompileError),
+    Compile(Box<EnrichedCompileError>),
     /// 推論エラー
     Infer(InferError),
     /// I/O エラー
@@ -77,7 +89,7 @@ impl std::error::Error for PipelineError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
             PipelineError::PerlConfig(e) => Some(e),
-            PipelineError::Compile(e) => Some(e),
+            PipelineError::Compile(e) => Some(e.as_ref()),
             PipelineError::Infer(e) => Some(e),
             PipelineError::Io(e) => Some(e),
         }
@@ -92,7 +104,7 @@ impl From<PerlConfigError> for PipelineError {
 
 impl From<EnrichedCompileError> for PipelineError {
     fn from(e: EnrichedCompileError) -> Self {
-        PipelineError::Compile(e)
+        PipelineError::Compile(Box::new(e))
     }
 }
 
@@ -134,6 +146,10 @@ pub struct PreprocessConfig {
     /// 末尾に `PL_xxx!()` 宣言マクロのセクションを追加する。
     /// 必要なければ `with_perlvar_collection(false)` で無効化できる。
     pub collect_perlvars: bool,
+    /// `#if` 中の `__has_builtin` / `__has_attribute` / `__has_feature` が参照する表
+    pub feature_tables: FeatureTables,
+    /// ディスクより優先して読むメモリ上のヘッダー
+    pub header_overlays: VirtualFs,
     /// デバッグ出力
     pub debug_pp: bool,
 }
@@ -149,6 +165,8 @@ impl PreprocessConfig {
             emit_markers: false,
             wrapped_macros: Vec::new(),
             collect_perlvars: true,
+            feature_tables: FeatureTables::default(),
+            header_overlays: VirtualFs::new(),
             debug_pp: false,
         }
     }
@@ -163,6 +181,8 @@ impl PreprocessConfig {
             debug_pp: self.debug_pp,
             target_dir: self.target_dir.clone(),
             emit_markers: self.emit_markers,
+            feature_tables: self.feature_tables.clone(),
+            overlays: self.header_overlays.clone(),
         }
     }
 }
@@ -190,6 +210,15 @@ pub struct InferConfig {
     /// `None` の場合は `auto-detect`（実行時に `perl Config{usethreads}` を読む）。
     /// `Some(...)` で明示指定（テスト用）。
     pub perl_build_mode: Option<crate::perl_config::PerlBuildMode>,
+    /// 対象 perl のバージョン（apidoc の自動検索・patches のファイル名に使う）
+    ///
+    /// `None` の場合は実行時に `perl` から取得する。
+    pub perl_version: Option<(u32, u32)>,
+    /// パースできない宣言を読み飛ばして推論を続けるか
+    ///
+    /// 読み飛ばした宣言のエラーは `InferResult::parse_errors` に入る。
+    /// 無効（デフォルト）なら最初のパースエラーで失敗する。
+    pub recover_parse_errors: bool,
 }
 
 impl InferConfig {
@@ -211,12 +240,26 @@ pub struct CodegenConfig {
     pub emit_inline_fns: bool,
     /// マクロを出力
     pub emit_macros: bool,
+    /// オブジェクトマクロ（定数）を出力
+    pub emit_object_macros: bool,
     /// ヘッダーに出力する use 文（空ならデフォルト）
     pub use_statements: Vec<String>,
     /// AST ダンプ対象関数名（デバッグ用）
     pub dump_ast_for: Option<String>,
     /// 型推論ダンプ対象関数名（デバッグ用）
     pub dump_types_for: Option<String>,
+    /// 生成結果レポート（JSON）の出力先
+    pub report_json: Option<PathBuf>,
+    /// 生成コードのコンパイル検査（None なら無効）
+    pub compile_check: Option<CompileCheckConfig>,
+    /// 変換できないマクロの C shim ファイルの出力先（None なら無効）
+    pub c_shim: Option<PathBuf>,
+    /// apidoc の NN / NULLOK / NZ 注釈の使い方
+    pub nullability: NullabilityMode,
+    /// perlapi の POD 本文と C のシグネチャを rustdoc として出力
+    pub pod_docs: bool,
+    /// 生成物を置くモジュールのクレートルートからのパス（`macro_rules!` の名前解決に使う）
+    pub macro_path: Option<String>,
 }
 
 impl Default for CodegenConfig {
@@ -227,9 +270,16 @@ impl Default for CodegenConfig {
             macro_comments: false,
             emit_inline_fns: true,
             emit_macros: true,
+            emit_object_macros: true,
             use_statements: Vec::new(),
             dump_ast_for: None,
             dump_types_for: None,
+            report_json: None,
+            compile_check: None,
+            c_shim: None,
+            nullability: NullabilityMode::Off,
+            pod_docs: false,
+            macro_path: None,
         }
     }
 }
@@ -240,10 +290,14 @@ impl CodegenConfig {
         RustCodegenConfig {
             emit_inline_fns: self.emit_inline_fns,
             emit_macros: self.emit_macros,
+            emit_object_macros: self.emit_object_macros,
             include_source_location: self.macro_comments,
             use_statements: self.use_statements.clone(),
             dump_ast_for: self.dump_ast_for.clone(),
             dump_types_for: self.dump_types_for.clone(),
+            nullability: self.nullability,
+            pod_docs: self.pod_docs,
+            macro_path: self.macro_path.clone(),
         }
     }
 }
@@ -258,6 +312,7 @@ pub struct PipelineBuilder {
     preprocess: PreprocessConfig,
     infer: InferConfig,
     codegen: CodegenConfig,
+    cache_dir: Option<PathBuf>,
 }
 
 impl PipelineBuilder {
@@ -267,6 +322,7 @@ impl PipelineBuilder {
             preprocess: PreprocessConfig::new(input_file),
             infer: InferConfig::new(),
             codegen: CodegenConfig::default(),
+            cache_dir: None,
         }
     }
 
@@ -276,11 +332,24 @@ impl PipelineBuilder {
     ///
     /// インクルードパス、プリプロセッサ定義、ターゲットディレクトリを
     /// Perl の Config.pm から取得して設定する。
-    pub fn with_auto_perl_config(mut self) -> Result<Self, PipelineError> {
-        let perl_cfg = get_perl_config()?;
+    pub fn with_auto_perl_config(self) -> Result<Self, PipelineError> {
+        self.with_auto_perl_config_from(&PerlConfigSource::default())
+    }
+
+    /// 指定した取得元の Perl Config から自動設定
+    ///
+    /// `with_auto_perl_config` と同じ設定に加え、build mode（明示指定が
+    /// なければ）と perl バージョンも取得元から決めるので、以降のフェーズで
+    /// perl を実行しない。
+    pub fn with_auto_perl_config_from(mut self, source: &PerlConfigSource) -> Result<Self, PipelineError> {
+        let perl_cfg = get_perl_config_from(source)?;
         self.preprocess.include_paths = perl_cfg.include_paths;
         self.preprocess.defines = perl_cfg.defines.into_iter().collect();
-        self.preprocess.target_dir = get_default_target_dir().ok();
+        self.preprocess.target_dir = perl_cfg.target_dir;
+        self.infer.perl_build_mode.get_or_insert(perl_cfg.build_mode);
+        if perl_cfg.version.is_some() {
+            self.infer.perl_version = perl_cfg.version;
+        }
         Ok(self)
     }
 
@@ -329,6 +398,24 @@ impl PipelineBuilder {
         self
     }
 
+    /// `#if` 中の `__has_builtin` / `__has_attribute` / `__has_feature` が
+    /// 真を返す名前の表を指定
+    pub fn with_feature_tables(mut self, tables: FeatureTables) -> Self {
+        self.preprocess.feature_tables = tables;
+        self
+    }
+
+    /// ヘッダーをメモリ上の内容で置き換える（または追加する）
+    ///
+    /// 絶対パスならそのファイルを置き換え（`is_target` の判定も元のパスで行う）、
+    /// 相対パスなら `#include` に書かれた名前として最優先で見つかる。
+    /// 入力ファイルと同じパスを渡せば、入力自体をメモリ上で組み立てられる。
+    /// build.rs で一時ファイルを書かずにパッチを当てた `config.h` を使う場合など。
+    pub fn with_header_overlay(mut self, path: impl Into<PathBuf>, contents: impl Into<Vec<u8>>) -> Self {
+        self.preprocess.header_overlays.add_file(path, contents);
+        self
+    }
+
     /// プリプロセッサデバッグ出力を有効化
     pub fn with_debug_pp(mut self) -> Self {
         self.preprocess.debug_pp = true;
@@ -386,6 +473,15 @@ impl PipelineBuilder {
         self
     }
 
+    /// パースできない宣言を読み飛ばして推論を続ける
+    ///
+    /// 新しい・特殊なシステムヘッダーの 1 宣言で全体が失敗しないようにする。
+    /// 読み飛ばした宣言のエラーは `InferResult::parse_errors` で取得できる。
+    pub fn with_parse_error_recovery(mut self) -> Self {
+        self.infer.recover_parse_errors = true;
+        self
+    }
+
     // === Codegen 設定 ===
 
     /// rustfmt 失敗時にエラー終了
@@ -406,6 +502,24 @@ impl PipelineBuilder {
         self
     }
 
+    /// 生成する関数に perlapi の POD 本文と C のシグネチャを rustdoc として付ける
+    ///
+    /// 詳細は [`crate::pod`] を参照。
+    pub fn with_pod_docs(mut self) -> Self {
+        self.codegen.pod_docs = true;
+        self
+    }
+
+    /// 生成物を置くモジュールのパスを指定（例: `ffi::macros`）
+    ///
+    /// `#[macro_export]` した `macro_rules!` は本体の名前を
+    /// `use $crate::<path>::*;` で解決する。生成コードをクレートルート以外に
+    /// 置く場合（`--output-dir` の子モジュールなど）に必要。
+    pub fn with_macro_path(mut self, path: impl Into<String>) -> Self {
+        self.codegen.macro_path = Some(path.into());
+        self
+    }
+
     /// AST ダンプ対象関数名を指定（デバッグ用）
     pub fn with_dump_ast_for(mut self, name: impl Into<String>) -> Self {
         self.codegen.dump_ast_for = Some(name.into());
@@ -418,6 +532,50 @@ impl PipelineBuilder {
         self
     }
 
+    /// 生成結果レポート（JSON）の出力先を指定
+    pub fn with_report_json(mut self, path: impl Into<PathBuf>) -> Self {
+        self.codegen.report_json = Some(path.into());
+        self
+    }
+
+    /// 変換できないマクロを C shim 経由で呼ぶコードを生成し、C ファイルを出力する
+    ///
+    /// 詳細は [`crate::c_shim`] を参照。
+    pub fn with_c_shim(mut self, path: impl Into<PathBuf>) -> Self {
+        self.codegen.c_shim = Some(path.into());
+        self
+    }
+
+    /// apidoc の NN / NULLOK / NZ 注釈を生成するマクロ関数に反映する
+    ///
+    /// 詳細は [`crate::nullability`] を参照。
+    pub fn with_nullability(mut self, mode: NullabilityMode) -> Self {
+        self.codegen.nullability = mode;
+        self
+    }
+
+    /// 生成コードのコンパイル検査を有効化
+    ///
+    /// bindings.rs のパスを省略した場合は `with_bindings` の値を使う。
+    pub fn with_compile_check(mut self, config: CompileCheckConfig) -> Self {
+        self.codegen.compile_check = Some(config);
+        self
+    }
+
+    // === Cache 設定 ===
+
+    /// 生成結果のディスクキャッシュを有効化
+    ///
+    /// ヘッダーツリー・定義・bindings.rs・apidoc・crate バージョン・設定が
+    /// 前回と同じなら、`Pipeline::generate` はプリプロセス・推論・コード生成を
+    /// すべて省略して前回の出力を書き出す。`build.rs` からは
+    /// `Path::new(&env::var("OUT_DIR")?).join("macrogen-cache")` などを渡す。
+    /// `with_compile_check` と併用した場合はキャッシュを使わない。
+    pub fn with_cache_dir(mut self, path: impl Into<PathBuf>) -> Self {
+        self.cache_dir = Some(path.into());
+        self
+    }
+
     // === Build ===
 
     /// Pipeline を構築
@@ -426,6 +584,7 @@ impl PipelineBuilder {
             preprocess_config: self.preprocess,
             infer_config: self.infer,
             codegen_config: self.codegen,
+            cache_dir: self.cache_dir,
         })
     }
 
@@ -454,6 +613,7 @@ pub struct Pipeline {
     preprocess_config: PreprocessConfig,
     infer_config: InferConfig,
     codegen_config: CodegenConfig,
+    cache_dir: Option<PathBuf>,
 }
 
 impl Pipeline {
@@ -511,7 +671,7 @@ impl Pipeline {
 
         // ファイルを処理
         if let Err(e) = pp.add_source_file(&self.preprocess_config.input_file) {
-            return Err(PipelineError::Compile(e.with_files(pp.files())));
+            return Err(PipelineError::Compile(Box::new(e.with_files(pp.files()))));
         }
 
         Ok(PreprocessedPipeline {
@@ -528,8 +688,135 @@ impl Pipeline {
     }
 
     /// Phase 3: コード生成まで実行（全フェーズ）
-    pub fn generate<W: Write>(self, writer: W) -> Result<GeneratedPipeline, PipelineError> {
-        self.infer()?.generate(writer)
+    ///
+    /// `with_cache_dir` 指定時は、入力が前回と同じならキャッシュから出力する。
+    /// その場合 `GeneratedPipeline::try_result()` は None になる。
+    pub fn generate<W: Write>(mut self, mut writer: W) -> Result<GeneratedPipeline, PipelineError> {
+        let Some(cache) = self.cache() else {
+            return self.infer()?.generate(writer);
+        };
+        // build mode を PATH 上の perl から検出する場合も、キーと推論で同じ値を使う
+        if self.infer_config.perl_build_mode.is_none() {
+            let mode = crate::perl_config::PerlBuildMode::detect_from_perl_config()
+                .unwrap_or(crate::perl_config::PerlBuildMode::Threaded);
+            self.infer_config.perl_build_mode = Some(mode);
+        }
+        let options = self.cache_options();
+
+        let miss = match cache.lookup(&options) {
+            Ok(cached) => {
+                writer.write_all(&cached.output)?;
+                if let Some(path) = &self.codegen_config.report_json {
+                    cached.report.write_json(path)?;
+                }
+                if let (Some(path), Some(shims)) = (&self.codegen_config.c_shim, &cached.c_shim) {
+                    std::fs::write(path, &shims.source)?;
+                }
+                return Ok(GeneratedPipeline {
+                    result: None,
+                    infer_elapsed: None,
+                    stats: cached.stats,
+                    report: cached.report,
+                    compile_check: None,
+                    segments: cached.segments,
+                    items: cached.items,
+                    c_shim: cached.c_shim,
+                    skipped_declarations: cached.skipped_declarations,
+                    cache: Some(CacheStatus::Hit),
+                });
+            }
+            Err(miss) => miss,
+        };
+
+        let inferred = self.infer()?;
+        let files: Vec<PathBuf> = inferred.result.preprocessor.files().iter()
+            .map(|(_, path)| path.to_path_buf())
+            .collect();
+        let mut buffer = Vec::new();
+        let mut generated = inferred.generate(&mut buffer)?;
+        writer.write_all(&buffer)?;
+
+        let cached = CachedOutput {
+            output: buffer,
+            stats: generated.stats.clone(),
+            report: generated.report.clone(),
+            segments: generated.segments.clone(),
+            items: generated.items.clone(),
+            c_shim: generated.c_shim.clone(),
+            skipped_declarations: generated.skipped_declarations.clone(),
+        };
+        cache.store(&options, files.iter().map(|p| p.as_path()), &cached)?;
+        generated.cache = Some(CacheStatus::Miss(miss));
+        Ok(generated)
+    }
+
+    /// キャッシュを使うか
+    ///
+    /// デバッグ出力を伴う設定では使わない。コンパイル検査もキャッシュキーに
+    /// 含まれない rustc に結果が依存し、patches の書き出しを伴うので使わない。
+    fn cache(&self) -> Option<GenCache> {
+        let debug = self.preprocess_config.debug_pp
+            || self.infer_config.dump_apidoc_after_merge.is_some()
+            || !self.infer_config.debug_type_inference.is_empty()
+            || self.codegen_config.dump_ast_for.is_some()
+            || self.codegen_config.dump_types_for.is_some();
+        if debug || self.codegen_config.compile_check.is_some() {
+            return None;
+        }
+        self.cache_dir.as_ref().map(GenCache::new)
+    }
+
+    /// キャッシュキーとなる設定項目と入力ファイル
+    fn cache_options(&self) -> CacheOptions {
+        let pp = &self.preprocess_config;
+        let mut options = CacheOptions::new();
+        options.add("input_file", &format!("{:?}", pp.input_file));
+        options.add("include_paths", &format!("{:?}", pp.include_paths));
+        let defines: std::collections::BTreeMap<_, _> = pp.defines.iter().collect();
+        options.add("defines", &format!("{:?}", defines));
+        options.add("target_dir", &format!("{:?}", pp.target_dir));
+        options.add("header_overlays", &format!("{:?}", pp.header_overlays));
+        options.add("preprocess", &format!("{:?}",
+            (pp.emit_markers, &pp.wrapped_macros, pp.collect_perlvars, &pp.feature_tables)));
+        options.add("infer", &format!("{:?}", self.infer_config));
+        // レポート・C shim の出力先は生成結果に影響しない
+        let codegen = CodegenConfig {
+            report_json: None,
+            c_shim: None,
+            ..self.codegen_config.clone()
+        };
+        options.add("codegen", &format!("{:?}", codegen));
+        options.add("c_shim", &format!("{}", self.codegen_config.c_shim.is_some()));
+        options.add("LIBPERL_APIDOC_URL", &format!("{:?}", std::env::var("LIBPERL_APIDOC_URL").ok()));
+
+        let infer = &self.infer_config;
+        for path in infer.bindings_path.iter()
+            .chain(infer.apidoc_path.iter())
+            .chain(infer.skip_codegen_lists.iter())
+        {
+            options.add_input(path);
+        }
+        // apidoc JSON と *.patches.json の置き場所。既定では埋め込みデータの展開先
+        let apidoc_dir = match &infer.apidoc_path {
+            Some(path) => Some(match path.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+                _ => PathBuf::from("."),
+            }),
+            None => crate::apidoc::find_apidoc_dir_from(infer.apidoc_dir.as_deref()),
+        };
+        if let Some(dir) = &apidoc_dir
+            && let Ok(entries) = std::fs::read_dir(dir)
+        {
+            let mut paths: Vec<PathBuf> = entries.flatten()
+                .map(|e| e.path())
+                .filter(|p| p.is_file())
+                .collect();
+            paths.sort();
+            for path in paths {
+                options.add_input(path);
+            }
+        }
+        options
     }
 }
 
@@ -602,15 +889,24 @@ impl PreprocessedPipeline {
 
     /// Phase 2: 推論を実行
     pub fn infer(self) -> Result<InferredPipeline, PipelineError> {
-        use crate::apidoc::resolve_apidoc_path;
-        use crate::infer_api::{run_inference_with_preprocessor, DebugOptions};
+        use crate::apidoc::{resolve_apidoc_path, resolve_apidoc_path_for_version};
+        use crate::infer_api::{
+            run_inference_with_parse_error_recovery, run_inference_with_preprocessor, DebugOptions,
+        };
 
-        // apidoc パスを解決
-        let apidoc_path = resolve_apidoc_path(
-            self.infer_config.apidoc_path.as_deref(),
-            true, // auto_mode
-            self.infer_config.apidoc_dir.as_deref(),
-        ).map_err(|e| PipelineError::Infer(InferError::ApidocResolve(e)))?;
+        let started = Instant::now();
+        // apidoc パスを解決（perl バージョンが分かっていれば perl を実行しない）
+        let apidoc_path = match self.infer_config.perl_version {
+            Some((major, minor)) if self.infer_config.apidoc_path.is_none() => {
+                resolve_apidoc_path_for_version(self.infer_config.apidoc_dir.as_deref(), major, minor)
+                    .map(Some)
+            }
+            _ => resolve_apidoc_path(
+                self.infer_config.apidoc_path.as_deref(),
+                true, // auto_mode
+                self.infer_config.apidoc_dir.as_deref(),
+            ),
+        }.map_err(|e| PipelineError::Infer(InferError::ApidocResolve(e)))?;
 
         // デバッグオプションを構築
         let has_debug_opts = self.infer_config.dump_apidoc_after_merge.is_some()
@@ -625,7 +921,12 @@ impl PreprocessedPipeline {
         };
 
         // 推論を実行
-        let result = run_inference_with_preprocessor(
+        let run = if self.infer_config.recover_parse_errors {
+            run_inference_with_parse_error_recovery
+        } else {
+            run_inference_with_preprocessor
+        };
+        let result = run(
             self.preprocessor,
             apidoc_path.as_deref(),
             self.infer_config.bindings_path.as_deref(),
@@ -643,9 +944,23 @@ impl PreprocessedPipeline {
                     // ではなく素直に clone で取り出す。
                     infer_result.perlvar_dict = rc.borrow().clone();
                 }
+                let mut codegen_config = self.codegen_config;
+                if let Some(check) = &mut codegen_config.compile_check
+                    && check.bindings_path.is_none()
+                {
+                    check.bindings_path = self.infer_config.bindings_path.clone();
+                }
+                // patches の出力先がディレクトリなら、ここでファイル名を決める
+                if let Some(check) = &mut codegen_config.compile_check
+                    && let Some((major, minor)) = self.infer_config.perl_version
+                    && let Some(dir) = check.patches_out.as_mut().filter(|p| p.is_dir())
+                {
+                    *dir = dir.join(format!("v{}.{}.patches.json", major, minor));
+                }
                 Ok(InferredPipeline {
                     result: infer_result,
-                    codegen_config: self.codegen_config,
+                    codegen_config,
+                    infer_elapsed: started.elapsed(),
                 })
             }
             None => {
@@ -674,6 +989,8 @@ impl PreprocessedPipeline {
 pub struct InferredPipeline {
     result: InferResult,
     codegen_config: CodegenConfig,
+    /// 推論フェーズ（ヘッダーの読み込みを含む）の所要時間
+    infer_elapsed: Duration,
 }
 
 impl InferredPipeline {
@@ -692,6 +1009,11 @@ impl InferredPipeline {
         &self.codegen_config
     }
 
+    /// 推論フェーズ（ヘッダーの読み込みを含む）の所要時間
+    pub fn infer_elapsed(&self) -> Duration {
+        self.infer_elapsed
+    }
+
     // === Codegen 設定を追加で指定可能 ===
 
     /// rustfmt 失敗時にエラー終了
@@ -712,6 +1034,18 @@ impl InferredPipeline {
         self
     }
 
+    /// 生成する関数に perlapi の POD 本文と C のシグネチャを rustdoc として付ける
+    pub fn with_pod_docs(mut self) -> Self {
+        self.codegen_config.pod_docs = true;
+        self
+    }
+
+    /// 生成物を置くモジュールのパスを指定（`macro_rules!` の名前解決に使う）
+    pub fn with_macro_path(mut self, path: impl Into<String>) -> Self {
+        self.codegen_config.macro_path = Some(path.into());
+        self
+    }
+
     /// AST ダンプ対象関数名を指定（デバッグ用）
     pub fn with_dump_ast_for(mut self, name: impl Into<String>) -> Self {
         self.codegen_config.dump_ast_for = Some(name.into());
@@ -724,8 +1058,108 @@ impl InferredPipeline {
         self
     }
 
+    /// 生成結果レポート（JSON）の出力先を指定
+    pub fn with_report_json(mut self, path: impl Into<PathBuf>) -> Self {
+        self.codegen_config.report_json = Some(path.into());
+        self
+    }
+
+    /// 変換できないマクロを C shim 経由で呼ぶコードを生成し、C ファイルを出力する
+    pub fn with_c_shim(mut self, path: impl Into<PathBuf>) -> Self {
+        self.codegen_config.c_shim = Some(path.into());
+        self
+    }
+
+    /// apidoc の NN / NULLOK / NZ 注釈を生成するマクロ関数に反映する
+    pub fn with_nullability(mut self, mode: NullabilityMode) -> Self {
+        self.codegen_config.nullability = mode;
+        self
+    }
+
+    /// 生成コードのコンパイル検査を有効化
+    ///
+    /// 生成後に rustc でコンパイルし、エラーになった関数を
+    /// `[CODEGEN_SUPPRESSED]` に降格して再生成する。
+    pub fn with_compile_check(mut self, config: CompileCheckConfig) -> Self {
+        self.codegen_config.compile_check = Some(config);
+        self
+    }
+
     /// Phase 3: コード生成
-    pub fn generate<W: Write>(self, mut writer: W) -> Result<GeneratedPipeline, PipelineError> {
+    pub fn generate<W: Write>(mut self, mut writer: W) -> Result<GeneratedPipeline, PipelineError> {
+        let mut compile_check = None;
+        let output = match self.codegen_config.compile_check.clone() {
+            Some(check) => {
+                let (buffer, output, outcome) = self.generate_compile_checked(&check)?;
+                writer.write_all(&buffer)?;
+                compile_check = Some(outcome);
+                output
+            }
+            None => self.generate_once(&mut writer)?,
+        };
+
+        if let Some(path) = &self.codegen_config.report_json {
+            output.report.write_json(path)?;
+        }
+        if let (Some(path), Some(shims)) = (&self.codegen_config.c_shim, &output.c_shim) {
+            std::fs::write(path, &shims.source)?;
+        }
+
+        // TODO: strict_rustfmt の処理
+        // 現状は CodegenDriver が rustfmt を呼び出さないため、
+        // ここで別途 rustfmt を実行する必要がある
+
+        let skipped_declarations = self.result.parse_errors.iter()
+            .map(|e| e.render(self.result.preprocessor.files()))
+            .collect();
+
+        Ok(GeneratedPipeline {
+            result: Some(self.result),
+            infer_elapsed: Some(self.infer_elapsed),
+            stats: output.stats,
+            report: output.report,
+            compile_check,
+            segments: output.segments,
+            items: output.items,
+            c_shim: output.c_shim,
+            skipped_declarations,
+            cache: None,
+        })
+    }
+
+    /// Phase 3: コード生成（定義元ヘッダー別のモジュールに分割）
+    ///
+    /// 返り値の [`ModuleOutput`] を `write_to_dir` でディレクトリに書き出す。
+    pub fn generate_modules(self) -> Result<(GeneratedPipeline, ModuleOutput), PipelineError> {
+        let mut buffer = Vec::new();
+        let generated = self.generate(&mut buffer)?;
+        let modules = generated.split_modules(&buffer);
+        Ok((generated, modules))
+    }
+
+    /// コードを 1 回生成する
+    fn generate_once<W: Write>(&self, mut writer: W) -> Result<GenerateOutput, PipelineError> {
+        let mut output = self.generate_code(&mut writer)?;
+
+        if self.codegen_config.c_shim.is_some() {
+            let shims = CShimSet::collect(&self.result, &output.report);
+            shims.emit_rust(&mut writer, self.result.preprocessor.interner())?;
+            output.c_shim = Some(shims.output(self.result.preprocessor.interner()));
+        }
+
+        // PERLVAR section: emit at end of macro_bindings.rs.
+        // Empty dict (e.g. when collect_perlvars=false) is a no-op.
+        crate::perlvar_emitter::emit_perlvar_section(
+            &mut writer,
+            &self.result.perlvar_dict,
+            self.result.perl_build_mode.is_threaded(),
+        )?;
+
+        Ok(output)
+    }
+
+    /// マクロ / inline 関数のコードを生成する（PERLVAR セクションは含まない）
+    pub(crate) fn generate_code<W: Write>(&self, mut writer: W) -> Result<GenerateOutput, PipelineError> {
         let rust_codegen_config = self.codegen_config.to_rust_codegen_config();
 
         let bindings_info = self.result.rust_decl_dict.as_ref()
@@ -743,36 +1177,129 @@ impl InferredPipeline {
 
         driver.generate(&self.result)?;
 
-        let stats = driver.stats().clone();
-
-        // PERLVAR section: emit at end of macro_bindings.rs.
-        // Empty dict (e.g. when collect_perlvars=false) is a no-op.
-        crate::perlvar_emitter::emit_perlvar_section(
-            &mut writer,
-            &self.result.perlvar_dict,
-            self.result.perl_build_mode.is_threaded(),
-        )?;
+        Ok(GenerateOutput {
+            stats: driver.stats().clone(),
+            report: driver.report().clone(),
+            segments: driver.segments().to_vec(),
+            items: driver.items().to_vec(),
+            c_shim: None,
+        })
+    }
 
-        // TODO: strict_rustfmt の処理
-        // 現状は CodegenDriver が rustfmt を呼び出さないため、
-        // ここで別途 rustfmt を実行する必要がある
+    /// コンパイルが通るまで、失敗した関数を抑止しながら再生成する
+    fn generate_compile_checked(
+        &mut self,
+        check: &CompileCheckConfig,
+    ) -> Result<(Vec<u8>, GenerateOutput, CompileCheckOutcome), PipelineError> {
+        let bindings_path = check.bindings_path.clone().ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "compile check requires a bindings.rs path",
+            )
+        })?;
+        let mut outcome = CompileCheckOutcome::default();
+
+        loop {
+            let mut buffer = Vec::new();
+            let output = self.generate_once(&mut buffer)?;
+            outcome.iterations += 1;
+
+            let diags = run_rustc_check(check, &bindings_path, &buffer)?;
+            let (items, unmapped) = map_diagnostics_to_items(&String::from_utf8_lossy(&buffer), &diags);
+            // 抑止済みなのにエラーが残るもの（抑止対象が見つからない名前）は再試行しない
+            let (new_items, stuck): (Vec<_>, Vec<_>) = items.into_iter()
+                .partition(|(name, _)| !outcome.suppressed.contains_key(name));
+
+            if new_items.is_empty() || outcome.iterations >= check.max_iterations {
+                outcome.remaining = unmapped;
+                outcome.remaining.extend(stuck.into_iter().chain(new_items).map(|(_, d)| d));
+                if let Some(path) = &check.patches_out {
+                    let added = write_compile_check_patches(path, &outcome)?;
+                    outcome.patches_written = Some((path.clone(), added));
+                }
+                return Ok((buffer, output, outcome));
+            }
 
-        Ok(GeneratedPipeline {
-            result: self.result,
-            stats,
-        })
+            outcome.rounds.push(CompileCheckRound { errors: diags.len(), suppressed: new_items.len() });
+            for (name, diag) in new_items {
+                let reason = format!("rustc: {}", diag.summary());
+                self.result.apidoc_patches.skip_codegen.insert(name.clone(), reason.clone());
+                outcome.suppressed.insert(name, reason);
+            }
+            let InferResult { infer_ctx, inline_fn_dict, apidoc_patches, preprocessor, .. } = &mut self.result;
+            infer_ctx.apply_late_suppressions(apidoc_patches, preprocessor.interner(), inline_fn_dict);
+        }
     }
 }
 
+/// 1 回分のコード生成結果
+pub(crate) struct GenerateOutput {
+    pub(crate) stats: CodegenStats,
+    pub(crate) report: CodegenReport,
+    pub(crate) segments: Vec<OutputSegment>,
+    pub(crate) items: Vec<OutputItem>,
+    /// C shim の生成結果（`with_c_shim` 指定時）
+    pub(crate) c_shim: Option<CShimOutput>,
+}
+
+/// コンパイル検査で抑止した関数を `skip_codegen` パッチとして書き出す
+///
+/// 既存のパッチファイル（手で整備した `apidoc/v$X.$Y.patches.json` など）が
+/// あれば読み込んで追記し、既存エントリはそのまま残す。ディレクトリ指定は
+/// `infer()` で対象 perl の Config のバージョンから `v$X.$Y.patches.json` に
+/// 解決済み。バージョンが分からない（Config 未使用）ならエラー。
+/// 返り値は新たに追加したエントリ数。
+fn write_compile_check_patches(path: &Path, outcome: &CompileCheckOutcome) -> Result<usize, PipelineError> {
+    if path.is_dir() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("{}: a patches directory needs the perl version from Config; \
+                     use --auto or give a file path", path.display()),
+        ).into());
+    }
+    let mut file = if path.exists() {
+        ApidocPatchFile::load_json(path)?
+    } else {
+        ApidocPatchFile::new(Some(
+            "Auto-generated skip_codegen patches: functions whose generated body \
+             fails to compile (libperl-macrogen compile check).".to_string(),
+        ))
+    };
+    let added = outcome.suppressed.iter()
+        .filter(|(name, reason)| file.add_skip_codegen(name, reason))
+        .count();
+    file.write_json(path)?;
+    Ok(added)
+}
+
 // ============================================================================
 // GeneratedPipeline
 // ============================================================================
 
 /// コード生成完了状態
 pub struct GeneratedPipeline {
-    result: InferResult,
+    /// 推論結果（キャッシュヒット時は None）
+    result: Option<InferResult>,
+    /// 推論フェーズの所要時間（キャッシュヒット時は None）
+    infer_elapsed: Option<Duration>,
     /// コード生成の統計情報
     pub stats: CodegenStats,
+    /// 名前ごとの生成結果レポート
+    pub report: CodegenReport,
+    /// コンパイル検査の結果（有効時のみ）
+    pub compile_check: Option<CompileCheckOutcome>,
+    /// 出力区間と定義元ヘッダーの対応
+    pub segments: Vec<OutputSegment>,
+    /// アイテムごとの出力範囲
+    pub items: Vec<OutputItem>,
+    /// C shim の生成結果（`with_c_shim` 指定時のみ）
+    pub c_shim: Option<CShimOutput>,
+    /// パースエラー回復で読み飛ばした宣言のエラー（表示用に描画済み）
+    ///
+    /// キャッシュヒット時も前回の生成時の内容が入る。
+    pub skipped_declarations: Vec<String>,
+    /// キャッシュの利用結果（`with_cache_dir` 指定時のみ）
+    pub cache: Option<CacheStatus>,
 }
 
 impl GeneratedPipeline {
@@ -781,15 +1308,80 @@ impl GeneratedPipeline {
         &self.stats
     }
 
+    /// 名前ごとの生成結果レポートを取得
+    pub fn report(&self) -> &CodegenReport {
+        &self.report
+    }
+
+    /// コンパイル検査の結果を取得（有効時のみ）
+    pub fn compile_check(&self) -> Option<&CompileCheckOutcome> {
+        self.compile_check.as_ref()
+    }
+
+    /// キャッシュの利用結果を取得（`with_cache_dir` 指定時のみ）
+    pub fn cache_status(&self) -> Option<&CacheStatus> {
+        self.cache.as_ref()
+    }
+
+    /// 出力区間と定義元ヘッダーの対応を取得
+    pub fn segments(&self) -> &[OutputSegment] {
+        &self.segments
+    }
+
+    /// アイテムごとの出力範囲を取得
+    pub fn items(&self) -> &[OutputItem] {
+        &self.items
+    }
+
+    /// パースエラー回復で読み飛ばした宣言のエラーを取得
+    pub fn skipped_declarations(&self) -> &[String] {
+        &self.skipped_declarations
+    }
+
+    /// `generate` に渡した writer の出力をヘッダー別モジュールに分割する
+    pub fn split_modules(&self, output: &[u8]) -> ModuleOutput {
+        ModuleOutput::split(&String::from_utf8_lossy(output), &self.segments)
+    }
+
+    /// 生成結果レポートを JSON ファイルに書き出す
+    pub fn write_report_json(&self, path: impl AsRef<Path>) -> Result<(), PipelineError> {
+        self.report.write_json(path.as_ref())?;
+        Ok(())
+    }
+
     /// InferResult への参照を取得
+    ///
+    /// # Panics
+    ///
+    /// キャッシュヒットで推論を省略した場合（`with_cache_dir` 指定時のみ起こる）。
+    /// キャッシュを使う場合は [`Self::try_result`] を使う。
     pub fn result(&self) -> &InferResult {
-        &self.result
+        self.try_result().expect("InferResult is not available on a cache hit; use try_result()")
     }
 
     /// InferResult を消費して取得
+    ///
+    /// # Panics
+    ///
+    /// [`Self::result`] と同じ。キャッシュを使う場合は [`Self::try_into_result`] を使う。
     pub fn into_result(self) -> InferResult {
+        self.try_into_result().expect("InferResult is not available on a cache hit; use try_into_result()")
+    }
+
+    /// InferResult への参照を取得（キャッシュヒット時は None）
+    pub fn try_result(&self) -> Option<&InferResult> {
+        self.result.as_ref()
+    }
+
+    /// InferResult を消費して取得（キャッシュヒット時は None）
+    pub fn try_into_result(self) -> Option<InferResult> {
         self.result
     }
+
+    /// 推論フェーズ（ヘッダーの読み込みを含む）の所要時間（キャッシュヒット時は None）
+    pub fn infer_elapsed(&self) -> Option<Duration> {
+        self.infer_elapsed
+    }
 }
 
 // ============================================================================
@@ -799,6 +1391,7 @@ impl GeneratedPipeline {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::apidoc_patches::ApidocPatchSet;
 
     #[test]
     fn test_pipeline_builder_basic() {
@@ -813,6 +1406,112 @@ mod tests {
         assert_eq!(builder.infer.bindings_path, Some(PathBuf::from("bindings.rs")));
     }
 
+    #[test]
+    fn test_write_compile_check_patches_merges() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("v5.36.patches.json");
+        let mut outcome = CompileCheckOutcome::default();
+        outcome.suppressed.insert("SvIV".to_string(), "rustc: [E0308]".to_string());
+        assert_eq!(write_compile_check_patches(&path, &outcome).unwrap(), 1);
+
+        outcome.suppressed.clear();
+        outcome.suppressed.insert("Perl_foo".to_string(), "rustc: [E0599]".to_string());
+        outcome.suppressed.insert("SvIV".to_string(), "rustc: [E0061]".to_string());
+        // 既存の SvIV は追加しない
+        assert_eq!(write_compile_check_patches(&path, &outcome).unwrap(), 1);
+
+        let set = ApidocPatchSet::load_json(&path).unwrap();
+        assert_eq!(set.skip_codegen.len(), 2);
+        assert_eq!(set.skip_reason("SvIV"), Some("rustc: [E0308]"));
+        assert_eq!(set.skip_reason("Perl_foo"), Some("rustc: [E0599]"));
+
+        // バージョンが分からないディレクトリ指定はエラー
+        assert!(write_compile_check_patches(tmp.path(), &outcome).is_err());
+    }
+
+    fn cache_hit_pipeline() -> GeneratedPipeline {
+        GeneratedPipeline {
+            result: None,
+            infer_elapsed: None,
+            stats: CodegenStats::default(),
+            report: CodegenReport::default(),
+            compile_check: None,
+            segments: Vec::new(),
+            items: Vec::new(),
+            c_shim: None,
+            skipped_declarations: Vec::new(),
+            cache: Some(CacheStatus::Hit),
+        }
+    }
+
+    #[test]
+    fn test_cache_bypassed_with_compile_check() {
+        let pipeline = PipelineBuilder::new("test.h").with_cache_dir("cache").build().unwrap();
+        assert!(pipeline.cache().is_some());
+        let pipeline = PipelineBuilder::new("test.h")
+            .with_cache_dir("cache")
+            .with_compile_check(CompileCheckConfig::new())
+            .build()
+            .unwrap();
+        assert!(pipeline.cache().is_none());
+    }
+
+    #[test]
+    fn test_cache_key_covers_apidoc_patches() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let apidoc = tmp.path().join("v5.40.json");
+        let patches = tmp.path().join("common.patches.json");
+        std::fs::write(&apidoc, "{}").unwrap();
+        std::fs::write(&patches, "{}").unwrap();
+
+        let pipeline = PipelineBuilder::new("test.h")
+            .with_apidoc(&apidoc)
+            .with_cache_dir(tmp.path().join("cache"))
+            .build()
+            .unwrap();
+        let cache = pipeline.cache().unwrap();
+        cache.store(&pipeline.cache_options(), std::iter::empty(), &CachedOutput::default()).unwrap();
+        assert!(cache.lookup(&pipeline.cache_options()).is_ok());
+
+        std::fs::write(&patches, r#"{"skip_codegen": ["Foo"]}"#).unwrap();
+        assert_eq!(
+            cache.lookup(&pipeline.cache_options()).unwrap_err(),
+            crate::gen_cache::CacheMiss::FileChanged(patches),
+        );
+    }
+
+    #[test]
+    fn test_cache_key_covers_perl_build_mode() {
+        use crate::perl_config::PerlBuildMode;
+
+        let tmp = tempfile::TempDir::new().unwrap();
+        let build = |mode| {
+            PipelineBuilder::new("test.h")
+                .with_perl_build_mode(mode)
+                .with_cache_dir(tmp.path().join("cache"))
+                .build()
+                .unwrap()
+        };
+        let threaded = build(PerlBuildMode::Threaded);
+        let cache = threaded.cache().unwrap();
+        cache.store(&threaded.cache_options(), std::iter::empty(), &CachedOutput::default()).unwrap();
+        assert!(cache.lookup(&threaded.cache_options()).is_ok());
+
+        let non_threaded = build(PerlBuildMode::NonThreaded);
+        assert_eq!(
+            cache.lookup(&non_threaded.cache_options()).unwrap_err(),
+            crate::gen_cache::CacheMiss::OptionChanged("infer".to_string()),
+        );
+    }
+
+    #[test]
+    fn test_generated_pipeline_cache_hit_accessors() {
+        let generated = cache_hit_pipeline();
+        assert!(generated.try_result().is_none());
+        assert!(generated.infer_elapsed().is_none());
+        assert!(generated.try_into_result().is_none());
+    }
+
     #[test]
     fn test_pipeline_builder_codegen_defaults() {
         let builder = PipelineBuilder::new("test.h")
@@ -834,6 +1533,18 @@ mod tests {
         assert!(pp_config.debug_pp);
     }
 
+    #[test]
+    fn test_header_overlay_input() {
+        let mut preprocessed = PipelineBuilder::new("wrapper.h")
+            .with_header_overlay("wrapper.h", "#include \"defs.h\"\nint x = VALUE;\n")
+            .with_header_overlay("defs.h", "#define VALUE 42\n")
+            .build().unwrap()
+            .preprocess().unwrap();
+
+        let tokens = preprocessed.preprocessor_mut().collect_tokens().unwrap();
+        assert!(tokens.iter().any(|t| matches!(t.kind, crate::TokenKind::IntLit(42))));
+    }
+
     #[test]
     fn test_codegen_config_default() {
         let config = CodegenConfig::default();
@@ -841,5 +1552,7 @@ mod tests {
         assert!(!config.strict_rustfmt);
         assert!(config.emit_inline_fns);
         assert!(config.emit_macros);
+        assert!(config.emit_object_macros);
+        assert!(config.report_json.is_none());
     }
 }
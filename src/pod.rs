@@ -0,0 +1,423 @@
+//! perlapi の POD を rustdoc（Markdown）に変換する
+//!
+//! `=for apidoc` に続く POD 本文（[`ApidocEntry::doc`]）を、生成する関数の
+//! ドキュメントコメントにするための最小限の変換:
+//!
+//! - 通常段落: `C<>` → `` `code` ``、`B<>` → `**bold**`、`I<>` / `F<>` → `*italic*`、
+//!   `L<>` はリンクの表示文字列、`E<>` は文字、`S<>` は中身、`X<>` / `Z<>` は削除
+//! - 字下げされた段落（verbatim）: `c` のコードブロック
+//! - `=over` / `=item` / `=back`: 箇条書き
+//! - `=head1`〜`=head4`: 見出し
+//! - `=begin` 〜 `=end` の範囲と、その他のコマンド段落は捨てる
+
+use crate::apidoc::ApidocEntry;
+
+/// apidoc エントリの rustdoc 本文（C のシグネチャと POD 本文）
+///
+/// どちらもなければ None。
+pub fn apidoc_rustdoc(entry: &ApidocEntry) -> Option<String> {
+    let mut parts = Vec::new();
+    if let Some(sig) = entry.c_signature() {
+        parts.push(format!("```c\n{}\n```", sig));
+    }
+    if let Some(doc) = &entry.doc {
+        let markdown = pod_to_markdown(doc);
+        if !markdown.is_empty() {
+            parts.push(markdown);
+        }
+    }
+    (!parts.is_empty()).then(|| parts.join("\n\n"))
+}
+
+/// POD を Markdown に変換する
+pub fn pod_to_markdown(pod: &str) -> String {
+    let mut blocks: Vec<String> = Vec::new();
+    // 連続する verbatim 段落（1 つのコードブロックにまとめる）
+    let mut verbatim: Vec<String> = Vec::new();
+    let mut list_depth = 0usize;
+    // `=item *` / `=item 1.` の直後の段落に付ける箇条書きの記号
+    let mut item_marker: Option<String> = None;
+    let mut in_begin = false;
+
+    for para in paragraphs(pod) {
+        let first = para[0];
+        if in_begin {
+            in_begin = !first.starts_with("=end");
+            continue;
+        }
+        if first.starts_with(' ') || first.starts_with('\t') {
+            verbatim.push(para.join("\n"));
+            continue;
+        }
+        if !verbatim.is_empty() {
+            blocks.push(code_block(&verbatim.join("\n\n")));
+            verbatim.clear();
+        }
+
+        if first.starts_with('=') {
+            let text = para.join(" ");
+            let command = text[1..].trim_start();
+            let name = command.split_whitespace().next().unwrap_or("");
+            let arg = command.strip_prefix(name).unwrap_or(command).trim();
+            match name {
+                "head1" | "head2" | "head3" | "head4" => {
+                    let level = usize::from(name.as_bytes()[4] - b'0');
+                    blocks.push(format!("{} {}", "#".repeat(level), format_inline(arg)));
+                }
+                "over" => list_depth += 1,
+                "back" => {
+                    list_depth = list_depth.saturating_sub(1);
+                    item_marker = None;
+                }
+                "item" => {
+                    let number = arg.trim_end_matches('.');
+                    if arg.is_empty() || arg == "*" || arg == "-" {
+                        item_marker = Some("- ".to_string());
+                    } else if !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit()) {
+                        item_marker = Some(format!("{}. ", number));
+                    } else {
+                        blocks.push(format!("- {}", format_inline(arg)));
+                        item_marker = None;
+                    }
+                }
+                "begin" => in_begin = true,
+                // =pod / =cut / =for / =encoding など
+                _ => {}
+            }
+            continue;
+        }
+
+        let text = format_inline(&para.join("\n"));
+        match item_marker.take() {
+            Some(marker) => blocks.push(format!("{}{}", marker, text)),
+            // 箇条書きの項目に続く段落は字下げして項目に含める
+            None if list_depth > 0 => {
+                let indented: Vec<String> = text.lines().map(|l| format!("  {}", l)).collect();
+                blocks.push(indented.join("\n"));
+            }
+            None => blocks.push(text),
+        }
+    }
+    if !verbatim.is_empty() {
+        blocks.push(code_block(&verbatim.join("\n\n")));
+    }
+    blocks.join("\n\n")
+}
+
+/// 空行で区切られた段落（各段落は 1 行以上）
+fn paragraphs(pod: &str) -> Vec<Vec<&str>> {
+    let mut paras = Vec::new();
+    let mut current = Vec::new();
+    for line in pod.lines() {
+        if line.trim().is_empty() {
+            if !current.is_empty() {
+                paras.push(std::mem::take(&mut current));
+            }
+        } else {
+            current.push(line.trim_end());
+        }
+    }
+    if !current.is_empty() {
+        paras.push(current);
+    }
+    paras
+}
+
+/// verbatim 段落を共通の字下げを除いたコードブロックにする
+fn code_block(text: &str) -> String {
+    let indent = text.lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| l.len() - l.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    let body: Vec<&str> = text.lines()
+        .map(|l| if l.len() >= indent { &l[indent..] } else { l.trim_start() })
+        .collect();
+    format!("```c\n{}\n```", body.join("\n"))
+}
+
+/// 書式コード（`C<...>` など）の構文木
+enum Node {
+    Text(String),
+    Code(char, Vec<Node>),
+}
+
+/// 通常段落の書式コードを Markdown にする
+fn format_inline(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let (nodes, _) = parse_nodes(&chars, 0, None);
+    let mut out = String::new();
+    render(&nodes, &mut out);
+    out
+}
+
+/// `close` 個の `>` で閉じるまで（None なら末尾まで）をパースする
+///
+/// `C<< ... >>` のように `<` が複数のときは、内側の前後に空白が必要。
+fn parse_nodes(chars: &[char], mut pos: usize, close: Option<usize>) -> (Vec<Node>, usize) {
+    let mut nodes = Vec::new();
+    let mut text = String::new();
+    while pos < chars.len() {
+        match close {
+            Some(1) if chars[pos] == '>' => {
+                if !text.is_empty() {
+                    nodes.push(Node::Text(text));
+                }
+                return (nodes, pos + 1);
+            }
+            Some(n) if n > 1
+                && chars[pos].is_whitespace()
+                && chars.len() >= pos + 1 + n
+                && chars[pos + 1..pos + 1 + n].iter().all(|c| *c == '>') =>
+            {
+                if !text.is_empty() {
+                    nodes.push(Node::Text(text));
+                }
+                return (nodes, pos + 1 + n);
+            }
+            _ => {}
+        }
+
+        let c = chars[pos];
+        let starts_code = "BCEFILSXZ".contains(c)
+            && chars.get(pos + 1) == Some(&'<')
+            && (pos == 0 || !chars[pos - 1].is_alphanumeric());
+        if starts_code {
+            let open = chars[pos + 1..].iter().take_while(|c| **c == '<').count();
+            let start = if open == 1 {
+                Some(pos + 2)
+            } else {
+                chars.get(pos + 1 + open)
+                    .filter(|c| c.is_whitespace())
+                    .map(|_| pos + 2 + open)
+            };
+            if let Some(start) = start {
+                if !text.is_empty() {
+                    nodes.push(Node::Text(std::mem::take(&mut text)));
+                }
+                let (inner, next) = parse_nodes(chars, start, Some(open));
+                nodes.push(Node::Code(c, inner));
+                pos = next;
+                continue;
+            }
+        }
+        text.push(c);
+        pos += 1;
+    }
+    if !text.is_empty() {
+        nodes.push(Node::Text(text));
+    }
+    (nodes, pos)
+}
+
+fn render(nodes: &[Node], out: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(&escape_markdown(text)),
+            Node::Code('C', inner) => out.push_str(&code_span(plain_text(inner).trim())),
+            Node::Code('B', inner) => {
+                out.push_str("**");
+                render(inner, out);
+                out.push_str("**");
+            }
+            Node::Code('I' | 'F', inner) => {
+                out.push('*');
+                render(inner, out);
+                out.push('*');
+            }
+            Node::Code('L', inner) => {
+                let text = link_text(&plain_text(inner));
+                if is_identifier(&text) {
+                    out.push_str(&code_span(&text));
+                } else {
+                    out.push_str(&escape_markdown(&text));
+                }
+            }
+            Node::Code('E', inner) => out.push_str(&escape_markdown(&entity(&plain_text(inner)))),
+            Node::Code('X' | 'Z', _) => {}
+            Node::Code(_, inner) => render(inner, out),
+        }
+    }
+}
+
+/// 書式を除いた文字列（`C<>` の中身用）
+fn plain_text(nodes: &[Node]) -> String {
+    let mut out = String::new();
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Code('L', inner) => out.push_str(&link_text(&plain_text(inner))),
+            Node::Code('E', inner) => out.push_str(&entity(&plain_text(inner))),
+            Node::Code('X' | 'Z', _) => {}
+            Node::Code(_, inner) => out.push_str(&plain_text(inner)),
+        }
+    }
+    out
+}
+
+/// `L<>` の表示文字列
+///
+/// `L<text|target>` → text、`L</sec>` → sec、`L<page/sec>` → `"sec" in page`、
+/// `L<page>` → page。
+fn link_text(link: &str) -> String {
+    if let Some((text, _)) = link.split_once('|') {
+        return text.to_string();
+    }
+    if link.contains("://") {
+        return link.to_string();
+    }
+    match link.split_once('/') {
+        Some(("", section)) => section.trim_matches('"').to_string(),
+        Some((page, section)) => format!("\"{}\" in {}", section.trim_matches('"'), page),
+        None => link.to_string(),
+    }
+}
+
+/// `E<>` の実体参照
+fn entity(name: &str) -> String {
+    let c = match name {
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "verbar" => Some('|'),
+        "sol" => Some('/'),
+        "quot" => Some('"'),
+        "amp" => Some('&'),
+        "apos" => Some('\''),
+        _ => name.strip_prefix("0x")
+            .map_or_else(|| name.parse().ok(), |hex| u32::from_str_radix(hex, 16).ok())
+            .and_then(char::from_u32),
+    };
+    c.map_or_else(|| name.to_string(), String::from)
+}
+
+fn is_identifier(s: &str) -> bool {
+    s.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// コードスパン（中身のバッククォートより長いバッククォートで囲む）
+fn code_span(code: &str) -> String {
+    if code.is_empty() {
+        return String::new();
+    }
+    let code = code.replace('\n', " ");
+    let mut longest = 0;
+    let mut run = 0;
+    for c in code.chars() {
+        run = if c == '`' { run + 1 } else { 0 };
+        longest = longest.max(run);
+    }
+    let fence = "`".repeat(longest + 1);
+    if longest > 0 {
+        format!("{} {} {}", fence, code, fence)
+    } else {
+        format!("{}{}{}", fence, code, fence)
+    }
+}
+
+/// Markdown として解釈される文字をエスケープする
+///
+/// `_` は単語の途中では強調にならないので、単語の境界にあるものだけ。
+/// 行頭の `#` / `>` / `-` / `+` は見出し・引用・箇条書きになるのでエスケープする。
+fn escape_markdown(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    for (i, &c) in chars.iter().enumerate() {
+        let line_start = chars[..i].iter().rev()
+            .take_while(|c| **c != '\n')
+            .all(|c| c.is_whitespace());
+        let escape = match c {
+            '\\' | '`' | '*' | '[' | ']' | '<' => true,
+            '_' => {
+                let word = |c: Option<&char>| c.is_some_and(|c| c.is_alphanumeric());
+                let inside_word = i > 0 && word(chars.get(i - 1)) && word(chars.get(i + 1));
+                !inside_word
+            }
+            '#' | '>' | '-' | '+' => line_start,
+            _ => false,
+        };
+        if escape {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_inline() {
+        assert_eq!(format_inline("Returns C<SvCUR(sv)> in B<bytes>."), "Returns `SvCUR(sv)` in **bytes**.");
+        assert_eq!(format_inline("See C<L</SvLEN>> and L</SvEND>."), "See `SvLEN` and `SvEND`.");
+        assert_eq!(format_inline("C<< $a->{b} >> is I<not> E<lt>0"), "`$a->{b}` is *not* \\<0");
+        assert_eq!(format_inline("L<perlguts/\"Autoloading\">"), "\"Autoloading\" in perlguts");
+        assert_eq!(format_inline("an SV* or sv_setsv"), "an SV\\* or sv_setsv");
+        assert_eq!(format_inline("C<`>"), "`` ` ``");
+    }
+
+    #[test]
+    fn test_pod_to_markdown() {
+        let pod = "\
+These are like C<L</SvPV>>.
+
+    char *s = SvPV_force(sv, len);
+    s[0] = 'x';
+
+The forms are:
+
+=over
+
+=item C<SvPV_force_nolen>
+
+Ignores C<len>.
+
+=item *
+
+second
+
+=back
+
+=begin html
+
+<b>ignored</b>
+
+=end html";
+        let expected = "\
+These are like `SvPV`.
+
+```c
+char *s = SvPV_force(sv, len);
+s[0] = 'x';
+```
+
+The forms are:
+
+- `SvPV_force_nolen`
+
+  Ignores `len`.
+
+- second";
+        assert_eq!(pod_to_markdown(pod), expected);
+    }
+
+    #[test]
+    fn test_pod_command_with_space_after_equals() {
+        // コマンド名の前の空白や非 ASCII の名前で引数を切り損ねない
+        assert_eq!(pod_to_markdown("= é\n\nbody"), "body");
+        assert_eq!(pod_to_markdown("=  head2 Name here"), "## Name here");
+        assert_eq!(pod_to_markdown("=item\n\nx"), "- x");
+    }
+
+    #[test]
+    fn test_apidoc_rustdoc() {
+        let mut entry = ApidocEntry::parse_apidoc_line("=for apidoc Am|STRLEN|SvCUR|SV* sv").unwrap();
+        entry.doc = Some("Returns the length, in bytes.".to_string());
+        assert_eq!(apidoc_rustdoc(&entry).as_deref(),
+            Some("```c\nSTRLEN SvCUR(SV* sv)\n```\n\nReturns the length, in bytes."));
+        let entry = ApidocEntry::parse_apidoc_line("=for apidoc av_pop").unwrap();
+        assert!(apidoc_rustdoc(&entry).is_none());
+    }
+}
@@ -1,12 +1,267 @@
 //! プリプロセッサ条件式の評価
 //!
 //! #if / #elif ディレクティブの条件式を評価する。
+//! オブジェクトマクロの定数畳み込み（[`ConstFoldEnv`]）にも再利用する。
+//!
+//! `__has_builtin` / `__has_attribute` / `__has_feature` は [`FeatureTables`] を
+//! 参照して評価する。`__has_include` は生のヘッダー名とインクルードパス探索が
+//! 必要なため、Preprocessor が条件式の収集時に 0 / 1 に置き換える。
+
+use std::collections::{BTreeSet, HashSet};
+
+use serde::Serialize;
 
 use crate::error::{CompileError, PPError};
 use crate::intern::{InternedStr, StringInterner};
 use crate::macro_def::MacroTable;
 use crate::source::SourceLocation;
-use crate::token::{Token, TokenKind};
+use crate::token::{IntLitType, Token, TokenKind};
+
+/// 定数畳み込み用の評価環境
+///
+/// `#if` の評価とは異なり、未知の識別子は 0 ではなくエラーとし、
+/// 値とともに C の型（LP64）を追跡する。`(U32)` のような整数型への
+/// キャストはその型への変換として評価する。
+pub struct ConstFoldEnv<'a> {
+    /// 識別子の値を解決する（bindings.rs の定数など）。型は値から int / long とする
+    pub resolve_ident: &'a dyn Fn(InternedStr) -> Option<i64>,
+    /// キャストとして扱う typedef 名
+    pub type_names: &'a HashSet<InternedStr>,
+    /// typedef 名の整数型を解決する（整数型でなければ None で、そのキャストはエラー）
+    pub resolve_type: &'a dyn Fn(InternedStr) -> Option<CIntType>,
+}
+
+/// 定数畳み込みで追跡する C の整数型（LP64）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub struct CIntType {
+    /// 符号付きか
+    pub signed: bool,
+    /// ビット幅（8 / 16 / 32 / 64）
+    pub bits: u32,
+}
+
+impl CIntType {
+    pub const INT: Self = Self { signed: true, bits: 32 };
+    pub const UINT: Self = Self { signed: false, bits: 32 };
+    pub const LONG: Self = Self { signed: true, bits: 64 };
+    pub const ULONG: Self = Self { signed: false, bits: 64 };
+    /// `_Bool`（変換時は 0 / 1 にする）
+    pub const BOOL: Self = Self { signed: false, bits: 1 };
+
+    /// Rust の整数型名（`i32`、`c_ulong`、`usize` など）から作る
+    pub fn from_rust_name(name: &str) -> Option<Self> {
+        let (signed, bits) = match name {
+            "i8" | "c_schar" | "c_char" => (true, 8),
+            "u8" | "c_uchar" => (false, 8),
+            "i16" | "c_short" => (true, 16),
+            "u16" | "c_ushort" => (false, 16),
+            "i32" | "c_int" => (true, 32),
+            "u32" | "c_uint" => (false, 32),
+            "i64" | "isize" | "c_long" | "c_longlong" => (true, 64),
+            "u64" | "usize" | "c_ulong" | "c_ulonglong" => (false, 64),
+            _ => return None,
+        };
+        Some(Self { signed, bits })
+    }
+
+    /// C 標準ライブラリの整数 typedef（bindings.rs にないことが多い）
+    pub fn from_std_typedef(name: &str) -> Option<Self> {
+        let (signed, bits) = match name {
+            "int8_t" => (true, 8),
+            "uint8_t" => (false, 8),
+            "int16_t" => (true, 16),
+            "uint16_t" => (false, 16),
+            "int32_t" => (true, 32),
+            "uint32_t" => (false, 32),
+            "int64_t" | "intmax_t" | "intptr_t" | "ptrdiff_t" | "ssize_t" | "off_t" => (true, 64),
+            "uint64_t" | "uintmax_t" | "uintptr_t" | "size_t" => (false, 64),
+            _ => return None,
+        };
+        Some(Self { signed, bits })
+    }
+
+    /// 対応する Rust の型名（`std::ffi` の C 型）
+    pub fn rust_name(self) -> &'static str {
+        match (self.signed, self.bits) {
+            (true, 8) => "c_schar",
+            (false, 8) => "c_uchar",
+            (true, 16) => "c_short",
+            (false, 16) => "c_ushort",
+            (true, 32) => "c_int",
+            (false, 32) => "c_uint",
+            (true, _) => "c_long",
+            (false, _) => "c_ulong",
+        }
+    }
+
+    /// 整数リテラルの型
+    fn of_literal(ty: IntLitType) -> Self {
+        Self { signed: ty.is_signed(), bits: ty.bits() }
+    }
+
+    /// 識別子の値（enum 定数など）の型。int に収まらなければ long
+    fn of_value(value: i64) -> Self {
+        if i32::try_from(value).is_ok() { Self::INT } else { Self::LONG }
+    }
+
+    /// 整数拡張（int より狭い型は int になる）
+    fn promote(self) -> Self {
+        if self.bits < 32 { Self::INT } else { self }
+    }
+
+    /// 通常の算術型変換（C11 6.3.1.8）で 2 つの型の共通の型を求める
+    fn common(a: Self, b: Self) -> Self {
+        let (a, b) = (a.promote(), b.promote());
+        if a == b {
+            a
+        } else if a.signed == b.signed {
+            if a.bits >= b.bits { a } else { b }
+        } else {
+            let (signed, unsigned) = if a.signed { (a, b) } else { (b, a) };
+            if unsigned.bits >= signed.bits {
+                unsigned
+            } else {
+                // 符号付きの型が符号なしの型の値をすべて表せる
+                signed
+            }
+        }
+    }
+
+    /// 値をこの型に変換する（幅への切り詰めと符号拡張）
+    pub fn convert(self, value: i128) -> i128 {
+        if self == Self::BOOL {
+            return (value != 0) as i128;
+        }
+        let modulus = 1i128 << self.bits;
+        let v = value.rem_euclid(modulus);
+        if self.signed && v >= modulus / 2 { v - modulus } else { v }
+    }
+
+    /// 値をこの型で表せるか
+    pub fn contains(self, value: i128) -> bool {
+        self.convert(value) == value
+    }
+}
+
+/// 定数畳み込みの結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct FoldedConst {
+    /// C での値（`ty` の範囲内）
+    pub value: i128,
+    /// C での型
+    pub ty: CIntType,
+}
+
+/// 型付きの中間値
+///
+/// `#if` の評価ではすべて `long`（intmax_t）として扱う。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Value {
+    value: i128,
+    ty: CIntType,
+}
+
+impl Value {
+    fn new(value: i128, ty: CIntType) -> Self {
+        Self { value: ty.convert(value), ty }
+    }
+
+    fn is_true(self) -> bool {
+        self.value != 0
+    }
+}
+
+/// `__has_*` 形式の問い合わせ演算子
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HasOperator {
+    /// `__has_builtin`
+    Builtin,
+    /// `__has_attribute` / `__has_c_attribute` / `__has_cpp_attribute`
+    Attribute,
+    /// `__has_feature` / `__has_extension`
+    Feature,
+    /// `__has_include`
+    Include,
+    /// `__has_include_next`
+    IncludeNext,
+}
+
+impl HasOperator {
+    /// 演算子名と種類の対応
+    pub const ALL: &'static [(&'static str, HasOperator)] = &[
+        ("__has_builtin", HasOperator::Builtin),
+        ("__has_attribute", HasOperator::Attribute),
+        ("__has_c_attribute", HasOperator::Attribute),
+        ("__has_cpp_attribute", HasOperator::Attribute),
+        ("__has_feature", HasOperator::Feature),
+        ("__has_extension", HasOperator::Feature),
+        ("__has_include", HasOperator::Include),
+        ("__has_include_next", HasOperator::IncludeNext),
+    ];
+
+    /// 名前から演算子を引く
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().find(|(n, _)| *n == name).map(|(_, op)| *op)
+    }
+}
+
+/// `__has_builtin` / `__has_attribute` / `__has_feature` が真を返す名前の表
+///
+/// 実際のコンパイラを持たないため、対応している扱いにする名前を設定で与える。
+/// 表にない名前は 0（そのコンパイラにない機能）になり、ヘッダーは
+/// 互換用のフォールバック側を通る。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FeatureTables {
+    /// `__has_builtin` で真になる名前
+    pub builtins: BTreeSet<String>,
+    /// `__has_attribute` で真になる属性名（`__nonnull__` は `nonnull` として引く）
+    pub attributes: BTreeSet<String>,
+    /// `__has_feature` / `__has_extension` で真になる名前
+    pub features: BTreeSet<String>,
+}
+
+impl FeatureTables {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `__has_builtin` で真になる名前を追加
+    pub fn with_builtin(mut self, name: impl Into<String>) -> Self {
+        self.builtins.insert(name.into());
+        self
+    }
+
+    /// `__has_attribute` で真になる属性名を追加
+    pub fn with_attribute(mut self, name: impl Into<String>) -> Self {
+        self.attributes.insert(normalize_attribute(&name.into()).to_string());
+        self
+    }
+
+    /// `__has_feature` で真になる名前を追加
+    pub fn with_feature(mut self, name: impl Into<String>) -> Self {
+        self.features.insert(name.into());
+        self
+    }
+
+    /// 問い合わせに答える（`__has_include` 系は常に false）
+    pub fn query(&self, op: HasOperator, name: &str) -> bool {
+        match op {
+            HasOperator::Builtin => self.builtins.contains(name),
+            HasOperator::Attribute => self.attributes.contains(normalize_attribute(name)),
+            HasOperator::Feature => self.features.contains(name),
+            HasOperator::Include | HasOperator::IncludeNext => false,
+        }
+    }
+}
+
+/// `gnu::__nonnull__` → `nonnull`
+fn normalize_attribute(name: &str) -> &str {
+    let name = name.rsplit("::").next().unwrap_or(name);
+    name.strip_prefix("__")
+        .and_then(|n| n.strip_suffix("__"))
+        .filter(|n| !n.is_empty())
+        .unwrap_or(name)
+}
 
 /// プリプロセッサ式評価器
 pub struct PPExprEvaluator<'a> {
@@ -17,6 +272,12 @@ pub struct PPExprEvaluator<'a> {
     loc: SourceLocation,
     /// "defined" キーワードのインターン済み文字列
     defined_id: Option<InternedStr>,
+    /// `__has_*` 演算子のインターン済み文字列
+    has_ids: Vec<(InternedStr, HasOperator)>,
+    /// `__has_builtin` などが参照する表（None ならすべて 0）
+    features: Option<&'a FeatureTables>,
+    /// 定数畳み込みモードの環境（None なら通常の #if 評価）
+    const_env: Option<ConstFoldEnv<'a>>,
 }
 
 impl<'a> PPExprEvaluator<'a> {
@@ -29,6 +290,9 @@ impl<'a> PPExprEvaluator<'a> {
     ) -> Self {
         // "defined" を検索
         let defined_id = interner.lookup("defined");
+        let has_ids = HasOperator::ALL.iter()
+            .filter_map(|(name, op)| interner.lookup(name).map(|id| (id, *op)))
+            .collect();
 
         Self {
             tokens,
@@ -37,13 +301,129 @@ impl<'a> PPExprEvaluator<'a> {
             macros,
             loc,
             defined_id,
+            has_ids,
+            features: None,
+            const_env: None,
         }
     }
 
+    /// `__has_builtin` などが参照する表を指定
+    pub fn with_feature_tables(mut self, features: &'a FeatureTables) -> Self {
+        self.features = Some(features);
+        self
+    }
+
+    fn has_operator(&self, id: InternedStr) -> Option<HasOperator> {
+        self.has_ids.iter().find(|(i, _)| *i == id).map(|(_, op)| *op)
+    }
+
+    /// 定数畳み込みモードに切り替える
+    pub fn with_const_env(mut self, env: ConstFoldEnv<'a>) -> Self {
+        self.const_env = Some(env);
+        self
+    }
+
     /// 条件式を評価
     pub fn evaluate(&mut self) -> Result<i64, CompileError> {
         let result = self.expr()?;
-        Ok(result)
+        Ok(result.value as i64)
+    }
+
+    /// 定数式として評価
+    ///
+    /// トークン列全体が 1 つの定数式であることを要求する。
+    /// 末尾に余分なトークンが残る場合はエラー。
+    pub fn evaluate_constant(&mut self) -> Result<FoldedConst, CompileError> {
+        let result = self.expr()?;
+        if self.pos < self.tokens.len() {
+            return Err(self.error("trailing tokens in constant expression"));
+        }
+        Ok(FoldedConst { value: result.value, ty: result.ty })
+    }
+
+    /// 定数畳み込みモードか
+    fn is_const_mode(&self) -> bool {
+        self.const_env.is_some()
+    }
+
+    /// 比較・論理演算の結果（`int` の 0 / 1）
+    fn truth(&self, b: bool) -> Value {
+        Value::new(b as i128, self.result_type(CIntType::INT))
+    }
+
+    /// `#if` の評価では型を区別せずすべて `long` として扱う
+    fn result_type(&self, ty: CIntType) -> CIntType {
+        if self.is_const_mode() { ty } else { CIntType::LONG }
+    }
+
+    /// 二項演算のオペランドを共通の型に変換する
+    fn convert_operands(&self, left: Value, right: Value) -> (i128, i128, CIntType) {
+        let ty = self.result_type(CIntType::common(left.ty, right.ty));
+        (ty.convert(left.value), ty.convert(right.value), ty)
+    }
+
+    /// 現在位置 `(` から始まるキャストのトークン数と変換先の型を返す
+    ///
+    /// 整数型キーワードと typedef 名のみを型名として認める。
+    /// ポインタ・`void`・整数以外の typedef へのキャストは定数にならないのでエラー。
+    fn cast_len(&self) -> Result<Option<(usize, CIntType)>, CompileError> {
+        let env = match &self.const_env {
+            Some(env) => env,
+            None => return Ok(None),
+        };
+        let mut i = self.pos + 1;
+        let mut has_type = false;
+        let mut is_pointer = false;
+        let mut is_void = false;
+        let mut is_bool = false;
+        let mut unsigned = false;
+        let mut bits = None;
+        let mut typedef: Option<Option<CIntType>> = None;
+        while let Some(tok) = self.tokens.get(i) {
+            match &tok.kind {
+                TokenKind::KwChar => bits = Some(8),
+                TokenKind::KwShort => bits = Some(16),
+                TokenKind::KwInt => {
+                    bits.get_or_insert(32);
+                }
+                TokenKind::KwLong => bits = Some(64),
+                TokenKind::KwSigned | TokenKind::KwSigned2 => {}
+                TokenKind::KwUnsigned => unsigned = true,
+                TokenKind::KwBool => is_bool = true,
+                TokenKind::KwVoid => is_void = true,
+                TokenKind::KwConst | TokenKind::KwConst2 | TokenKind::KwConst3
+                | TokenKind::KwVolatile | TokenKind::KwVolatile2 | TokenKind::KwVolatile3 => {
+                    i += 1;
+                    continue;
+                }
+                TokenKind::Ident(id) if env.type_names.contains(id) && !is_pointer && !has_type => {
+                    typedef = Some((env.resolve_type)(*id));
+                }
+                TokenKind::Star if has_type => is_pointer = true,
+                TokenKind::RParen if has_type => {
+                    if is_pointer {
+                        return Err(self.error("pointer cast is not a constant"));
+                    }
+                    let ty = if is_void {
+                        None
+                    } else if is_bool {
+                        Some(CIntType::BOOL)
+                    } else if let Some(ty) = typedef {
+                        ty
+                    } else {
+                        Some(CIntType { signed: !unsigned, bits: bits.unwrap_or(32) })
+                    };
+                    let Some(ty) = ty else {
+                        return Err(self.error("cast to a non-integer type is not a constant"));
+                    };
+                    return Ok(Some((i + 1 - self.pos, ty)));
+                }
+                _ => return Ok(None),
+            }
+            has_type = true;
+            i += 1;
+        }
+        Ok(None)
     }
 
     /// 現在のトークンを取得
@@ -72,7 +452,7 @@ impl<'a> PPExprEvaluator<'a> {
     }
 
     /// 条件式 (ternary)
-    fn expr(&mut self) -> Result<i64, CompileError> {
+    fn expr(&mut self) -> Result<Value, CompileError> {
         let cond = self.logical_or()?;
 
         if matches!(self.current_kind(), Some(TokenKind::Question)) {
@@ -83,255 +463,266 @@ impl<'a> PPExprEvaluator<'a> {
             }
             self.advance();
             let else_val = self.expr()?;
-            Ok(if cond != 0 { then_val } else { else_val })
+            let ty = self.result_type(CIntType::common(then_val.ty, else_val.ty));
+            let chosen = if cond.is_true() { then_val } else { else_val };
+            Ok(Value::new(chosen.value, ty))
         } else {
             Ok(cond)
         }
     }
 
     /// 論理OR
-    fn logical_or(&mut self) -> Result<i64, CompileError> {
+    fn logical_or(&mut self) -> Result<Value, CompileError> {
         let mut left = self.logical_and()?;
 
         while matches!(self.current_kind(), Some(TokenKind::PipePipe)) {
             self.advance();
             let right = self.logical_and()?;
-            left = if left != 0 || right != 0 { 1 } else { 0 };
+            left = self.truth(left.is_true() || right.is_true());
         }
 
         Ok(left)
     }
 
     /// 論理AND
-    fn logical_and(&mut self) -> Result<i64, CompileError> {
+    fn logical_and(&mut self) -> Result<Value, CompileError> {
         let mut left = self.bitwise_or()?;
 
         while matches!(self.current_kind(), Some(TokenKind::AmpAmp)) {
             self.advance();
             let right = self.bitwise_or()?;
-            left = if left != 0 && right != 0 { 1 } else { 0 };
+            left = self.truth(left.is_true() && right.is_true());
         }
 
         Ok(left)
     }
 
     /// ビットOR
-    fn bitwise_or(&mut self) -> Result<i64, CompileError> {
+    fn bitwise_or(&mut self) -> Result<Value, CompileError> {
         let mut left = self.bitwise_xor()?;
 
         while matches!(self.current_kind(), Some(TokenKind::Pipe)) {
             self.advance();
             let right = self.bitwise_xor()?;
-            left |= right;
+            let (l, r, ty) = self.convert_operands(left, right);
+            left = Value::new(l | r, ty);
         }
 
         Ok(left)
     }
 
     /// ビットXOR
-    fn bitwise_xor(&mut self) -> Result<i64, CompileError> {
+    fn bitwise_xor(&mut self) -> Result<Value, CompileError> {
         let mut left = self.bitwise_and()?;
 
         while matches!(self.current_kind(), Some(TokenKind::Caret)) {
             self.advance();
             let right = self.bitwise_and()?;
-            left ^= right;
+            let (l, r, ty) = self.convert_operands(left, right);
+            left = Value::new(l ^ r, ty);
         }
 
         Ok(left)
     }
 
     /// ビットAND
-    fn bitwise_and(&mut self) -> Result<i64, CompileError> {
+    fn bitwise_and(&mut self) -> Result<Value, CompileError> {
         let mut left = self.equality()?;
 
         while matches!(self.current_kind(), Some(TokenKind::Amp)) {
             self.advance();
             let right = self.equality()?;
-            left &= right;
+            let (l, r, ty) = self.convert_operands(left, right);
+            left = Value::new(l & r, ty);
         }
 
         Ok(left)
     }
 
     /// 等価比較
-    fn equality(&mut self) -> Result<i64, CompileError> {
+    fn equality(&mut self) -> Result<Value, CompileError> {
         let mut left = self.relational()?;
 
         loop {
-            match self.current_kind() {
-                Some(TokenKind::EqEq) => {
-                    self.advance();
-                    let right = self.relational()?;
-                    left = if left == right { 1 } else { 0 };
-                }
-                Some(TokenKind::BangEq) => {
-                    self.advance();
-                    let right = self.relational()?;
-                    left = if left != right { 1 } else { 0 };
-                }
+            let eq = match self.current_kind() {
+                Some(TokenKind::EqEq) => true,
+                Some(TokenKind::BangEq) => false,
                 _ => break,
-            }
+            };
+            self.advance();
+            let right = self.relational()?;
+            let (l, r, _) = self.convert_operands(left, right);
+            left = self.truth((l == r) == eq);
         }
 
         Ok(left)
     }
 
     /// 関係比較
-    fn relational(&mut self) -> Result<i64, CompileError> {
+    fn relational(&mut self) -> Result<Value, CompileError> {
         let mut left = self.shift()?;
 
         loop {
-            match self.current_kind() {
-                Some(TokenKind::Lt) => {
-                    self.advance();
-                    let right = self.shift()?;
-                    left = if left < right { 1 } else { 0 };
-                }
-                Some(TokenKind::Gt) => {
-                    self.advance();
-                    let right = self.shift()?;
-                    left = if left > right { 1 } else { 0 };
-                }
-                Some(TokenKind::LtEq) => {
-                    self.advance();
-                    let right = self.shift()?;
-                    left = if left <= right { 1 } else { 0 };
-                }
-                Some(TokenKind::GtEq) => {
-                    self.advance();
-                    let right = self.shift()?;
-                    left = if left >= right { 1 } else { 0 };
-                }
+            let op = match self.current_kind() {
+                Some(TokenKind::Lt) => i128::lt,
+                Some(TokenKind::Gt) => i128::gt,
+                Some(TokenKind::LtEq) => i128::le,
+                Some(TokenKind::GtEq) => i128::ge,
                 _ => break,
-            }
+            };
+            self.advance();
+            let right = self.shift()?;
+            let (l, r, _) = self.convert_operands(left, right);
+            left = self.truth(op(&l, &r));
         }
 
         Ok(left)
     }
 
-    /// シフト演算
-    fn shift(&mut self) -> Result<i64, CompileError> {
+    /// シフト演算（結果は左オペランドを整数拡張した型）
+    fn shift(&mut self) -> Result<Value, CompileError> {
         let mut left = self.additive()?;
 
         loop {
-            match self.current_kind() {
-                Some(TokenKind::LtLt) => {
-                    self.advance();
-                    let right = self.additive()?;
-                    left <<= right;
-                }
-                Some(TokenKind::GtGt) => {
-                    self.advance();
-                    let right = self.additive()?;
-                    left >>= right;
-                }
+            let is_left = match self.current_kind() {
+                Some(TokenKind::LtLt) => true,
+                Some(TokenKind::GtGt) => false,
                 _ => break,
+            };
+            self.advance();
+            let right = self.additive()?;
+            let ty = self.result_type(left.ty.promote());
+            if right.value < 0 || right.value >= i128::from(ty.bits) {
+                return Err(self.error("shift count out of range"));
             }
+            let value = ty.convert(left.value);
+            left = if is_left {
+                Value::new(value << right.value, ty)
+            } else {
+                Value::new(value >> right.value, ty)
+            };
         }
 
         Ok(left)
     }
 
     /// 加減算
-    fn additive(&mut self) -> Result<i64, CompileError> {
+    fn additive(&mut self) -> Result<Value, CompileError> {
         let mut left = self.multiplicative()?;
 
         loop {
-            match self.current_kind() {
-                Some(TokenKind::Plus) => {
-                    self.advance();
-                    let right = self.multiplicative()?;
-                    left = left.wrapping_add(right);
-                }
-                Some(TokenKind::Minus) => {
-                    self.advance();
-                    let right = self.multiplicative()?;
-                    left = left.wrapping_sub(right);
-                }
+            let is_add = match self.current_kind() {
+                Some(TokenKind::Plus) => true,
+                Some(TokenKind::Minus) => false,
                 _ => break,
-            }
+            };
+            self.advance();
+            let right = self.multiplicative()?;
+            let (l, r, ty) = self.convert_operands(left, right);
+            left = Value::new(if is_add { l + r } else { l - r }, ty);
         }
 
         Ok(left)
     }
 
     /// 乗除算
-    fn multiplicative(&mut self) -> Result<i64, CompileError> {
+    fn multiplicative(&mut self) -> Result<Value, CompileError> {
         let mut left = self.unary()?;
 
         loop {
-            match self.current_kind() {
-                Some(TokenKind::Star) => {
-                    self.advance();
-                    let right = self.unary()?;
-                    left = left.wrapping_mul(right);
-                }
-                Some(TokenKind::Slash) => {
-                    self.advance();
-                    let right = self.unary()?;
-                    if right == 0 {
-                        return Err(self.error("division by zero"));
-                    }
-                    left /= right;
-                }
-                Some(TokenKind::Percent) => {
-                    self.advance();
-                    let right = self.unary()?;
-                    if right == 0 {
-                        return Err(self.error("modulo by zero"));
-                    }
-                    left %= right;
-                }
+            let op = match self.current_kind() {
+                Some(TokenKind::Star) => TokenKind::Star,
+                Some(TokenKind::Slash) => TokenKind::Slash,
+                Some(TokenKind::Percent) => TokenKind::Percent,
                 _ => break,
-            }
+            };
+            self.advance();
+            let right = self.unary()?;
+            let (l, r, ty) = self.convert_operands(left, right);
+            let value = match op {
+                TokenKind::Star => l.wrapping_mul(r),
+                _ if r == 0 => {
+                    let what = if op == TokenKind::Slash { "division" } else { "modulo" };
+                    return Err(self.error(&format!("{} by zero", what)));
+                }
+                // i128 の除算は C と同じく 0 方向への切り捨て
+                TokenKind::Slash => l / r,
+                _ => l % r,
+            };
+            left = Value::new(value, ty);
         }
 
         Ok(left)
     }
 
     /// 単項演算
-    fn unary(&mut self) -> Result<i64, CompileError> {
+    fn unary(&mut self) -> Result<Value, CompileError> {
         match self.current_kind() {
             Some(TokenKind::Plus) => {
                 self.advance();
-                self.unary()
+                let val = self.unary()?;
+                Ok(Value::new(val.value, self.result_type(val.ty.promote())))
             }
             Some(TokenKind::Minus) => {
                 self.advance();
-                Ok(-self.unary()?)
+                let val = self.unary()?;
+                Ok(Value::new(-val.value, self.result_type(val.ty.promote())))
             }
             Some(TokenKind::Bang) => {
                 self.advance();
                 let val = self.unary()?;
-                Ok(if val == 0 { 1 } else { 0 })
+                Ok(self.truth(!val.is_true()))
             }
             Some(TokenKind::Tilde) => {
                 self.advance();
-                Ok(!self.unary()?)
+                let val = self.unary()?;
+                Ok(Value::new(!val.value, self.result_type(val.ty.promote())))
+            }
+            Some(TokenKind::LParen) => {
+                if let Some((n, ty)) = self.cast_len()? {
+                    self.pos += n;
+                    let val = self.unary()?;
+                    return Ok(Value::new(val.value, ty));
+                }
+                self.primary()
             }
             _ => self.primary(),
         }
     }
 
+    /// 整数リテラルの値（字句解析で決めた C の型があればそれに従う）
+    fn literal(&self, value: u64, unsigned: bool) -> Value {
+        if !self.is_const_mode() {
+            return Value::new(value as i64 as i128, CIntType::LONG);
+        }
+        let lit_ty = self.current()
+            .and_then(|t| t.int_type)
+            .unwrap_or_else(|| IntLitType::from_literal(value, true, unsigned, false));
+        Value::new(value as i128, CIntType::of_literal(lit_ty))
+    }
+
     /// 一次式
-    fn primary(&mut self) -> Result<i64, CompileError> {
+    fn primary(&mut self) -> Result<Value, CompileError> {
         match self.current_kind().cloned() {
             Some(TokenKind::IntLit(n)) => {
+                let val = self.literal(n as u64, false);
                 self.advance();
-                Ok(n)
+                Ok(val)
             }
             Some(TokenKind::UIntLit(n)) => {
+                let val = self.literal(n, true);
                 self.advance();
-                Ok(n as i64)
+                Ok(val)
             }
             Some(TokenKind::CharLit(c)) => {
                 self.advance();
-                Ok(c as i64)
+                // 文字定数は int。char は符号付き
+                let value = if self.is_const_mode() { c as i8 as i128 } else { c as i128 };
+                Ok(Value::new(value, self.result_type(CIntType::INT)))
             }
             Some(TokenKind::WideCharLit(c)) => {
                 self.advance();
-                Ok(c as i64)
+                Ok(Value::new(c as i128, self.result_type(CIntType::INT)))
             }
             Some(TokenKind::LParen) => {
                 self.advance();
@@ -342,16 +733,31 @@ impl<'a> PPExprEvaluator<'a> {
                 self.advance();
                 Ok(val)
             }
+            Some(TokenKind::Ident(id)) if self.const_env.is_some() => {
+                let value = self.const_env.as_ref().and_then(|env| (env.resolve_ident)(id));
+                match value {
+                    Some(v) => {
+                        self.advance();
+                        Ok(Value::new(v as i128, CIntType::of_value(v)))
+                    }
+                    None => Err(self.error(&format!(
+                        "'{}' is not a constant", self.interner.get(id)))),
+                }
+            }
             Some(TokenKind::Ident(id)) => {
                 // defined演算子のチェック
                 if Some(id) == self.defined_id {
                     self.advance();
-                    return self.parse_defined();
+                    return self.parse_defined().map(|v| Value::new(v as i128, CIntType::LONG));
+                }
+                if let Some(op) = self.has_operator(id) {
+                    self.advance();
+                    return self.parse_has_operator(op).map(|v| Value::new(v as i128, CIntType::LONG));
                 }
 
                 // 未定義の識別子は0として扱う（C標準）
                 self.advance();
-                Ok(0)
+                Ok(Value::new(0, CIntType::LONG))
             }
             Some(_) => Err(self.error("unexpected token in preprocessor expression")),
             None => Err(self.error("unexpected end of expression")),
@@ -387,11 +793,46 @@ impl<'a> PPExprEvaluator<'a> {
         }
 
         // nameがNoneの場合、マクロは定義されていない
+        // `__has_*` 演算子は `defined(__has_include)` のように検査されるので定義済み扱い
         Ok(match name {
-            Some(n) if self.macros.is_defined(n) => 1,
+            Some(n) if self.macros.is_defined(n) || self.has_operator(n).is_some() => 1,
             _ => 0,
         })
     }
+
+    /// `__has_builtin(name)` などをパース
+    ///
+    /// 引数はマクロ展開せずにトークンの綴りを連結する（`gnu::nonnull` など）。
+    fn parse_has_operator(&mut self, op: HasOperator) -> Result<i64, CompileError> {
+        if matches!(op, HasOperator::Include | HasOperator::IncludeNext) {
+            return Err(self.error("__has_include must be resolved by the preprocessor"));
+        }
+        if !matches!(self.current_kind(), Some(TokenKind::LParen)) {
+            return Err(self.error("expected '(' after __has_* operator"));
+        }
+        self.advance();
+
+        let mut name = String::new();
+        let mut depth = 0usize;
+        loop {
+            match self.current_kind() {
+                Some(TokenKind::RParen) if depth == 0 => break,
+                Some(kind) => {
+                    match kind {
+                        TokenKind::LParen => depth += 1,
+                        TokenKind::RParen => depth -= 1,
+                        _ => {}
+                    }
+                    name.push_str(&kind.format(self.interner));
+                }
+                None => return Err(self.error("expected ')' after __has_* operand")),
+            }
+            self.advance();
+        }
+        self.advance();
+
+        Ok(self.features.is_some_and(|f| f.query(op, &name)) as i64)
+    }
 }
 
 #[cfg(test)]
@@ -572,6 +1013,87 @@ mod tests {
         assert_eq!(eval_tokens(&tokens, &interner, &macros), 0);
     }
 
+    /// C ソースの式をプリプロセッサで字句解析し、定数畳み込みモードで評価する
+    ///
+    /// `IV` / `UV` / `U32` / `NV` を typedef とし、`KNOWN` を 4 に解決する。
+    fn fold(src: &str) -> Result<FoldedConst, CompileError> {
+        let mut pp = crate::preprocessor::Preprocessor::new(crate::preprocessor::PPConfig::default());
+        pp.add_source_str("test.h", src);
+        let mut tokens = Vec::new();
+        loop {
+            let token = pp.next_token().unwrap();
+            if matches!(token.kind, TokenKind::Eof) {
+                break;
+            }
+            if !matches!(token.kind, TokenKind::Newline) {
+                tokens.push(token);
+            }
+        }
+        let interner = pp.interner();
+        let lookup = |name: &str| interner.lookup(name);
+        let type_names: HashSet<InternedStr> = ["IV", "UV", "U32", "NV"].into_iter().filter_map(lookup).collect();
+        let resolve = |id: InternedStr| (Some(id) == lookup("KNOWN")).then_some(4);
+        let resolve_type = |id: InternedStr| match interner.get(id) {
+            "IV" => Some(CIntType::LONG),
+            "UV" => Some(CIntType::ULONG),
+            "U32" => Some(CIntType::UINT),
+            _ => None,
+        };
+        let env = ConstFoldEnv { resolve_ident: &resolve, type_names: &type_names, resolve_type: &resolve_type };
+        let loc = SourceLocation::new(FileId::default(), 1, 1);
+        PPExprEvaluator::new(&tokens, interner, pp.macros(), loc)
+            .with_const_env(env)
+            .evaluate_constant()
+    }
+
+    #[test]
+    fn test_const_fold_cast_and_ident() {
+        assert_eq!(fold("((U32)1 << KNOWN)").unwrap(), FoldedConst { value: 16, ty: CIntType::UINT });
+
+        // 未知の識別子・ポインタキャスト・整数以外へのキャストはエラー
+        assert!(fold("UNKNOWN").is_err());
+        assert!(fold("(U32 *)0").is_err());
+        assert!(fold("(NV)1").is_err());
+        assert!(fold("(void)0").is_err());
+    }
+
+    #[test]
+    fn test_const_fold_c_semantics() {
+        // PERL_QUAD_MAX: ((IV)(UV_MAX >> 1))
+        assert_eq!(
+            fold("((IV)((~(UV)0) >> 1))").unwrap(),
+            FoldedConst { value: i64::MAX as i128, ty: CIntType::LONG },
+        );
+        // PERL_QUAD_MIN: (-PERL_QUAD_MAX - ((3 & -1) == 3))
+        assert_eq!(fold("(-((IV)((~(UV)0) >> 1)) - ((3 & -1) == 3))").unwrap().value, i64::MIN as i128);
+        // SvREFCNT_IMMORTAL: 符号なしの除算
+        assert_eq!(
+            fold("((~(U32)0)/2)").unwrap(),
+            FoldedConst { value: i32::MAX as i128, ty: CIntType::UINT },
+        );
+        // MAX_LEGAL_CP: ((UV)IV_MAX)
+        assert_eq!(fold("((UV)((IV)((~(UV)0) >> 1)))").unwrap().ty, CIntType::ULONG);
+
+        // int に収まらない 10 進リテラルは long、16 進リテラルは unsigned int
+        assert_eq!(
+            fold("67768036191676799").unwrap(),
+            FoldedConst { value: 67768036191676799, ty: CIntType::LONG },
+        );
+        assert_eq!(
+            fold("(0xFFFF0000|0x00000080)").unwrap(),
+            FoldedConst { value: 0xFFFF0080, ty: CIntType::UINT },
+        );
+        assert_eq!(fold("1L << 40").unwrap(), FoldedConst { value: 1 << 40, ty: CIntType::LONG });
+        assert_eq!(fold("4294967295UL").unwrap().ty, CIntType::ULONG);
+
+        // 通常の算術型変換: -1 は unsigned int に変換されてから比較される
+        assert_eq!(fold("-1 < 0u").unwrap().value, 0);
+        assert_eq!(fold("-1 < 0").unwrap().value, 1);
+
+        // int の幅を超えるシフトはエラー
+        assert!(fold("1 << 40").is_err());
+    }
+
     #[test]
     fn test_parentheses() {
         let interner = StringInterner::new();
@@ -589,4 +1111,39 @@ mod tests {
         ];
         assert_eq!(eval_tokens(&tokens, &interner, &macros), 20);
     }
+
+    #[test]
+    fn test_has_builtin_and_attribute() {
+        let mut interner = StringInterner::new();
+        let macros = MacroTable::new();
+        let has_builtin = interner.intern("__has_builtin");
+        let has_attribute = interner.intern("__has_attribute");
+        let defined = interner.intern("defined");
+        let expect = interner.intern("__builtin_expect");
+        let unknown = interner.intern("__builtin_unknown");
+        let nonnull = interner.intern("__nonnull__");
+        let features = FeatureTables::new()
+            .with_builtin("__builtin_expect")
+            .with_attribute("nonnull");
+        let loc = SourceLocation::default();
+        let eval = |tokens: &[Token]| {
+            PPExprEvaluator::new(tokens, &interner, &macros, loc.clone())
+                .with_feature_tables(&features)
+                .evaluate()
+                .unwrap()
+        };
+        let call = |op, arg| vec![
+            make_token(TokenKind::Ident(op)),
+            make_token(TokenKind::LParen),
+            make_token(TokenKind::Ident(arg)),
+            make_token(TokenKind::RParen),
+        ];
+
+        assert_eq!(eval(&call(has_builtin, expect)), 1);
+        assert_eq!(eval(&call(has_builtin, unknown)), 0);
+        // `__nonnull__` は `nonnull` として引く
+        assert_eq!(eval(&call(has_attribute, nonnull)), 1);
+        // `defined(__has_builtin)` は真
+        assert_eq!(eval(&call(defined, has_builtin)), 1);
+    }
 }
@@ -8,11 +8,21 @@ use std::io::{self, Write};
 use crate::ast::{AssertKind, AssignOp, BinOp, BlockItem, CompoundStmt, Declaration, DeclSpecs, DerivedDecl, Expr, ExprKind, ForInit, FunctionDef, Initializer, ParamDecl, Stmt, TypeSpec};
 
 use crate::intern::InternedStr;
+use crate::codegen_report::{CodegenReport, ReportEntry, ReportItemKind, ReportLocation, ReportOutcome, ReportSignature};
 use crate::enum_dict::EnumDict;
+use crate::goto_lower::{can_restructure_gotos, plan_goto_regions, GotoKind, GotoRegion};
 use crate::infer_api::InferResult;
 use crate::intern::StringInterner;
 use crate::macro_infer::{MacroInferContext, MacroInferInfo, MacroParam, ParseResult};
+use crate::module_split::{module_name_for_header, OutputItem, OutputSegment};
 use crate::rust_decl::RustDeclDict;
+use crate::source::SourceLocation;
+use crate::apidoc::{ApidocDict, Nullability};
+use crate::macro_purity::{Purity, PurityChecker};
+use crate::pp_expr::CIntType;
+use crate::nullability::{nonnull_type, wrap_nonnull_arg, NullabilityDict, NullabilityMode};
+use crate::pod::apidoc_rustdoc;
+use crate::stack_macro::StackMacroDict;
 use crate::syn_codegen::normalize_parens;
 use crate::unified_type::UnifiedType;
 use crate::sexp::SexpPrinter;
@@ -219,7 +229,7 @@ impl KnownSymbols {
     }
 
     /// シンボル名が既知かどうかチェック
-    fn contains(&self, name: &str) -> bool {
+    pub(crate) fn contains(&self, name: &str) -> bool {
         self.names.contains(name)
     }
 
@@ -247,7 +257,7 @@ const RUST_KEYWORDS: &[&str] = &[
 ///
 /// - Rust の予約語は r# を付ける
 /// - C のプリプロセッサマクロは Rust の同等品に変換
-fn escape_rust_keyword(name: &str) -> String {
+pub(crate) fn escape_rust_keyword(name: &str) -> String {
     match name {
         // C プリプロセッサマクロ → Rust マクロ
         "__FILE__" => "file!()".to_string(),
@@ -481,7 +491,7 @@ fn is_type_repr_pointer(ty: &crate::type_repr::TypeRepr) -> bool {
 /// 自家生成マクロの param に対する全制約のうち、Tier が最も高い
 /// (=数値が小さい) 非 void TypeRepr のクローンを返す。
 /// `param.expr_id()` および `param_to_exprs` から得た全 ExprId を走査する。
-fn best_constraint_for_macro_param(
+pub(crate) fn best_constraint_for_macro_param(
     info: &MacroInferInfo,
     param: &MacroParam,
 ) -> Option<crate::type_repr::TypeRepr> {
@@ -776,6 +786,70 @@ fn normalize_integer_type(ty: &str) -> Option<&'static str> {
     }
 }
 
+/// `(x) * 2` や `(x) & MASK` が型パラメータ x へのキャストと誤認識されているか
+///
+/// パース時の型パラメータ検出は `(param)` の後に単項演算子が続くと
+/// キャストとして解釈するため、`BIT_DIGITS(N)` の `(N)*146` などが
+/// `Cast { N, Deref(146) }` になる。キャスト対象が単項 `*`/`&`/`-`/`+`
+/// であれば二項演算の誤認識とみなす。
+fn has_ambiguous_type_param_cast(info: &MacroInferInfo) -> bool {
+    let ParseResult::Expression(expr) = &info.parse_result else {
+        return false;
+    };
+    let type_params: HashSet<InternedStr> = info.generic_type_params.keys()
+        .filter_map(|idx| usize::try_from(*idx).ok())
+        .filter_map(|idx| info.params.get(idx).map(|p| p.name))
+        .collect();
+    fn walk(expr: &Expr, type_params: &HashSet<InternedStr>, found: &mut bool) {
+        if let ExprKind::Cast { type_name, expr: inner } = &expr.kind {
+            let casts_to_param = type_name.specs.type_specs.iter().any(|ts| {
+                matches!(ts, TypeSpec::TypedefName(n) if type_params.contains(n))
+            });
+            if casts_to_param && matches!(inner.kind,
+                ExprKind::Deref(_) | ExprKind::AddrOf(_)
+                | ExprKind::UnaryMinus(_) | ExprKind::UnaryPlus(_))
+            {
+                *found = true;
+            }
+        }
+        crate::macro_infer::walk_expr_children(expr, &mut |e| walk(e, type_params, found));
+    }
+    let mut found = false;
+    walk(expr, &type_params, &mut found);
+    found
+}
+
+/// 式が代入・インクリメント・デクリメントを含むか
+fn expr_has_side_effects(expr: &Expr) -> bool {
+    if matches!(expr.kind,
+        ExprKind::Assign { .. }
+        | ExprKind::PreInc(_) | ExprKind::PreDec(_)
+        | ExprKind::PostInc(_) | ExprKind::PostDec(_))
+    {
+        return true;
+    }
+    let mut found = false;
+    crate::macro_infer::walk_expr_children(expr, &mut |e| found |= expr_has_side_effects(e));
+    found
+}
+
+/// 整数型名を (符号付きか, バイト幅) に解決する
+///
+/// `normalize_integer_type` で解決できない名前は bindings.rs の
+/// type alias を辿る（`Size_t` → `usize` など）。
+fn resolve_integer_type(ty: &str, dict: Option<&RustDeclDict>) -> Option<(bool, u8)> {
+    let mut cur = ty.trim().to_string();
+    // alias の循環に備えて深さを制限
+    for _ in 0..8 {
+        if let Some(rank) = integer_type_rank(&cur) {
+            return Some(rank);
+        }
+        let alias = dict?.lookup_type(&cur)?;
+        cur = alias.uty.to_rust_string();
+    }
+    None
+}
+
 /// 64-bit プラットフォームで i64/isize, u64/usize を同一視して比較
 fn integer_types_compatible(a: &str, b: &str) -> bool {
     if a == b { return true; }
@@ -1225,10 +1299,54 @@ fn collect_mut_params_from_stmt(stmt: &Stmt, params: &HashSet<InternedStr>, resu
             }
             collect_mut_params_from_stmt(body, params, result);
         }
+        Stmt::Switch { expr, body, .. } => {
+            collect_mut_params_from_expr(expr, params, result);
+            collect_mut_params_from_stmt(body, params, result);
+        }
+        Stmt::Label { stmt, .. } | Stmt::Case { stmt, .. } | Stmt::Default { stmt, .. } => {
+            collect_mut_params_from_stmt(stmt, params, result);
+        }
         _ => {}
     }
 }
 
+/// ブロック要素列から宣言されたローカル変数名を再帰的に収集
+fn collect_local_decl_names(items: &[BlockItem], names: &mut HashSet<InternedStr>) {
+    fn from_decl(decl: &Declaration, names: &mut HashSet<InternedStr>) {
+        names.extend(decl.declarators.iter().filter_map(|d| d.declarator.name));
+    }
+    fn from_stmt(stmt: &Stmt, names: &mut HashSet<InternedStr>) {
+        match stmt {
+            Stmt::Compound(c) => collect_local_decl_names(&c.items, names),
+            Stmt::If { then_stmt, else_stmt, .. } => {
+                from_stmt(then_stmt, names);
+                if let Some(es) = else_stmt {
+                    from_stmt(es, names);
+                }
+            }
+            Stmt::For { init, body, .. } => {
+                if let Some(ForInit::Decl(d)) = init {
+                    from_decl(d, names);
+                }
+                from_stmt(body, names);
+            }
+            Stmt::While { body, .. } | Stmt::DoWhile { body, .. } | Stmt::Switch { body, .. } => {
+                from_stmt(body, names);
+            }
+            Stmt::Label { stmt, .. } | Stmt::Case { stmt, .. } | Stmt::Default { stmt, .. } => {
+                from_stmt(stmt, names);
+            }
+            _ => {}
+        }
+    }
+    for item in items {
+        match item {
+            BlockItem::Decl(d) => from_decl(d, names),
+            BlockItem::Stmt(s) => from_stmt(s, names),
+        }
+    }
+}
+
 /// 構造体フィールド名 → 型の逆引きマップを構築
 /// 全構造体で同名フィールドの型が一致する場合のみ含む
 /// 型文字列が関数ポインタ形式（裸 fn または `Option<...fn(...)>`）かを判定する。
@@ -1293,6 +1411,8 @@ pub struct CodegenConfig {
     pub emit_inline_fns: bool,
     /// マクロを出力するか
     pub emit_macros: bool,
+    /// オブジェクトマクロ（定数）を出力するか
+    pub emit_object_macros: bool,
     /// コメントにソース位置を含めるか
     pub include_source_location: bool,
     /// ヘッダーに出力する use 文
@@ -1302,6 +1422,12 @@ pub struct CodegenConfig {
     pub dump_ast_for: Option<String>,
     /// 型推論ダンプ対象関数名（デバッグ用）
     pub dump_types_for: Option<String>,
+    /// apidoc の NN / NULLOK / NZ 注釈の使い方
+    pub nullability: NullabilityMode,
+    /// perlapi の POD 本文と C のシグネチャを rustdoc として出力するか
+    pub pod_docs: bool,
+    /// 生成物を置くモジュールのクレートルートからのパス（`macro_rules!` 用、None ならクレートルート）
+    pub macro_path: Option<String>,
 }
 
 impl Default for CodegenConfig {
@@ -1309,10 +1435,14 @@ impl Default for CodegenConfig {
         Self {
             emit_inline_fns: true,
             emit_macros: true,
+            emit_object_macros: true,
             include_source_location: true,
             use_statements: Vec::new(),
             dump_ast_for: None,
             dump_types_for: None,
+            nullability: NullabilityMode::Off,
+            pod_docs: false,
+            macro_path: None,
         }
     }
 }
@@ -1347,7 +1477,8 @@ impl CodegenConfig {
 }
 
 /// 生成ステータス
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum GenerateStatus {
     /// 正常生成
     Success,
@@ -1413,7 +1544,7 @@ fn stmt_contains_top_level_break(stmt: &Stmt) -> bool {
 }
 
 /// コード生成統計
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct CodegenStats {
     /// 正常生成されたマクロ数
     pub macros_success: usize,
@@ -1427,8 +1558,19 @@ pub struct CodegenStats {
     pub macros_cascade_unavailable: usize,
     /// ジェネリクス未対応マクロ数
     pub macros_generic_unsupported: usize,
+    /// `macro_rules!` として生成された type/cast 引数マクロ数
+    pub macros_generic_rules: usize,
+    /// `macro_rules!` として生成された呼び出し側ローカルを使うマクロ数
+    #[serde(default)]
+    pub macros_stack_rules: usize,
     /// 未解決シンボルを含むマクロ数
     pub macros_unresolved_names: usize,
+    /// 正常生成されたマクロ（関数にしたオブジェクトマクロを含む）のうち安全な `pub fn` として出力された数
+    #[serde(default)]
+    pub macros_safe_fn: usize,
+    /// 正常生成されたマクロ（関数にしたオブジェクトマクロを含む）のうち `pub const fn` として出力された数
+    #[serde(default)]
+    pub macros_const_fn: usize,
     /// 正常生成された inline 関数数
     pub inline_fns_success: usize,
     /// 型推論失敗 inline 関数数
@@ -1439,6 +1581,10 @@ pub struct CodegenStats {
     pub inline_fns_cascade_unavailable: usize,
     /// goto を含む inline 関数数
     pub inline_fns_contains_goto: usize,
+    /// `pub const` として出力されたオブジェクトマクロ数
+    pub object_macros_const: usize,
+    /// 引数なし関数として出力されたオブジェクトマクロ数
+    pub object_macros_fn: usize,
 }
 
 /// 一つの関数の生成結果
@@ -1454,6 +1600,10 @@ pub struct GeneratedCode {
     pub used_libc_fns: HashSet<String>,
     /// codegen で検出されたエラー（コメントアウトの理由）
     pub codegen_errors: Vec<String>,
+    /// 関数の安全性（`pub unsafe fn` / `pub fn` / `pub const fn`）
+    pub purity: Purity,
+    /// 出力した関数 / 定数のシグネチャ（`macro_rules!` などでは None）
+    pub signature: Option<ReportSignature>,
 }
 
 impl GeneratedCode {
@@ -1530,6 +1680,51 @@ pub struct RustCodegen<'a> {
     bool_return_macros: HashSet<InternedStr>,
     /// 対象 perl が threaded build か（false なら my_perl 注入を抑止）
     perl_threaded: bool,
+    /// `macro_rules!` として生成中の場合のメタ変数化するパラメータ名の集合
+    macro_rules_params: HashSet<InternedStr>,
+    /// 呼び出し側ローカルを使うマクロ（呼び出しを `NAME!(...)` として出力する）
+    stack_macros: Option<&'a StackMacroDict>,
+    /// 呼び出しを本体の式に展開するマクロ（型の決まらない補助マクロ）
+    inline_macros: HashSet<InternedStr>,
+    /// 生成済みマクロの安全性（純粋な呼び出し先の判定に使う）
+    macro_purity: Option<&'a HashMap<InternedStr, Purity>>,
+    /// 生成中の関数の安全性
+    purity: Purity,
+    /// 出力した関数 / 定数のシグネチャ
+    signature: Option<ReportSignature>,
+    /// apidoc の NN / NULLOK / NZ 注釈（引数の検査・NonNull 化に使う）
+    nullability: Option<&'a NullabilityDict>,
+    /// rustdoc に使う apidoc（POD 本文と C のシグネチャ）
+    apidoc_docs: Option<&'a ApidocDict>,
+    /// 出力中のラベル付きブロック / ループ: ラベル名 → goto の向き
+    goto_labels: HashMap<InternedStr, GotoKind>,
+    /// 生成物を置くモジュールのクレートルートからのパス（None ならクレートルート）
+    macro_path: Option<&'a str>,
+}
+
+/// `macro_rules!` 生成時にメタ変数 `$name` へ置き換えるための仮の識別子接頭辞
+///
+/// syn で扱える識別子として一旦出力し、最後に文字列置換で `$` にする。
+const MACRO_RULES_PARAM_PREFIX: &str = "__macrogen_param_";
+
+/// 書き込んだバイト数を数える Write ラッパー
+///
+/// CodegenDriver が各アイテムの出力開始位置を記録するために使う。
+struct OffsetWriter<W: Write> {
+    inner: W,
+    offset: usize,
+}
+
+impl<W: Write> Write for OffsetWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.offset += n;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
 }
 
 /// コード生成全体を管理する構造体
@@ -1537,7 +1732,7 @@ pub struct RustCodegen<'a> {
 /// 実際の出力先（Write）を保持し、生成の成功/失敗に応じて
 /// 適切な形式で出力する。
 pub struct CodegenDriver<'a, W: Write> {
-    writer: W,
+    writer: OffsetWriter<W>,
     interner: &'a StringInterner,
     /// Enum バリアント辞書（パターンマッチ用）
     enum_dict: &'a EnumDict,
@@ -1547,6 +1742,14 @@ pub struct CodegenDriver<'a, W: Write> {
     bindings_info: BindingsInfo,
     config: CodegenConfig,
     stats: CodegenStats,
+    /// 名前ごとの生成結果レポート
+    report: CodegenReport,
+    /// 出力区間と定義元ヘッダーの対応（モジュール分割用）
+    segments: Vec<OutputSegment>,
+    /// アイテムごとの出力範囲（threaded / non-threaded の突き合わせ用）
+    items: Vec<OutputItem>,
+    /// items の最後のアイテムが出力中か（end が未確定）
+    item_open: bool,
     /// 生成されたコード全体で使用された libc 関数名
     used_libc_fns: HashSet<String>,
     /// 正常生成された inline 関数名（クロスドメインカスケード検出用）
@@ -1557,8 +1760,14 @@ pub struct CodegenDriver<'a, W: Write> {
     const_pointer_params: HashMap<InternedStr, HashSet<usize>>,
     /// bool を返すと判定されたマクロの集合
     bool_return_macros: HashSet<InternedStr>,
+    /// 安全な関数（`pub fn` / `pub const fn`）として生成したマクロ
+    macro_purity: HashMap<InternedStr, Purity>,
+    /// apidoc の NN / NULLOK / NZ 注釈（`config.nullability` が Off なら空）
+    nullability: NullabilityDict,
     /// 対象 perl が threaded build か（false なら my_perl 注入を抑止）
     perl_threaded: bool,
+    /// 呼び出し側ローカル（sp, mark, ...）を使うマクロ
+    stack_macros: StackMacroDict,
 }
 
 impl<'a> RustCodegen<'a> {
@@ -1602,6 +1811,16 @@ impl<'a> RustCodegen<'a> {
             // デフォルトは threaded（後方互換）。Driver 経由で
             // with_perl_threaded() で上書きされる。
             perl_threaded: true,
+            macro_rules_params: HashSet::new(),
+            stack_macros: None,
+            inline_macros: HashSet::new(),
+            macro_purity: None,
+            purity: Purity::Unsafe,
+            signature: None,
+            nullability: None,
+            apidoc_docs: None,
+            goto_labels: HashMap::new(),
+            macro_path: None,
         }
     }
 
@@ -1628,8 +1847,11 @@ impl<'a> RustCodegen<'a> {
         // bindings.rs に無い自動生成 struct (body_details 等) の
         // フィールド型を field_type_map にマージし、Member 式の型推論で
         // 利用できるようにする。bindings.rs 側で既に登録されている
-        // フィールドは優先する。
-        for (_name, def) in dict.iter_struct_defs() {
+        // フィールドは優先する。同名フィールドは先に見た構造体の型になるので、
+        // 実行ごとに変わらないよう構造体名順に走査する。
+        let mut defs: Vec<_> = dict.iter_struct_defs().collect();
+        defs.sort_by_key(|(name, _)| self.interner.get(**name));
+        for (_name, def) in defs {
             for m in &def.members {
                 let member_name = self.interner.get(m.name).to_string();
                 if self.field_type_map.contains_key(&member_name) {
@@ -1649,6 +1871,58 @@ impl<'a> RustCodegen<'a> {
         self
     }
 
+    /// スタックマクロ辞書を設定
+    pub fn with_stack_macros(mut self, dict: &'a StackMacroDict) -> Self {
+        self.stack_macros = Some(dict);
+        self
+    }
+
+    /// 呼び出しを本体の式に展開するマクロを設定
+    ///
+    /// `EXTEND` が使う `_EXTEND_NEEDS_GROW` のように、パラメータの型が決まらず
+    /// 単独では生成できない式マクロを、スタックマクロの本体に直接展開する。
+    pub fn with_inline_macros(mut self, names: HashSet<InternedStr>) -> Self {
+        self.inline_macros = names;
+        self
+    }
+
+    /// 生成済みマクロの安全性の表を設定
+    ///
+    /// 設定すると、純粋なマクロを `pub fn` / `pub const fn` として生成する。
+    pub fn with_macro_purity(mut self, purity: &'a HashMap<InternedStr, Purity>) -> Self {
+        self.macro_purity = Some(purity);
+        self
+    }
+
+    /// apidoc の NN / NULLOK / NZ 注釈を設定
+    pub fn with_nullability(mut self, dict: &'a NullabilityDict) -> Self {
+        self.nullability = Some(dict);
+        self
+    }
+
+    /// rustdoc に使う apidoc を設定（None なら 1 行の説明のみ）
+    pub fn with_apidoc_docs(mut self, apidoc: Option<&'a ApidocDict>) -> Self {
+        self.apidoc_docs = apidoc;
+        self
+    }
+
+    /// `macro_rules!` の本体が名前を解決するモジュールのパスを設定
+    ///
+    /// `#[macro_export]` したマクロは呼び出し側のクレート・モジュールで展開
+    /// されるので、本体は `use $crate::<path>::*;` で生成物を参照する。
+    pub fn with_macro_path(mut self, path: Option<&'a str>) -> Self {
+        self.macro_path = path;
+        self
+    }
+
+    /// `macro_rules!` 本体の先頭に置く glob import
+    fn macro_rules_use(&self) -> String {
+        match self.macro_path.map(|p| p.trim_start_matches("crate::").trim_matches(':')) {
+            Some(path) if !path.is_empty() && path != "crate" => format!("        use $crate::{}::*;", path),
+            _ => "        use $crate::*;".to_string(),
+        }
+    }
+
     /// bool 戻り値フラグと bool マクロ集合を設定
     pub fn with_bool_return(mut self, is_bool: bool, bool_macros: HashSet<InternedStr>) -> Self {
         self.is_bool_return = is_bool;
@@ -2713,6 +2987,31 @@ impl<'a> RustCodegen<'a> {
         false
     }
 
+    /// apidoc の POD 本文と C のシグネチャをドキュメントコメントとして出力
+    ///
+    /// 1 行の説明の直後に呼ぶ。apidoc にエントリがなければ何もしない。
+    fn write_apidoc_docs(&mut self, name: &str) {
+        let Some(doc) = self.apidoc_docs.and_then(|d| d.get(name)).and_then(apidoc_rustdoc) else {
+            return;
+        };
+        self.writeln("///");
+        for line in doc.lines() {
+            if line.is_empty() {
+                self.writeln("///");
+            } else {
+                self.writeln(&format!("/// {}", line));
+            }
+        }
+    }
+
+    /// `NonNull` で受け取る引数への実引数を包む（それ以外はそのまま）
+    fn wrap_nonnull_call_arg(&self, callee: Option<InternedStr>, index: usize, arg: String) -> String {
+        match callee.and_then(|c| self.nullability?.nonnull_param(c, index)) {
+            Some(nullability) => wrap_nonnull_arg(&arg, nullability),
+            None => arg,
+        }
+    }
+
     /// 不完全マーカー: 型が不明
     fn unknown_marker(&mut self) -> &'static str {
         self.incomplete_count += 1;
@@ -2870,6 +3169,8 @@ impl<'a> RustCodegen<'a> {
             unresolved_names: self.unresolved_names,
             used_libc_fns: self.used_libc_fns,
             codegen_errors: self.codegen_errors,
+            purity: self.purity,
+            signature: self.signature,
         }
     }
 
@@ -2937,18 +3238,39 @@ impl<'a> RustCodegen<'a> {
         // AST ダンプ（デバッグ用）
         self.dump_ast_comment_for_expr(name_str, &info.parse_result);
 
+        // 純粋なマクロは安全な関数にする（定数評価できれば const fn）
+        if let Some(callees) = self.macro_purity {
+            let param_types: Vec<String> = info.params.iter()
+                .filter_map(|p| self.current_param_types.get(&p.name))
+                .map(|ut| ut.to_rust_string())
+                .collect();
+            self.purity = PurityChecker::new(self.interner, self.enum_dict, self.macro_ctx, self.rust_decl_dict, callees)
+                .macro_purity(info, &param_types, &return_type);
+        }
+
         // ドキュメントコメント
         let thx_info = if info.is_thx_dependent { " [THX]" } else { "" };
         let generic_info = if !generic_clause.is_empty() { " [generic]" } else { "" };
-        self.writeln(&format!("/// {}{}{} - macro function", name_str, thx_info, generic_info));
+        let kind = if info.is_function { "macro function" } else { "object macro" };
+        self.writeln(&format!("/// {}{}{} - {}", name_str, thx_info, generic_info, kind));
+        self.write_apidoc_docs(name_str);
         self.writeln("#[inline]");
-        self.writeln("#[allow(unsafe_op_in_unsafe_fn)]");
+        if self.purity == Purity::Unsafe {
+            self.writeln("#[allow(unsafe_op_in_unsafe_fn)]");
+        }
 
         // 関数定義（ジェネリック句付き）
-        self.writeln(&format!("pub unsafe fn {}{}({}) -> {} {{", name_str, generic_clause, params_str, return_type));
+        self.writeln(&format!("pub {}fn {}{}({}) -> {} {{",
+            self.purity.fn_qualifier(), name_str, generic_clause, params_str, return_type));
+        self.signature = Some(ReportSignature::new(&params_str, &return_type));
 
-        // unsafe 操作（関数呼び出し or デリファレンス）を含む場合のみ unsafe ブロックを生成
-        let needs_unsafe = info.has_unsafe_ops();
+        for line in self.param_check_lines(info) {
+            self.writeln(&format!("    {}", line));
+        }
+
+        // unsafe 操作（関数呼び出し or デリファレンス）を含む場合のみ unsafe ブロックを生成。
+        // 純粋なマクロの呼び出しは安全な関数の呼び出しなので不要
+        let needs_unsafe = self.purity == Purity::Unsafe && info.has_unsafe_ops();
         let body_indent = if needs_unsafe { "        " } else { "    " };
 
         if needs_unsafe {
@@ -2983,14 +3305,7 @@ impl<'a> RustCodegen<'a> {
                     self.writeln(&format!("{}{}", body_indent, s));
                 }
             }
-            ParseResult::Statement(block_items) => {
-                for item in block_items {
-                    if let BlockItem::Stmt(stmt) = item {
-                        let rust_stmt = self.stmt_to_rust(stmt, info);
-                        self.writeln(&format!("{}{}", body_indent, rust_stmt));
-                    }
-                }
-            }
+            ParseResult::Statement(block_items) => self.write_statement_body(block_items, body_indent),
             ParseResult::Unparseable(_) => {
                 self.writeln(&format!("{}unimplemented!()", body_indent));
             }
@@ -3006,6 +3321,389 @@ impl<'a> RustCodegen<'a> {
         self.into_generated_code()
     }
 
+    /// 文マクロの本体を出力
+    ///
+    /// `STMT_START { ... } STMT_END` や if 文も inline 関数と同じ変換で展開し、
+    /// 内側の宣言（`const IV tmpXSoff = ...` など）も解決済みのローカルにする。
+    fn write_statement_body(&mut self, block_items: &[BlockItem], body_indent: &str) {
+        let mut decl_names = HashSet::new();
+        collect_local_decl_names(block_items, &mut decl_names);
+        for item in block_items {
+            self.collect_local_names_from_block_item(item);
+            if let BlockItem::Stmt(stmt) = item {
+                collect_mut_params_from_stmt(stmt, &decl_names, &mut self.mut_local_names);
+            }
+        }
+        for item in block_items {
+            match item {
+                BlockItem::Stmt(stmt) => {
+                    let rust_stmt = self.stmt_to_rust_inline(stmt, body_indent);
+                    self.writeln(&rust_stmt);
+                }
+                BlockItem::Decl(decl) => {
+                    let rust_decl = self.decl_to_rust_let(decl, body_indent);
+                    self.buffer.push_str(&rust_decl);
+                }
+            }
+        }
+    }
+
+    /// オブジェクトマクロを `pub const` として生成（self を消費）
+    ///
+    /// 整数定数（Step 7 で畳み込んだ `info.const_value`）と文字列リテラルが対象。
+    /// 推論された型が畳み込んだ値を表せればその型、表せなければ畳み込んだ C の
+    /// 型から Rust の型を決める。ただし apidoc・bindings.rs などで宣言された型で
+    /// 表せない場合は C と異なる値になるので、その理由を `Err` で返す。
+    /// 推論された型が整数型に解決できない場合（ポインタ等）は `Ok(None)` を返し、
+    /// 呼び出し側で `generate_object_macro_fn` にフォールバックさせる。
+    pub fn generate_object_macro_const(mut self, info: &MacroInferInfo) -> Result<Option<GeneratedCode>, String> {
+        let name_str = self.interner.get(info.name);
+
+        let Some(folded) = info.const_value else {
+            if let ParseResult::Expression(expr) = &info.parse_result {
+                // NUL を含む文字列は C 文字列リテラルにできない
+                if let ExprKind::StringLit(bytes) = &expr.kind && !bytes.contains(&0) {
+                    self.writeln(&format!("/// {} - object macro constant", name_str));
+                    self.write_apidoc_docs(name_str);
+                    self.writeln(&format!("pub const {}: &std::ffi::CStr = c\"{}\";", name_str, escape_string(bytes)));
+                    self.signature = Some(ReportSignature::constant("&std::ffi::CStr"));
+                    self.writeln("");
+                    return Ok(Some(self.into_generated_code()));
+                }
+            }
+            return Ok(None);
+        };
+
+        let ty = match info.get_return_type() {
+            Some(repr) => {
+                let ty = self.get_return_type(info);
+                let Some((signed, width)) = resolve_integer_type(&ty, self.rust_decl_dict) else {
+                    return Ok(None);
+                };
+                if (CIntType { signed, bits: u32::from(width) * 8 }).contains(folded.value) {
+                    ty
+                } else if repr.confidence_tier() <= 3 {
+                    // apidoc・bindings.rs・ヘッダーで宣言された型では表せない
+                    return Err(format!(
+                        "constant value {} ({}) does not fit the declared type {}",
+                        folded.value, folded.ty.rust_name(), ty,
+                    ));
+                } else {
+                    // 式から推論した型（整数リテラルを int とみなしたものなど）より
+                    // 畳み込んだ C の型を優先する
+                    folded.ty.rust_name().to_string()
+                }
+            }
+            None => folded.ty.rust_name().to_string(),
+        };
+
+        self.writeln(&format!("/// {} - object macro constant", name_str));
+        self.write_apidoc_docs(name_str);
+        self.writeln(&format!("pub const {}: {} = {};", name_str, ty, folded.value));
+        self.signature = Some(ReportSignature::constant(&ty));
+        self.writeln("");
+
+        Ok(Some(self.into_generated_code()))
+    }
+
+    /// オブジェクトマクロを引数なし関数として生成（self を消費）
+    ///
+    /// 定数にできない式マクロ用。戻り値が void（呼び出し側の文脈に依存する
+    /// 文マクロ）や浮動小数点の場合、ポインタ型に整数 0 を返す場合は None を返す。
+    pub fn generate_object_macro_fn(mut self, info: &MacroInferInfo) -> Option<GeneratedCode> {
+        let return_type_str = self.get_return_type(info);
+        let return_type = UnifiedType::from_rust_str(&return_type_str);
+        // `*mut ()` は void* の推論失敗の名残
+        if return_type.is_void() || return_type.is_float() || return_type_str.contains("()") {
+            return None;
+        }
+        if let ParseResult::Expression(expr) = &info.parse_result {
+            // C 文字列リテラルにできない文字列（NUL 入り）
+            if matches!(expr.kind, ExprKind::StringLit(_)) {
+                return None;
+            }
+            let is_null = match &expr.kind {
+                ExprKind::Cast { expr: inner, .. } => is_zero_constant(inner),
+                _ => is_zero_constant(expr),
+            };
+            if is_null && !return_type.is_bool() && integer_type_rank(&return_type.to_rust_string()).is_none() {
+                return None;
+            }
+        }
+        // generate_macro で戻り値型を再計算するので、ここでの不完全マーカーは数えない
+        self.incomplete_count = 0;
+        Some(self.generate_macro(info))
+    }
+
+    /// type/cast 引数を持つマクロを `macro_rules!` として生成（self を消費）
+    ///
+    /// Rust の関数では `as T` のようなジェネリック型へのキャストができないため、
+    /// 型引数を `$t:ty`、値引数を `$x:expr` として受け取り、C マクロと同じく
+    /// 本体に直接埋め込む。型パラメータが仮引数に対応しない（戻り値型だけが
+    /// ジェネリックと推論された）マクロは None。
+    pub fn generate_macro_rules(mut self, info: &MacroInferInfo) -> Option<GeneratedCode> {
+        let name_str = self.interner.get(info.name);
+
+        let param_generics: HashSet<&String> = info.generic_type_params.iter()
+            .filter(|(idx, _)| **idx >= 0)
+            .map(|(_, g)| g)
+            .collect();
+        if param_generics.is_empty()
+            || info.generic_type_params.values().any(|g| !param_generics.contains(g))
+            || !info.literal_string_params.is_empty()
+        {
+            return None;
+        }
+
+        for p in &info.params {
+            self.current_local_names.insert(p.name);
+            self.macro_rules_params.insert(p.name);
+        }
+
+        // 型パラメータはメタ変数名そのものを型名として出力する
+        self.current_type_param_map = info.generic_type_params.iter()
+            .filter(|(idx, _)| **idx >= 0)
+            .filter_map(|(idx, _)| {
+                info.params.get(*idx as usize).map(|p| {
+                    (p.name, format!("{}{}", MACRO_RULES_PARAM_PREFIX, self.interner.get(p.name)))
+                })
+            })
+            .collect();
+
+        // 型推論用にパラメータ型・戻り値型を登録する。シグネチャは出力しない
+        // ので、ここで型が不明でも不完全としては数えない。
+        self.build_param_list(info);
+        let mut return_type = self.get_return_type(info);
+        for (idx, generic_name) in &info.generic_type_params {
+            if let Some(p) = info.params.get(*idx as usize).filter(|_| *idx >= 0) {
+                let placeholder = format!("{}{}", MACRO_RULES_PARAM_PREFIX, self.interner.get(p.name));
+                return_type = replace_word(&return_type, generic_name, &placeholder);
+            }
+        }
+        self.current_return_type = Some(UnifiedType::from_rust_str(&return_type));
+        self.incomplete_count = 0;
+
+        let mut matchers = Vec::new();
+        let thx = self.perl_threaded && info.is_thx_dependent;
+        if thx {
+            matchers.push("$my_perl:expr".to_string());
+        }
+        for (i, p) in info.params.iter().enumerate() {
+            let frag = if info.generic_type_params.contains_key(&(i as i32)) { "ty" } else { "expr" };
+            matchers.push(format!("${}:{}", self.interner.get(p.name), frag));
+        }
+
+        let thx_info = if info.is_thx_dependent { " [THX]" } else { "" };
+        self.writeln(&format!("/// {}{} [generic] - macro function (macro_rules!)", name_str, thx_info));
+        self.write_apidoc_docs(name_str);
+        self.writeln("#[macro_export]");
+        self.writeln(&format!("macro_rules! {} {{", name_str));
+        self.writeln(&format!("    ({}) => {{{{", matchers.join(", ")));
+        // 本体の識別子は定義側のクレートで解決させる
+        self.writeln("        #[allow(unused_imports)]");
+        let use_line = self.macro_rules_use();
+        self.writeln(&use_line);
+        if thx {
+            self.writeln("        let my_perl: *mut PerlInterpreter = $my_perl;");
+        }
+
+        let needs_unsafe = info.has_unsafe_ops();
+        let body_indent = if needs_unsafe { "            " } else { "        " };
+        if needs_unsafe {
+            self.writeln("        unsafe {");
+        }
+        match &info.parse_result {
+            ParseResult::Expression(expr) => {
+                let type_hint = self.current_return_type.as_ref().map(|ut| ut.to_rust_string());
+                let syn_expr = self.build_syn_expr_with_type_hint(expr, Some(info), type_hint.as_deref());
+                let s = normalize_parens(&crate::syn_codegen::expr_to_string(&syn_expr));
+                if self.current_return_type.as_ref().is_some_and(|ut| ut.is_void()) {
+                    self.writeln(&format!("{}{};", body_indent, s));
+                } else {
+                    self.writeln(&format!("{}{}", body_indent, s));
+                }
+            }
+            ParseResult::Statement(block_items) => self.write_statement_body(block_items, body_indent),
+            ParseResult::Unparseable(_) => return None,
+        }
+        if needs_unsafe {
+            self.writeln("        }");
+        }
+        self.writeln("    }};");
+        self.writeln("}");
+        self.writeln("");
+
+        self.buffer = self.buffer.replace(MACRO_RULES_PARAM_PREFIX, "$");
+        Some(self.into_generated_code())
+    }
+
+    /// 呼び出し側ローカルを使うマクロを `macro_rules!` として生成（self を消費）
+    ///
+    /// `locals`（`sp` など）は `$sp:ident` として、パラメータは `$x:expr` として
+    /// 受け取る。呼び出し側の変数をそのまま読み書きし、`return` も呼び出し側の
+    /// 関数から戻るので、C マクロと同じ意味になる: `PUSHs!(sp, sv)`。
+    /// 型引数を持つマクロは None。
+    pub fn generate_stack_macro_rules(mut self, info: &MacroInferInfo, locals: &[InternedStr]) -> Option<GeneratedCode> {
+        let name_str = self.interner.get(info.name);
+        if !info.generic_type_params.is_empty() || !info.literal_string_params.is_empty() {
+            return None;
+        }
+
+        for p in &info.params {
+            self.current_local_names.insert(p.name);
+            self.macro_rules_params.insert(p.name);
+        }
+        for &local in locals {
+            self.current_local_names.insert(local);
+            self.macro_rules_params.insert(local);
+            if let Some(ty) = StackMacroDict::local_type(local, self.interner) {
+                self.current_param_types.insert(local, UnifiedType::from_rust_str(ty));
+            }
+        }
+
+        // 型推論用にパラメータ型・戻り値型を登録する（シグネチャは出力しない）
+        self.build_param_list(info);
+        let return_type = self.get_return_type(info);
+        self.current_return_type = Some(UnifiedType::from_rust_str(&return_type));
+        self.incomplete_count = 0;
+
+        let mut matchers = Vec::new();
+        let thx = self.perl_threaded && info.is_thx_dependent;
+        if thx {
+            matchers.push("$my_perl:expr".to_string());
+        }
+        for &local in locals {
+            matchers.push(format!("${}:ident", self.interner.get(local)));
+        }
+        for p in &info.params {
+            matchers.push(format!("${}:expr", self.interner.get(p.name)));
+        }
+
+        let thx_info = if info.is_thx_dependent { " [THX]" } else { "" };
+        let locals_str: Vec<&str> = locals.iter().map(|l| self.interner.get(*l)).collect();
+        self.writeln(&format!("/// {}{} [stack: {}] - macro using caller locals (macro_rules!)",
+            name_str, thx_info, locals_str.join(", ")));
+        self.write_apidoc_docs(name_str);
+        self.writeln("#[macro_export]");
+        self.writeln(&format!("macro_rules! {} {{", name_str));
+        if let ParseResult::Statement(items) = &info.parse_result
+            && self.stack_macros.is_some_and(|d| d.is_declaration(info.name))
+        {
+            self.write_declaration_macro_body(items, info, &matchers, thx);
+            self.writeln("}");
+            self.writeln("");
+            self.buffer = self.buffer.replace(MACRO_RULES_PARAM_PREFIX, "$");
+            return Some(self.into_generated_code());
+        }
+        self.writeln(&format!("    ({}) => {{{{", matchers.join(", ")));
+        self.writeln("        #[allow(unused_imports)]");
+        let use_line = self.macro_rules_use();
+        self.writeln(&use_line);
+        if thx {
+            self.writeln("        let my_perl: *mut PerlInterpreter = $my_perl;");
+        }
+        let needs_unsafe = info.has_unsafe_ops();
+        let body_indent = if needs_unsafe { "            " } else { "        " };
+        if needs_unsafe {
+            self.writeln("        unsafe {");
+        }
+        match &info.parse_result {
+            ParseResult::Expression(expr) => {
+                // `({ SV *_sv = ...; ... })` の中の宣言も解決済みにする
+                self.collect_local_names_from_expr(expr);
+                let type_hint = self.current_return_type.as_ref().map(|ut| ut.to_rust_string());
+                let syn_expr = self.build_syn_expr_with_type_hint(expr, Some(info), type_hint.as_deref());
+                let s = normalize_parens(&crate::syn_codegen::expr_to_string(&syn_expr));
+                if self.current_return_type.as_ref().is_some_and(|ut| ut.is_void()) {
+                    self.writeln(&format!("{}{};", body_indent, s));
+                } else {
+                    self.writeln(&format!("{}{}", body_indent, s));
+                }
+            }
+            ParseResult::Statement(block_items) => self.write_statement_body(block_items, body_indent),
+            ParseResult::Unparseable(_) => return None,
+        }
+        if needs_unsafe {
+            self.writeln("        }");
+        }
+        self.writeln("    }};");
+        self.writeln("}");
+        self.writeln("");
+
+        self.buffer = self.buffer.replace(MACRO_RULES_PARAM_PREFIX, "$");
+        Some(self.into_generated_code())
+    }
+
+    /// 宣言だけのマクロ（`dSP` など）の `macro_rules!` の規則を出力
+    ///
+    /// 展開結果をブロックで囲まず、`let mut $sp: *mut *mut SV = { ... };` を
+    /// 呼び出し側のスコープに直接置く。初期化式は宣言ごとにブロックで評価する。
+    fn write_declaration_macro_body(&mut self, items: &[BlockItem], info: &MacroInferInfo, matchers: &[String], thx: bool) {
+        let use_line = self.macro_rules_use();
+        let needs_unsafe = info.has_unsafe_ops();
+        self.writeln(&format!("    ({}) => {{", matchers.join(", ")));
+        for item in items {
+            let BlockItem::Decl(decl) = item else { continue };
+            let base_type = self.decl_specs_to_rust(&decl.specs);
+            for init_decl in &decl.declarators {
+                let Some(name) = init_decl.declarator.name else { continue };
+                let ty = self.apply_derived_to_type(&base_type, &init_decl.declarator.derived);
+                let init = match &init_decl.init {
+                    Some(Initializer::Expr(expr)) => Some(self.decl_init_to_rust(expr, &ty, Some(info))),
+                    _ => None,
+                };
+                // 以降の初期化式の型推論で使う
+                self.current_param_types.insert(name, UnifiedType::from_rust_str(&ty));
+                let name_str = self.interner.get(name);
+                self.writeln("        #[allow(unused_mut)]");
+                let Some(init) = init else {
+                    self.writeln(&format!("        let mut ${}: {};", name_str, ty));
+                    continue;
+                };
+                self.writeln(&format!("        let mut ${}: {} = {{", name_str, ty));
+                self.writeln("            #[allow(unused_imports)]");
+                self.writeln(&format!("    {}", use_line));
+                if thx {
+                    self.writeln("            let my_perl: *mut PerlInterpreter = $my_perl;");
+                }
+                if needs_unsafe {
+                    self.writeln(&format!("            unsafe {{ {} }}", strip_outer_parens(&init)));
+                } else {
+                    self.writeln(&format!("            {}", strip_outer_parens(&init)));
+                }
+                self.writeln("        };");
+            }
+        }
+        self.writeln("    };");
+    }
+
+    /// スタックマクロの呼び出しを `NAME!(my_perl, sp, args...)` として組み立てる
+    ///
+    /// 呼び出し側ローカルは同名の識別子をそのまま渡す（呼び出し元も
+    /// スタックマクロなので、それ自身のメタ変数になる）。
+    fn build_stack_macro_call(&mut self, name: InternedStr, args: &[Expr], info: Option<&MacroInferInfo>) -> Option<syn::Expr> {
+        let locals = self.stack_macros?.get(name)?;
+        let callee_thx = self.macro_ctx.macros.get(&name).is_some_and(|m| m.is_thx_dependent);
+        let mut a: Vec<String> = Vec::new();
+        if self.perl_threaded && callee_thx {
+            a.push("my_perl".to_string());
+        }
+        for &local in locals {
+            let local_str = self.interner.get(local);
+            if self.macro_rules_params.contains(&local) {
+                a.push(format!("{}{}", MACRO_RULES_PARAM_PREFIX, local_str));
+            } else {
+                a.push(local_str.to_string());
+            }
+        }
+        for arg in args {
+            let arg_str = crate::syn_codegen::expr_to_string(&self.build_syn_expr(arg, info));
+            a.push(normalize_parens(&arg_str));
+        }
+        Some(syn::parse_str(&format!("{}!({})", self.interner.get(name), a.join(", ")))
+            .unwrap_or_else(|_| crate::syn_codegen::int_lit(0)))
+    }
+
     /// ジェネリック句を生成（例: "<T>" or "<T, U>"）
     fn build_generic_clause(&self, info: &MacroInferInfo) -> String {
         if info.generic_type_params.is_empty() {
@@ -3034,12 +3732,61 @@ impl<'a> RustCodegen<'a> {
             let ty = self.get_param_type(p, info, i);
             // current_param_types に登録（bool 判定等で使用）
             self.current_param_types.insert(p.name, UnifiedType::from_rust_str(&ty));
+            // NonNull で受け取る引数は本体の先頭で生ポインタに戻す（param_check_lines）
+            if let Some(nonnull) = self.nullability
+                .and_then(|d| d.nonnull_param(info.name, i))
+                .and_then(|n| nonnull_type(&ty, n))
+            {
+                parts.push(format!("{}: {}", name, nonnull));
+                continue;
+            }
             let mut_prefix = if mut_params.contains(&p.name) { "mut " } else { "" };
             parts.push(format!("{}{}: {}", mut_prefix, name, ty));
         }
         parts.join(", ")
     }
 
+    /// apidoc の NN / NULLOK / NZ 注釈による本体先頭の行
+    ///
+    /// NonNull で受け取った引数を同名の生ポインタに戻し、NN / NZ の引数を
+    /// `debug_assert!` で検査する。
+    fn param_check_lines(&self, info: &MacroInferInfo) -> Vec<String> {
+        let Some(dict) = self.nullability else {
+            return Vec::new();
+        };
+        let Some(checks) = dict.get(info.name) else {
+            return Vec::new();
+        };
+        let mut_params = collect_mut_params(&info.parse_result, &info.params);
+        let mut lines = Vec::new();
+        for (i, (p, check)) in info.params.iter().zip(checks).enumerate() {
+            let name = escape_rust_keyword(self.interner.get(p.name));
+            let Some(ty) = self.current_param_types.get(&p.name).map(|ut| ut.to_rust_string()) else {
+                continue;
+            };
+            let is_pointer = ty.starts_with('*');
+            match dict.nonnull_param(info.name, i).filter(|_| is_pointer) {
+                Some(nullability) => {
+                    let mut_prefix = if mut_params.contains(&p.name) { "mut " } else { "" };
+                    let raw = if nullability == Nullability::NotNull {
+                        format!("{}.as_ptr()", name)
+                    } else {
+                        format!("{}.map_or(std::ptr::null_mut(), |p| p.as_ptr())", name)
+                    };
+                    lines.push(format!("let {}{}: {} = {};", mut_prefix, name, ty, raw));
+                }
+                None if is_pointer && (check.nullability == Nullability::NotNull || check.non_zero) => {
+                    lines.push(format!("debug_assert!(!{}.is_null());", name));
+                }
+                None => {}
+            }
+            if check.non_zero && !is_pointer && normalize_integer_type(&ty).is_some() {
+                lines.push(format!("debug_assert!({} != 0);", name));
+            }
+        }
+        lines
+    }
+
     /// パラメータの型を取得
     fn get_param_type(&mut self, param: &MacroParam, info: &MacroInferInfo, param_index: usize) -> String {
         // ジェネリック型パラメータかチェック
@@ -3254,16 +4001,22 @@ impl<'a> RustCodegen<'a> {
             _ => return None,
         };
 
+        // THX 依存だと第一引数が my_perl で arg と zip がずれる → 当面非対応
+        if self.macro_ctx.macros.get(&callee_id)?.is_thx_dependent {
+            return None;
+        }
+        self.inline_macro_body(callee_id, args)
+    }
+
+    /// 式マクロの本体のパラメータを実引数で置き換えた式を返す
+    ///
+    /// 本体が式でないマクロや、引数の数が合わない呼び出しは None。
+    fn inline_macro_body(&self, callee_id: InternedStr, args: &[Expr]) -> Option<Expr> {
         let callee_info = self.macro_ctx.macros.get(&callee_id)?;
         let body = match &callee_info.parse_result {
             ParseResult::Expression(e) => e,
             _ => return None,
         };
-
-        // THX 依存だと第一引数が my_perl で arg と zip がずれる → 当面非対応
-        if callee_info.is_thx_dependent {
-            return None;
-        }
         if callee_info.params.len() != args.len() {
             return None;
         }
@@ -3294,6 +4047,14 @@ impl<'a> RustCodegen<'a> {
                     return syn::parse_str(subst).unwrap_or_else(|_| int_lit(0));
                 }
                 let name_str = self.interner.get(*name);
+                // macro_rules! のメタ変数（後で `$name` に置換される）
+                if self.macro_rules_params.contains(name) {
+                    if self.current_type_param_map.contains_key(name) {
+                        self.codegen_errors.push(format!(
+                            "type parameter `{}` used as a value", name_str));
+                    }
+                    return ident_expr(&format!("{}{}", MACRO_RULES_PARAM_PREFIX, name_str));
+                }
                 // libc 関数の使用を記録
                 if LIBC_FUNCTIONS.contains(&name_str) {
                     self.used_libc_fns.insert(name_str.to_string());
@@ -4195,6 +4956,14 @@ impl<'a> RustCodegen<'a> {
                 }
                 // builtin 関数の特殊処理
                 if let ExprKind::Ident(name) = &func.kind {
+                    if let Some(call) = self.build_stack_macro_call(*name, args, info) {
+                        return call;
+                    }
+                    if self.inline_macros.contains(name)
+                        && let Some(inlined) = self.inline_macro_body(*name, args)
+                    {
+                        return self.build_syn_expr(&inlined, info);
+                    }
                     let func_name = self.interner.get(*name);
                     // __builtin_expect → 引数を透過
                     if func_name == "__builtin_expect" && !args.is_empty() {
@@ -4288,21 +5057,30 @@ impl<'a> RustCodegen<'a> {
                 } else { vec![] };
                 let arg_offset = if needs_my_perl { 1usize } else { 0 };
                 for (i, arg) in args.iter().enumerate() {
-                    arg_strs.push(self.build_arg_string_unified(arg, info, callee_name, i + arg_offset));
+                    let arg_str = self.build_arg_string_unified(arg, info, callee_name, i + arg_offset);
+                    arg_strs.push(self.wrap_nonnull_call_arg(callee_name, i, arg_str));
                 }
                 syn::parse_str(&format!("{}({})", f_str, arg_strs.join(", ")))
                     .unwrap_or_else(|_| int_lit(0))
             }
             ExprKind::MacroCall { name, args, expanded, .. } => {
+                if let Some(call) = self.build_stack_macro_call(*name, args, info) {
+                    return call;
+                }
+                if self.inline_macros.contains(name)
+                    && let Some(inlined) = self.inline_macro_body(*name, args)
+                {
+                    return self.build_syn_expr(&inlined, info);
+                }
                 if self.should_emit_as_macro_call(*name) {
                     let name_str = escape_rust_keyword(self.interner.get(*name));
                     let needs_my_perl = self.needs_my_perl_for_call(*name, args.len());
                     let mut a: Vec<String> = if needs_my_perl {
                         vec!["my_perl".to_string()]
                     } else { vec![] };
-                    for arg in args {
-                        let arg_str = expr_to_string(&self.build_syn_expr(arg, info));
-                        a.push(normalize_parens(&arg_str));
+                    for (i, arg) in args.iter().enumerate() {
+                        let arg_str = normalize_parens(&expr_to_string(&self.build_syn_expr(arg, info)));
+                        a.push(self.wrap_nonnull_call_arg(Some(*name), i, arg_str));
                     }
                     syn::parse_str(&format!("{}({})", name_str, a.join(", ")))
                         .unwrap_or_else(|_| int_lit(0))
@@ -4727,7 +5505,15 @@ impl<'a> RustCodegen<'a> {
     fn build_assign_syn_expr(&mut self, op: AssignOp, lhs: &Expr, rhs: &Expr,
                              info: Option<&MacroInferInfo>) -> syn::Expr {
         use crate::syn_codegen::*;
-        let l = self.build_lvalue_syn_expr(lhs, info);
+        // `*++sp = s` のように参照先の計算に副作用がある場合、値として左辺を
+        // 再評価すると副作用が二重になるので、ポインタを一度だけ評価しておく
+        let (pre, l) = match &lhs.kind {
+            ExprKind::Deref(inner) if expr_has_side_effects(inner) => {
+                let ptr = self.build_syn_expr(inner, info);
+                (Some(let_stmt("__lhs_ptr", ptr)), deref(ident_expr("__lhs_ptr")))
+            }
+            _ => (None, self.build_lvalue_syn_expr(lhs, info)),
+        };
         let lhs_ut = self.infer_expr_type_unified(lhs, info);
 
         // RHS の構築（null リテラル特別扱い + プレーン Assign の整数幅キャスト）
@@ -4819,7 +5605,7 @@ impl<'a> RustCodegen<'a> {
                             vec![arg_val],
                         );
                         let stmt = semi_stmt(setter_call);
-                        return block_with_value(vec![stmt], l);
+                        return block_with_value(pre.into_iter().chain([stmt]).collect(), l);
                     }
                 }
             }
@@ -4884,7 +5670,7 @@ impl<'a> RustCodegen<'a> {
                 semi_stmt(assign_op_expr(l.clone(), syn_op, r))
             }
         };
-        block_with_value(vec![stmt], l)
+        block_with_value(pre.into_iter().chain([stmt]).collect(), l)
     }
 
     /// lvalue 用の文字列を構築（`build_lvalue_syn_expr` の文字列化版）
@@ -5366,16 +6152,8 @@ impl<'a> RustCodegen<'a> {
                     }
                 }
             }
-            // ローカル変数名も収集
-            for item in &func_def.body.items {
-                if let BlockItem::Decl(decl) = item {
-                    for init_decl in &decl.declarators {
-                        if let Some(var_name) = init_decl.declarator.name {
-                            all_names.insert(var_name);
-                        }
-                    }
-                }
-            }
+            // ローカル変数名も収集（ネストしたブロック内の宣言を含む）
+            collect_local_decl_names(&func_def.body.items, &mut all_names);
             let mut result = HashSet::new();
             for item in &func_def.body.items {
                 if let BlockItem::Stmt(stmt) = item {
@@ -5433,11 +6211,13 @@ impl<'a> RustCodegen<'a> {
 
         // ドキュメントコメント
         self.writeln(&format!("/// {}{} - inline function", name_str, thx_info));
+        self.write_apidoc_docs(name_str);
         self.writeln("#[inline]");
         self.writeln("#[allow(unsafe_op_in_unsafe_fn)]");
 
         // 関数定義
         self.writeln(&format!("pub unsafe fn {}({}) -> {} {{", name_str, params_str, return_type));
+        self.signature = Some(ReportSignature::new(&params_str, &return_type));
 
         // unsafe 操作（関数呼び出し or デリファレンス）を含む場合のみ unsafe ブロックを生成
         let needs_unsafe = func_def.function_call_count > 0 || func_def.deref_count > 0;
@@ -5556,46 +6336,7 @@ impl<'a> RustCodegen<'a> {
             if let Some(ref init) = init_decl.init {
                 match init {
                     Initializer::Expr(expr) => {
-                        // 宣言型と式の推論型が異なる整数型なら as キャストを syn レベルで挿入。
-                        // 文字列ベースの `({} as {})` は normalize_parens に剥がされて
-                        // `a as u32 & b as u8` の優先順位崩壊を起こすため、syn::Expr で
-                        // 構築して expr_to_string に括弧の挿入を任せる。
-                        let mut init_syn = self.build_syn_expr(expr, None);
-                        if let Some(expr_ut) = self.infer_expr_type_inline(expr) {
-                            let decl_s = ty.clone();
-                            let expr_s = expr_ut.to_rust_string();
-                            let nd = normalize_integer_type(&decl_s);
-                            let ne = normalize_integer_type(&expr_s);
-                            if let (Some(d), Some(e)) = (nd, ne) {
-                                if !integer_types_compatible(d, e) {
-                                    init_syn = crate::syn_codegen::cast_syn_expr(init_syn, d);
-                                }
-                            }
-                        }
-                        // ポインタの const/mut 不一致は init 側に明示キャストを挿入する。
-                        // 旧実装は宣言型を *const に書換えていたが、それだと以降の
-                        // 代入で *mut 型が期待されるマクロ引数などで破綻する。
-                        // 例: `let s: *mut U8 = s0;` (s0: *const U8) → `s0 as *mut U8`
-                        let decl_is_mut_ptr = ty.contains("*mut ") && !ty.contains("*const ");
-                        let decl_is_const_ptr = ty.contains("*const ") && !ty.contains("*mut ");
-                        if let Some(expr_ut) = self.infer_expr_type_inline(expr) {
-                            if expr_ut.is_pointer() {
-                                if decl_is_mut_ptr && expr_ut.is_const_pointer() {
-                                    init_syn = crate::syn_codegen::cast_syn_expr(init_syn, &ty);
-                                } else if decl_is_const_ptr && !expr_ut.is_const_pointer() {
-                                    init_syn = crate::syn_codegen::cast_syn_expr(init_syn, &ty);
-                                }
-                            }
-                        }
-                        let init_expr = normalize_parens(&crate::syn_codegen::expr_to_string(&init_syn));
-                        // null リテラルの場合は型推論に任せて std::ptr::null_mut()
-                        let init_expr = if is_null_literal(expr) && ty.contains("*mut") {
-                            "std::ptr::null_mut()".to_string()
-                        } else if is_null_literal(expr) && ty.contains("*const") {
-                            "std::ptr::null()".to_string()
-                        } else {
-                            init_expr
-                        };
+                        let init_expr = self.decl_init_to_rust(expr, &ty, None);
                         let mut_kw = if init_decl.declarator.name.is_some_and(|n| self.mut_local_names.contains(&n)) { "mut " } else { "" };
                         result.push_str(&format!("{}let {}{}: {} = {};\n", indent, mut_kw, name, ty, strip_outer_parens(&init_expr)));
                     }
@@ -5614,25 +6355,145 @@ impl<'a> RustCodegen<'a> {
         result
     }
 
+    /// 宣言の初期化式を、宣言型 `ty` に合わせて Rust の式にする
+    fn decl_init_to_rust(&mut self, expr: &Expr, ty: &str, info: Option<&MacroInferInfo>) -> String {
+        // 宣言型と式の推論型が異なる整数型なら as キャストを syn レベルで挿入。
+        // 文字列ベースの `({} as {})` は normalize_parens に剥がされて
+        // `a as u32 & b as u8` の優先順位崩壊を起こすため、syn::Expr で
+        // 構築して expr_to_string に括弧の挿入を任せる。
+        let mut init_syn = self.build_syn_expr(expr, info);
+        if let Some(expr_ut) = self.infer_expr_type_inline(expr) {
+            let expr_s = expr_ut.to_rust_string();
+            let nd = normalize_integer_type(ty);
+            let ne = normalize_integer_type(&expr_s);
+            if let (Some(d), Some(e)) = (nd, ne) {
+                if !integer_types_compatible(d, e) {
+                    init_syn = crate::syn_codegen::cast_syn_expr(init_syn, d);
+                }
+            }
+        }
+        // ポインタの const/mut 不一致は init 側に明示キャストを挿入する。
+        // 旧実装は宣言型を *const に書換えていたが、それだと以降の
+        // 代入で *mut 型が期待されるマクロ引数などで破綻する。
+        // 例: `let s: *mut U8 = s0;` (s0: *const U8) → `s0 as *mut U8`
+        let decl_is_mut_ptr = ty.contains("*mut ") && !ty.contains("*const ");
+        let decl_is_const_ptr = ty.contains("*const ") && !ty.contains("*mut ");
+        if let Some(expr_ut) = self.infer_expr_type_inline(expr) {
+            if expr_ut.is_pointer() {
+                if decl_is_mut_ptr && expr_ut.is_const_pointer() {
+                    init_syn = crate::syn_codegen::cast_syn_expr(init_syn, ty);
+                } else if decl_is_const_ptr && !expr_ut.is_const_pointer() {
+                    init_syn = crate::syn_codegen::cast_syn_expr(init_syn, ty);
+                }
+            }
+        }
+        // null リテラルの場合は型推論に任せて std::ptr::null_mut()
+        if is_null_literal(expr) && ty.contains("*mut") {
+            "std::ptr::null_mut()".to_string()
+        } else if is_null_literal(expr) && ty.contains("*const") {
+            "std::ptr::null()".to_string()
+        } else {
+            normalize_parens(&crate::syn_codegen::expr_to_string(&init_syn))
+        }
+    }
+
     /// 複合文を文字列として生成
     fn compound_stmt_to_string(&mut self, stmt: &CompoundStmt, indent: &str) -> String {
+        self.block_items_to_rust(&stmt.items, indent)
+    }
+
+    /// ブロック要素列を Rust コードに変換
+    ///
+    /// goto のラベルを含む場合は、`goto_lower` で計算したリージョンを
+    /// ラベル付きブロック / ループで囲む。
+    fn block_items_to_rust(&mut self, items: &[BlockItem], indent: &str) -> String {
+        match plan_goto_regions(items) {
+            Some(regions) if !regions.is_empty() => {
+                self.goto_region_to_rust(items, &regions, 0, items.len(), indent)
+            }
+            _ => {
+                let mut result = String::new();
+                for item in items {
+                    result.push_str(&self.block_item_to_rust(item, indent, false));
+                }
+                result
+            }
+        }
+    }
+
+    /// items[start..end] を出力（regions は start 昇順・end 降順）
+    fn goto_region_to_rust(
+        &mut self,
+        items: &[BlockItem],
+        regions: &[GotoRegion],
+        start: usize,
+        end: usize,
+        indent: &str,
+    ) -> String {
         let mut result = String::new();
-        for item in &stmt.items {
-            match item {
-                BlockItem::Decl(decl) => {
-                    self.collect_decl_types(decl);
-                    result.push_str(&self.decl_to_rust_let(decl, indent));
+        let mut i = start;
+        while i < end {
+            let Some(k) = regions.iter().position(|r| r.start == i && r.end <= end) else {
+                result.push_str(&self.block_item_to_rust(&items[i], indent, true));
+                i += 1;
+                continue;
+            };
+            let region = &regions[k];
+            let inner: Vec<GotoRegion> = regions.iter().enumerate()
+                .filter(|(x, r)| *x != k && region.start <= r.start && r.end <= region.end)
+                .map(|(_, r)| r.clone())
+                .collect();
+            let label_str = self.interner.get(region.label);
+            let nested_indent = format!("{}    ", indent);
+            let prev = self.goto_labels.insert(region.label, region.kind);
+            match region.kind {
+                GotoKind::Forward => {
+                    result.push_str(&format!("{}'{}: {{\n", indent, label_str));
+                    result.push_str(&self.goto_region_to_rust(items, &inner, region.start, region.end, &nested_indent));
                 }
-                BlockItem::Stmt(s) => {
-                    let rust_stmt = self.stmt_to_rust_inline(s, indent);
-                    result.push_str(&rust_stmt);
-                    result.push('\n');
+                GotoKind::Backward => {
+                    result.push_str(&format!("{}'{}: loop {{\n", indent, label_str));
+                    result.push_str(&self.goto_region_to_rust(items, &inner, region.start, region.end, &nested_indent));
+                    // 末尾が return / goto ならループを抜ける break は到達しない
+                    let diverges = matches!(items[region.end - 1],
+                        BlockItem::Stmt(Stmt::Return(..) | Stmt::Goto(..)));
+                    if !diverges {
+                        result.push_str(&format!("{}break '{};\n", nested_indent, label_str));
+                    }
                 }
             }
+            result.push_str(&format!("{}}}\n", indent));
+            match prev {
+                Some(kind) => { self.goto_labels.insert(region.label, kind); }
+                None => { self.goto_labels.remove(&region.label); }
+            }
+            i = region.end;
         }
         result
     }
 
+    /// ブロック要素を 1 つ出力
+    ///
+    /// `strip_label` が true ならラベル文のラベルを外して中身だけを出力する
+    /// （ラベルはリージョン側で付与済み）。
+    fn block_item_to_rust(&mut self, item: &BlockItem, indent: &str, strip_label: bool) -> String {
+        match item {
+            BlockItem::Decl(decl) => {
+                self.collect_decl_types(decl);
+                self.decl_to_rust_let(decl, indent)
+            }
+            BlockItem::Stmt(s) => {
+                let s = match s {
+                    Stmt::Label { stmt, .. } if strip_label => stmt.as_ref(),
+                    _ => s,
+                };
+                let mut rust_stmt = self.stmt_to_rust_inline(s, indent);
+                rust_stmt.push('\n');
+                rust_stmt
+            }
+        }
+    }
+
     /// 文を Rust コードに変換（インライン関数用）
     fn stmt_to_rust_inline(&mut self, stmt: &Stmt, indent: &str) -> String {
         match stmt {
@@ -5666,20 +6527,8 @@ impl<'a> RustCodegen<'a> {
             }
             Stmt::Compound(compound) => {
                 let mut result = format!("{}{{\n", indent);
-                for item in &compound.items {
-                    match item {
-                        BlockItem::Stmt(s) => {
-                            let nested_indent = format!("{}    ", indent);
-                            result.push_str(&self.stmt_to_rust_inline(s, &nested_indent));
-                            result.push_str("\n");
-                        }
-                        BlockItem::Decl(decl) => {
-                            self.collect_decl_types(decl);
-                            let nested_indent = format!("{}    ", indent);
-                            result.push_str(&self.decl_to_rust_let(decl, &nested_indent));
-                        }
-                    }
-                }
+                let nested_indent = format!("{}    ", indent);
+                result.push_str(&self.block_items_to_rust(&compound.items, &nested_indent));
                 result.push_str(&format!("{}}}", indent));
                 result
             }
@@ -5810,7 +6659,11 @@ impl<'a> RustCodegen<'a> {
             }
             Stmt::Goto(label, _) => {
                 let label_str = self.interner.get(*label);
-                format!("{}break '{}; // goto", indent, label_str)
+                match self.goto_labels.get(label) {
+                    Some(GotoKind::Forward) => format!("{}break '{};", indent, label_str),
+                    Some(GotoKind::Backward) => format!("{}continue '{};", indent, label_str),
+                    None => format!("{}break '{}; // goto", indent, label_str),
+                }
             }
             Stmt::Label { name, stmt: label_stmt, .. } => {
                 let label_str = self.interner.get(*name);
@@ -6018,18 +6871,25 @@ impl<'a, W: Write> CodegenDriver<'a, W> {
         config: CodegenConfig,
     ) -> Self {
         Self {
-            writer,
+            writer: OffsetWriter { inner: writer, offset: 0 },
             interner,
             enum_dict,
             macro_ctx,
             bindings_info,
             config,
             stats: CodegenStats::default(),
+            report: CodegenReport::default(),
+            segments: Vec::new(),
+            items: Vec::new(),
+            item_open: false,
             used_libc_fns: HashSet::new(),
             successfully_generated_inlines: HashSet::new(),
+            stack_macros: StackMacroDict::default(),
             generatable_macros: HashSet::new(),
             const_pointer_params: HashMap::new(),
             bool_return_macros: HashSet::new(),
+            macro_purity: HashMap::new(),
+            nullability: NullabilityDict::default(),
             // デフォルトは threaded（後方互換）。`generate()` で
             // result.perl_build_mode から書き換える。
             perl_threaded: true,
@@ -6047,6 +6907,118 @@ impl<'a, W: Write> CodegenDriver<'a, W> {
         &self.stats
     }
 
+    /// 名前ごとの生成結果レポートを取得
+    pub fn report(&self) -> &CodegenReport {
+        &self.report
+    }
+
+    /// 出力区間の情報を取得
+    ///
+    /// 各区間はアイテムの定義元ヘッダーに対応するモジュール名を持つ
+    /// （共通部分は None）。`ModuleOutput::split` で分割に使う。
+    pub fn segments(&self) -> &[OutputSegment] {
+        &self.segments
+    }
+
+    /// アイテムごとの出力範囲を取得
+    ///
+    /// アイテムの間にある部分（ヘッダー・セクション見出し・use 文など）は含まない。
+    pub fn items(&self) -> &[OutputItem] {
+        &self.items
+    }
+
+    /// 出力中のアイテムを現在の出力位置で閉じる
+    fn end_item(&mut self) {
+        if self.item_open {
+            if let Some(last) = self.items.last_mut() {
+                last.end = self.writer.offset;
+            }
+            self.item_open = false;
+        }
+    }
+
+    /// 現在の出力位置から新しい区間を開始する
+    ///
+    /// 出力中のアイテムはここで閉じる。
+    fn begin_segment(&mut self, module: Option<String>) {
+        self.end_item();
+        let offset = self.writer.offset;
+        if let Some(last) = self.segments.last_mut() {
+            if last.module == module {
+                return;
+            }
+            if last.offset == offset {
+                last.module = module;
+                return;
+            }
+        }
+        self.segments.push(OutputSegment { offset, module });
+    }
+
+    /// アイテムの定義元ヘッダーを振り分け先として区間を開始する
+    fn begin_item_segment(&mut self, result: &InferResult, name: InternedStr, kind: ReportItemKind) {
+        let module = Self::item_loc(result, name, kind)
+            .and_then(|loc| result.preprocessor.files().try_get_path(loc.file_id))
+            .map(module_name_for_header);
+        self.begin_segment(module);
+        let offset = self.writer.offset;
+        self.items.push(OutputItem {
+            name: self.interner.get(name).to_string(),
+            kind,
+            start: offset,
+            end: offset,
+        });
+        self.item_open = true;
+    }
+
+    /// マクロ / inline 関数の定義位置
+    fn item_loc(result: &InferResult, name: InternedStr, kind: ReportItemKind) -> Option<SourceLocation> {
+        match kind {
+            ReportItemKind::InlineFn => result.inline_fn_dict.get(name).map(|f| f.info.loc.clone()),
+            ReportItemKind::Macro | ReportItemKind::ObjectMacro => {
+                result.preprocessor.macros().get(name).map(|def| def.def_loc.clone())
+            }
+        }
+    }
+
+    /// レポートにエントリを追加し、追加したエントリを返す
+    ///
+    /// 定義位置と THX フラグはここで埋める。
+    fn record(
+        &mut self,
+        result: &InferResult,
+        name: InternedStr,
+        kind: ReportItemKind,
+        outcome: ReportOutcome,
+    ) -> &mut ReportEntry {
+        let mut entry = ReportEntry::new(self.interner.get(name), kind, outcome);
+        entry.thx = match kind {
+            ReportItemKind::InlineFn => result.inline_fn_dict.get(name)
+                .is_some_and(|f| self.inline_fn_takes_my_perl(f)),
+            ReportItemKind::Macro | ReportItemKind::ObjectMacro => {
+                result.infer_ctx.macros.get(&name).is_some_and(|info| info.is_thx_dependent)
+            }
+        };
+        entry.location = Self::item_loc(result, name, kind).and_then(|loc| {
+            let path = result.preprocessor.files().try_get_path(loc.file_id)?;
+            Some(ReportLocation { file: path.display().to_string(), line: loc.line })
+        });
+        self.report.entries.push(entry);
+        self.report.entries.last_mut().unwrap()
+    }
+
+    /// inline 関数が `pTHX_` 引数（my_perl）を取るか
+    fn inline_fn_takes_my_perl(&self, func_def: &FunctionDef) -> bool {
+        func_def.declarator.derived.iter().any(|d| match d {
+            DerivedDecl::Function(params) => params.params.iter().any(|p| {
+                p.declarator.as_ref()
+                    .and_then(|d| d.name)
+                    .is_some_and(|n| self.interner.get(n) == "my_perl")
+            }),
+            _ => false,
+        })
+    }
+
     /// 全体を生成
     // デバッグ用: ビルド時のタイムスタンプを埋め込む場合はコメントを外す
     // const BUILD_TIMESTAMP: &'static str = "2025-01-24T17:50:00+09:00";
@@ -6054,6 +7026,7 @@ impl<'a, W: Write> CodegenDriver<'a, W> {
     pub fn generate(&mut self, result: &InferResult) -> io::Result<()> {
         // 対象 perl の threaded フラグを反映（is_thx_dependent 経路の防御的ガード）
         self.perl_threaded = result.perl_build_mode.is_threaded();
+        self.nullability = NullabilityDict::collect(result, self.config.nullability);
 
         // 自動生成 struct/typedef を先に決定（known_symbols 構築前）
         // 実際に出力される名前のみ known_symbols に登録するため、emit を先行実施。
@@ -6138,11 +7111,23 @@ impl<'a, W: Write> CodegenDriver<'a, W> {
             self.generate_inline_fns(result, &known_symbols)?;
         }
 
+        // 呼び出し側ローカル（sp, mark, ...）を使うマクロは macro_rules! にするので、
+        // オブジェクトマクロのセクションでは出力しない
+        self.stack_macros = StackMacroDict::collect(result, &known_symbols);
+
         // マクロセクション
         if self.config.emit_macros {
             self.generate_macros(result, &known_symbols)?;
         }
 
+        // オブジェクトマクロセクション
+        if self.config.emit_object_macros {
+            self.generate_object_macros(result, &known_symbols)?;
+        }
+
+        // 以降（libc の use 文、Pipeline が続けて出力する PERLVAR セクション）は共通部分
+        self.begin_segment(None);
+
         // 使用された libc 関数の use 文を出力（rustfmt が先頭に移動する）
         if !self.used_libc_fns.is_empty() {
             let mut fns: Vec<_> = self.used_libc_fns.iter().cloned().collect();
@@ -6266,6 +7251,7 @@ impl<'a, W: Write> CodegenDriver<'a, W> {
     /// - Pass 2: カスケード検査 — 生成成功した関数が失敗した inline 関数や
     ///   マクロを呼び出している場合、CASCADE_UNAVAILABLE に降格
     pub fn generate_inline_fns(&mut self, result: &InferResult, known_symbols: &KnownSymbols) -> io::Result<()> {
+        self.begin_segment(None);
         writeln!(self.writer, "// =============================================================================")?;
         writeln!(self.writer, "// Inline Functions")?;
         writeln!(self.writer, "// =============================================================================")?;
@@ -6287,7 +7273,7 @@ impl<'a, W: Write> CodegenDriver<'a, W> {
             UnresolvedNames { code: String, unresolved: Vec<String> },
             CodegenError { code: String, errors: Vec<String> },
             Incomplete { code: String },
-            Success { code: String, used_libc: HashSet<String> },
+            Success { code: String, used_libc: HashSet<String>, signature: Option<ReportSignature> },
             Suppressed { reason: String },
         }
 
@@ -6312,7 +7298,10 @@ impl<'a, W: Write> CodegenDriver<'a, W> {
                 continue;
             }
 
-            if block_items_contain_goto(&func_def.body.items) {
+            // ラベル付きブロック / ループに再構成できない goto は除外
+            if block_items_contain_goto(&func_def.body.items)
+                && !can_restructure_gotos(&func_def.body.items)
+            {
                 gen_results.push((**name, InlineGenResult::ContainsGoto));
                 continue;
             }
@@ -6322,6 +7311,8 @@ impl<'a, W: Write> CodegenDriver<'a, W> {
                 .with_dump_ast_for(self.config.dump_ast_for.clone())
                         .with_dump_types_for(self.config.dump_types_for.clone())
                 .with_fields_dict(&result.fields_dict)
+                .with_nullability(&self.nullability)
+                .with_apidoc_docs(self.config.pod_docs.then_some(&result.apidoc))
                 .with_bool_return(false, self.bool_return_macros.clone());
             let generated = codegen.generate_inline_fn(**name, func_def);
 
@@ -6339,6 +7330,7 @@ impl<'a, W: Write> CodegenDriver<'a, W> {
                 gen_results.push((**name, InlineGenResult::Success {
                     code: generated.code,
                     used_libc: generated.used_libc_fns,
+                    signature: generated.signature,
                 }));
             } else {
                 gen_results.push((**name, InlineGenResult::Incomplete {
@@ -6392,13 +7384,16 @@ impl<'a, W: Write> CodegenDriver<'a, W> {
 
         // Pass 3: 出力
         for (name, gen_result) in gen_results {
+            self.begin_item_segment(result, name, ReportItemKind::InlineFn);
             match gen_result {
                 InlineGenResult::CallsUnavailable => {
                     let name_str = self.interner.get(name);
                     let calls = result.inline_fn_dict.get_called_functions(name);
                     // 原因を分類: 純粋に存在しない関数を呼ぶ (absent) と
                     // 推移的に unavailable な依存先を呼ぶ (cascade) を区別する。
-                    let absent: Vec<String> = calls
+                    // called_functions は HashSet なので、出力が実行ごとに
+                    // 揺れないよう名前順に並べる（以下の一覧も同様）。
+                    let mut absent: Vec<String> = calls
                         .map(|cs| cs.iter()
                             .filter(|c| {
                                 let fn_name = self.interner.get(**c);
@@ -6407,7 +7402,8 @@ impl<'a, W: Write> CodegenDriver<'a, W> {
                             .map(|c| self.interner.get(*c).to_string())
                             .collect())
                         .unwrap_or_default();
-                    let cascade_deps: Vec<String> = calls
+                    absent.sort();
+                    let mut cascade_deps: Vec<String> = calls
                         .map(|cs| cs.iter()
                             .filter(|c| {
                                 let is_unavailable_inline = result.inline_fn_dict.get(**c).is_some()
@@ -6420,14 +7416,19 @@ impl<'a, W: Write> CodegenDriver<'a, W> {
                             .map(|c| self.interner.get(*c).to_string())
                             .collect())
                         .unwrap_or_default();
+                    cascade_deps.sort();
                     if absent.is_empty() {
                         writeln!(self.writer,
                             "// [CASCADE_UNAVAILABLE] {} - dependency not generated: {}",
                             name_str, cascade_deps.join(", "))?;
+                        self.record(result, name, ReportItemKind::InlineFn, ReportOutcome::CascadeUnavailable)
+                            .cascade_from = cascade_deps;
                     } else {
                         writeln!(self.writer,
                             "// [CALLS_UNAVAILABLE] {} - calls unavailable function(s): {}",
                             name_str, absent.join(", "))?;
+                        self.record(result, name, ReportItemKind::InlineFn, ReportOutcome::CallsUnavailable)
+                            .unavailable_calls = absent;
                     }
                     writeln!(self.writer)?;
                     self.stats.inline_fns_cascade_unavailable += 1;
@@ -6436,6 +7437,7 @@ impl<'a, W: Write> CodegenDriver<'a, W> {
                     let name_str = self.interner.get(name);
                     writeln!(self.writer, "// [CONTAINS_GOTO] {} - excluded (contains goto)", name_str)?;
                     writeln!(self.writer)?;
+                    self.record(result, name, ReportItemKind::InlineFn, ReportOutcome::ContainsGoto);
                     self.stats.inline_fns_contains_goto += 1;
                 }
                 InlineGenResult::Suppressed { reason } => {
@@ -6445,6 +7447,8 @@ impl<'a, W: Write> CodegenDriver<'a, W> {
                         name_str)?;
                     writeln!(self.writer, "// Reason: {}", reason)?;
                     writeln!(self.writer)?;
+                    self.record(result, name, ReportItemKind::InlineFn, ReportOutcome::Suppressed)
+                        .reason = Some(reason);
                 }
                 InlineGenResult::UnresolvedNames { code, unresolved } => {
                     let name_str = self.interner.get(name);
@@ -6454,6 +7458,8 @@ impl<'a, W: Write> CodegenDriver<'a, W> {
                         writeln!(self.writer, "// {}", line)?;
                     }
                     writeln!(self.writer)?;
+                    self.record(result, name, ReportItemKind::InlineFn, ReportOutcome::UnresolvedNames)
+                        .unresolved_names = unresolved;
                     self.stats.inline_fns_unresolved_names += 1;
                 }
                 InlineGenResult::CodegenError { code, errors } => {
@@ -6466,6 +7472,8 @@ impl<'a, W: Write> CodegenDriver<'a, W> {
                         writeln!(self.writer, "// {}", line)?;
                     }
                     writeln!(self.writer)?;
+                    self.record(result, name, ReportItemKind::InlineFn, ReportOutcome::CodegenError)
+                        .codegen_errors = errors;
                 }
                 InlineGenResult::Incomplete { code } => {
                     let name_str = self.interner.get(name);
@@ -6474,29 +7482,44 @@ impl<'a, W: Write> CodegenDriver<'a, W> {
                         writeln!(self.writer, "// {}", line)?;
                     }
                     writeln!(self.writer)?;
+                    self.record(result, name, ReportItemKind::InlineFn, ReportOutcome::TypeIncomplete);
                     self.stats.inline_fns_type_incomplete += 1;
                 }
-                InlineGenResult::Success { code, used_libc } => {
+                InlineGenResult::Success { code, used_libc, signature } => {
                     if self.successfully_generated_inlines.contains(&name) {
                         // 正常出力
                         write!(self.writer, "{}", code)?;
                         self.used_libc_fns.extend(used_libc.iter().cloned());
+                        self.record(result, name, ReportItemKind::InlineFn, ReportOutcome::Success)
+                            .signature = signature;
                         self.stats.inline_fns_success += 1;
                     } else {
                         // カスケード降格: 呼び出し先の inline 関数が codegen 時に失敗
                         let name_str = self.interner.get(name);
-                        let unavailable: Vec<String> = result.inline_fn_dict.get_called_functions(name)
+                        let mut unavailable: Vec<String> = result.inline_fn_dict.get_called_functions(name)
                             .map(|calls| calls.iter()
-                                .filter(|c| inline_set.contains(c) && !self.successfully_generated_inlines.contains(c))
+                                .filter(|c| {
+                                    if inline_set.contains(c) {
+                                        return !self.successfully_generated_inlines.contains(c);
+                                    }
+                                    // Pass 2 と同じ inline→macro 判定
+                                    result.infer_ctx.macros.get(c).is_some_and(|m| {
+                                        m.is_target && self.should_include_macro(m)
+                                            && !self.generatable_macros.contains(c)
+                                    })
+                                })
                                 .map(|c| self.interner.get(*c).to_string())
                                 .collect())
                             .unwrap_or_default();
+                        unavailable.sort();
                         writeln!(self.writer, "// [CASCADE_UNAVAILABLE] {} - dependency not generated: {}",
                             name_str, unavailable.join(", "))?;
                         for line in code.lines() {
                             writeln!(self.writer, "// {}", line)?;
                         }
                         writeln!(self.writer)?;
+                        self.record(result, name, ReportItemKind::InlineFn, ReportOutcome::CascadeUnavailable)
+                            .cascade_from = unavailable;
                         self.stats.inline_fns_cascade_unavailable += 1;
                     }
                 }
@@ -6509,14 +7532,19 @@ impl<'a, W: Write> CodegenDriver<'a, W> {
 
     /// マクロセクションを生成
     pub fn generate_macros(&mut self, result: &InferResult, known_symbols: &KnownSymbols) -> io::Result<()> {
+        self.begin_segment(None);
         writeln!(self.writer, "// =============================================================================")?;
         writeln!(self.writer, "// Macro Functions")?;
         writeln!(self.writer, "// =============================================================================")?;
         writeln!(self.writer)?;
 
+        // 呼び出し側ローカル（sp, mark, ...）を使うマクロはオブジェクトマクロも含める
+        // （生成中は self を可変で借りるので、辞書は一時的に取り出す）
+        let stack_macros = std::mem::take(&mut self.stack_macros);
+
         // 対象マクロを収集
         let macros: Vec<_> = result.infer_ctx.macros.iter()
-            .filter(|(_, info)| self.should_include_macro(info))
+            .filter(|(name, info)| self.should_include_macro(info) || stack_macros.contains(**name))
             .collect();
         let included_set: HashSet<InternedStr> = macros.iter().map(|(n, _)| **n).collect();
 
@@ -6544,11 +7572,13 @@ impl<'a, W: Write> CodegenDriver<'a, W> {
 
         for name in sorted_names {
             let info = result.infer_ctx.macros.get(&name).unwrap();
+            let kind = if info.is_function { ReportItemKind::Macro } else { ReportItemKind::ObjectMacro };
 
             // ── apidoc skip_codegen 対象なら早期に [CODEGEN_SUPPRESSED] ──
             // Phase 2 (Step 4.4) で `info.apidoc_suppressed` が立っているので
             // それを参照する。reason 文字列は apidoc_patches から取得。
             if info.apidoc_suppressed {
+                self.begin_item_segment(result, name, kind);
                 let name_str_for_patch = self.interner.get(name);
                 let reason = result.apidoc_patches.skip_reason(name_str_for_patch)
                     .unwrap_or("apidoc skip_codegen");
@@ -6558,6 +7588,8 @@ impl<'a, W: Write> CodegenDriver<'a, W> {
                     name_str_for_patch, thx_info)?;
                 writeln!(self.writer, "// Reason: {}", reason)?;
                 writeln!(self.writer)?;
+                self.record(result, name, kind, ReportOutcome::Suppressed)
+                    .reason = Some(reason.to_string());
                 continue;
             }
 
@@ -6566,7 +7598,7 @@ impl<'a, W: Write> CodegenDriver<'a, W> {
             // カスケード失敗。マクロ→マクロ依存と マクロ→inline 関数依存の両方を検査。
             // （uses ではなく called_functions を使う：uses はインライン展開された
             //   マクロも含むが、called_functions は AST 上の Call 式のみ）
-            let unavailable_deps: Vec<String> = info.called_functions.iter()
+            let mut unavailable_deps: Vec<String> = info.called_functions.iter()
                 .filter(|called| {
                     // Case 1: マクロ→マクロ依存
                     if included_set.contains(called) {
@@ -6586,10 +7618,76 @@ impl<'a, W: Write> CodegenDriver<'a, W> {
                 })
                 .map(|called| self.interner.get(*called).to_string())
                 .collect();
+            unavailable_deps.sort();
+
+            // ── 呼び出し側ローカルを使うマクロ ──
+            // `$sp:ident` のように変数を受け取る macro_rules! にする。呼び出すのは
+            // 他のスタックマクロだけなので、成功すれば successfully_generated に入れる。
+            if let Some(locals) = stack_macros.get(name) {
+                let status = self.get_macro_status(info);
+                // 型が決まらず生成できなかった式マクロ（`EXTEND` の `_EXTEND_NEEDS_GROW` など）は
+                // 本体に展開する。macro_rules! の中なので型は呼び出し側で決まる。
+                let inline_macros: HashSet<InternedStr> = info.called_functions.iter()
+                    .filter(|called| unavailable_deps.iter().any(|d| d == self.interner.get(**called)))
+                    .filter(|called| self.is_inlinable_helper(**called, info, result, &stack_macros, &included_set, &successfully_generated))
+                    .copied()
+                    .collect();
+                let unavailable_deps: Vec<String> = unavailable_deps.iter()
+                    .filter(|d| !inline_macros.iter().any(|m| self.interner.get(*m) == d.as_str()))
+                    .cloned()
+                    .collect();
+                let generated = (unavailable_deps.is_empty()
+                    && matches!(status, GenerateStatus::Success | GenerateStatus::TypeIncomplete))
+                    .then(|| RustCodegen::new(self.interner, self.enum_dict, self.macro_ctx, self.bindings_info.clone(), known_symbols, result.rust_decl_dict.as_ref(), Some(&result.inline_fn_dict))
+                        .with_perl_threaded(self.perl_threaded)
+                        .with_fields_dict(&result.fields_dict)
+                        .with_nullability(&self.nullability)
+                        .with_apidoc_docs(self.config.pod_docs.then_some(&result.apidoc))
+                        .with_stack_macros(&stack_macros)
+                        .with_inline_macros(inline_macros)
+                        .with_macro_path(self.config.macro_path.as_deref())
+                        .generate_stack_macro_rules(info, locals))
+                    .flatten()
+                    .filter(|g| g.is_complete() && !g.has_unresolved_names() && g.codegen_errors.is_empty());
+                if let Some(generated) = generated {
+                    self.begin_item_segment(result, name, kind);
+                    write!(self.writer, "{}", generated.code)?;
+                    self.used_libc_fns.extend(generated.used_libc_fns);
+                    self.stats.macros_stack_rules += 1;
+                    successfully_generated.insert(name);
+                    self.record(result, name, kind, ReportOutcome::MacroRules)
+                        .generate_status = Some(status);
+                    continue;
+                }
+                // オブジェクトマクロは生成できなければ従来どおり出力からは省き、
+                // レポートにだけ理由を残す
+                if !info.is_function {
+                    let outcome = match status {
+                        _ if !unavailable_deps.is_empty() => ReportOutcome::CascadeUnavailable,
+                        GenerateStatus::ParseFailed => ReportOutcome::ParseFailed,
+                        GenerateStatus::TypeIncomplete => ReportOutcome::TypeIncomplete,
+                        GenerateStatus::CallsUnavailable => ReportOutcome::CallsUnavailable,
+                        GenerateStatus::ContainsGoto => ReportOutcome::ContainsGoto,
+                        GenerateStatus::GenericUnsupported => ReportOutcome::GenericUnsupported,
+                        GenerateStatus::Success | GenerateStatus::Skip => ReportOutcome::CodegenError,
+                    };
+                    let entry = self.record(result, name, kind, outcome);
+                    entry.generate_status = Some(status);
+                    entry.cascade_from = unavailable_deps;
+                    continue;
+                }
+            }
+
+            // ここから先はどの分岐もコメントかコードを出力する
+            self.begin_item_segment(result, name, kind);
 
             if !unavailable_deps.is_empty() {
                 self.generate_macro_cascade_unavailable(info, &unavailable_deps)?;
                 self.stats.macros_cascade_unavailable += 1;
+                let status = self.get_macro_status(info);
+                let entry = self.record(result, name, kind, ReportOutcome::CascadeUnavailable);
+                entry.generate_status = Some(status);
+                entry.cascade_from = unavailable_deps;
                 continue;
             }
 
@@ -6606,8 +7704,11 @@ impl<'a, W: Write> CodegenDriver<'a, W> {
                         .with_dump_ast_for(self.config.dump_ast_for.clone())
                         .with_dump_types_for(self.config.dump_types_for.clone())
                         .with_fields_dict(&result.fields_dict)
+                        .with_nullability(&self.nullability)
+                        .with_apidoc_docs(self.config.pod_docs.then_some(&result.apidoc))
                         .with_const_pointer_positions(const_positions)
-                        .with_bool_return(is_bool, self.bool_return_macros.clone());
+                        .with_bool_return(is_bool, self.bool_return_macros.clone())
+                        .with_macro_purity(&self.macro_purity);
                     let generated = codegen.generate_macro(info);
 
                     if generated.has_unresolved_names() {
@@ -6621,6 +7722,9 @@ impl<'a, W: Write> CodegenDriver<'a, W> {
                         }
                         writeln!(self.writer)?;
                         self.stats.macros_unresolved_names += 1;
+                        let entry = self.record(result, name, kind, ReportOutcome::UnresolvedNames);
+                        entry.generate_status = Some(status);
+                        entry.unresolved_names = generated.unresolved_names;
                     } else if !generated.codegen_errors.is_empty() {
                         // codegen エラー検出：コメントアウトして問題点列挙
                         let name_str = self.interner.get(info.name);
@@ -6633,12 +7737,26 @@ impl<'a, W: Write> CodegenDriver<'a, W> {
                             writeln!(self.writer, "// {}", line)?;
                         }
                         writeln!(self.writer)?;
+                        let entry = self.record(result, name, kind, ReportOutcome::CodegenError);
+                        entry.generate_status = Some(status);
+                        entry.codegen_errors = generated.codegen_errors;
                     } else if generated.is_complete() {
                         // 完全な生成：そのまま出力
                         write!(self.writer, "{}", generated.code)?;
                         self.used_libc_fns.extend(generated.used_libc_fns.iter().cloned());
                         self.stats.macros_success += 1;
+                        match generated.purity {
+                            Purity::Safe => self.stats.macros_safe_fn += 1,
+                            Purity::Const => self.stats.macros_const_fn += 1,
+                            Purity::Unsafe => {}
+                        }
+                        if generated.purity != Purity::Unsafe {
+                            self.macro_purity.insert(name, generated.purity);
+                        }
                         successfully_generated.insert(name);
+                        let entry = self.record(result, name, kind, ReportOutcome::Success);
+                        entry.generate_status = Some(status);
+                        entry.signature = generated.signature;
                     } else {
                         // 不完全な生成：コメントアウトして出力
                         let name_str = self.interner.get(info.name);
@@ -6649,30 +7767,37 @@ impl<'a, W: Write> CodegenDriver<'a, W> {
                         }
                         writeln!(self.writer)?;
                         self.stats.macros_type_incomplete += 1;
+                        self.record(result, name, kind, ReportOutcome::TypeIncomplete)
+                            .generate_status = Some(status);
                     }
                 }
                 GenerateStatus::ParseFailed => {
                     self.generate_macro_parse_failed(info)?;
                     self.stats.macros_parse_failed += 1;
+                    self.record(result, name, kind, ReportOutcome::ParseFailed)
+                        .generate_status = Some(status);
                 }
                 GenerateStatus::TypeIncomplete => {
                     self.generate_macro_type_incomplete(info, result)?;
                     self.stats.macros_type_incomplete += 1;
+                    self.record(result, name, kind, ReportOutcome::TypeIncomplete)
+                        .generate_status = Some(status);
                 }
                 GenerateStatus::CallsUnavailable => {
                     // 原因を分類: 「実在しない関数を呼ぶ」vs「推移的に
                     // unavailable な依存先を呼ぶ」。前者なら [CALLS_UNAVAILABLE]、
                     // 後者なら [CASCADE_UNAVAILABLE] を出す。
-                    let absent: Vec<&str> = info.called_functions.iter()
+                    let mut absent: Vec<&str> = info.called_functions.iter()
                         .filter(|fn_id| {
                             let fn_name = self.interner.get(**fn_id);
                             !self.is_function_available(**fn_id, fn_name, result)
                         })
                         .map(|fn_id| self.interner.get(*fn_id))
                         .collect();
+                    absent.sort();
                     if absent.is_empty() {
                         // 純粋に推移依存。unavailable な依存先を列挙
-                        let cascade_deps: Vec<String> = info.called_functions.iter()
+                        let mut cascade_deps: Vec<String> = info.called_functions.iter()
                             .filter(|fn_id| {
                                 if let Some(m) = result.infer_ctx.macros.get(*fn_id) {
                                     return m.is_unavailable_for_codegen();
@@ -6685,19 +7810,49 @@ impl<'a, W: Write> CodegenDriver<'a, W> {
                             })
                             .map(|fn_id| self.interner.get(*fn_id).to_string())
                             .collect();
+                        cascade_deps.sort();
                         self.generate_macro_cascade_unavailable(info, &cascade_deps)?;
                         self.stats.macros_cascade_unavailable += 1;
+                        let entry = self.record(result, name, kind, ReportOutcome::CascadeUnavailable);
+                        entry.generate_status = Some(status);
+                        entry.cascade_from = cascade_deps;
                     } else {
+                        let absent: Vec<String> = absent.iter().map(|s| s.to_string()).collect();
                         self.generate_macro_calls_unavailable(info, result)?;
                         self.stats.macros_calls_unavailable += 1;
+                        let entry = self.record(result, name, kind, ReportOutcome::CallsUnavailable);
+                        entry.generate_status = Some(status);
+                        entry.unavailable_calls = absent;
                     }
                 }
                 GenerateStatus::ContainsGoto => {
                     let name_str = self.interner.get(info.name);
                     writeln!(self.writer, "// [CONTAINS_GOTO] {} - excluded (contains goto)", name_str)?;
                     writeln!(self.writer)?;
+                    self.record(result, name, kind, ReportOutcome::ContainsGoto)
+                        .generate_status = Some(status);
                 }
                 GenerateStatus::GenericUnsupported => {
+                    // type/cast 引数を持つマクロは macro_rules! として出力を試みる。
+                    // 呼び出し側は関数呼び出しとして生成されるため successfully_generated
+                    // には入れない（カスケード判定上は従来どおり生成不可扱い）。
+                    let rules = (!has_ambiguous_type_param_cast(info)).then(|| RustCodegen::new(self.interner, self.enum_dict, self.macro_ctx, self.bindings_info.clone(), known_symbols, result.rust_decl_dict.as_ref(), Some(&result.inline_fn_dict))
+                        .with_perl_threaded(self.perl_threaded)
+                        .with_fields_dict(&result.fields_dict)
+                        .with_nullability(&self.nullability)
+                        .with_apidoc_docs(self.config.pod_docs.then_some(&result.apidoc))
+                        .with_macro_path(self.config.macro_path.as_deref())
+                        .generate_macro_rules(info)).flatten();
+                    if let Some(generated) = rules.filter(|g| {
+                        g.is_complete() && !g.has_unresolved_names() && g.codegen_errors.is_empty()
+                    }) {
+                        write!(self.writer, "{}", generated.code)?;
+                        self.used_libc_fns.extend(generated.used_libc_fns);
+                        self.stats.macros_generic_rules += 1;
+                        self.record(result, name, kind, ReportOutcome::MacroRules)
+                            .generate_status = Some(status);
+                        continue;
+                    }
                     let name_str = self.interner.get(info.name);
                     let thx_info = if info.is_thx_dependent { " [THX]" } else { "" };
                     writeln!(self.writer, "// [GENERIC_UNSUPPORTED] {}{} - Rust cannot cast to generic type T", name_str, thx_info)?;
@@ -6708,6 +7863,7 @@ impl<'a, W: Write> CodegenDriver<'a, W> {
                     let codegen = RustCodegen::new(self.interner, self.enum_dict, self.macro_ctx, self.bindings_info.clone(), known_symbols, result.rust_decl_dict.as_ref(), Some(&result.inline_fn_dict))
                         .with_perl_threaded(self.perl_threaded)
                         .with_fields_dict(&result.fields_dict)
+                        .with_nullability(&self.nullability)
                         .with_const_pointer_positions(const_positions)
                         .with_bool_return(is_bool, self.bool_return_macros.clone());
                     let generated = codegen.generate_macro(info);
@@ -6716,6 +7872,8 @@ impl<'a, W: Write> CodegenDriver<'a, W> {
                     }
                     writeln!(self.writer)?;
                     self.stats.macros_generic_unsupported += 1;
+                    self.record(result, name, kind, ReportOutcome::GenericUnsupported)
+                        .generate_status = Some(status);
                 }
                 GenerateStatus::Skip => {
                     // 何もしない
@@ -6723,9 +7881,220 @@ impl<'a, W: Write> CodegenDriver<'a, W> {
             }
         }
 
+        self.stack_macros = stack_macros;
+        Ok(())
+    }
+
+    /// オブジェクトマクロセクションを生成
+    ///
+    /// 定数畳み込みに成功したものは `pub const`、それ以外の式マクロは
+    /// 引数なしの `#[inline] pub unsafe fn` として出力する。
+    /// 関数形式にも落とせないものはコメントも残さずに省く
+    /// （オブジェクトマクロは数が多く、失敗理由の列挙はノイズになるため）。
+    pub fn generate_object_macros(&mut self, result: &InferResult, known_symbols: &KnownSymbols) -> io::Result<()> {
+        let mut macros: Vec<&MacroInferInfo> = result.infer_ctx.macros.values()
+            .filter(|info| self.should_include_object_macro(info, result))
+            .collect();
+        if macros.is_empty() {
+            return Ok(());
+        }
+        macros.sort_by_key(|info| self.interner.get(info.name));
+
+        self.begin_segment(None);
+        writeln!(self.writer, "// =============================================================================")?;
+        writeln!(self.writer, "// Object Macros")?;
+        writeln!(self.writer, "// =============================================================================")?;
+        writeln!(self.writer)?;
+
+        // 区間は実際に出力するときだけ開く（省いたマクロで空の OutputItem を作らない）
+        for info in macros {
+            let new_codegen = || RustCodegen::new(
+                self.interner, self.enum_dict, self.macro_ctx,
+                self.bindings_info.clone(), known_symbols,
+                result.rust_decl_dict.as_ref(), Some(&result.inline_fn_dict),
+            ).with_perl_threaded(self.perl_threaded);
+
+            match new_codegen().generate_object_macro_const(info) {
+                Ok(Some(generated)) => {
+                    self.begin_item_segment(result, info.name, ReportItemKind::ObjectMacro);
+                    write!(self.writer, "{}", generated.code)?;
+                    self.stats.object_macros_const += 1;
+                    self.record(result, info.name, ReportItemKind::ObjectMacro, ReportOutcome::Const)
+                        .signature = generated.signature;
+                    continue;
+                }
+                Ok(None) => {}
+                // 関数にしても同じ型で値が変わるので出力しない
+                Err(reason) => {
+                    self.record(result, info.name, ReportItemKind::ObjectMacro, ReportOutcome::CodegenError)
+                        .codegen_errors.push(reason);
+                    continue;
+                }
+            }
+
+            if self.get_macro_status(info) != GenerateStatus::Success {
+                continue;
+            }
+            let ParseResult::Expression(expr) = &info.parse_result else {
+                continue;
+            };
+            if !self.object_macro_is_closed(expr, info, result) {
+                continue;
+            }
+            let Some(generated) = new_codegen()
+                .with_fields_dict(&result.fields_dict)
+                .with_nullability(&self.nullability)
+                .with_apidoc_docs(self.config.pod_docs.then_some(&result.apidoc))
+                // 比較・論理演算の式は C では int だが bool として返す（関数マクロと同じ扱い）
+                .with_bool_return(info.is_bool_return || is_boolean_expr(expr), self.bool_return_macros.clone())
+                .with_macro_purity(&self.macro_purity)
+                .generate_object_macro_fn(info)
+            else {
+                continue;
+            };
+            if generated.is_complete()
+                && !generated.has_unresolved_names()
+                && generated.codegen_errors.is_empty()
+            {
+                self.begin_item_segment(result, info.name, ReportItemKind::ObjectMacro);
+                write!(self.writer, "{}", generated.code)?;
+                self.used_libc_fns.extend(generated.used_libc_fns);
+                self.stats.object_macros_fn += 1;
+                match generated.purity {
+                    Purity::Safe => self.stats.macros_safe_fn += 1,
+                    Purity::Const => self.stats.macros_const_fn += 1,
+                    Purity::Unsafe => {}
+                }
+                self.record(result, info.name, ReportItemKind::ObjectMacro, ReportOutcome::Success)
+                    .signature = generated.signature;
+            }
+        }
+
         Ok(())
     }
 
+    /// スタックマクロの本体に展開できる補助マクロか
+    ///
+    /// 本体が式で、呼び出し側ローカルを使わず、THX 依存なら呼び出し側も THX 依存で、
+    /// 自身が呼ぶ生成対象マクロがすべて生成済みのもの。
+    fn is_inlinable_helper(
+        &self,
+        callee: InternedStr,
+        caller: &MacroInferInfo,
+        result: &InferResult,
+        stack_macros: &StackMacroDict,
+        included_set: &HashSet<InternedStr>,
+        successfully_generated: &HashSet<InternedStr>,
+    ) -> bool {
+        let Some(info) = result.infer_ctx.macros.get(&callee) else { return false };
+        info.is_expression()
+            && !stack_macros.contains(callee)
+            && !info.is_unavailable_for_codegen()
+            && (!info.is_thx_dependent || caller.is_thx_dependent)
+            && info.called_functions.iter()
+                .all(|c| !included_set.contains(c) || successfully_generated.contains(c))
+    }
+
+    /// オブジェクトマクロを出力対象にするかどうか
+    fn should_include_object_macro(&self, info: &MacroInferInfo, result: &InferResult) -> bool {
+        if !info.is_target || !info.has_body || info.is_function {
+            return false;
+        }
+        if info.apidoc_suppressed || !info.is_expression() {
+            return false;
+        }
+        // `DOUBLEINFBYTES` や `SIG_NAME` のような配列の初期化子用の並び
+        // （トップレベルのカンマ式）は 1 つの値にならない
+        if let ParseResult::Expression(expr) = &info.parse_result
+            && matches!(expr.kind, ExprKind::Comma { .. })
+        {
+            return false;
+        }
+        // スタックマクロは macro_rules! として出力済み
+        if self.stack_macros.contains(info.name) {
+            return false;
+        }
+        let name = self.interner.get(info.name);
+        // bindgen が既に定数・関数として出力している名前は重複になる
+        if let Some(dict) = &result.rust_decl_dict
+            && (dict.consts.contains_key(name)
+                || dict.fns.contains_key(name)
+                || dict.statics.contains(name)
+                || dict.types.contains_key(name)
+                || dict.structs.contains_key(name))
+        {
+            return false;
+        }
+        if result.inline_fn_dict.get(info.name).is_some() {
+            return false;
+        }
+        // PL_xxx は PERLVAR アクセサマクロ側で扱う
+        if let Some(var) = name.strip_prefix("PL_")
+            && result.perlvar_dict.get(var).is_some()
+        {
+            return false;
+        }
+        true
+    }
+
+    /// オブジェクトマクロ本体が展開先のローカル変数に依存しないか
+    ///
+    /// `#define PUTBACK PL_stack_sp = sp` のように展開先スコープの変数を
+    /// 暗黙に参照するマクロは、引数なし関数にすると名前が解決できない。
+    /// 値として参照してよいのは bindings の定数・static と enum バリアント、
+    /// 呼び出し位置に置けるのは bindings の関数・inline 関数・生成可能な関数マクロのみ。
+    /// `#define foo Perl_foo` のような関数名の別名もここで除外される。
+    fn object_macro_is_closed(&self, expr: &Expr, info: &MacroInferInfo, result: &InferResult) -> bool {
+        let mut closed = true;
+        self.check_object_macro_closed(expr, info, result, &mut closed);
+        closed
+    }
+
+    fn check_object_macro_closed(&self, expr: &Expr, info: &MacroInferInfo, result: &InferResult, closed: &mut bool) {
+        if !*closed {
+            return;
+        }
+        match &expr.kind {
+            ExprKind::Call { func, args } => {
+                let is_callable = match &func.kind {
+                    ExprKind::Ident(name) => {
+                        result.rust_decl_dict.as_ref()
+                            .is_some_and(|d| d.fns.contains_key(self.interner.get(*name)))
+                            || result.inline_fn_dict.get(*name).is_some()
+                            || self.generatable_macros.contains(name)
+                    }
+                    _ => false,
+                };
+                if !is_callable {
+                    *closed = false;
+                    return;
+                }
+                for arg in args {
+                    self.check_object_macro_closed(arg, info, result, closed);
+                }
+            }
+            ExprKind::Ident(name) => {
+                let name_str = self.interner.get(*name);
+                let is_global = result.rust_decl_dict.as_ref().is_some_and(|d| {
+                    d.consts.contains_key(name_str) || d.statics.contains(name_str)
+                })
+                    || self.enum_dict.is_enum_variant(*name)
+                    || (name_str == "my_perl" && info.is_thx_dependent && self.perl_threaded);
+                if !is_global {
+                    *closed = false;
+                }
+            }
+            // sizeof は usize になり、推論された戻り値型 (c_ulong) と合わない
+            ExprKind::StmtExpr(_)
+            | ExprKind::CompoundLit { .. }
+            | ExprKind::Sizeof(_)
+            | ExprKind::SizeofType(_) => *closed = false,
+            _ => crate::macro_infer::walk_expr_children(expr, &mut |e| {
+                self.check_object_macro_closed(e, info, result, closed)
+            }),
+        }
+    }
+
     /// マクロを依存順にソート（葉マクロ先頭、循環はアルファベット順で末尾）
     ///
     /// Kahn's algorithm を使用。`info.uses` の関係から DAG を構築し、
@@ -6887,7 +8256,11 @@ impl<'a, W: Write> CodegenDriver<'a, W> {
 
         // エラーメッセージがあれば出力
         if let ParseResult::Unparseable(Some(err_msg)) = &info.parse_result {
-            writeln!(self.writer, "// Error: {}", err_msg)?;
+            let mut lines = err_msg.lines();
+            writeln!(self.writer, "// Error: {}", lines.next().unwrap_or_default())?;
+            for line in lines {
+                writeln!(self.writer, "// {}", line)?;
+            }
         }
 
         // TODO: トークン列の S 式出力（Phase 2 で詳細実装）
@@ -6917,7 +8290,7 @@ impl<'a, W: Write> CodegenDriver<'a, W> {
         writeln!(self.writer, "// [CALLS_UNAVAILABLE] {}{}{} - calls unavailable function(s)", name_str, params_str, thx_info)?;
 
         // 利用不可関数を特定して出力
-        let unavailable_fns: Vec<_> = info.called_functions.iter()
+        let mut unavailable_fns: Vec<_> = info.called_functions.iter()
             .filter(|&fn_id| {
                 let fn_name = self.interner.get(*fn_id);
                 // bindings.rs にもマクロにも存在しない関数を検出
@@ -6925,6 +8298,7 @@ impl<'a, W: Write> CodegenDriver<'a, W> {
             })
             .map(|fn_id| self.interner.get(*fn_id))
             .collect();
+        unavailable_fns.sort();
 
         if !unavailable_fns.is_empty() {
             writeln!(self.writer, "// Unavailable: {}", unavailable_fns.join(", "))?;
@@ -7096,3 +8470,277 @@ impl<'a, W: Write> CodegenDriver<'a, W> {
         "<unknown>".to_string()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::type_env::TypeConstraint;
+    use crate::type_repr::{CTypeSource, CTypeSpecs, InferredType, TypeRepr};
+    use crate::pp_expr::FoldedConst;
+
+    #[test]
+    fn test_resolve_integer_type() {
+        assert_eq!(resolve_integer_type("c_int", None), Some((true, 4)));
+        assert_eq!(resolve_integer_type(" U32 ", None), Some((false, 4)));
+        assert_eq!(resolve_integer_type("Size_t", None), None);
+        assert_eq!(resolve_integer_type("*mut SV", None), None);
+
+        let dict = RustDeclDict::parse("pub type Size_t = usize;\npub type Off_t = Size_t;\n");
+        assert_eq!(resolve_integer_type("Size_t", Some(&dict)), Some((false, 8)));
+        assert_eq!(resolve_integer_type("Off_t", Some(&dict)), Some((false, 8)));
+
+        // 循環 alias は打ち切る
+        let cyclic = RustDeclDict::parse("pub type A = B;\npub type B = A;\n");
+        assert_eq!(resolve_integer_type("A", Some(&cyclic)), None);
+    }
+
+    /// `#define NAME(t, x) ((t)(x))` 相当のジェネリックマクロを `generate_macro_rules` に通す
+    fn gen_cast_macro_rules(macro_path: Option<&str>) -> String {
+        let mut interner = StringInterner::new();
+        let loc = SourceLocation::default();
+        let t = interner.intern("t");
+        let x = interner.intern("x");
+        let mut info = MacroInferInfo::new(interner.intern("CAST_TO"));
+        info.params = vec![MacroParam::new(t, loc.clone()), MacroParam::new(x, loc.clone())];
+        info.generic_type_params.insert(0, "T".to_string());
+        let type_name = crate::ast::TypeName {
+            specs: DeclSpecs { type_specs: vec![TypeSpec::TypedefName(t)], ..Default::default() },
+            declarator: None,
+        };
+        let cast = ExprKind::Cast {
+            type_name: Box::new(type_name),
+            expr: Box::new(Expr::new(ExprKind::Ident(x), loc.clone())),
+        };
+        info.parse_result = ParseResult::Expression(Box::new(Expr::new(cast, loc)));
+
+        let enum_dict = EnumDict::new();
+        let macro_ctx = MacroInferContext::new();
+        let known_symbols = KnownSymbols { names: HashSet::new() };
+        RustCodegen::new(
+            &interner, &enum_dict, &macro_ctx, BindingsInfo::default(),
+            &known_symbols, None, None,
+        )
+        .with_macro_path(macro_path)
+        .generate_macro_rules(&info)
+        .unwrap()
+        .code
+    }
+
+    #[test]
+    fn test_macro_rules_codegen() {
+        let code = gen_cast_macro_rules(None);
+        assert!(code.contains("#[macro_export]\nmacro_rules! CAST_TO {\n"), "{code}");
+        assert!(code.contains("($t:ty, $x:expr) => {{\n"), "{code}");
+        assert!(code.contains("        use $crate::*;\n"), "{code}");
+        assert!(code.contains("$x as $t"), "{code}");
+        assert!(!code.contains(MACRO_RULES_PARAM_PREFIX), "{code}");
+    }
+
+    #[test]
+    fn test_macro_rules_use_macro_path() {
+        let code = gen_cast_macro_rules(Some("ffi::macros"));
+        assert!(code.contains("        use $crate::ffi::macros::*;\n"), "{code}");
+        assert!(!code.contains("use $crate::*;"), "{code}");
+
+        // `crate::` 付き・クレートルートの指定も受け付ける
+        let code = gen_cast_macro_rules(Some("crate::ffi"));
+        assert!(code.contains("        use $crate::ffi::*;\n"), "{code}");
+        let code = gen_cast_macro_rules(Some("crate"));
+        assert!(code.contains("        use $crate::*;\n"), "{code}");
+    }
+
+    /// C ソースの最後の関数定義をパースする
+    fn parse_c_fn(src: &str) -> (crate::preprocessor::Preprocessor, FunctionDef) {
+        let mut pp = crate::preprocessor::Preprocessor::new(crate::preprocessor::PPConfig::default());
+        pp.add_source_str("test.c", src);
+        let tu = crate::parser::Parser::new(&mut pp).unwrap().parse().unwrap();
+        let func_def = tu.decls.into_iter()
+            .filter_map(|d| match d {
+                crate::ast::ExternalDecl::FunctionDef(f) => Some(f),
+                _ => None,
+            })
+            .next_back()
+            .unwrap();
+        (pp, func_def)
+    }
+
+    #[test]
+    fn test_macro_rules_statement_body_keeps_declarations() {
+        // `#define CAST_INC(t, x) STMT_START { t tmp = (t)(x); tmp++; } STMT_END` 相当
+        let (mut pp, func_def) = parse_c_fn(
+            "typedef int t;\n\
+             void f(int x) { t tmp = (t)x; tmp++; }\n");
+        let t = pp.interner_mut().intern("t");
+        let x = pp.interner_mut().intern("x");
+        let name = pp.interner_mut().intern("CAST_INC");
+        let loc = SourceLocation::default();
+        let mut info = MacroInferInfo::new(name);
+        info.params = vec![MacroParam::new(t, loc.clone()), MacroParam::new(x, loc)];
+        info.generic_type_params.insert(0, "T".to_string());
+        info.parse_result = ParseResult::Statement(func_def.body.items);
+
+        let enum_dict = EnumDict::new();
+        let macro_ctx = MacroInferContext::new();
+        let known_symbols = KnownSymbols { names: HashSet::new() };
+        let code = RustCodegen::new(
+            pp.interner(), &enum_dict, &macro_ctx, BindingsInfo::default(),
+            &known_symbols, None, None,
+        )
+        .generate_macro_rules(&info)
+        .unwrap()
+        .code;
+        assert!(code.contains("let mut tmp"), "{code}");
+        assert!(code.contains("$x as $t"), "{code}");
+    }
+
+    fn gen_inline_fn(src: &str) -> String {
+        let (pp, func_def) = parse_c_fn(src);
+        assert!(can_restructure_gotos(&func_def.body.items));
+        let enum_dict = EnumDict::new();
+        let macro_ctx = MacroInferContext::new();
+        let known_symbols = KnownSymbols { names: HashSet::new() };
+        let name = func_def.declarator.name.unwrap();
+        RustCodegen::new(
+            pp.interner(), &enum_dict, &macro_ctx, BindingsInfo::default(),
+            &known_symbols, None, None,
+        )
+        .generate_inline_fn(name, &func_def)
+        .code
+    }
+
+    #[test]
+    fn test_inline_fn_records_signature() {
+        let (pp, func_def) = parse_c_fn("static int SvOK_off(int flags, char *p) { return flags; }\n");
+        let enum_dict = EnumDict::new();
+        let macro_ctx = MacroInferContext::new();
+        let known_symbols = KnownSymbols { names: HashSet::new() };
+        let generated = RustCodegen::new(
+            pp.interner(), &enum_dict, &macro_ctx, BindingsInfo::default(),
+            &known_symbols, None, None,
+        )
+        .generate_inline_fn(func_def.declarator.name.unwrap(), &func_def);
+        let sig = generated.signature.unwrap();
+        assert_eq!(sig.params, vec!["flags: c_int", "p: *mut c_char"]);
+        assert_eq!(sig.ret, "c_int");
+    }
+
+    #[test]
+    fn test_goto_region_keeps_inner_loop_break() {
+        let code = gen_inline_fn(
+            "static int f(int n) {\n\
+             retry:\n\
+                 while (n > 10) { n--; if (n == 15) break; }\n\
+                 if (n > 5) { n--; goto retry; }\n\
+                 return n;\n\
+             }\n");
+        assert!(code.contains("'retry: loop {"), "{code}");
+        assert!(code.contains("continue 'retry;"), "{code}");
+        // 内側の while を対象とする break はラベルなしのまま
+        let while_pos = code.find("while ").unwrap();
+        let break_pos = code.find("break;").unwrap();
+        assert!(while_pos < break_pos, "{code}");
+        assert!(!code.contains("break 'retry;"), "{code}");
+    }
+
+    #[test]
+    fn test_goto_region_with_outer_break_is_not_lowered() {
+        // ラベル付きブロックの中の break は外側の for を対象にできない
+        let (_pp, func_def) = parse_c_fn(
+            "static int f(int n) {\n\
+                 int i;\n\
+                 for (i = 0; i < n; i++) {\n\
+                     if (i == 3) goto out;\n\
+                     if (i == 5) break;\n\
+                 out:\n\
+                     n--;\n\
+                 }\n\
+                 return n;\n\
+             }\n");
+        assert!(!can_restructure_gotos(&func_def.body.items));
+        // continue も同様（後方リージョンの loop に捕まる）
+        let (_pp, func_def) = parse_c_fn(
+            "static int f(int n) {\n\
+                 while (n > 0) {\n\
+                 again:\n\
+                     n--;\n\
+                     if (n == 7) continue;\n\
+                     if (n == 3) goto again;\n\
+                 }\n\
+                 return n;\n\
+             }\n");
+        assert!(!can_restructure_gotos(&func_def.body.items));
+    }
+
+    /// オブジェクトマクロを 1 つ `generate_object_macro_const` に通す
+    fn gen_object_const(
+        interner: &mut StringInterner,
+        name: &str,
+        const_value: Option<FoldedConst>,
+        kind: ExprKind,
+        return_type: Option<TypeRepr>,
+    ) -> Result<Option<String>, String> {
+        let mut info = MacroInferInfo::new(interner.intern(name));
+        info.const_value = const_value;
+        let expr = Expr::new(kind, SourceLocation::default());
+        if let Some(ty) = return_type {
+            info.type_env.add_return_constraint(TypeConstraint::new(expr.id, ty, "test"));
+        }
+        info.parse_result = ParseResult::Expression(Box::new(expr));
+
+        let enum_dict = EnumDict::new();
+        let macro_ctx = MacroInferContext::new();
+        let known_symbols = KnownSymbols { names: HashSet::new() };
+        RustCodegen::new(
+            interner, &enum_dict, &macro_ctx, BindingsInfo::default(),
+            &known_symbols, None, None,
+        )
+        .generate_object_macro_const(&info)
+        .map(|generated| generated.map(|g| g.code))
+    }
+
+    fn folded(value: i128, ty: CIntType) -> Option<FoldedConst> {
+        Some(FoldedConst { value, ty })
+    }
+
+    #[test]
+    fn test_object_macro_const_codegen() {
+        let mut interner = StringInterner::new();
+
+        // 型が付かない整数は畳み込んだ C の型
+        let code = gen_object_const(&mut interner, "FOO", folded(-1, CIntType::INT), ExprKind::IntLit(-1), None);
+        assert!(code.unwrap().unwrap().contains("pub const FOO: c_int = -1;"));
+        let code = gen_object_const(&mut interner, "BIG", folded(1 << 40, CIntType::LONG), ExprKind::IntLit(1 << 40), None);
+        assert!(code.unwrap().unwrap().contains("pub const BIG: c_long = 1099511627776;"));
+        let code = gen_object_const(&mut interner, "MASK", folded(0xFFFF0080, CIntType::UINT), ExprKind::IntLit(0), None);
+        assert!(code.unwrap().unwrap().contains("pub const MASK: c_uint = 4294901888;"));
+
+        // 式から推論した型は値を表せるときだけ使う
+        let u32_cast = TypeRepr::CType {
+            specs: CTypeSpecs::TypedefName(interner.intern("U32")),
+            derived: Vec::new(),
+            source: CTypeSource::Cast,
+        };
+        let code = gen_object_const(&mut interner, "FLAGS", folded(0x80, CIntType::UINT), ExprKind::IntLit(0), Some(u32_cast));
+        assert!(code.unwrap().unwrap().contains("pub const FLAGS: U32 = 128;"));
+        let int_lit = TypeRepr::Inferred(InferredType::IntLiteral);
+        let code = gen_object_const(
+            &mut interner, "GMTIME_MAX", folded(67768036191676799, CIntType::LONG), ExprKind::IntLit(0), Some(int_lit));
+        assert!(code.unwrap().unwrap().contains("pub const GMTIME_MAX: c_long = 67768036191676799;"));
+
+        // 宣言された型（bindings.rs など）は表せる場合だけ使い、表せなければ出力しない
+        let u8_ty = || Some(TypeRepr::from_rust_string("u8"));
+        let code = gen_object_const(&mut interner, "SMALL", folded(0xff, CIntType::INT), ExprKind::IntLit(0xff), u8_ty());
+        assert!(code.unwrap().unwrap().contains("pub const SMALL: u8 = 255;"));
+        let code = gen_object_const(&mut interner, "WIDE", folded(0x1ff, CIntType::INT), ExprKind::IntLit(0x1ff), u8_ty());
+        assert!(code.unwrap_err().contains("does not fit"));
+
+        // 文字列リテラルは &CStr
+        let code = gen_object_const(&mut interner, "NAME", None, ExprKind::StringLit(b"perl".to_vec()), None).unwrap().unwrap();
+        assert!(code.contains("pub const NAME: &std::ffi::CStr = c\"perl\";"), "{code}");
+
+        // NUL を含む文字列やポインタ型は定数にしない
+        assert!(gen_object_const(&mut interner, "NUL", None, ExprKind::StringLit(b"a\0b".to_vec()), None).unwrap().is_none());
+        let ptr = gen_object_const(&mut interner, "PTR", folded(0, CIntType::INT), ExprKind::IntLit(0), Some(TypeRepr::from_rust_string("*mut SV")));
+        assert!(ptr.unwrap().is_none());
+    }
+}
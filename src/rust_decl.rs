@@ -19,6 +19,8 @@ pub struct RustConst {
     pub name: String,
     pub ty: String,
     pub uty: UnifiedType,
+    /// 整数リテラルで初期化されている場合の値（定数畳み込み用）
+    pub value: Option<i64>,
 }
 
 /// Rust関数パラメータ
@@ -144,7 +146,8 @@ impl RustDeclDict {
                     let name = item_const.ident.to_string();
                     let uty = UnifiedType::from_syn_type(&item_const.ty);
                     let ty = Self::type_to_string(&item_const.ty);
-                    self.consts.insert(name.clone(), RustConst { name, ty, uty });
+                    let value = Self::int_lit_value(&item_const.expr);
+                    self.consts.insert(name.clone(), RustConst { name, ty, uty, value });
                 }
             }
             Item::Type(item_type) => {
@@ -234,6 +237,24 @@ impl RustDeclDict {
         }
     }
 
+    /// 整数リテラル（単項マイナス付きを含む）の値を取り出す
+    ///
+    /// bindgen は `#define` 由来の定数を `pub const X: u32 = 256;` の形で
+    /// 出力するので、単純なリテラルだけ扱えば十分。
+    fn int_lit_value(expr: &syn::Expr) -> Option<i64> {
+        match expr {
+            syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Int(i), .. }) => {
+                i.base10_parse::<i64>().ok()
+                    .or_else(|| i.base10_parse::<u64>().ok().map(|v| v as i64))
+            }
+            syn::Expr::Unary(syn::ExprUnary { op: syn::UnOp::Neg(_), expr, .. }) => {
+                Self::int_lit_value(expr).map(|v| v.wrapping_neg())
+            }
+            syn::Expr::Paren(p) => Self::int_lit_value(&p.expr),
+            _ => None,
+        }
+    }
+
     /// 可視性がpubかどうか
     fn is_pub(vis: &Visibility) -> bool {
         matches!(vis, Visibility::Public(_))
@@ -438,6 +459,13 @@ mod tests {
         assert_eq!(c.ty, "u32");
     }
 
+    #[test]
+    fn test_parse_const_value() {
+        let dict = RustDeclDict::parse("pub const SVf_IOK: u32 = 256;\npub const NEG: i32 = -3;");
+        assert_eq!(dict.consts.get("SVf_IOK").unwrap().value, Some(256));
+        assert_eq!(dict.consts.get("NEG").unwrap().value, Some(-3));
+    }
+
     #[test]
     fn test_parse_const_array() {
         let dict = RustDeclDict::parse("pub const MSG: &[u8; 10] = b\"(unknown)\\0\";");
@@ -1,8 +1,13 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::Serialize;
 
 /// ファイル識別子
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Default, Serialize)]
 pub struct FileId(u32);
 
 impl FileId {
@@ -13,7 +18,7 @@ impl FileId {
 }
 
 /// ソース位置
-#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize)]
 pub struct SourceLocation {
     pub file_id: FileId,
     pub line: u32,
@@ -31,11 +36,94 @@ impl SourceLocation {
     }
 }
 
+/// インクルードパスの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IncludeKind {
+    /// <...> システムヘッダ
+    System,
+    /// "..." ローカルヘッダ
+    Local,
+}
+
+/// `#include` 1 回分（インクルードグラフの辺）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncludeEdge {
+    /// インクルードしたファイル
+    pub from: FileId,
+    /// インクルードされたファイル
+    pub to: FileId,
+    /// `#include` ディレクティブの位置
+    pub loc: SourceLocation,
+    /// 書かれたとおりのパス
+    pub spelling: String,
+    pub kind: IncludeKind,
+    /// `#include_next` によるものか
+    pub is_include_next: bool,
+    /// インクルードガードにより読み込みを省略したか
+    pub skipped_by_guard: bool,
+}
+
+/// メモリ上のファイル（ヘッダーオーバーレイ）
+///
+/// プリプロセッサはファイルを読むときにまずここを参照し、なければディスクから読む。
+/// - 絶対パスは、そのパスのファイルを置き換える（パッチを当てた `config.h` など）。
+///   ディスク上に存在しなくてもよい。
+/// - 相対パス（`wrapper.h`、`sys/foo.h`）は `#include` に書かれた名前と比較され、
+///   どのインクルードパスよりも先に見つかる（`#include_next` を除く）。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VirtualFs {
+    files: BTreeMap<PathBuf, Arc<[u8]>>,
+}
+
+impl VirtualFs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// ファイルを追加（同じパスなら置き換える）
+    pub fn add_file(&mut self, path: impl Into<PathBuf>, contents: impl Into<Vec<u8>>) {
+        self.files.insert(path.into(), contents.into().into());
+    }
+
+    /// メモリ上のファイルの内容
+    pub fn get(&self, path: &Path) -> Option<&[u8]> {
+        self.files.get(path).map(|c| &c[..])
+    }
+
+    /// メモリ上かディスク上にファイルがあるか
+    pub fn exists(&self, path: &Path) -> bool {
+        self.files.contains_key(path) || path.exists()
+    }
+
+    /// ファイルを読む（メモリ上になければディスクから）
+    pub fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        match self.get(path) {
+            Some(contents) => Ok(contents.to_vec()),
+            None => fs::read(path),
+        }
+    }
+
+    /// 登録されたファイルがないか
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// 登録されたファイルをイテレート
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, &[u8])> {
+        self.files.iter().map(|(p, c)| (p.as_path(), &c[..]))
+    }
+}
+
 /// ファイルレジストリ
 #[derive(Debug, Default, Clone)]
 pub struct FileRegistry {
     paths: Vec<PathBuf>,
     path_to_id: HashMap<PathBuf, FileId>,
+    /// 処理した `#include`（出現順）
+    includes: Vec<IncludeEdge>,
+    /// ディスクではなくメモリから読んだファイルの内容（`VirtualFs`・`add_source_str`）
+    in_memory: HashMap<FileId, Arc<[u8]>>,
 }
 
 impl FileRegistry {
@@ -44,9 +132,35 @@ impl FileRegistry {
         Self {
             paths: Vec::new(),
             path_to_id: HashMap::new(),
+            includes: Vec::new(),
+            in_memory: HashMap::new(),
         }
     }
 
+    /// メモリから読んだファイルの内容を記録する（診断の該当行表示に使う）
+    pub fn set_in_memory_source(&mut self, id: FileId, contents: impl Into<Arc<[u8]>>) {
+        self.in_memory.insert(id, contents.into());
+    }
+
+    /// ソースの該当行（1 始まり）を取得
+    ///
+    /// メモリ上の内容があればそれを、なければディスクのファイルを読む。
+    /// 読めない場合（`<cmdline>` など）は None。
+    pub fn source_line(&self, id: FileId, line: u32) -> Option<String> {
+        use std::io::{BufRead, BufReader};
+        if line == 0 {
+            return None;
+        }
+        let target = (line as usize) - 1;
+        if let Some(contents) = self.in_memory.get(&id) {
+            return contents.split(|&b| b == b'\n')
+                .nth(target)
+                .map(|l| String::from_utf8_lossy(l.strip_suffix(b"\r").unwrap_or(l)).into_owned());
+        }
+        let f = fs::File::open(self.try_get_path(id)?).ok()?;
+        BufReader::new(f).lines().nth(target).and_then(|r| r.ok())
+    }
+
     /// パスを登録してIDを返す
     pub fn register(&mut self, path: PathBuf) -> FileId {
         if let Some(&id) = self.path_to_id.get(&path) {
@@ -78,6 +192,16 @@ impl FileRegistry {
         self.paths.is_empty()
     }
 
+    /// `#include` を記録
+    pub fn add_include(&mut self, edge: IncludeEdge) {
+        self.includes.push(edge);
+    }
+
+    /// 記録した `#include`（出現順）
+    pub fn includes(&self) -> &[IncludeEdge] {
+        &self.includes
+    }
+
     /// 登録されたファイルをイテレート
     pub fn iter(&self) -> impl Iterator<Item = (FileId, &Path)> {
         self.paths
@@ -112,6 +236,22 @@ mod tests {
         assert_eq!(registry.len(), 1);
     }
 
+    #[test]
+    fn test_virtual_fs() {
+        let dir = tempfile::tempdir().unwrap();
+        let on_disk = dir.path().join("config.h");
+        fs::write(&on_disk, "#define A 1\n").unwrap();
+
+        let mut vfs = VirtualFs::new();
+        assert_eq!(vfs.read(&on_disk).unwrap(), b"#define A 1\n");
+        vfs.add_file(&on_disk, "#define A 2\n");
+        vfs.add_file("wrapper.h", "#include <config.h>\n");
+        assert_eq!(vfs.read(&on_disk).unwrap(), b"#define A 2\n");
+        assert!(vfs.exists(Path::new("wrapper.h")));
+        assert!(!vfs.exists(&dir.path().join("missing.h")));
+        assert!(vfs.read(&dir.path().join("missing.h")).is_err());
+    }
+
     #[test]
     fn test_source_location() {
         let loc = SourceLocation::new(FileId(0), 10, 5);
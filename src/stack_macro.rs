@@ -0,0 +1,386 @@
+//! XS スタック操作マクロの検出
+//!
+//! `PUSHs` / `XPUSHs` / `POPs` / `SPAGAIN` / `XSRETURN` などは、呼び出し側の
+//! ローカル変数（`dSP` の `sp`、`dXSARGS` の `mark` / `ax` / `items` など）を
+//! 直接読み書きする。引数として受け取らない変数を書き換えるので
+//! `unsafe fn` にはできず、通常の生成では未解決シンボルになる。
+//!
+//! こうしたマクロは呼び出し側の変数を `$sp:ident` のように明示的に受け取る
+//! `macro_rules!` として生成する（`PUSHs!(sp, sv)`）。ここでは
+//!
+//! - いずれかのマクロが代入・インクリメントする、パラメータでもローカル宣言でもない識別子
+//! - 宣言だけのマクロ（`dSP` など）が呼び出し側に宣言する変数
+//!
+//! を呼び出し側ローカルの候補とし、各マクロが（`assert` の中以外で）参照する候補を
+//! そのマクロの呼び出し側ローカルとする。さらに他のスタックマクロの呼び出しを通じて
+//! 推移的に伝播させる。読むだけのマクロでも、候補に入っていない変数は対象にしない
+//! （`PERL_ARGS_ASSERT_*` の `assert(sp)` などを拾わないため）。
+
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::{BlockItem, CompoundStmt, Declaration, DerivedDecl, Expr, ExprKind, ForInit, Initializer, Stmt};
+use crate::infer_api::InferResult;
+use crate::intern::{InternedStr, StringInterner};
+use crate::macro_infer::{walk_expr_children, MacroInferInfo, ParseResult};
+use crate::rust_codegen::KnownSymbols;
+
+/// XS 関数の慣用的なローカル変数と、その Rust の型
+///
+/// `dSP` / `dMARK` / `dAX` / `dITEMS`（`dXSARGS`）で宣言される。
+/// `macro_rules!` の引数はこの順に並べる。
+pub const XS_STACK_LOCALS: &[(&str, &str)] = &[
+    ("sp", "*mut *mut SV"),
+    ("mark", "*mut *mut SV"),
+    ("ax", "I32"),
+    ("items", "I32"),
+];
+
+/// 呼び出し側ローカルを使うマクロの辞書
+#[derive(Debug, Default)]
+pub struct StackMacroDict {
+    /// マクロ名 → 呼び出し側ローカル（`macro_rules!` の引数順）
+    locals: HashMap<InternedStr, Vec<InternedStr>>,
+    /// 宣言だけのマクロ（`dSP` など）
+    declarations: HashSet<InternedStr>,
+}
+
+impl StackMacroDict {
+    /// ターゲットマクロからスタックマクロを検出する
+    pub fn collect(result: &InferResult, known_symbols: &KnownSymbols) -> Self {
+        let interner = result.preprocessor.interner();
+        // `my_perl` はスレッド版の THX 引数として別に渡すので、`dTHX` などは対象外
+        let my_perl = interner.lookup("my_perl");
+        let is_free = |name: InternedStr, info: &MacroInferInfo, decls: &HashSet<InternedStr>| {
+            !info.params.iter().any(|p| p.name == name)
+                && !decls.contains(&name)
+                && !result.enum_dict.is_enum_variant(name)
+                && !result.infer_ctx.macros.contains_key(&name)
+                && !known_symbols.contains(interner.get(name))
+        };
+
+        // 各マクロが参照する自由な識別子と、呼び出し側ローカルの候補
+        let mut scans: Vec<(InternedStr, BodyScan, HashSet<InternedStr>)> = Vec::new();
+        let mut candidates: HashSet<InternedStr> = HashSet::new();
+        let mut declarations = HashSet::new();
+        for (&name, info) in &result.infer_ctx.macros {
+            if !info.is_target || !info.has_body || info.apidoc_suppressed {
+                continue;
+            }
+            let mut scan = BodyScan::default();
+            match &info.parse_result {
+                ParseResult::Expression(expr) => scan.expr(expr),
+                ParseResult::Statement(items) => {
+                    scan.items(items);
+                    // 宣言だけのマクロは、宣言した変数を呼び出し側に置く
+                    if let Some(exported) = declared_locals(items, info) {
+                        scan.exported = exported.into_iter()
+                            .filter(|id| Some(*id) != my_perl)
+                            .collect();
+                        for id in &scan.exported {
+                            scan.decls.remove(id);
+                        }
+                        declarations.insert(name);
+                    }
+                }
+                ParseResult::Unparseable(_) => continue,
+            }
+            if scan.non_lvalue_assign {
+                continue;
+            }
+            let free: HashSet<InternedStr> = scan.idents.iter()
+                .filter(|id| is_free(**id, info, &scan.decls))
+                .copied()
+                .collect();
+            candidates.extend(free.iter().filter(|id| scan.assigned.contains(id)));
+            candidates.extend(scan.exported.iter().copied());
+            scans.push((name, scan, free));
+        }
+
+        // 本体が直接参照する呼び出し側ローカルと、呼び出すマクロ
+        let direct: HashMap<InternedStr, (HashSet<InternedStr>, HashSet<InternedStr>)> = scans.into_iter()
+            .map(|(name, scan, free)| {
+                let locals = free.into_iter()
+                    .filter(|id| candidates.contains(id))
+                    .chain(scan.exported.iter().copied())
+                    .collect();
+                (name, (locals, scan.callees))
+            })
+            .collect();
+
+        // 呼び出し先のローカルを不動点まで伝播
+        let mut all: HashMap<InternedStr, HashSet<InternedStr>> = direct.iter()
+            .map(|(name, (locals, _))| (*name, locals.clone()))
+            .collect();
+        loop {
+            let mut changed = false;
+            for (name, (_, callees)) in &direct {
+                let inherited: Vec<InternedStr> = callees.iter()
+                    .filter_map(|c| all.get(c))
+                    .flatten()
+                    .copied()
+                    .collect();
+                let set = all.get_mut(name).unwrap();
+                for id in inherited {
+                    changed |= set.insert(id);
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        let locals = all.into_iter()
+            .filter(|(_, set)| !set.is_empty())
+            .map(|(name, set)| {
+                let mut locals: Vec<InternedStr> = set.into_iter().collect();
+                locals.sort_by_key(|id| {
+                    let s = interner.get(*id);
+                    (XS_STACK_LOCALS.iter().position(|(n, _)| *n == s).unwrap_or(usize::MAX), s)
+                });
+                (name, locals)
+            })
+            .collect();
+        Self { locals, declarations }
+    }
+
+    /// マクロの呼び出し側ローカル（スタックマクロでなければ None）
+    pub fn get(&self, name: InternedStr) -> Option<&[InternedStr]> {
+        self.locals.get(&name).map(|v| v.as_slice())
+    }
+
+    /// 宣言だけのマクロ（`dSP` など）か
+    pub fn is_declaration(&self, name: InternedStr) -> bool {
+        self.declarations.contains(&name)
+    }
+
+    /// スタックマクロか
+    pub fn contains(&self, name: InternedStr) -> bool {
+        self.locals.contains_key(&name)
+    }
+
+    /// スタックマクロの数
+    pub fn len(&self) -> usize {
+        self.locals.len()
+    }
+
+    /// スタックマクロがないか
+    pub fn is_empty(&self) -> bool {
+        self.locals.is_empty()
+    }
+
+    /// 呼び出し側ローカルの Rust の型（[`XS_STACK_LOCALS`] にあるもののみ）
+    pub fn local_type(name: InternedStr, interner: &StringInterner) -> Option<&'static str> {
+        let s = interner.get(name);
+        XS_STACK_LOCALS.iter().find(|(n, _)| *n == s).map(|(_, ty)| *ty)
+    }
+}
+
+/// 宣言だけのマクロ（`dSP` など）が宣言する変数（宣言順）
+///
+/// 宣言以外を含む本体、関数の宣言（`XSPROTO(name)`）、パラメータ名を
+/// 宣言するものは対象外で None。
+fn declared_locals(items: &[BlockItem], info: &MacroInferInfo) -> Option<Vec<InternedStr>> {
+    let mut names = Vec::new();
+    for item in items {
+        let BlockItem::Decl(decl) = item else { return None };
+        for d in &decl.declarators {
+            if d.declarator.derived.iter().any(|dd| matches!(dd, DerivedDecl::Function(_))) {
+                return None;
+            }
+            let name = d.declarator.name?;
+            if info.params.iter().any(|p| p.name == name) {
+                return None;
+            }
+            names.push(name);
+        }
+    }
+    (!names.is_empty()).then_some(names)
+}
+
+/// マクロ本体の走査結果
+#[derive(Default)]
+struct BodyScan {
+    /// 値として参照される識別子（関数名の位置と `assert` の中を除く）
+    idents: HashSet<InternedStr>,
+    /// 代入・インクリメントの対象になる識別子
+    assigned: HashSet<InternedStr>,
+    /// 本体内で宣言されるローカル変数
+    decls: HashSet<InternedStr>,
+    /// 呼び出すマクロ・関数
+    callees: HashSet<InternedStr>,
+    /// 呼び出し側に宣言する変数（宣言だけのマクロのみ）
+    exported: Vec<InternedStr>,
+    /// 左辺値にならない式への代入を含む（宣言の誤パースなど）
+    non_lvalue_assign: bool,
+}
+
+impl BodyScan {
+    fn expr(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Ident(name) => {
+                self.idents.insert(*name);
+            }
+            ExprKind::Call { func, args } => {
+                match &func.kind {
+                    ExprKind::Ident(name) => {
+                        self.callees.insert(*name);
+                    }
+                    _ => self.expr(func),
+                }
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            ExprKind::MacroCall { name, args, expanded, .. } => {
+                self.callees.insert(*name);
+                for arg in args {
+                    self.expr(arg);
+                }
+                self.expr(expanded);
+            }
+            ExprKind::Assign { lhs, rhs, .. } => {
+                self.assign_target(lhs);
+                self.expr(lhs);
+                self.expr(rhs);
+            }
+            ExprKind::PreInc(inner) | ExprKind::PreDec(inner)
+            | ExprKind::PostInc(inner) | ExprKind::PostDec(inner) => {
+                self.assign_target(inner);
+                self.expr(inner);
+            }
+            ExprKind::StmtExpr(compound) => self.compound(compound),
+            // 引数チェックだけの参照では呼び出し側ローカルとみなさない
+            ExprKind::Assert { .. } => {}
+            _ => walk_expr_children(expr, &mut |e| self.expr(e)),
+        }
+    }
+
+    fn assign_target(&mut self, lhs: &Expr) {
+        match &lhs.kind {
+            ExprKind::Ident(name) => {
+                self.assigned.insert(*name);
+            }
+            ExprKind::Deref(_) | ExprKind::Index { .. } | ExprKind::Member { .. }
+            | ExprKind::PtrMember { .. } | ExprKind::Call { .. } | ExprKind::MacroCall { .. } => {}
+            _ => self.non_lvalue_assign = true,
+        }
+    }
+
+    fn items(&mut self, items: &[BlockItem]) {
+        for item in items {
+            match item {
+                BlockItem::Decl(decl) => self.decl(decl),
+                BlockItem::Stmt(stmt) => self.stmt(stmt),
+            }
+        }
+    }
+
+    fn decl(&mut self, decl: &Declaration) {
+        for d in &decl.declarators {
+            if let Some(name) = d.declarator.name {
+                self.decls.insert(name);
+            }
+            if let Some(Initializer::Expr(e)) = &d.init {
+                self.expr(e);
+            }
+        }
+    }
+
+    fn compound(&mut self, compound: &CompoundStmt) {
+        self.items(&compound.items);
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Compound(c) => self.compound(c),
+            Stmt::Expr(Some(e), _) | Stmt::Return(Some(e), _) => self.expr(e),
+            Stmt::If { cond, then_stmt, else_stmt, .. } => {
+                self.expr(cond);
+                self.stmt(then_stmt);
+                if let Some(es) = else_stmt {
+                    self.stmt(es);
+                }
+            }
+            Stmt::While { cond, body, .. } | Stmt::DoWhile { body, cond, .. } => {
+                self.expr(cond);
+                self.stmt(body);
+            }
+            Stmt::For { init, cond, step, body, .. } => {
+                match init {
+                    Some(ForInit::Expr(e)) => self.expr(e),
+                    Some(ForInit::Decl(decl)) => self.decl(decl),
+                    None => {}
+                }
+                for e in cond.iter().chain(step.iter()) {
+                    self.expr(e);
+                }
+                self.stmt(body);
+            }
+            Stmt::Switch { expr, body, .. } => {
+                self.expr(expr);
+                self.stmt(body);
+            }
+            Stmt::Case { expr, stmt, .. } => {
+                self.expr(expr);
+                self.stmt(stmt);
+            }
+            Stmt::Default { stmt, .. } | Stmt::Label { stmt, .. } => self.stmt(stmt),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{AssertKind, AssignOp};
+    use crate::source::SourceLocation;
+
+    fn e(kind: ExprKind) -> Box<Expr> {
+        Box::new(Expr::new(kind, SourceLocation::default()))
+    }
+
+    #[test]
+    fn test_body_scan() {
+        let mut interner = StringInterner::new();
+        let sp = interner.intern("sp");
+        let s = interner.intern("s");
+        let sv_setsv = interner.intern("sv_setsv");
+        let tmp = interner.intern("tmp");
+
+        // *++sp = sv_setsv(s, tmp)
+        let body = e(ExprKind::Assign {
+            op: AssignOp::Assign,
+            lhs: e(ExprKind::Deref(e(ExprKind::PreInc(e(ExprKind::Ident(sp)))))),
+            rhs: e(ExprKind::Call {
+                func: e(ExprKind::Ident(sv_setsv)),
+                args: vec![*e(ExprKind::Ident(s)), *e(ExprKind::Ident(tmp))],
+            }),
+        });
+        let mut scan = BodyScan::default();
+        scan.expr(&body);
+        assert_eq!(scan.assigned, HashSet::from([sp]));
+        assert_eq!(scan.idents, HashSet::from([sp, s, tmp]));
+        assert_eq!(scan.callees, HashSet::from([sv_setsv]));
+        assert!(!scan.non_lvalue_assign);
+
+        // (tmp, sp) = s: 左辺値にならない式への代入は検出対象外にする
+        let cast_lhs = e(ExprKind::Assign {
+            op: AssignOp::Assign,
+            lhs: e(ExprKind::Comma { lhs: e(ExprKind::Ident(tmp)), rhs: e(ExprKind::Ident(sp)) }),
+            rhs: e(ExprKind::Ident(s)),
+        });
+        let mut scan = BodyScan::default();
+        scan.expr(&cast_lhs);
+        assert!(scan.non_lvalue_assign);
+
+        // assert(sp): 引数チェックの中の参照は数えない
+        let assert_sp = e(ExprKind::Assert {
+            kind: AssertKind::Assert,
+            condition: e(ExprKind::Ident(sp)),
+        });
+        let mut scan = BodyScan::default();
+        scan.expr(&assert_sp);
+        assert!(scan.idents.is_empty());
+    }
+}
@@ -1,5 +1,7 @@
 use std::sync::atomic::{AtomicU64, Ordering};
 
+use serde::Serialize;
+
 use crate::intern::{InternedStr, StringInterner};
 use crate::source::SourceLocation;
 
@@ -98,7 +100,7 @@ pub struct MacroEndInfo {
 // ============================================================================
 
 /// コメント種別
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub enum CommentKind {
     /// 行コメント: // ...
     Line,
@@ -107,7 +109,7 @@ pub enum CommentKind {
 }
 
 /// コメント
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct Comment {
     pub kind: CommentKind,
     pub text: String,
@@ -789,6 +791,57 @@ fn escape_wide_string(s: &[u32]) -> String {
     s.iter().map(|&c| escape_wide_char(c)).collect()
 }
 
+/// 整数リテラルの C の型（LP64 なので long long は long と同じ幅として扱う）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IntLitType {
+    Int,
+    UInt,
+    Long,
+    ULong,
+}
+
+impl IntLitType {
+    /// 値・基数・サフィックスから C の規則（C11 6.4.4.1）で型を決める
+    ///
+    /// 10 進はサフィックスがなければ符号付きの型だけが候補になり、
+    /// 16 進・8 進は符号なしの型も候補になる。どれにも収まらなければ `ULong`。
+    pub fn from_literal(value: u64, decimal: bool, unsigned: bool, long: bool) -> Self {
+        use IntLitType::*;
+        let candidates: &[IntLitType] = match (unsigned, long, decimal) {
+            (false, false, true) => &[Int, Long],
+            (false, false, false) => &[Int, UInt, Long, ULong],
+            (true, false, _) => &[UInt, ULong],
+            (false, true, true) => &[Long],
+            (false, true, false) => &[Long, ULong],
+            (true, true, _) => &[ULong],
+        };
+        candidates.iter().copied().find(|ty| value <= ty.max_value()).unwrap_or(ULong)
+    }
+
+    /// 表せる最大値
+    pub fn max_value(self) -> u64 {
+        match self {
+            IntLitType::Int => i32::MAX as u64,
+            IntLitType::UInt => u32::MAX as u64,
+            IntLitType::Long => i64::MAX as u64,
+            IntLitType::ULong => u64::MAX,
+        }
+    }
+
+    /// 符号付きか
+    pub fn is_signed(self) -> bool {
+        matches!(self, IntLitType::Int | IntLitType::Long)
+    }
+
+    /// ビット幅
+    pub fn bits(self) -> u32 {
+        match self {
+            IntLitType::Int | IntLitType::UInt => 32,
+            IntLitType::Long | IntLitType::ULong => 64,
+        }
+    }
+}
+
 /// 位置情報付きトークン
 #[derive(Debug, Clone, PartialEq)]
 pub struct Token {
@@ -798,6 +851,10 @@ pub struct Token {
     pub loc: SourceLocation,
     /// このトークンの直前にあったコメント群
     pub leading_comments: Vec<Comment>,
+    /// 整数リテラルの C の型（プリプロセッサの字句解析で決めたもの）
+    ///
+    /// None の場合、整数リテラルの型は値から 10 進リテラルと同じ規則で決める。
+    pub int_type: Option<IntLitType>,
 }
 
 impl Default for Token {
@@ -807,6 +864,7 @@ impl Default for Token {
             kind: TokenKind::Eof,
             loc: SourceLocation::default(),
             leading_comments: Vec::new(),
+            int_type: None,
         }
     }
 }
@@ -819,6 +877,7 @@ impl Token {
             kind,
             loc,
             leading_comments: Vec::new(),
+            int_type: None,
         }
     }
 
@@ -829,6 +888,7 @@ impl Token {
             kind,
             loc,
             leading_comments: comments,
+            int_type: None,
         }
     }
 
@@ -842,6 +902,7 @@ impl Token {
             kind: self.kind.clone(),
             loc: self.loc.clone(),
             leading_comments: self.leading_comments.clone(),
+            int_type: self.int_type,
         }
     }
 }
@@ -3,7 +3,7 @@
 //! Parser がプリプロセッサ以外のソース（トークン列など）からも
 //! トークンを読めるようにするための抽象化層。
 
-use crate::error::Result;
+use crate::error::{ExpansionFrame, Result};
 use crate::intern::StringInterner;
 use crate::source::{FileRegistry, SourceLocation};
 use crate::token::{Token, TokenKind};
@@ -31,6 +31,13 @@ pub trait TokenSource {
         let _ = file_id;
         false
     }
+
+    /// トークンがどのマクロ展開から生じたか（外側から内側へ）
+    /// デフォルト実装は展開情報を持たないので常に空を返す
+    fn expansion_backtrace(&self, token: &Token) -> Vec<ExpansionFrame> {
+        let _ = token;
+        Vec::new()
+    }
 }
 
 /// トークン列からトークンを供給する実装
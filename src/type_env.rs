@@ -6,6 +6,8 @@
 
 use std::collections::HashMap;
 
+use serde::Serialize;
+
 use crate::ast::ExprId;
 use crate::intern::InternedStr;
 use crate::type_repr::TypeRepr;
@@ -14,7 +16,7 @@ use crate::type_repr::TypeRepr;
 ///
 /// 簡約せずにそのまま保持し、デバッグ・観察可能にする。
 /// 出所情報は TypeRepr 内に含まれる。
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct TypeConstraint {
     /// 対象となる式の ID
     pub expr_id: ExprId,
@@ -41,7 +43,7 @@ impl TypeConstraint {
 }
 
 /// ExprId とパラメータ名のリンク情報
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ParamLink {
     /// 式 ID
     pub expr_id: ExprId,
@@ -55,7 +57,7 @@ pub struct ParamLink {
 ///
 /// マクロの型推論に使用する型制約を収集・管理する。
 /// パラメータ、式、戻り値それぞれに対する制約を保持する。
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct TypeEnv {
     /// パラメータ名 → 型制約リスト
     pub param_constraints: HashMap<InternedStr, Vec<TypeConstraint>>,
@@ -5,6 +5,8 @@
 
 use std::fmt;
 
+use serde::Serialize;
+
 use crate::ast::BinOp;
 use crate::intern::InternedStr;
 
@@ -13,7 +15,7 @@ use crate::intern::InternedStr;
 // ============================================================================
 
 /// 型表現（出所情報を含む）
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum TypeRepr {
     /// C 言語の型（CHeader, Apidoc, InlineFn 共通）
     CType {
@@ -42,7 +44,7 @@ pub enum TypeRepr {
 // ============================================================================
 
 /// C 型の出所
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum CTypeSource {
     /// C ヘッダーのパース結果
     Header,
@@ -70,7 +72,7 @@ pub enum CTypeSource {
 // ============================================================================
 
 /// Rust 型の出所
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum RustTypeSource {
     /// bindings.rs の関数引数
     FnParam { func_name: String, param_index: usize },
@@ -87,7 +89,7 @@ pub enum RustTypeSource {
 // ============================================================================
 
 /// C 型指定子（DeclSpecs から抽出）
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub enum CTypeSpecs {
     /// void
     Void,
@@ -112,7 +114,7 @@ pub enum CTypeSpecs {
 }
 
 /// 整数サイズ
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub enum IntSize {
     /// short
     Short,
@@ -127,7 +129,7 @@ pub enum IntSize {
 }
 
 /// C 派生型
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub enum CDerivedType {
     /// ポインタ
     Pointer {
@@ -149,7 +151,7 @@ pub enum CDerivedType {
 // ============================================================================
 
 /// Rust 型表現（syn::Type から変換）
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub enum RustTypeRepr {
     /// C互換基本型 (c_int, c_char, etc.)
     CPrimitive(CPrimitiveKind),
@@ -181,7 +183,7 @@ pub enum RustTypeRepr {
 }
 
 /// C互換基本型
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub enum CPrimitiveKind {
     CChar,
     CSchar,
@@ -199,7 +201,7 @@ pub enum CPrimitiveKind {
 }
 
 /// Rust基本型
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub enum RustPrimitiveKind {
     I8,
     I16,
@@ -223,7 +225,7 @@ pub enum RustPrimitiveKind {
 // ============================================================================
 
 /// 推論で導出された型
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum InferredType {
     // ==================== リテラル ====================
     /// 整数リテラル (42, 0x1F, etc.)
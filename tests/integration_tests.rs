@@ -438,3 +438,150 @@ fn test_pointer_qualifiers() {
     let decls = parse_source(source);
     assert_eq!(decls.len(), 3);
 }
+
+// ==================== Pipeline parse error recovery ====================
+
+fn infer_with_bad_declaration(recover: bool) -> Result<libperl_macrogen::InferredPipeline, libperl_macrogen::PipelineError> {
+    let mut file = tempfile::Builder::new().suffix(".h").tempfile().unwrap();
+    file.write_all(b"int ok1;\nint bad( ;\nstatic inline int ok2(void) { return 1; }\n").unwrap();
+    file.flush().unwrap();
+
+    let mut builder = libperl_macrogen::Pipeline::builder(file.path())
+        .with_perl_build_mode(libperl_macrogen::perl_config::PerlBuildMode::NonThreaded);
+    if recover {
+        builder = builder.with_parse_error_recovery();
+    }
+    builder.build().unwrap().infer()
+}
+
+#[test]
+fn test_pipeline_parse_error_recovery_is_opt_in() {
+    assert!(infer_with_bad_declaration(false).is_err());
+
+    let inferred = infer_with_bad_declaration(true).unwrap();
+    let result = inferred.result();
+    assert_eq!(result.parse_errors.len(), 1);
+    assert_eq!(result.stats.parse_error_count, 1);
+}
+
+// ==================== API diff determinism ====================
+
+fn xs_wrapper_builder() -> libperl_macrogen::PipelineBuilder {
+    libperl_macrogen::Pipeline::builder("samples/xs-wrapper.h")
+        .with_parse_error_recovery()
+        .with_auto_perl_config()
+        .unwrap()
+        .with_bindings("samples/bindings.rs")
+        .with_apidoc("apidoc/v5.36.json")
+}
+
+#[test]
+fn test_api_diff_self_diff_is_empty() {
+    // 同じ perl 同士の差分は、推論・生成の反復順に依らず空になる。
+    // 実ヘッダの解析はテストスレッドの既定スタックに収まらないので別スレッドで回す
+    let diff = std::thread::Builder::new()
+        .stack_size(64 * 1024 * 1024)
+        .spawn(|| libperl_macrogen::ApiDiff::from_pipelines(xs_wrapper_builder(), xs_wrapper_builder()).unwrap())
+        .unwrap()
+        .join()
+        .unwrap();
+    assert!(diff.is_empty(), "{}", diff.to_text());
+}
+
+/// 生成コードから `macro_rules! NAME` の定義を、空白を除いて取り出す
+/// （rustfmt を通すかどうかで整形が変わるため）
+fn macro_rules_def(code: &str, name: &str) -> String {
+    let start = code.find(&format!("macro_rules! {} {{", name))
+        .unwrap_or_else(|| panic!("macro_rules! {} not generated", name));
+    let end = code[start..].find("\n}\n").map(|i| start + i + 3).unwrap_or(code.len());
+    squash(&code[start..end])
+}
+
+fn squash(s: &str) -> String {
+    s.split_whitespace().collect()
+}
+
+/// xs-wrapper.h 全体の生成結果
+struct XsWrapperOutput {
+    code: String,
+    items: Vec<libperl_macrogen::module_split::OutputItem>,
+    report: libperl_macrogen::CodegenReport,
+}
+
+/// xs-wrapper.h 全体を生成する（重いのでテスト間で 1 度だけ生成する）
+fn xs_wrapper_output() -> &'static XsWrapperOutput {
+    static OUTPUT: std::sync::OnceLock<XsWrapperOutput> = std::sync::OnceLock::new();
+    OUTPUT.get_or_init(|| {
+        std::thread::Builder::new()
+            .stack_size(64 * 1024 * 1024)
+            .spawn(|| {
+                let mut out = Vec::new();
+                let generated = xs_wrapper_builder().with_codegen_defaults().build().unwrap().generate(&mut out).unwrap();
+                XsWrapperOutput {
+                    code: String::from_utf8(out).unwrap(),
+                    items: generated.items,
+                    report: generated.report,
+                }
+            })
+            .unwrap()
+            .join()
+            .unwrap()
+    })
+}
+
+#[test]
+fn test_stack_macros_generated_as_macro_rules() {
+    let code = &xs_wrapper_output().code;
+
+    // 呼び出し側の sp を受け取って書き換える
+    let pushs = macro_rules_def(code, "PUSHs");
+    assert!(pushs.contains(&squash("($sp:ident, $s:expr) => {{")), "{pushs}");
+    assert!(pushs.contains(&squash("$sp = $sp.wrapping_add(1);")), "{pushs}");
+    let pops = macro_rules_def(code, "POPs");
+    assert!(pops.contains(&squash("($sp:ident) => {{")), "{pops}");
+    assert!(pops.contains(&squash("$sp = $sp.wrapping_sub(1);")), "{pops}");
+
+    // 宣言マクロはブロックで囲まず、呼び出し側のスコープに let を置く
+    let dsp = macro_rules_def(code, "dSP");
+    assert!(dsp.contains(&squash("($my_perl:expr, $sp:ident) => { #[allow(unused_mut)] let mut $sp: *mut *mut SV = {")), "{dsp}");
+    assert!(dsp.contains(&squash("(*my_perl).Istack_sp")), "{dsp}");
+    let dxsargs = macro_rules_def(code, "dXSARGS");
+    assert!(dxsargs.contains(&squash("($my_perl:expr, $sp:ident, $mark:ident, $ax:ident, $items:ident) => { #[allow(unused_mut)]")), "{dxsargs}");
+
+    // 型の決まらない補助マクロは本体に展開するので EXTEND も生成できる
+    let xpushs = macro_rules_def(code, "XPUSHs");
+    assert!(xpushs.contains(&squash("EXTEND!(my_perl, $sp, $sp, 1);")), "{xpushs}");
+    let xsreturn = macro_rules_def(code, "XSRETURN");
+    assert!(xsreturn.contains(&squash("($my_perl:expr, $ax:ident, $off:expr) => {{")), "{xsreturn}");
+
+    // assert で読むだけのマクロはスタックマクロにしない
+    assert!(!code.contains("macro_rules! PERL_ARGS_ASSERT_"), "PERL_ARGS_ASSERT_* became stack macros");
+    assert!(code.contains("pub unsafe fn PUSHMARK("), "PUSHMARK not generated");
+}
+
+#[test]
+fn test_object_macro_comma_lists_not_generated() {
+    let code = &xs_wrapper_output().code;
+
+    // 配列の初期化子用の並びは 1 つの値にならないので、最後の要素だけを返す関数にしない
+    for name in ["DOUBLEINFBYTES", "DOUBLENANBYTES", "SIG_NAME", "SIG_NUM"] {
+        assert!(!code.contains(&format!("fn {}(", name)), "{name} generated");
+        assert!(!code.contains(&format!("const {}:", name)), "{name} generated");
+    }
+}
+
+#[test]
+fn test_failing_object_stack_macros_reported() {
+    let output = xs_wrapper_output();
+
+    // 生成できなかったオブジェクトのスタックマクロは出力しないが、レポートには残す
+    let popi = output.report.get("POPi").expect("POPi not reported");
+    assert_eq!(popi.kind, libperl_macrogen::ReportItemKind::ObjectMacro);
+    assert!(!output.code.contains("macro_rules! POPi "));
+    // 何も出力しなかったアイテムの区間は作らない
+    let empty: Vec<&str> = output.items.iter()
+        .filter(|item| item.start == item.end)
+        .map(|item| item.name.as_str())
+        .collect();
+    assert!(empty.is_empty(), "{empty:?}");
+}
@@ -4,7 +4,7 @@ use std::io::Write;
 use tempfile::NamedTempFile;
 use libperl_macrogen::{
     DerivedDecl, Expr, ExprKind, ExternalDecl, Initializer, PPConfig, Parser, Preprocessor,
-    StorageClass, TypeSpec,
+    StorageClass, TypeSpec, VirtualFs, write_translation_unit_json,
 };
 
 /// Helper to parse a source string and return translation unit
@@ -575,3 +575,117 @@ fn test_generic_selection_in_function_body() {
     assert_eq!(decls.len(), 1);
     assert!(matches!(decls[0], ExternalDecl::FunctionDef(_)));
 }
+
+#[test]
+fn test_parse_error_macro_backtrace() {
+    let source = concat!(
+        "#define BAD_EXPR (1 + )\n",
+        "#define WRAP(x) (x * 2)\n",
+        "int x = WRAP(BAD_EXPR);\n",
+    );
+    let mut file = NamedTempFile::new().unwrap();
+    file.write_all(source.as_bytes()).unwrap();
+    file.flush().unwrap();
+
+    let mut pp = Preprocessor::new(PPConfig::default());
+    pp.add_source_file(file.path()).unwrap();
+    let err = Parser::new(&mut pp).unwrap().parse().unwrap_err();
+
+    // `)` は BAD_EXPR の展開結果で、BAD_EXPR は WRAP の引数として展開された
+    let names: Vec<_> = err.backtrace().iter().map(|f| f.macro_name.as_str()).collect();
+    assert_eq!(names, vec!["WRAP", "BAD_EXPR"]);
+    assert!(err.backtrace().iter().all(|f| f.call_loc.line == 3));
+
+    let rendered = err.render(pp.files());
+    let lines: Vec<_> = rendered.lines().collect();
+    assert!(lines[0].ends_with("parse error: expected primary expression, found RParen"), "{}", rendered);
+    assert_eq!(lines[1], "     3 | int x = WRAP(BAD_EXPR);");
+    assert_eq!(lines[2], "       |         ^");
+    assert!(lines[3].starts_with("       = note: in expansion of macro `BAD_EXPR` at "));
+    assert!(lines[4].starts_with("       = note: in expansion of macro `WRAP` at "));
+    assert!(lines[4].ends_with(":3"));
+}
+
+#[test]
+fn test_parse_error_snippet_from_overlay_header() {
+    let mut overlays = VirtualFs::new();
+    overlays.add_file("main.c", "#include \"bad.h\"\n");
+    overlays.add_file("bad.h", "int ok;\nint y = (1 + );\n");
+    let mut pp = Preprocessor::new(PPConfig { overlays, ..Default::default() });
+    pp.add_source_file("main.c".as_ref()).unwrap();
+    let err = Parser::new(&mut pp).unwrap().parse().unwrap_err();
+
+    let rendered = err.render(pp.files());
+    let lines: Vec<_> = rendered.lines().collect();
+    assert!(lines[0].starts_with("bad.h:2:"), "{}", rendered);
+    assert_eq!(lines[1], "     2 | int y = (1 + );");
+    assert_eq!(err.with_files(pp.files()).source_line.as_deref(), Some("int y = (1 + );"));
+}
+
+#[test]
+fn test_error_recovery_skips_bad_declarations() {
+    let source = concat!(
+        "int a;\n",
+        "int bad1 = (1 + );\n",
+        "static int f(void) { return 1 +; }\n",
+        "struct s { int x; int 3; } v;\n",
+        "int g(void) { { return 2; } }\n",
+        "int b;\n",
+    );
+    let mut file = NamedTempFile::new().unwrap();
+    file.write_all(source.as_bytes()).unwrap();
+    file.flush().unwrap();
+
+    let mut pp = Preprocessor::new(PPConfig::default());
+    pp.add_source_file(file.path()).unwrap();
+    let mut parser = Parser::new(&mut pp).unwrap();
+    parser.set_error_recovery(true);
+    let decls = parser.parse().unwrap().decls;
+
+    // a, g, b が残り、エラーは 3 件
+    assert_eq!(decls.len(), 3);
+    assert!(matches!(decls[0], ExternalDecl::Declaration(_)));
+    assert!(matches!(decls[1], ExternalDecl::FunctionDef(_)));
+    assert!(matches!(decls[2], ExternalDecl::Declaration(_)));
+    let lines: Vec<_> = parser.errors().iter().map(|e| e.loc().line).collect();
+    assert_eq!(lines, vec![2, 3, 4]);
+}
+
+#[test]
+fn test_error_recovery_disabled_by_default() {
+    let mut file = NamedTempFile::new().unwrap();
+    file.write_all(b"int bad = (1 + );\nint b;\n").unwrap();
+    file.flush().unwrap();
+
+    let mut pp = Preprocessor::new(PPConfig::default());
+    pp.add_source_file(file.path()).unwrap();
+    let mut parser = Parser::new(&mut pp).unwrap();
+    assert!(parser.parse().is_err());
+    assert!(parser.errors().is_empty());
+}
+
+#[test]
+fn test_translation_unit_json() {
+    let mut file = NamedTempFile::new().unwrap();
+    file.write_all(b"static int add(int a, int b) { return a + b; }\n").unwrap();
+    file.flush().unwrap();
+
+    let mut pp = Preprocessor::new(PPConfig::default());
+    pp.add_source_file(file.path()).unwrap();
+    let tu = Parser::new(&mut pp).unwrap().parse().unwrap();
+
+    let mut buf = Vec::new();
+    write_translation_unit_json(&mut buf, &tu, pp.interner(), pp.files(), false).unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+
+    // file_id は files の添字
+    let func = &json["decls"][0]["FunctionDef"];
+    let file_id = func["info"]["loc"]["file_id"].as_u64().unwrap() as usize;
+    assert_eq!(json["files"][file_id], file.path().display().to_string());
+    // 名前は文字列に解決される
+    assert_eq!(func["declarator"]["name"], "add");
+    assert_eq!(func["specs"]["storage"], "Static");
+    let ret = &func["body"]["items"][0]["Stmt"]["Return"][0];
+    assert_eq!(ret["kind"]["Binary"]["op"], "Add");
+    assert_eq!(ret["kind"]["Binary"]["lhs"]["kind"]["Ident"], "a");
+}
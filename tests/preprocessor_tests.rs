@@ -1,8 +1,9 @@
 //! Preprocessor integration tests
 
 use std::io::Write;
+use std::path::PathBuf;
 use tempfile::NamedTempFile;
-use libperl_macrogen::{PPConfig, Preprocessor, TokenKind};
+use libperl_macrogen::{PPConfig, Preprocessor, TokenKind, VirtualFs};
 
 /// Helper to create a preprocessor from source string
 fn preprocess(source: &str) -> Preprocessor {
@@ -23,6 +24,13 @@ fn preprocess(source: &str) -> Preprocessor {
     pp
 }
 
+/// Helper to create a preprocessor from an in-memory source (`add_source_str`)
+fn preprocess_str(source: &str) -> Preprocessor {
+    let mut pp = Preprocessor::new(PPConfig::default());
+    pp.add_source_str("test.c", source);
+    pp
+}
+
 /// Helper to collect all tokens from preprocessor (excluding Newline tokens)
 fn collect_tokens(pp: &mut Preprocessor) -> Vec<(TokenKind, String)> {
     let mut tokens = Vec::new();
@@ -214,6 +222,70 @@ fn test_variadic_macro() {
     assert!(tokens.iter().any(|(kind, _)| matches!(kind, TokenKind::Semi)));
 }
 
+/// Helper to join token texts with spaces
+fn joined(pp: &mut Preprocessor) -> String {
+    collect_tokens(pp).into_iter().map(|(_, text)| text).collect::<Vec<_>>().join(" ")
+}
+
+#[test]
+fn test_va_opt_comma() {
+    let mut pp = preprocess(concat!(
+        "#define F(a, ...) f(a __VA_OPT__(,) __VA_ARGS__)\n",
+        "F(1);\n",
+        "F(1, 2, 3);\n",
+        "F(1, );\n",
+    ));
+    assert_eq!(joined(&mut pp), "f ( 1 ) ; f ( 1 , 2 , 3 ) ; f ( 1 ) ;");
+}
+
+#[test]
+fn test_va_opt_gnu_named_variadic() {
+    let mut pp = preprocess(concat!(
+        "#define G(fmt, args...) g(fmt __VA_OPT__(,) args)\n",
+        "G(x);\n",
+        "G(x, y);\n",
+    ));
+    assert_eq!(joined(&mut pp), "g ( x ) ; g ( x , y ) ;");
+}
+
+#[test]
+fn test_va_opt_nested_parens_and_expansion() {
+    let mut pp = preprocess(concat!(
+        "#define ONE 1\n",
+        "#define H(...) h(__VA_OPT__((ONE, __VA_ARGS__)))\n",
+        "H();\n",
+        "H(ONE);\n",
+    ));
+    assert_eq!(joined(&mut pp), "h ( ) ; h ( ( 1 , 1 ) ) ;");
+}
+
+#[test]
+fn test_va_opt_stringify() {
+    let mut pp = preprocess(concat!(
+        "#define S(...) #__VA_OPT__(x __VA_ARGS__)\n",
+        "const char *a = S();\n",
+        "const char *b = S(y);\n",
+    ));
+    let strings: Vec<_> = collect_tokens(&mut pp)
+        .into_iter()
+        .filter_map(|(kind, _)| match kind {
+            TokenKind::StringLit(s) => Some(String::from_utf8(s).unwrap()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(strings, vec!["", "x y"]);
+}
+
+#[test]
+fn test_va_opt_token_paste() {
+    let mut pp = preprocess(concat!(
+        "#define P(a, ...) a ## __VA_OPT__(_b)\n",
+        "#define Q(...) __VA_OPT__(pre) ## fix\n",
+        "int P(foo), P(foo, 1), Q(), Q(1);\n",
+    ));
+    assert_eq!(joined(&mut pp), "int foo , foo_b , fix , prefix ;");
+}
+
 #[test]
 fn test_predefined_macros() {
     let config = PPConfig {
@@ -410,3 +482,59 @@ fn test_macro_param_keyword_does_not_error() {
     assert_eq!(tokens.len(), 3);
     assert_eq!(tokens[0].1, "int");
 }
+
+/// Helper to preprocess `main.c` with in-memory headers
+fn preprocess_with_overlays(config: PPConfig, files: &[(&str, &str)]) -> String {
+    let mut overlays = VirtualFs::new();
+    for (path, contents) in files {
+        overlays.add_file(*path, *contents);
+    }
+    let mut pp = Preprocessor::new(PPConfig { overlays, ..config });
+    pp.add_source_file("main.c".as_ref()).unwrap();
+    joined(&mut pp)
+}
+
+#[test]
+fn test_overlay_headers_by_name() {
+    let out = preprocess_with_overlays(PPConfig::default(), &[
+        ("main.c", "#include \"defs.h\"\n#include <sys/types.h>\nint x = A + B;"),
+        ("defs.h", "#define A 1"),
+        ("sys/types.h", "#if __has_include(\"defs.h\")\n#define B 2\n#endif"),
+    ]);
+    assert_eq!(out, "int x = 1 + 2 ;");
+}
+
+#[test]
+fn test_overlay_replaces_file_on_disk() {
+    let dir = tempfile::tempdir().unwrap();
+    let core = dir.path().join("CORE");
+    std::fs::create_dir(&core).unwrap();
+    std::fs::write(core.join("perl.h"), "#include \"config.h\"\nint x = USE_THREADS;").unwrap();
+    std::fs::write(core.join("config.h"), "#define USE_THREADS 0").unwrap();
+    let patched = core.join("config.h").to_string_lossy().into_owned();
+
+    let config = PPConfig { include_paths: vec![core.clone()], ..Default::default() };
+    let out = preprocess_with_overlays(config, &[
+        ("main.c", "#include <perl.h>"),
+        (&patched, "#define USE_THREADS 1"),
+    ]);
+    assert_eq!(out, "int x = 1 ;");
+}
+
+#[test]
+fn test_add_source_str_matches_file() {
+    let source = "#define A(x) (x + 1)\nint y = A(2);\n";
+    let mut from_str = preprocess_str(source);
+    let mut from_file = preprocess(source);
+    assert_eq!(joined(&mut from_str), joined(&mut from_file));
+}
+
+#[test]
+fn test_add_source_str_is_target() {
+    let config = PPConfig { target_dir: Some(PathBuf::from("/virtual/CORE")), ..Default::default() };
+    let mut pp = Preprocessor::new(config);
+    pp.add_source_str("/virtual/CORE/sv.h", "#define SvIV(sv) 0\n");
+    pp.collect_tokens().unwrap();
+    let name = pp.interner().lookup("SvIV").unwrap();
+    assert!(pp.macros().get(name).unwrap().is_target);
+}